@@ -0,0 +1,63 @@
+// Renders the operations of a JSON OpenAPI document. Other documents, such
+// as YAML ones, are shown as text.
+(function () {
+  "use strict";
+  var root = document.getElementById("spec");
+
+  function element(tag, className, text) {
+    var node = document.createElement(tag);
+    if (className) node.className = className;
+    if (text !== undefined) node.textContent = text;
+    return node;
+  }
+
+  function renderOperation(path, method, operation) {
+    var details = element("details", "operation");
+    var summary = element("summary");
+    summary.appendChild(element("span", "method " + method, method));
+    summary.appendChild(document.createTextNode(" " + path + " "));
+    if (operation.summary) summary.appendChild(element("em", "", operation.summary));
+    details.appendChild(summary);
+    var body = element("div", "body");
+    if (operation.description) body.appendChild(element("p", "", operation.description));
+    (operation.parameters || []).forEach(function (parameter) {
+      var text = parameter.name + " (" + parameter["in"] + ")";
+      if (parameter.required) text += " required";
+      if (parameter.description) text += ": " + parameter.description;
+      body.appendChild(element("p", "", text));
+    });
+    Object.keys(operation.responses || {}).forEach(function (status) {
+      var response = operation.responses[status];
+      body.appendChild(element("p", "", status + " " + (response.description || "")));
+    });
+    details.appendChild(body);
+    return details;
+  }
+
+  function render(spec) {
+    root.textContent = "";
+    var info = spec.info || {};
+    if (info.title) root.appendChild(element("h2", "", info.title + (info.version ? " " + info.version : "")));
+    if (info.description) root.appendChild(element("p", "", info.description));
+    var paths = spec.paths || {};
+    Object.keys(paths).forEach(function (path) {
+      Object.keys(paths[path]).forEach(function (method) {
+        if (["get", "put", "post", "delete", "options", "head", "patch", "trace"].indexOf(method) >= 0) {
+          root.appendChild(renderOperation(path, method, paths[path][method]));
+        }
+      });
+    });
+  }
+
+  fetch(root.getAttribute("data-url"))
+    .then(function (response) { return response.text(); })
+    .then(function (text) {
+      try {
+        render(JSON.parse(text));
+      } catch (err) {
+        root.textContent = "";
+        root.appendChild(element("pre", "", text));
+      }
+    })
+    .catch(function (err) { root.textContent = "Failed to load the spec: " + err; });
+})();
@@ -0,0 +1,853 @@
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{
+    body::{Bytes, Sender},
+    header::{
+        ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+        IF_RANGE, LAST_MODIFIED, RANGE,
+    },
+    http::response::Builder,
+    Body, Request, Response, StatusCode,
+};
+use log::{error, info, warn};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::{
+    error_response, etag, json, path_depth, percent_decode, query_param, resolve_path,
+    retain_servable, tree, ServeConfig,
+};
+
+/// Directory levels below the archived directory that are included, unless
+/// `--max-depth` stops sooner.
+const MAX_DEPTH: usize = 32;
+
+pub struct ArchiveConfig {
+    pub endpoint: String,
+    pub max_files: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = ();
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "zip" => Ok(Self::Zip),
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            _ => Err(()),
+        }
+    }
+}
+
+impl ArchiveFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+        }
+    }
+}
+
+/// CRC-32 as used by ZIP and gzip (reflected, polynomial 0xEDB88320).
+#[derive(Clone, Copy, Default)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    0xEDB8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    pub fn update(&mut self, data: &[u8]) {
+        let mut crc = !self.0;
+        for &byte in data {
+            crc = Self::TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+        self.0 = !crc;
+    }
+
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::default();
+    crc.update(data);
+    crc.value()
+}
+
+/// MS-DOS `(time, date)` fields for a ZIP entry, in UTC.
+fn dos_time(modified: SystemTime) -> (u16, u16) {
+    let stamp = json::rfc3339(modified);
+    let field = |range: std::ops::Range<usize>| stamp[range].parse::<u16>().unwrap_or(0);
+    let year = field(0..4).clamp(1980, 2107);
+    let date = ((year - 1980) << 9) | (field(5..7) << 5) | field(8..10);
+    let time = (field(11..13) << 11) | (field(14..16) << 5) | (field(17..19) / 2);
+    (time, date)
+}
+
+/// Writes a ZIP archive entry by entry. Entries are stored uncompressed, so
+/// each file only needs to be read once and the archive can be streamed.
+#[derive(Default)]
+pub struct ZipWriter {
+    offset: u32,
+    central_directory: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    /// The local header and data for one file.
+    pub fn entry(
+        &mut self,
+        name: &str,
+        modified: SystemTime,
+        data: &[u8],
+    ) -> std::io::Result<Vec<u8>> {
+        let too_large = || std::io::Error::other("archive exceeds the ZIP size limits");
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let name_len = u16::try_from(name.len()).map_err(|_| too_large())?;
+        self.entries = self.entries.checked_add(1).ok_or_else(too_large)?;
+        let (time, date) = dos_time(modified);
+        let crc = crc32(data);
+
+        // Version 2.0, UTF-8 names, stored.
+        let common = |out: &mut Vec<u8>| {
+            out.extend_from_slice(&20u16.to_le_bytes());
+            out.extend_from_slice(&0x0800u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&time.to_le_bytes());
+            out.extend_from_slice(&date.to_le_bytes());
+            out.extend_from_slice(&crc.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&name_len.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        };
+
+        let mut local = Vec::with_capacity(30 + name.len() + data.len());
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        common(&mut local);
+        local.extend_from_slice(name.as_bytes());
+        local.extend_from_slice(data);
+
+        let central = &mut self.central_directory;
+        central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes());
+        common(central);
+        // Comment length, disk number, internal and external attributes.
+        central.extend_from_slice(&[0; 10]);
+        central.extend_from_slice(&self.offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+
+        self.offset = u32::try_from(local.len())
+            .ok()
+            .and_then(|len| self.offset.checked_add(len))
+            .ok_or_else(too_large)?;
+        Ok(local)
+    }
+
+    /// The central directory and end record that close the archive.
+    pub fn finish(self) -> Vec<u8> {
+        let mut out = self.central_directory;
+        let size = out.len() as u32;
+        out.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&self.entries.to_le_bytes());
+        out.extend_from_slice(&self.entries.to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(&self.offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out
+    }
+}
+
+/// A ustar header followed by the file data padded to whole blocks.
+pub fn tar_entry(name: &str, modified: SystemTime, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut header = [0u8; 512];
+    // Names over 100 bytes are split into the 155 byte prefix field.
+    let (prefix, name) = if name.len() <= 100 {
+        ("", name)
+    } else {
+        name.char_indices()
+            .filter(|&(index, c)| c == '/' && index <= 155 && name.len() - index - 1 <= 100)
+            .map(|(index, _)| (&name[..index], &name[index + 1..]))
+            .next()
+            .ok_or_else(|| std::io::Error::other(format!("path too long for tar: {}", name)))?
+    };
+    let octal = |field: &mut [u8], value: u64| {
+        let digits = format!("{:0width$o}", value, width = field.len() - 1);
+        field[..digits.len()].copy_from_slice(digits.as_bytes());
+    };
+    let mtime = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], 0o644);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], data.len() as u64);
+    octal(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    // The checksum is computed with its own field read as spaces.
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&byte| byte as u32).sum();
+    let digits = format!("{:06o}\0 ", checksum);
+    header[148..156].copy_from_slice(digits.as_bytes());
+
+    let padding = (512 - data.len() % 512) % 512;
+    let mut out = Vec::with_capacity(512 + data.len() + padding);
+    out.extend_from_slice(&header);
+    out.extend_from_slice(data);
+    out.resize(out.len() + padding, 0);
+    Ok(out)
+}
+
+/// Wraps a stream in gzip using stored (uncompressed) deflate blocks, so
+/// output can be produced as input arrives without a compressor.
+#[derive(Default)]
+pub struct GzipWriter {
+    crc: Crc32,
+    size: u32,
+    started: bool,
+}
+
+impl GzipWriter {
+    pub fn write(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() + 10 + data.len() / 65535 * 5 + 5);
+        if !self.started {
+            self.started = true;
+            // Magic, deflate, no flags, no mtime, no extra flags, unknown OS.
+            out.extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+        }
+        for block in data.chunks(u16::MAX as usize) {
+            let len = block.len() as u16;
+            out.push(0);
+            out.extend_from_slice(&len.to_le_bytes());
+            out.extend_from_slice(&(!len).to_le_bytes());
+            out.extend_from_slice(block);
+        }
+        self.crc.update(data);
+        self.size = self.size.wrapping_add(data.len() as u32);
+        out
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let mut out = self.write(&[]);
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+        out.extend_from_slice(&self.crc.value().to_le_bytes());
+        out.extend_from_slice(&self.size.to_le_bytes());
+        out
+    }
+}
+
+/// Streams every file under `dir` into `sender`, one file in memory at a
+/// time.
+async fn write_archive(
+    mut sender: Sender,
+    dir: PathBuf,
+    entries: Vec<tree::FileEntry>,
+    format: ArchiveFormat,
+) -> std::io::Result<()> {
+    let send_err = |err: hyper::Error| std::io::Error::other(err);
+    let mut zip = ZipWriter::default();
+    let mut gzip = GzipWriter::default();
+    for entry in entries {
+        let name = &entry.path[1..];
+        let data = tokio::fs::read(dir.join(name)).await?;
+        let chunk = match format {
+            ArchiveFormat::Zip => zip.entry(name, entry.modified, &data)?,
+            ArchiveFormat::TarGz => gzip.write(&tar_entry(name, entry.modified, &data)?),
+        };
+        sender
+            .send_data(Bytes::from(chunk))
+            .await
+            .map_err(send_err)?;
+    }
+    let end = match format {
+        ArchiveFormat::Zip => zip.finish(),
+        ArchiveFormat::TarGz => {
+            let mut end = gzip.write(&[0; 1024]);
+            end.extend(gzip.finish());
+            end
+        }
+    };
+    sender.send_data(Bytes::from(end)).await.map_err(send_err)
+}
+
+/// The single `bytes=` range of a `Range` header as `(start, end)`, both
+/// inclusive. `None` when the header should be ignored, such as for
+/// multiple ranges, and `Some(Err(()))` when no byte of it is in `len`.
+fn parse_range(header: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    let range = if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 {
+            return Some(Err(()));
+        }
+        (len.saturating_sub(suffix), len.checked_sub(1)?)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = match end {
+            "" => u64::MAX,
+            end => end.parse().ok()?,
+        };
+        if end < start {
+            return None;
+        }
+        (start, end.min(len.saturating_sub(1)))
+    };
+    Some(if range.0 < len { Ok(range) } else { Err(()) })
+}
+
+/// Builds the ZIP in a temporary file so a range of it can be sent.
+async fn write_zip_file(dir: &Path, entries: &[tree::FileEntry]) -> std::io::Result<PathBuf> {
+    static ARCHIVES: AtomicU64 = AtomicU64::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "serve-dir-archive-{}-{}.zip",
+        std::process::id(),
+        ARCHIVES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = async {
+        let mut file = tokio::fs::File::create(&path).await?;
+        let mut zip = ZipWriter::default();
+        for entry in entries {
+            let name = &entry.path[1..];
+            let data = tokio::fs::read(dir.join(name)).await?;
+            file.write_all(&zip.entry(name, entry.modified, &data)?)
+                .await?;
+        }
+        file.write_all(&zip.finish()).await?;
+        file.flush().await
+    }
+    .await;
+    match result {
+        Ok(()) => Ok(path),
+        Err(err) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            Err(err)
+        }
+    }
+}
+
+/// Streams `len` bytes of `path` from `start`, then deletes the file.
+async fn send_range(mut sender: Sender, path: &Path, start: u64, len: u64) -> std::io::Result<()> {
+    let result = async {
+        let mut file = tokio::fs::File::open(path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        let mut file = file.take(len);
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            sender
+                .send_data(Bytes::copy_from_slice(&buffer[..read]))
+                .await
+                .map_err(std::io::Error::other)?;
+        }
+    }
+    .await;
+    let _ = tokio::fs::remove_file(path).await;
+    result
+}
+
+async fn handle_zip_range(
+    shared_data: &ServeConfig,
+    response_builder: Builder,
+    request: &Request<Body>,
+    range: &str,
+    dir: PathBuf,
+    entries: Vec<tree::FileEntry>,
+    time_of_request: u128,
+) -> Response<Body> {
+    let (uri, method) = (request.uri(), request.method());
+    let path = match write_zip_file(&dir, &entries).await {
+        Ok(path) => path,
+        Err(err) => {
+            error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+            return error_response(
+                shared_data,
+                response_builder,
+                500,
+                "Something Went Wrong :(",
+            )
+            .await;
+        }
+    };
+    let len = tokio::fs::metadata(&path)
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or_default();
+    let (start, end) = match parse_range(range, len) {
+        Some(Ok(range)) => range,
+        Some(Err(())) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            warn!(
+                "{}: [416] [{}] {} unsatisfiable archive range {}",
+                time_of_request, method, uri, range
+            );
+            let response_builder =
+                response_builder.header(CONTENT_RANGE, format!("bytes */{}", len));
+            return error_response(shared_data, response_builder, 416, "Range Not Satisfiable")
+                .await;
+        }
+        // An unusable header is ignored and the whole archive is sent.
+        None => (0, len.saturating_sub(1)),
+    };
+    let partial = end + 1 - start != len;
+    info!(
+        "{}: [{}] [{}] {} archiving {} files, bytes {}-{}/{}",
+        time_of_request,
+        if partial { 206 } else { 200 },
+        method,
+        uri,
+        entries.len(),
+        start,
+        end,
+        len
+    );
+    let (sender, body) = Body::channel();
+    let (logged_method, logged_uri) = (method.clone(), uri.clone());
+    tokio::spawn(async move {
+        if let Err(err) = send_range(sender, &path, start, end + 1 - start).await {
+            error!(
+                "{}: [{}] {} archive aborted {}",
+                time_of_request, logged_method, logged_uri, err
+            );
+        }
+    });
+    let response_builder = if partial {
+        response_builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len))
+    } else {
+        response_builder
+    };
+    response_builder
+        .header(CONTENT_LENGTH, end + 1 - start)
+        .body(body)
+        .unwrap()
+}
+
+/// A strong validator for the ZIP of `entries`, which is the same bytes for
+/// the same files, sizes and modification times.
+fn archive_etag(entries: &[tree::FileEntry]) -> String {
+    let mut key = String::new();
+    for entry in entries {
+        let mtime = entry
+            .modified
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        key.push_str(&format!("{}\0{}\0{}\n", entry.path, entry.size, mtime));
+    }
+    etag::content_etag(key.as_bytes())
+}
+
+/// Whether `If-Range`, when sent, still names this archive: its ETag, or
+/// the Last-Modified date of its newest file. A range is only honored when
+/// it does; otherwise the whole archive is sent.
+fn if_range_matches(
+    request: &Request<Body>,
+    etag: &str,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    let Some(if_range) = request
+        .headers()
+        .get(IF_RANGE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return true;
+    };
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        // A weak tag never matches, as If-Range needs a strong comparison.
+        return if_range == etag;
+    }
+    let secs = |time: SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    };
+    match (httpdate::parse_http_date(if_range), last_modified) {
+        (Ok(date), Some(modified)) => secs(date) == secs(modified),
+        _ => false,
+    }
+}
+
+/// The `path` an archive request packs, `/` by default.
+pub fn archived_path(uri: &hyper::Uri) -> &str {
+    query_param(uri.query(), "path").unwrap_or("/")
+}
+
+pub async fn handle_archive(
+    request: &Request<Body>,
+    shared_data: &ServeConfig,
+    config: &ArchiveConfig,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let (uri, method) = (request.uri(), request.method());
+    let format = match query_param(uri.query(), "format")
+        .unwrap_or("zip")
+        .parse::<ArchiveFormat>()
+    {
+        Ok(format) => format,
+        Err(()) => {
+            warn!(
+                "{}: [400] [{}] {} unknown archive format",
+                time_of_request, method, uri
+            );
+            return error_response(shared_data, response_builder, 400, "Unknown archive format")
+                .await;
+        }
+    };
+    let path = archived_path(uri);
+    let dir = if path.trim_matches('/').is_empty() {
+        Some(PathBuf::from(&shared_data.directory_path))
+    } else {
+        resolve_path(&shared_data.directory_path, path)
+    };
+    let dir = match dir.filter(|dir| dir.is_dir()) {
+        Some(dir) => dir,
+        None => {
+            warn!(
+                "{}: [404] [{}] {} archive directory not found",
+                time_of_request, method, uri
+            );
+            return error_response(shared_data, response_builder, 404, "404 Not Found").await;
+        }
+    };
+
+    let walk_dir = dir.clone();
+    let base = percent_decode(path).trim_matches('/').to_string();
+    let max_depth = shared_data.max_depth.map_or(MAX_DEPTH, |max_depth| {
+        max_depth.saturating_sub(path_depth(&base)).min(MAX_DEPTH)
+    });
+    let entries = tokio::task::spawn_blocking(move || tree::walk(&walk_dir, Some(max_depth)))
+        .await
+        .unwrap_or_else(|err| Err(std::io::Error::other(err)));
+    let entries = match entries {
+        Ok(mut entries) => {
+            // Only what a GET for each file would also serve.
+            retain_servable(
+                request,
+                shared_data,
+                &format!("/{}", base),
+                &dir,
+                &mut entries,
+            )
+            .await;
+            entries
+        }
+        Err(err) => {
+            error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+            return error_response(
+                shared_data,
+                response_builder,
+                500,
+                "Something Went Wrong :(",
+            )
+            .await;
+        }
+    };
+    if entries.len() > config.max_files {
+        warn!(
+            "{}: [413] [{}] {} archive of {} files exceeds the limit of {}",
+            time_of_request,
+            method,
+            uri,
+            entries.len(),
+            config.max_files
+        );
+        return error_response(
+            shared_data,
+            response_builder,
+            413,
+            "Too many files to archive",
+        )
+        .await;
+    }
+
+    let name = Path::new(percent_decode(path).trim_matches('/'))
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("archive"));
+    // A ZIP is stored, so the same files give the same bytes and a range
+    // of it can be resumed. A gzip stream can only be sent whole.
+    let response_builder = response_builder
+        .header(CONTENT_TYPE, format.content_type())
+        .header(
+            CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}.{}\"",
+                name.replace(['"', '\\'], "_"),
+                format.extension()
+            ),
+        )
+        .header(
+            ACCEPT_RANGES,
+            match format {
+                ArchiveFormat::Zip => "bytes",
+                ArchiveFormat::TarGz => "none",
+            },
+        );
+    // A resumed download must not splice in bytes of a different archive,
+    // so ranges are only given for the files as they are now.
+    let (response_builder, validators_match) = match format {
+        ArchiveFormat::Zip => {
+            let etag = archive_etag(&entries);
+            let last_modified = entries.iter().map(|entry| entry.modified).max();
+            let matches = if_range_matches(request, &etag, last_modified);
+            let response_builder = response_builder.header(ETAG, etag);
+            match last_modified {
+                Some(modified) => (
+                    response_builder.header(LAST_MODIFIED, httpdate::fmt_http_date(modified)),
+                    matches,
+                ),
+                None => (response_builder, matches),
+            }
+        }
+        ArchiveFormat::TarGz => (response_builder, true),
+    };
+    let range = request
+        .headers()
+        .get(RANGE)
+        .and_then(|range| range.to_str().ok())
+        .filter(|_| validators_match);
+    if let Some(range) = range {
+        if format == ArchiveFormat::TarGz {
+            warn!(
+                "{}: [416] [{}] {} range requested for a tar.gz archive",
+                time_of_request, method, uri
+            );
+            // The length isn't known until the stream has been written.
+            let response_builder = response_builder.header(CONTENT_RANGE, "bytes */*");
+            return error_response(shared_data, response_builder, 416, "Range Not Satisfiable")
+                .await;
+        }
+        return handle_zip_range(
+            shared_data,
+            response_builder,
+            request,
+            range,
+            dir,
+            entries,
+            time_of_request,
+        )
+        .await;
+    }
+    info!(
+        "{}: [200] [{}] {} archiving {} files",
+        time_of_request,
+        method,
+        uri,
+        entries.len()
+    );
+    let (sender, body) = Body::channel();
+    let (logged_method, logged_uri) = (method.clone(), uri.clone());
+    tokio::spawn(async move {
+        if let Err(err) = write_archive(sender, dir, entries, format).await {
+            error!(
+                "{}: [{}] {} archive aborted {}",
+                time_of_request, logged_method, logged_uri, err
+            );
+        }
+    });
+    response_builder.body(body).unwrap()
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn u16_at(data: &[u8], at: usize) -> usize {
+        u16::from_le_bytes([data[at], data[at + 1]]) as usize
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    /// `(name, contents)` of each entry, read through the central directory.
+    pub fn read_zip(zip: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = zip.len() - 22;
+        assert_eq!(0x0605_4b50, u32_at(zip, end));
+        let count = u16_at(zip, end + 10);
+        let mut at = u32_at(zip, end + 16);
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            assert_eq!(0x0201_4b50, u32_at(zip, at));
+            let crc = u32_at(zip, at + 16);
+            let name_len = u16_at(zip, at + 28);
+            let local = u32_at(zip, at + 42);
+            let name = String::from_utf8(zip[at + 46..at + 46 + name_len].to_vec()).unwrap();
+            assert_eq!(0x0403_4b50, u32_at(zip, local));
+            let size = u32_at(zip, local + 22);
+            let data_at = local + 30 + u16_at(zip, local + 26);
+            let data = zip[data_at..data_at + size].to_vec();
+            assert_eq!(crc, crc32(&data) as usize);
+            entries.push((name, data));
+            at += 46 + name_len;
+        }
+        entries
+    }
+
+    /// Undoes [`GzipWriter`], which only emits stored blocks.
+    pub fn read_gzip(gzip: &[u8]) -> Vec<u8> {
+        assert_eq!([0x1f, 0x8b, 8], gzip[..3]);
+        let mut at = 10;
+        let mut out = Vec::new();
+        loop {
+            let last = gzip[at] & 1 == 1;
+            assert_eq!(0, gzip[at] >> 1, "only stored blocks are expected");
+            let len = u16_at(gzip, at + 1);
+            assert_eq!(!(len as u16) as usize, u16_at(gzip, at + 3));
+            out.extend_from_slice(&gzip[at + 5..at + 5 + len]);
+            at += 5 + len;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(crc32(&out) as usize, u32_at(gzip, at));
+        assert_eq!(out.len(), u32_at(gzip, at + 4));
+        out
+    }
+
+    /// `(name, contents)` of each file in a tar stream.
+    pub fn read_tar(tar: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut entries = Vec::new();
+        let mut at = 0;
+        while tar[at..at + 512].iter().any(|&byte| byte != 0) {
+            let header = &tar[at..at + 512];
+            let field = |range: std::ops::Range<usize>| {
+                let field = &header[range];
+                let end = field
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(field.len());
+                String::from_utf8(field[..end].to_vec()).unwrap()
+            };
+            let mut checked = header.to_vec();
+            checked[148..156].fill(b' ');
+            let checksum: u32 = checked.iter().map(|&byte| byte as u32).sum();
+            assert_eq!(
+                checksum,
+                u32::from_str_radix(field(148..154).trim(), 8).unwrap()
+            );
+            let size = usize::from_str_radix(&field(124..135), 8).unwrap();
+            let prefix = field(345..500);
+            let name = match prefix.as_str() {
+                "" => field(0..100),
+                prefix => format!("{}/{}", prefix, field(0..100)),
+            };
+            entries.push((name, tar[at + 512..at + 512 + size].to_vec()));
+            at += 512 + size.div_ceil(512) * 512;
+        }
+        entries
+    }
+
+    #[test]
+    fn parse_range_test() {
+        assert_eq!(Some(Ok((0, 9))), parse_range("bytes=0-9", 100));
+        assert_eq!(Some(Ok((90, 99))), parse_range("bytes=90-", 100));
+        assert_eq!(Some(Ok((90, 99))), parse_range("bytes=-10", 100));
+        assert_eq!(Some(Ok((0, 99))), parse_range("bytes=-500", 100));
+        assert_eq!(Some(Ok((50, 99))), parse_range("bytes=50-500", 100));
+        assert_eq!(Some(Err(())), parse_range("bytes=100-", 100));
+        assert_eq!(Some(Err(())), parse_range("bytes=-0", 100));
+        assert_eq!(None, parse_range("bytes=0-1,5-6", 100));
+        assert_eq!(None, parse_range("bytes=9-1", 100));
+        assert_eq!(None, parse_range("items=0-1", 100));
+    }
+
+    #[test]
+    fn crc32_test() {
+        assert_eq!(0xCBF4_3926, crc32(b"123456789"));
+        assert_eq!(0, crc32(b""));
+    }
+
+    #[test]
+    fn dos_time_test() {
+        // 2024-02-29T13:45:30Z
+        let (time, date) = dos_time(UNIX_EPOCH + std::time::Duration::from_secs(1_709_214_330));
+        assert_eq!((13 << 11) | (45 << 5) | 15, time);
+        assert_eq!((44 << 9) | (2 << 5) | 29, date);
+    }
+
+    #[test]
+    fn zip_writer_test() {
+        let mut zip = ZipWriter::default();
+        let mut archive = zip.entry("a.txt", UNIX_EPOCH, b"alpha").unwrap();
+        archive.extend(zip.entry("dir/b.txt", UNIX_EPOCH, b"").unwrap());
+        archive.extend(zip.finish());
+        assert_eq!(
+            vec![
+                (String::from("a.txt"), b"alpha".to_vec()),
+                (String::from("dir/b.txt"), Vec::new())
+            ],
+            read_zip(&archive)
+        );
+    }
+
+    #[test]
+    fn tar_gz_test() {
+        let long_name = format!("{}/file.txt", "d".repeat(120));
+        let mut gzip = GzipWriter::default();
+        let mut archive = gzip.write(&tar_entry("a.txt", UNIX_EPOCH, b"alpha").unwrap());
+        archive.extend(gzip.write(&tar_entry(&long_name, UNIX_EPOCH, &[7; 70000]).unwrap()));
+        archive.extend(gzip.write(&[0; 1024]));
+        archive.extend(gzip.finish());
+        assert_eq!(
+            vec![
+                (String::from("a.txt"), b"alpha".to_vec()),
+                (long_name, vec![7; 70000])
+            ],
+            read_tar(&read_gzip(&archive))
+        );
+    }
+}
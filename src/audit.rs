@@ -0,0 +1,79 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use crate::json;
+
+/// One write operation, recorded as a JSON line in the audit log.
+pub struct AuditEntry<'a> {
+    pub timestamp: u128,
+    pub client: Option<String>,
+    pub user: Option<&'a str>,
+    pub operation: &'static str,
+    pub path: &'a str,
+    pub size_before: Option<u64>,
+    pub size_after: Option<u64>,
+    pub success: bool,
+}
+
+impl AuditEntry<'_> {
+    pub fn to_json(&self) -> String {
+        let optional_string =
+            |value: Option<&str>| value.map_or(String::from("null"), json::string);
+        let optional_number =
+            |value: Option<u64>| value.map_or(String::from("null"), |n| n.to_string());
+        format!(
+            "{{\"timestamp\":{},\"client\":{},\"user\":{},\"operation\":{},\"path\":{},\"size_before\":{},\"size_after\":{},\"success\":{}}}",
+            self.timestamp,
+            optional_string(self.client.as_deref()),
+            optional_string(self.user),
+            json::string(self.operation),
+            json::string(self.path),
+            optional_number(self.size_before),
+            optional_number(self.size_after),
+            self.success
+        )
+    }
+}
+
+/// Append-only audit log that is fsynced after every entry.
+pub struct AuditLogger {
+    file: File,
+}
+
+impl AuditLogger {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    pub fn record(&mut self, entry: &AuditEntry) -> std::io::Result<()> {
+        writeln!(self.file, "{}", entry.to_json())?;
+        self.file.sync_all()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn audit_entry_json_test() {
+        let entry = AuditEntry {
+            timestamp: 1,
+            client: Some(String::from("127.0.0.1")),
+            user: None,
+            operation: "PUT",
+            path: "/a\"b.txt",
+            size_before: None,
+            size_after: Some(5),
+            success: true,
+        };
+        assert_eq!(
+            r#"{"timestamp":1,"client":"127.0.0.1","user":null,"operation":"PUT","path":"/a\"b.txt","size_before":null,"size_after":5,"success":true}"#,
+            entry.to_json()
+        );
+    }
+}
@@ -0,0 +1,55 @@
+/// Draws `rows` as a two column table in box-drawing characters.
+pub fn render(rows: &[(&str, String)]) -> String {
+    let width = |text: &str| text.chars().count();
+    let key_width = rows.iter().map(|(key, _)| width(key)).max().unwrap_or(0);
+    let value_width = rows
+        .iter()
+        .map(|(_, value)| width(value))
+        .max()
+        .unwrap_or(0);
+    let line = |left: &str, middle: &str, right: &str| {
+        format!(
+            "{}{}{}{}{}\n",
+            left,
+            "─".repeat(key_width + 2),
+            middle,
+            "─".repeat(value_width + 2),
+            right
+        )
+    };
+
+    let mut out = line("┌", "┬", "┐");
+    for (key, value) in rows {
+        out.push_str(&format!(
+            "│ {}{} │ {}{} │\n",
+            key,
+            " ".repeat(key_width - width(key)),
+            value,
+            " ".repeat(value_width - width(value))
+        ));
+    }
+    out.push_str(&line("└", "┴", "┘"));
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_test() {
+        let rows = [
+            ("directory", String::from("./é/")),
+            ("tls", String::from("off")),
+        ];
+        assert_eq!(
+            concat!(
+                "┌───────────┬──────┐\n",
+                "│ directory │ ./é/ │\n",
+                "│ tls       │ off  │\n",
+                "└───────────┴──────┘\n"
+            ),
+            render(&rows)
+        );
+    }
+}
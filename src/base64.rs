@@ -0,0 +1,33 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard padded base64, as used by `Authorization: Basic`.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+
+    #[test]
+    fn encode_test() {
+        assert_eq!("", super::encode(b""));
+        assert_eq!("Zg==", super::encode(b"f"));
+        assert_eq!("Zm8=", super::encode(b"fo"));
+        assert_eq!("Zm9v", super::encode(b"foo"));
+        assert_eq!("dXNlcjpwYXNz", super::encode(b"user:pass"));
+    }
+}
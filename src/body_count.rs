@@ -0,0 +1,86 @@
+use hyper::{body::HttpBody, header::CONTENT_LENGTH, Body, Response};
+
+/// Hands back `response` with a body that passes the original through and
+/// calls `done` with the bytes the connection took once it has ended,
+/// failed or been dropped by a client that went away. `size_hint` can't do
+/// this: streamed bodies such as archives and event streams have none.
+pub fn count(response: Response<Body>, done: impl FnOnce(u64) + Send + 'static) -> Response<Body> {
+    let (mut parts, mut body) = response.into_parts();
+    let exact = body.size_hint().exact();
+    if exact == Some(0) {
+        done(0);
+        return Response::from_parts(parts, body);
+    }
+    // A channel has no size, so the length GET would have had is kept.
+    if let Some(length) = exact {
+        parts.headers.entry(CONTENT_LENGTH).or_insert(length.into());
+    }
+
+    let (mut sender, counted) = Body::channel();
+    tokio::spawn(async move {
+        let mut sent = 0;
+        while let Some(chunk) = body.data().await {
+            let Ok(chunk) = chunk else {
+                sender.abort();
+                done(sent);
+                return;
+            };
+            let len = chunk.len() as u64;
+            if sender.send_data(chunk).await.is_err() {
+                done(sent);
+                return;
+            }
+            sent += len;
+        }
+        if let Ok(Some(trailers)) = body.trailers().await {
+            let _ = sender.send_trailers(trailers).await;
+        }
+        done(sent);
+    });
+    Response::from_parts(parts, counted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn count_test() {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data("streamed ".into()).await.unwrap();
+            sender.send_data("body".into()).await.unwrap();
+        });
+        let (done, received) = tokio::sync::oneshot::channel();
+        let response = count(Response::new(body), move |bytes| {
+            done.send(bytes).unwrap();
+        });
+        assert!(!response.headers().contains_key(CONTENT_LENGTH));
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("streamed body", body);
+        assert_eq!(13, received.await.unwrap());
+
+        // A client that goes away still ends the count.
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move { while sender.send_data("x".into()).await.is_ok() {} });
+        let (done, received) = tokio::sync::oneshot::channel();
+        drop(count(Response::new(body), move |bytes| {
+            done.send(bytes).unwrap();
+        }));
+        assert_eq!(0, received.await.unwrap());
+
+        let response = count(Response::new(Body::from("hello")), |_| {});
+        assert_eq!("5", response.headers()[CONTENT_LENGTH]);
+
+        let counted = Arc::new(AtomicU64::new(u64::MAX));
+        let recorded = counted.clone();
+        count(Response::new(Body::empty()), move |bytes| {
+            recorded.store(bytes, Ordering::Relaxed);
+        });
+        assert_eq!(0, counted.load(Ordering::Relaxed));
+    }
+}
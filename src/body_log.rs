@@ -0,0 +1,123 @@
+use hyper::{body::HttpBody, Body, Request};
+
+/// Markers whose value is replaced, with the bytes that end that value.
+const SECRETS: [(&str, &[char]); 2] = [
+    ("password=", &['&', ';', '\r', '\n']),
+    ("authorization:", &['\r', '\n']),
+];
+
+/// Replaces whatever follows `password=` or `Authorization:`, in any case,
+/// with `[REDACTED]`.
+pub fn redact(text: &str) -> String {
+    // ASCII lowercasing keeps byte offsets, so matches index `text` too.
+    let lowercase = text.to_ascii_lowercase();
+    let mut redacted = String::new();
+    let mut position = 0;
+    while let Some((start, marker, terminators)) = SECRETS
+        .iter()
+        .filter_map(|(marker, terminators)| {
+            let start = position + lowercase[position..].find(marker)?;
+            Some((start, marker, terminators))
+        })
+        .min_by_key(|(start, _, _)| *start)
+    {
+        let value = start + marker.len();
+        let end = text[value..]
+            .find(*terminators)
+            .map_or(text.len(), |end| value + end);
+        redacted.push_str(&text[position..value]);
+        redacted.push_str("[REDACTED]");
+        position = end;
+    }
+    redacted.push_str(&text[position..]);
+    redacted
+}
+
+/// Reads up to `limit` bytes of the body for `--log-body` and hands back a
+/// request whose body replays them before the rest, so the handler still
+/// sees all of it and large uploads keep streaming.
+pub async fn peek(request: Request<Body>, limit: usize) -> (Request<Body>, String) {
+    let (parts, mut body) = request.into_parts();
+    let mut chunks = Vec::new();
+    let mut read = 0;
+    let mut failed = None;
+    while read < limit {
+        match body.data().await {
+            Some(Ok(chunk)) => {
+                read += chunk.len();
+                chunks.push(chunk);
+            }
+            Some(Err(err)) => {
+                failed = Some(err);
+                break;
+            }
+            None => break,
+        }
+    }
+    let mut logged: Vec<u8> = chunks.iter().flatten().copied().collect();
+    logged.truncate(limit);
+    let logged = redact(&String::from_utf8_lossy(&logged));
+
+    let (mut sender, replayed) = Body::channel();
+    tokio::spawn(async move {
+        for chunk in chunks {
+            if sender.send_data(chunk).await.is_err() {
+                return;
+            }
+        }
+        if failed.is_some() {
+            sender.abort();
+            return;
+        }
+        while let Some(chunk) = body.data().await {
+            match chunk {
+                Ok(chunk) => {
+                    if sender.send_data(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Err(_) => {
+                    sender.abort();
+                    return;
+                }
+            }
+        }
+    });
+    (Request::from_parts(parts, replayed), logged)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redact_test() {
+        assert_eq!(
+            "user=ann&password=[REDACTED]&remember=1",
+            redact("user=ann&password=hunter2&remember=1")
+        );
+        assert_eq!(
+            "new_Password=[REDACTED]\nAuthorization:[REDACTED]\r\nx-other: kept",
+            redact("new_Password=a b\nAuthorization: Basic dXNlcjpwYXNz\r\nx-other: kept")
+        );
+        assert_eq!("password=[REDACTED]", redact("password=secret"));
+        assert_eq!("no secrets here", redact("no secrets here"));
+        // Offsets stay right around multi-byte characters.
+        assert_eq!("café password=[REDACTED]", redact("café password=ünïcode"));
+    }
+
+    #[tokio::test]
+    async fn peek_test() {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data("password=12".into()).await.unwrap();
+            sender.send_data("34&rest".into()).await.unwrap();
+            sender.send_data(" of the body".into()).await.unwrap();
+        });
+        let request = Request::put("/form").body(body).unwrap();
+        let (request, logged) = peek(request, 10).await;
+        assert_eq!("password=[REDACTED]", logged);
+        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+        assert_eq!("password=1234&rest of the body", body);
+    }
+}
@@ -0,0 +1,58 @@
+/// Appends `stale-while-revalidate` and `stale-if-error` to a `Cache-Control`
+/// value. Directives are kept in order and only the first occurrence of each
+/// is kept, the stale directives being replaced when given.
+pub fn merge_cache_control(base: &str, swr: Option<u64>, sie: Option<u64>) -> String {
+    let mut directives: Vec<String> = Vec::new();
+    let name_of = |directive: &str| {
+        directive
+            .split('=')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+    };
+    let replaced = |name: &str| {
+        (name == "stale-while-revalidate" && swr.is_some())
+            || (name == "stale-if-error" && sie.is_some())
+    };
+    for directive in base.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        let name = name_of(directive);
+        if replaced(&name) || directives.iter().any(|d| name_of(d) == name) {
+            continue;
+        }
+        directives.push(String::from(directive));
+    }
+    if let Some(swr) = swr {
+        directives.push(format!("stale-while-revalidate={}", swr));
+    }
+    if let Some(sie) = sie {
+        directives.push(format!("stale-if-error={}", sie));
+    }
+    directives.join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::merge_cache_control;
+
+    #[test]
+    fn merge_cache_control_test() {
+        assert_eq!("max-age=60", merge_cache_control("max-age=60", None, None));
+        assert_eq!(
+            "public, max-age=60, stale-while-revalidate=3600",
+            merge_cache_control("public, max-age=60", Some(3600), None)
+        );
+        assert_eq!(
+            "stale-while-revalidate=1, stale-if-error=2",
+            merge_cache_control("", Some(1), Some(2))
+        );
+        assert_eq!(
+            "max-age=60, stale-if-error=5, stale-while-revalidate=10",
+            merge_cache_control(
+                "max-age=60, stale-if-error=5, stale-while-revalidate=1, Max-Age=5",
+                Some(10),
+                None
+            )
+        );
+    }
+}
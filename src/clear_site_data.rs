@@ -0,0 +1,110 @@
+use crate::glob;
+
+/// Data types browsers accept in `Clear-Site-Data`.
+const TYPES: [&str; 8] = [
+    "cache",
+    "cookies",
+    "storage",
+    "executionContexts",
+    "clientHints",
+    "prefetchCache",
+    "prerenderCache",
+    "*",
+];
+
+/// Clears browser data for `types` on every response, or only on paths
+/// matching `pattern`, such as a logout page.
+pub struct ClearSiteDataRule {
+    pub pattern: Option<String>,
+    pub types: Vec<String>,
+}
+
+impl ClearSiteDataRule {
+    /// Parses a comma separated list of data types.
+    pub fn parse(pattern: Option<&str>, types: &str) -> Option<Self> {
+        let types: Vec<String> = types
+            .split(',')
+            .map(|data_type| String::from(data_type.trim()))
+            .collect();
+        if !types
+            .iter()
+            .all(|data_type| TYPES.contains(&data_type.as_str()))
+        {
+            return None;
+        }
+        Some(Self {
+            pattern: pattern.map(String::from),
+            types,
+        })
+    }
+
+    /// Parses `/logout:cookies,storage`.
+    pub fn parse_path(rule: &str) -> Option<Self> {
+        let (pattern, types) = rule.split_once(':')?;
+        if !pattern.starts_with('/') {
+            return None;
+        }
+        Self::parse(Some(pattern), types)
+    }
+}
+
+/// `Clear-Site-Data` value for `uri_path`: `"cookies", "cache"`.
+pub fn clear_site_data(rules: &[ClearSiteDataRule], uri_path: &str) -> Option<String> {
+    let mut types: Vec<&str> = Vec::new();
+    for rule in rules {
+        let matches = rule
+            .pattern
+            .as_ref()
+            .is_none_or(|pattern| glob::matches(pattern, uri_path));
+        if matches {
+            for data_type in &rule.types {
+                if !types.contains(&data_type.as_str()) {
+                    types.push(data_type);
+                }
+            }
+        }
+    }
+    if types.contains(&"*") {
+        types = vec!["*"];
+    }
+    (!types.is_empty()).then(|| {
+        types
+            .iter()
+            .map(|data_type| format!("\"{}\"", data_type))
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let rule = ClearSiteDataRule::parse_path("/logout:cookies, storage").unwrap();
+        assert_eq!(Some(String::from("/logout")), rule.pattern);
+        assert_eq!(vec!["cookies", "storage"], rule.types);
+        assert!(ClearSiteDataRule::parse(None, "cookies,everything").is_none());
+        assert!(ClearSiteDataRule::parse_path("logout:cookies").is_none());
+        assert!(ClearSiteDataRule::parse_path("/logout").is_none());
+    }
+
+    #[test]
+    fn clear_site_data_test() {
+        let rules = [
+            ClearSiteDataRule::parse_path("/logout:cookies").unwrap(),
+            ClearSiteDataRule::parse_path("/logout:cookies,cache").unwrap(),
+            ClearSiteDataRule::parse_path("/reset:*,cookies").unwrap(),
+        ];
+        assert_eq!(
+            Some(String::from("\"cookies\", \"cache\"")),
+            clear_site_data(&rules, "/logout")
+        );
+        assert_eq!(
+            Some(String::from("\"*\"")),
+            clear_site_data(&rules, "/reset")
+        );
+        assert_eq!(None, clear_site_data(&rules, "/index.html"));
+    }
+}
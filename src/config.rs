@@ -0,0 +1,483 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
+
+use hyper::header::{HeaderName, HeaderValue};
+
+use crate::ServeConfig;
+
+pub const DEFAULT_HOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+pub const DEFAULT_PORT: u16 = 8080;
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    MissingDirectory,
+    DirectoryNotFound(PathBuf),
+    InvalidHeader(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingDirectory => write!(f, "no directory to serve"),
+            Self::DirectoryNotFound(path) => write!(f, "'{}' is not a directory", path.display()),
+            Self::InvalidHeader(header) => write!(f, "invalid header {}", header),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Where an effective setting came from. serve-dir reads no config file or
+/// environment variables, so it is either the command line or a default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Cli,
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Cli => "cli",
+            Self::Default => "default",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ConfigEntry {
+    pub name: &'static str,
+    pub value: String,
+    pub source: Source,
+}
+
+impl ServeConfig {
+    /// Effective values of the commonly tuned options. `options` are the
+    /// names of the given arguments, as collected for the banner; an entry
+    /// is attributed to the command line when one of its flags is there.
+    /// Credentials are summarized, never shown.
+    pub fn explain(&self, options: &[String]) -> Vec<ConfigEntry> {
+        let on_off = |enabled: bool| String::from(if enabled { "on" } else { "off" });
+        let list = |items: &[String]| {
+            if items.is_empty() {
+                String::from("none")
+            } else {
+                items.join(", ")
+            }
+        };
+        let addr = self
+            .addr
+            .unwrap_or(SocketAddr::new(DEFAULT_HOST, DEFAULT_PORT));
+        let header_names: Vec<String> = self.headers.iter().map(|(key, _)| key.clone()).collect();
+        let disabled_methods: Vec<String> = self
+            .disabled_methods
+            .iter()
+            .map(|method| method.to_string())
+            .collect();
+        let auth = match (&self.auth, &self.auth_user) {
+            (Some(_), Some(user)) => format!("basic, user {}", user),
+            (Some(_), None) => String::from("basic"),
+            (None, _) => on_off(false),
+        };
+        let entries: [(&'static str, &[&str], String); 28] = [
+            ("directory", &[], self.directory_path.clone()),
+            ("--host", &["--host", "-h"], addr.ip().to_string()),
+            ("--port", &["--port", "-p"], addr.port().to_string()),
+            (
+                "--404",
+                &["--404"],
+                self.not_found_file_path
+                    .clone()
+                    .unwrap_or_else(|| String::from("none")),
+            ),
+            ("--header", &["--header", "-H"], list(&header_names)),
+            (
+                "--header-file",
+                &["--header-file"],
+                self.header_file.as_ref().map_or_else(
+                    || String::from("none"),
+                    |file| file.path.display().to_string(),
+                ),
+            ),
+            ("--auth", &["--auth"], auth),
+            (
+                "--cors-origin",
+                &["--cors-origin"],
+                list(&self.cors.allowed_origins),
+            ),
+            (
+                "--etag-mode",
+                &["--etag-mode"],
+                String::from(self.etag_mode.as_str()),
+            ),
+            ("--weak-etag", &["--weak-etag"], on_off(self.weak_etag)),
+            (
+                "--cache-control",
+                &["--cache-control"],
+                self.cache_control
+                    .clone()
+                    .unwrap_or_else(|| String::from("none")),
+            ),
+            (
+                "--list-directories",
+                &["--list-directories"],
+                on_off(self.list_directories),
+            ),
+            ("--index-json", &["--index-json"], on_off(self.index_json)),
+            (
+                "--index-fallback",
+                &["--index-fallback"],
+                if self.index_fallback.is_empty() {
+                    String::from("index.html")
+                } else {
+                    list(&self.index_fallback)
+                },
+            ),
+            (
+                "--sort",
+                &["--sort"],
+                String::from(self.default_sort.as_str()),
+            ),
+            ("--allow-put", &["--allow-put"], on_off(self.allow_put)),
+            (
+                "--allow-upload",
+                &["--allow-upload", "--allow-overwrite"],
+                String::from(match (self.allow_upload, self.allow_overwrite) {
+                    (false, _) => "off",
+                    (true, false) => "on",
+                    (true, true) => "on, overwriting",
+                }),
+            ),
+            (
+                "--max-body-size",
+                &["--max-body-size"],
+                self.max_body_size
+                    .map_or_else(|| String::from("unlimited"), |size| size.to_string()),
+            ),
+            (
+                "--allow-delete",
+                &["--allow-delete"],
+                on_off(self.allow_delete),
+            ),
+            ("--webdav", &["--webdav"], on_off(self.webdav)),
+            (
+                "--allow-trace",
+                &["--allow-trace"],
+                on_off(self.allow_trace),
+            ),
+            (
+                "--disable-method",
+                &["--disable-method"],
+                list(&disabled_methods),
+            ),
+            ("--forbid-ext", &["--forbid-ext"], list(&self.forbid_ext)),
+            (
+                "--forbid-hidden",
+                &["--forbid-hidden"],
+                on_off(self.forbid_hidden),
+            ),
+            (
+                "--max-depth",
+                &["--max-depth"],
+                self.max_depth
+                    .map_or_else(|| String::from("unlimited"), |depth| depth.to_string()),
+            ),
+            (
+                "--follow-symlinks",
+                &["--follow-symlinks"],
+                String::from(self.follow_symlinks.as_str()),
+            ),
+            (
+                "--slow-log-threshold",
+                &["--slow-log-threshold"],
+                self.slow_log_threshold.map_or_else(
+                    || String::from("off"),
+                    |threshold| format!("{:?}", threshold),
+                ),
+            ),
+            ("--dir-config", &["--dir-config"], on_off(self.dir_config)),
+        ];
+        entries
+            .into_iter()
+            .map(|(name, flags, value)| {
+                // The directory is the required first argument.
+                let given = flags.is_empty()
+                    || options
+                        .iter()
+                        .any(|option| flags.contains(&option.as_str()));
+                ConfigEntry {
+                    name,
+                    value,
+                    source: if given { Source::Cli } else { Source::Default },
+                }
+            })
+            .collect()
+    }
+}
+
+/// Lays `entries` out in aligned `OPTION`, `VALUE` and `SOURCE` columns.
+pub fn format_config_table(entries: &[ConfigEntry]) -> String {
+    const HEADINGS: [&str; 3] = ["OPTION", "VALUE", "SOURCE"];
+    let rows: Vec<[String; 3]> = entries
+        .iter()
+        .map(|entry| {
+            [
+                String::from(entry.name),
+                entry.value.clone(),
+                entry.source.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADINGS.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    let headings = HEADINGS.map(String::from);
+    for row in std::iter::once(&headings).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// Builds the core of a [`ServeConfig`]: where to serve from and listen
+/// on, and the headers sent with every response. Everything else keeps its
+/// default and can be filled in with struct update syntax on the result.
+#[derive(Default)]
+pub struct ServeConfigBuilder {
+    directory: Option<PathBuf>,
+    headers: Vec<(String, String)>,
+    host: Option<IpAddr>,
+    port: Option<u16>,
+    not_found_path: Option<String>,
+}
+
+impl ServeConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_directory(mut self, path: impl Into<PathBuf>) -> Self {
+        self.directory = Some(path.into());
+        self
+    }
+
+    /// Adds a response header, replacing an earlier one of the same name.
+    pub fn with_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.headers
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(&key));
+        self.headers.push((key, value.into()));
+        self
+    }
+
+    pub fn with_host(mut self, host: IpAddr) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn with_not_found_path(mut self, path: impl Into<String>) -> Self {
+        self.not_found_path = Some(path.into());
+        self
+    }
+
+    pub fn build(self) -> Result<ServeConfig, ConfigError> {
+        let directory = self.directory.ok_or(ConfigError::MissingDirectory)?;
+        if !directory.is_dir() {
+            return Err(ConfigError::DirectoryNotFound(directory));
+        }
+        for (key, value) in &self.headers {
+            let is_valid = HeaderName::from_bytes(key.as_bytes()).is_ok()
+                && HeaderValue::from_str(value).is_ok();
+            if !is_valid {
+                return Err(ConfigError::InvalidHeader(format!("{}:{}", key, value)));
+            }
+        }
+        let directory_path =
+            normalize_directory(&directory.to_string_lossy(), std::path::MAIN_SEPARATOR);
+        Ok(ServeConfig {
+            directory_path,
+            headers: self.headers,
+            addr: Some(SocketAddr::new(
+                self.host.unwrap_or(DEFAULT_HOST),
+                self.port.unwrap_or(DEFAULT_PORT),
+            )),
+            not_found_file_path: self.not_found_path,
+            ..Default::default()
+        })
+    }
+}
+
+/// `path` with exactly one trailing `separator`, so request paths can be
+/// appended to it. Where the native separator is `\\`, forward slashes are
+/// converted too: `C:\\srv/` would otherwise reach Win32 APIs mixed. On
+/// Unix a backslash is an ordinary file name character and is kept.
+fn normalize_directory(path: &str, separator: char) -> String {
+    let mut path = if separator == '\\' {
+        path.replace('/', "\\")
+    } else {
+        String::from(path)
+    };
+    // A lone separator is the root and keeps it.
+    let trimmed = path.trim_end_matches(separator).len();
+    path.truncate(trimmed);
+    path.push(separator);
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("serve-dir-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn build_test() {
+        let dir = temp_dir();
+        let config = ServeConfigBuilder::new()
+            .with_directory(&dir)
+            .with_header("x-a", "1")
+            .with_header("x-b", "2")
+            .with_header("X-A", "3")
+            .with_host(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+            .with_port(9999)
+            .with_not_found_path("./404.html")
+            .build()
+            .unwrap();
+        assert_eq!(format!("{}/", dir.display()), config.directory_path);
+        assert_eq!(
+            vec![
+                (String::from("x-b"), String::from("2")),
+                (String::from("X-A"), String::from("3"))
+            ],
+            config.headers
+        );
+        assert_eq!(Some("0.0.0.0:9999".parse().unwrap()), config.addr);
+        assert_eq!(Some(String::from("./404.html")), config.not_found_file_path);
+
+        let config = ServeConfigBuilder::new()
+            .with_directory(&dir)
+            .build()
+            .unwrap();
+        assert_eq!(
+            Some(SocketAddr::new(DEFAULT_HOST, DEFAULT_PORT)),
+            config.addr
+        );
+    }
+
+    #[test]
+    fn explain_test() {
+        let config = ServeConfig {
+            auth: Some(String::from("Basic dXNlcjpzZWNyZXQ=")),
+            auth_user: Some(String::from("user")),
+            ..ServeConfigBuilder::new()
+                .with_directory(temp_dir())
+                .with_port(9000)
+                .build()
+                .unwrap()
+        };
+        let options = [String::from("-p"), String::from("--auth")];
+        let entries = config.explain(&options);
+        let entry = |name: &str| entries.iter().find(|entry| entry.name == name).unwrap();
+        assert_eq!(Source::Cli, entry("directory").source);
+        assert_eq!(
+            ("9000", Source::Cli),
+            (entry("--port").value.as_str(), entry("--port").source)
+        );
+        assert_eq!(
+            ("127.0.0.1", Source::Default),
+            (entry("--host").value.as_str(), entry("--host").source)
+        );
+        assert_eq!("basic, user user", entry("--auth").value);
+        assert_eq!(Source::Cli, entry("--auth").source);
+        assert_eq!(
+            ("mtime", Source::Default),
+            (
+                entry("--etag-mode").value.as_str(),
+                entry("--etag-mode").source
+            )
+        );
+
+        let table = format_config_table(&entries);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(entries.len() + 1, lines.len());
+        assert!(lines[0].starts_with("OPTION") && lines[0].ends_with("SOURCE"));
+        let port = lines
+            .iter()
+            .find(|line| line.starts_with("--port "))
+            .unwrap();
+        assert_eq!(
+            vec!["--port", "9000", "cli"],
+            port.split_whitespace().collect::<Vec<_>>()
+        );
+        let value_column = lines[0].find("VALUE").unwrap();
+        assert!(lines
+            .iter()
+            .skip(1)
+            .all(|line| line[..value_column].ends_with("  ")));
+        assert!(!table.contains("secret") && !table.contains("dXNlcjpzZWNyZXQ"));
+    }
+
+    #[test]
+    fn normalize_directory_test() {
+        assert_eq!("C:\\srv\\www\\", normalize_directory("C:\\srv\\www", '\\'));
+        assert_eq!("C:\\srv\\www\\", normalize_directory("C:\\srv/www/", '\\'));
+        assert_eq!(
+            "C:\\srv\\www\\",
+            normalize_directory("C:/srv\\www\\/", '\\')
+        );
+        assert_eq!("./public/", normalize_directory("./public", '/'));
+        assert_eq!("./public/", normalize_directory("./public//", '/'));
+        assert_eq!("/", normalize_directory("/", '/'));
+        assert_eq!("./odd\\name/", normalize_directory("./odd\\name", '/'));
+    }
+
+    #[test]
+    fn validation_test() {
+        let build_err = |builder: ServeConfigBuilder| builder.build().err().unwrap();
+        assert_eq!(
+            ConfigError::MissingDirectory,
+            build_err(ServeConfigBuilder::new())
+        );
+        let missing = temp_dir().join("missing");
+        assert_eq!(
+            ConfigError::DirectoryNotFound(missing.clone()),
+            build_err(ServeConfigBuilder::new().with_directory(&missing))
+        );
+        assert_eq!(
+            format!("'{}' is not a directory", missing.display()),
+            build_err(ServeConfigBuilder::new().with_directory(&missing)).to_string()
+        );
+        assert_eq!(
+            ConfigError::InvalidHeader(String::from("bad key:value")),
+            build_err(
+                ServeConfigBuilder::new()
+                    .with_directory(temp_dir())
+                    .with_header("bad key", "value")
+            )
+        );
+    }
+}
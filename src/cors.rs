@@ -0,0 +1,249 @@
+use hyper::{
+    header::{HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_ORIGIN, VARY},
+    HeaderMap,
+};
+
+const TIMING_ALLOW_ORIGIN: &str = "timing-allow-origin";
+
+#[derive(Clone, Default)]
+pub struct CorsConfig {
+    /// Origins echoed back in `access-control-allow-origin` when they match
+    /// the request's `Origin`. Empty means the static headers decide.
+    pub allowed_origins: Vec<String>,
+    pub credentials: bool,
+    pub vary_origin: bool,
+    /// Origins sent in `timing-allow-origin`. Empty falls back to echoing an
+    /// origin from `allowed_origins`.
+    pub timing_allow_origin: Vec<String>,
+}
+
+impl CorsConfig {
+    pub fn apply(&self, origin: Option<&HeaderValue>, headers: &mut HeaderMap) {
+        if !self.allowed_origins.is_empty() {
+            let allowed_origin = origin.filter(|origin| {
+                self.allowed_origins
+                    .iter()
+                    .any(|allowed| origin.as_bytes() == allowed.as_bytes())
+            });
+            match allowed_origin {
+                Some(origin) => {
+                    headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+                    if self.timing_allow_origin.is_empty() {
+                        headers.insert(TIMING_ALLOW_ORIGIN, origin.clone());
+                    }
+                }
+                None => {
+                    headers.remove(ACCESS_CONTROL_ALLOW_ORIGIN);
+                }
+            }
+        }
+        if !self.timing_allow_origin.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&self.timing_allow_origin.join(", ")) {
+                headers.insert(TIMING_ALLOW_ORIGIN, value);
+            }
+        }
+        if self.credentials && headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN) {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+
+        let vary_origin = self.vary_origin || !self.allowed_origins.is_empty();
+        let has_cors_header = headers
+            .keys()
+            .any(|name| name.as_str().starts_with("access-control-"));
+        if vary_origin && has_cors_header {
+            add_vary(headers, "Origin");
+        }
+    }
+}
+
+const FONT_EXTENSIONS: [&str; 5] = ["woff", "woff2", "ttf", "otf", "eot"];
+
+/// Opens font files up to cross-origin `@font-face` loads, independent of
+/// the configured CORS policy.
+pub fn apply_font_cors(path: &str, headers: &mut HeaderMap) {
+    let is_font = path.rsplit_once('.').is_some_and(|(_, extension)| {
+        FONT_EXTENSIONS
+            .iter()
+            .any(|font| extension.eq_ignore_ascii_case(font))
+    });
+    if is_font {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        headers.insert(
+            "cross-origin-resource-policy",
+            HeaderValue::from_static("cross-origin"),
+        );
+    }
+}
+
+/// `--cross-origin-isolated-mode`: whether embedded cross-origin
+/// resources must opt in with CORP or CORS, or are loaded without
+/// credentials instead.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum IsolationMode {
+    #[default]
+    Strict,
+    Permissive,
+}
+
+impl IsolationMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "strict" => Some(Self::Strict),
+            "permissive" => Some(Self::Permissive),
+            _ => None,
+        }
+    }
+}
+
+/// The headers that make pages cross-origin isolated, which browsers
+/// require for `SharedArrayBuffer` and high-resolution timers. CORP lets
+/// other isolated pages embed the files served here.
+pub fn isolation_headers(mode: IsolationMode) -> [(&'static str, &'static str); 4] {
+    let embedder_policy = match mode {
+        IsolationMode::Strict => "require-corp",
+        IsolationMode::Permissive => "credentialless",
+    };
+    [
+        ("cross-origin-opener-policy", "same-origin"),
+        ("cross-origin-embedder-policy", embedder_policy),
+        ("cross-origin-resource-policy", "cross-origin"),
+        ("origin-agent-cluster", "?1"),
+    ]
+}
+
+/// Adds `value` to the `Vary` header unless it is already listed.
+pub fn add_vary(headers: &mut HeaderMap, value: &'static str) {
+    let already_listed = headers.get_all(VARY).iter().any(|vary| {
+        vary.to_str().is_ok_and(|vary| {
+            vary.split(',')
+                .any(|name| name.trim().eq_ignore_ascii_case(value) || name.trim() == "*")
+        })
+    });
+    if !already_listed {
+        headers.append(VARY, HeaderValue::from_static(value));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn wildcard_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        headers
+    }
+
+    #[test]
+    fn wildcard_vary_test() {
+        let mut headers = wildcard_headers();
+        CorsConfig::default().apply(None, &mut headers);
+        assert!(!headers.contains_key(VARY));
+
+        let config = CorsConfig {
+            vary_origin: true,
+            ..Default::default()
+        };
+        config.apply(None, &mut headers);
+        config.apply(None, &mut headers);
+        assert_eq!(
+            vec!["Origin"],
+            headers.get_all(VARY).iter().collect::<Vec<_>>()
+        );
+
+        let mut headers = HeaderMap::new();
+        config.apply(None, &mut headers);
+        assert!(!headers.contains_key(VARY));
+    }
+
+    #[test]
+    fn per_origin_test() {
+        let config = CorsConfig {
+            allowed_origins: vec![String::from("https://a.example")],
+            credentials: true,
+            ..Default::default()
+        };
+        let mut headers = wildcard_headers();
+        config.apply(
+            Some(&HeaderValue::from_static("https://a.example")),
+            &mut headers,
+        );
+        assert_eq!("https://a.example", headers[ACCESS_CONTROL_ALLOW_ORIGIN]);
+        assert_eq!("true", headers[ACCESS_CONTROL_ALLOW_CREDENTIALS]);
+        assert_eq!("Origin", headers[VARY]);
+
+        let mut headers = wildcard_headers();
+        config.apply(
+            Some(&HeaderValue::from_static("https://b.example")),
+            &mut headers,
+        );
+        assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+    }
+
+    #[test]
+    fn timing_allow_origin_test() {
+        let a = HeaderValue::from_static("https://a.example");
+        let config = CorsConfig {
+            allowed_origins: vec![String::from("https://a.example")],
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        config.apply(Some(&a), &mut headers);
+        assert_eq!("https://a.example", headers[TIMING_ALLOW_ORIGIN]);
+        let mut headers = HeaderMap::new();
+        config.apply(
+            Some(&HeaderValue::from_static("https://b.example")),
+            &mut headers,
+        );
+        assert!(!headers.contains_key(TIMING_ALLOW_ORIGIN));
+
+        let config = CorsConfig {
+            allowed_origins: vec![String::from("https://a.example")],
+            timing_allow_origin: vec![
+                String::from("https://analytics.example"),
+                String::from("https://rum.example"),
+            ],
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        config.apply(Some(&a), &mut headers);
+        assert_eq!(
+            "https://analytics.example, https://rum.example",
+            headers[TIMING_ALLOW_ORIGIN]
+        );
+
+        let config = CorsConfig {
+            timing_allow_origin: vec![String::from("*")],
+            ..Default::default()
+        };
+        let mut headers = HeaderMap::new();
+        config.apply(None, &mut headers);
+        assert_eq!("*", headers[TIMING_ALLOW_ORIGIN]);
+    }
+
+    #[test]
+    fn isolation_headers_test() {
+        assert_eq!(
+            [
+                ("cross-origin-opener-policy", "same-origin"),
+                ("cross-origin-embedder-policy", "require-corp"),
+                ("cross-origin-resource-policy", "cross-origin"),
+                ("origin-agent-cluster", "?1"),
+            ],
+            isolation_headers(IsolationMode::Strict)
+        );
+        assert_eq!(
+            ("cross-origin-embedder-policy", "credentialless"),
+            isolation_headers(IsolationMode::Permissive)[1]
+        );
+        assert_eq!(
+            Some(IsolationMode::Permissive),
+            IsolationMode::parse("permissive")
+        );
+        assert_eq!(None, IsolationMode::parse("relaxed"));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::base64;
+
+/// A fresh 128-bit nonce, base64 encoded, read from the OS random source.
+#[cfg(unix)]
+pub fn nonce() -> std::io::Result<String> {
+    use std::io::Read;
+
+    let mut bytes = [0u8; 16];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(base64::encode(&bytes))
+}
+
+#[cfg(not(unix))]
+pub fn nonce() -> std::io::Result<String> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "CSP nonces need /dev/urandom",
+    ))
+}
+
+/// Adds `'nonce-<nonce>'` to the `script-src` directive of `policy`, adding
+/// the directive when there is none.
+pub fn with_nonce(policy: Option<&str>, nonce: &str) -> String {
+    let source = format!("'nonce-{}'", nonce);
+    let mut found = false;
+    let mut directives: Vec<String> = policy
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| {
+            let name = directive.split_whitespace().next().unwrap_or_default();
+            if name.eq_ignore_ascii_case("script-src") {
+                found = true;
+                format!("{} {}", directive, source)
+            } else {
+                String::from(directive)
+            }
+        })
+        .collect();
+    if !found {
+        directives.push(format!("script-src {}", source));
+    }
+    directives.join("; ")
+}
+
+/// Inserts `<meta name="csp-nonce">` before `</head>`, if the page has one.
+pub fn inject_meta(html: &[u8], nonce: &str) -> Option<Vec<u8>> {
+    let end_of_head = html
+        .windows(7)
+        .position(|window| window.eq_ignore_ascii_case(b"</head>"))?;
+    let meta = format!("<meta name=\"csp-nonce\" content=\"{}\">", nonce);
+    let mut out = Vec::with_capacity(html.len() + meta.len());
+    out.extend_from_slice(&html[..end_of_head]);
+    out.extend_from_slice(meta.as_bytes());
+    out.extend_from_slice(&html[end_of_head..]);
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn nonce_test() {
+        let first = nonce().unwrap();
+        assert_eq!(24, first.len());
+        assert_ne!(first, nonce().unwrap());
+    }
+
+    #[test]
+    fn with_nonce_test() {
+        assert_eq!("script-src 'nonce-abc'", with_nonce(None, "abc"));
+        assert_eq!(
+            "default-src 'self'; script-src 'self' 'nonce-abc'",
+            with_nonce(Some("default-src 'self'; script-src 'self';"), "abc")
+        );
+        assert_eq!(
+            "default-src 'self'; script-src 'nonce-abc'",
+            with_nonce(Some("default-src 'self'"), "abc")
+        );
+    }
+
+    #[test]
+    fn inject_meta_test() {
+        assert_eq!(
+            b"<html><HEAD><title>a</title><meta name=\"csp-nonce\" content=\"abc\"></HEAD></html>"
+                .to_vec(),
+            inject_meta(b"<html><HEAD><title>a</title></HEAD></html>", "abc").unwrap()
+        );
+        assert_eq!(None, inject_meta(b"<p>no head</p>", "abc"));
+    }
+}
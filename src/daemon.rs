@@ -0,0 +1,178 @@
+//! `--daemon` and `--stop`. The fork happens before the async runtime
+//! starts, while the process still has a single thread, and the parent only
+//! exits once the child reports the socket is bound.
+
+use std::path::Path;
+
+pub const DEFAULT_PID_FILE: &str = "serve-dir.pid";
+
+fn read_pid(pid_file: &Path) -> std::io::Result<i32> {
+    std::fs::read_to_string(pid_file)?
+        .trim()
+        .parse()
+        .ok()
+        .filter(|&pid: &i32| pid > 0)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid pid file"))
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        fs::{File, OpenOptions},
+        io::{Read, Write},
+        os::unix::io::{AsRawFd, FromRawFd},
+        path::Path,
+        sync::Mutex,
+    };
+
+    /// What the child still has to do once it is serving: the write end of
+    /// the readiness pipe and where stdout and stderr go from then on, until
+    /// [`detach`] has redirected them.
+    struct Pending {
+        ready: File,
+        output: Option<File>,
+    }
+
+    static PENDING: Mutex<Option<Pending>> = Mutex::new(None);
+
+    fn check(result: libc::c_int) -> std::io::Result<libc::c_int> {
+        if result == -1 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Forks into the background. Returns in the child, while the parent
+    /// waits for [`notify_ready`] and exits: with 0 after writing the child's
+    /// PID to `pid_file`, or with 1 if the child quit first.
+    pub fn daemonize(pid_file: &Path, log_file: Option<&Path>) -> std::io::Result<()> {
+        let output = match log_file {
+            Some(path) => OpenOptions::new().create(true).append(true).open(path)?,
+            None => OpenOptions::new().write(true).open("/dev/null")?,
+        };
+        let mut fds = [0; 2];
+        // SAFETY: `fds` has room for the two descriptors pipe writes.
+        check(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+        // SAFETY: pipe returned two fresh descriptors owned by nothing else.
+        let (mut read, ready) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+
+        // SAFETY: no other threads exist yet, so the child gets a consistent
+        // copy of the process.
+        let pid = check(unsafe { libc::fork() })?;
+        if pid == 0 {
+            drop(read);
+            // SAFETY: setsid has no preconditions; it detaches from the
+            // controlling terminal.
+            check(unsafe { libc::setsid() })?;
+            *PENDING.lock().unwrap() = Some(Pending {
+                ready,
+                output: Some(output),
+            });
+            return Ok(());
+        }
+
+        drop(ready);
+        let mut byte = [0];
+        if read.read(&mut byte)? == 1 {
+            std::fs::write(pid_file, format!("{}\n", pid))?;
+            std::process::exit(0);
+        }
+        eprintln!("serve-dir exited before it started serving");
+        std::process::exit(1);
+    }
+
+    /// Called by the child once it is bound: points stdin at /dev/null and
+    /// stdout and stderr at the log, so the banner printed next lands there.
+    pub fn detach() -> std::io::Result<()> {
+        let output = PENDING
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|pending| pending.output.take());
+        let Some(output) = output else {
+            return Ok(());
+        };
+        std::io::stdout().flush()?;
+        let null = File::open("/dev/null")?;
+        // SAFETY: all descriptors are open, and dup2 replaces the standard
+        // streams atomically.
+        unsafe {
+            check(libc::dup2(null.as_raw_fd(), 0))?;
+            check(libc::dup2(output.as_raw_fd(), 1))?;
+            check(libc::dup2(output.as_raw_fd(), 2))?;
+        }
+        Ok(())
+    }
+
+    /// Called by the child once its startup output is written: detaches if
+    /// that has not happened yet, then lets the parent exit.
+    pub fn notify_ready() -> std::io::Result<()> {
+        detach()?;
+        let Some(Pending { mut ready, .. }) = PENDING.lock().unwrap().take() else {
+            return Ok(());
+        };
+        std::io::stdout().flush()?;
+        ready.write_all(&[1])
+    }
+
+    /// Sends SIGTERM to the process in `pid_file` and removes the file.
+    pub fn stop(pid_file: &Path) -> std::io::Result<i32> {
+        let pid = super::read_pid(pid_file)?;
+        // SAFETY: kill only sends a signal.
+        check(unsafe { libc::kill(pid, libc::SIGTERM) })?;
+        std::fs::remove_file(pid_file)?;
+        Ok(pid)
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{daemonize, detach, notify_ready, stop};
+
+#[cfg(not(unix))]
+fn unsupported() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "--daemon and --stop need fork, which this platform does not have",
+    )
+}
+
+#[cfg(not(unix))]
+pub fn daemonize(_pid_file: &Path, _log_file: Option<&Path>) -> std::io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(unix))]
+pub fn detach() -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn notify_ready() -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn stop(pid_file: &Path) -> std::io::Result<i32> {
+    read_pid(pid_file)?;
+    Err(unsupported())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_pid_test() {
+        let dir = std::env::temp_dir().join(format!("serve-dir-pid-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pid_file = dir.join("serve-dir.pid");
+        std::fs::write(&pid_file, "1234\n").unwrap();
+        assert_eq!(1234, read_pid(&pid_file).unwrap());
+        for invalid in ["", "-1", "0", "abc"] {
+            std::fs::write(&pid_file, invalid).unwrap();
+            assert!(read_pid(&pid_file).is_err(), "{:?}", invalid);
+        }
+        assert!(read_pid(&dir.join("missing.pid")).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! `.servedir` files that override settings for the directory they are in
+//! and everything below it. They use a small subset of TOML:
+//!
+//! ```toml
+//! auth = "user:password"
+//! cache_control = "no-store"
+//! cors_origin = ["https://a.example", "https://b.example"]
+//!
+//! [headers]
+//! x-robots-tag = "noindex"
+//! ```
+
+use std::path::Path;
+
+use hyper::header::{HeaderName, HeaderValue};
+
+use crate::{base64, percent_decode};
+
+pub const FILE_NAME: &str = ".servedir";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DirConfig {
+    /// Expected `Authorization` header value, replacing `--auth`.
+    pub auth: Option<String>,
+    /// The user name of `auth`, for the audit log.
+    pub auth_user: Option<String>,
+    /// Added to every response, replacing global headers of the same name.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub cache_control: Option<String>,
+    /// Replaces `--cors-origin`.
+    pub cors_origins: Option<Vec<String>>,
+}
+
+enum Value {
+    String(String),
+    Array(Vec<String>),
+}
+
+/// Reads a basic (`"..."`) or literal (`'...'`) string off the front of
+/// `input`, returning it and the rest.
+fn parse_string(input: &str) -> Result<(String, &str), String> {
+    let mut chars = input.char_indices();
+    let quote = match chars.next() {
+        Some((_, quote @ ('"' | '\''))) => quote,
+        _ => return Err(format!("expected a string at {}", input)),
+    };
+    let mut value = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((value, &input[index + 1..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, c)| c) {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                other => return Err(format!("unsupported escape \\{}", other.unwrap_or(' '))),
+            },
+            c => value.push(c),
+        }
+    }
+    Err(String::from("unterminated string"))
+}
+
+fn parse_value(input: &str) -> Result<Value, String> {
+    let (value, rest) = match input.strip_prefix('[') {
+        Some(mut rest) => {
+            let mut items = Vec::new();
+            loop {
+                rest = rest.trim_start();
+                if let Some(after) = rest.strip_prefix(']') {
+                    break (Value::Array(items), after);
+                }
+                let (item, after) = parse_string(rest)?;
+                items.push(item);
+                rest = after.trim_start();
+                rest = rest.strip_prefix(',').unwrap_or(rest);
+            }
+        }
+        None => {
+            let (value, rest) = parse_string(input)?;
+            (Value::String(value), rest)
+        }
+    };
+    let rest = rest.trim();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected {} after value", rest));
+    }
+    Ok(value)
+}
+
+fn expect_string(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(value) => Ok(value),
+        Value::Array(_) => Err(format!("{} must be a string", key)),
+    }
+}
+
+pub fn parse(text: &str) -> Result<DirConfig, String> {
+    let mut config = DirConfig::default();
+    let mut in_headers = false;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let at_line = |err: String| format!("line {}: {}", number + 1, err);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(table) = line.strip_prefix('[') {
+            let table = table.split('#').next().unwrap_or_default().trim();
+            match table.strip_suffix(']').map(str::trim) {
+                Some("headers") => in_headers = true,
+                _ => return Err(at_line(format!("unknown table [{}", table))),
+            }
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| at_line(String::from("expected key = value")))?;
+        let key = key.trim().trim_matches('"');
+        let value = parse_value(value.trim()).map_err(at_line)?;
+        if in_headers {
+            let name = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|_| at_line(format!("invalid header name {}", key)))?;
+            let value = expect_string(key, value).map_err(at_line)?;
+            let value = HeaderValue::from_str(&value)
+                .map_err(|_| at_line(format!("invalid value for header {}", key)))?;
+            config.headers.push((name, value));
+            continue;
+        }
+        match key {
+            "auth" => {
+                let credentials = expect_string(key, value).map_err(at_line)?;
+                if !credentials.contains(':') {
+                    return Err(at_line(String::from("auth must be user:password")));
+                }
+                config.auth = Some(format!("Basic {}", base64::encode(credentials.as_bytes())));
+                config.auth_user = credentials.split(':').next().map(String::from);
+            }
+            "cache_control" => {
+                config.cache_control = Some(expect_string(key, value).map_err(at_line)?);
+            }
+            "cors_origin" => {
+                config.cors_origins = Some(match value {
+                    Value::String(origin) => vec![origin],
+                    Value::Array(origins) => origins,
+                });
+            }
+            key => return Err(at_line(format!("unknown setting {}", key))),
+        }
+    }
+    Ok(config)
+}
+
+impl DirConfig {
+    /// Layers `nested`, from a deeper directory, over `self`.
+    fn merge(&mut self, nested: DirConfig) {
+        if nested.auth.is_some() {
+            self.auth = nested.auth;
+            self.auth_user = nested.auth_user;
+        }
+        self.cache_control = nested.cache_control.or(self.cache_control.take());
+        self.cors_origins = nested.cors_origins.or(self.cors_origins.take());
+        for (name, value) in nested.headers {
+            self.headers.retain(|(existing, _)| *existing != name);
+            self.headers.push((name, value));
+        }
+    }
+}
+
+/// Whether `uri_path` names a `.servedir` file.
+pub fn is_config_path(uri_path: &str) -> bool {
+    percent_decode(uri_path)
+        .split('/')
+        .any(|segment| segment == FILE_NAME)
+}
+
+/// Merges every `.servedir` from the served root down to the directory
+/// `uri_path` points into, so the most specific file wins.
+pub async fn load(directory_path: &str, uri_path: &str) -> Result<DirConfig, String> {
+    let uri_path = percent_decode(uri_path);
+    let mut config = DirConfig::default();
+    let mut dir = Path::new(directory_path).to_path_buf();
+    let segments = uri_path.split('/').filter(|segment| !segment.is_empty());
+    for segment in std::iter::once("").chain(segments) {
+        if segment.starts_with('.') {
+            break;
+        }
+        dir.push(segment);
+        let file = dir.join(FILE_NAME);
+        match tokio::fs::read_to_string(&file).await {
+            Ok(text) => {
+                config.merge(parse(&text).map_err(|err| format!("{} {}", file.display(), err))?)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            // The segment is a file, so there are no directories left.
+            Err(err) if err.kind() == std::io::ErrorKind::NotADirectory => break,
+            Err(err) => return Err(format!("{} {}", file.display(), err)),
+        }
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let config = parse(concat!(
+            "# private area\n",
+            "auth = \"user:pass\"\n",
+            "cache_control = 'no-store' # never cache\n",
+            "cors_origin = [\"https://a.example\", 'https://b.example',]\n",
+            "\n",
+            "[headers]\n",
+            "x-robots-tag = \"noindex\"\n",
+            "\"x-quoted\" = \"a \\\"b\\\"\"\n",
+        ))
+        .unwrap();
+        assert_eq!(Some(String::from("Basic dXNlcjpwYXNz")), config.auth);
+        assert_eq!(Some(String::from("user")), config.auth_user);
+        assert_eq!(Some(String::from("no-store")), config.cache_control);
+        assert_eq!(
+            Some(vec![
+                String::from("https://a.example"),
+                String::from("https://b.example")
+            ]),
+            config.cors_origins
+        );
+        assert_eq!(
+            vec![
+                (
+                    HeaderName::from_static("x-robots-tag"),
+                    HeaderValue::from_static("noindex")
+                ),
+                (
+                    HeaderName::from_static("x-quoted"),
+                    HeaderValue::from_static("a \"b\"")
+                )
+            ],
+            config.headers
+        );
+    }
+
+    #[test]
+    fn parse_error_test() {
+        assert!(parse("compression = \"gzip\"")
+            .unwrap_err()
+            .contains("unknown setting"));
+        assert!(parse("auth = \"nopassword\"").is_err());
+        assert!(parse("auth = user:pass").is_err());
+        assert!(parse("auth = \"a:b").is_err());
+        assert!(parse("[other]").is_err());
+        assert!(parse("[headers]\nbad header = \"x\"").is_err());
+        assert_eq!(
+            "line 2: expected key = value",
+            parse("auth = \"a:b\"\nauth").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn merge_test() {
+        let mut config =
+            parse("auth = \"a:b\"\n[headers]\nx-a = \"root\"\nx-b = \"root\"").unwrap();
+        config.merge(parse("cache_control = \"no-store\"\n[headers]\nx-a = \"nested\"").unwrap());
+        assert_eq!(parse("auth = \"a:b\"").unwrap().auth, config.auth);
+        assert_eq!(Some(String::from("no-store")), config.cache_control);
+        assert_eq!(
+            vec!["root", "nested"],
+            config
+                .headers
+                .iter()
+                .map(|(_, value)| value.to_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn is_config_path_test() {
+        assert!(is_config_path("/.servedir"));
+        assert!(is_config_path("/private/%2Eservedir"));
+        assert!(!is_config_path("/servedir"));
+    }
+}
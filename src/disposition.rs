@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use mime_guess::{mime, Mime};
+
+/// Extensions that are always downloaded, whatever their MIME type.
+const DOWNLOAD_EXTENSIONS: [&str; 7] = ["exe", "msi", "dmg", "apk", "deb", "rpm", "bin"];
+
+fn is_displayable(content_type: &Mime) -> bool {
+    match content_type.type_() {
+        mime::IMAGE | mime::VIDEO | mime::AUDIO => true,
+        mime::TEXT => content_type.subtype() == mime::HTML || content_type.subtype() == mime::PLAIN,
+        mime::APPLICATION => content_type.subtype() == mime::PDF,
+        _ => false,
+    }
+}
+
+/// RFC 5987 `attr-char`s are kept, everything else is `%XX` encoded.
+fn encode_filename(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => (byte as char).to_string(),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// `Content-Disposition` for a served file: `inline` for types browsers
+/// display, `attachment` for executables and unknown binaries, and none for
+/// the rest so the browser keeps deciding.
+pub fn content_disposition(path: &Path, content_type: &Mime) -> Option<String> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    let disposition = if extension
+        .as_deref()
+        .is_some_and(|extension| DOWNLOAD_EXTENSIONS.contains(&extension))
+        || *content_type == mime::APPLICATION_OCTET_STREAM
+    {
+        "attachment"
+    } else if is_displayable(content_type) {
+        "inline"
+    } else {
+        return None;
+    };
+    let name = path.file_name()?.to_string_lossy();
+    if name.is_ascii() {
+        let quoted = name.replace('\\', "\\\\").replace('"', "\\\"");
+        Some(format!("{}; filename=\"{}\"", disposition, quoted))
+    } else {
+        // Older clients only read `filename`, so give them an ASCII stand-in.
+        let fallback: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii() && c != '"' && c != '\\' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Some(format!(
+            "{}; filename=\"{}\"; filename*=UTF-8''{}",
+            disposition,
+            fallback,
+            encode_filename(&name)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn disposition(name: &str) -> Option<String> {
+        let content_type = mime_guess::from_path(name).first_or_octet_stream();
+        content_disposition(Path::new(name), &content_type)
+    }
+
+    #[test]
+    fn content_disposition_test() {
+        assert_eq!(
+            Some(String::from("inline; filename=\"report.pdf\"")),
+            disposition("docs/report.pdf")
+        );
+        assert_eq!(
+            Some(String::from("inline; filename=\"photo.png\"")),
+            disposition("photo.png")
+        );
+        assert_eq!(
+            Some(String::from("attachment; filename=\"setup.exe\"")),
+            disposition("setup.exe")
+        );
+        assert_eq!(None, disposition("style.css"));
+        assert_eq!(
+            Some(String::from(
+                "inline; filename=\"r_sum_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"
+            )),
+            disposition("résumé.pdf")
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! `--log-format=ecs`: log lines as Elastic Common Schema JSON documents.
+
+use std::{
+    net::IpAddr,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use log::Level;
+
+use crate::json;
+
+const ECS_VERSION: &str = "8.11.0";
+
+/// One finished request, filled in by the code that served it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccessRecord {
+    pub request_id: Option<String>,
+    pub method: String,
+    /// The request target as received, path and query.
+    pub url: String,
+    pub status: u16,
+    /// Body bytes the connection took, which for a client that went away
+    /// is less than the whole body.
+    pub bytes: u64,
+    pub client: Option<IpAddr>,
+    pub duration: Duration,
+}
+
+/// One ECS document. `access` is set for the record written when a
+/// request finishes; the lines handlers log along the way only carry
+/// their message and what is known about the request they belong to.
+#[derive(Debug, PartialEq)]
+pub struct EcsLogRecord<'a> {
+    pub timestamp_millis: u128,
+    pub level: Level,
+    pub message: &'a str,
+    pub request_id: Option<&'a str>,
+    pub client: Option<IpAddr>,
+    pub duration: Option<Duration>,
+    pub access: Option<&'a AccessRecord>,
+}
+
+impl<'a> EcsLogRecord<'a> {
+    /// The document for a finished request, logged at the level of its
+    /// status.
+    pub fn access(
+        timestamp_millis: u128,
+        level: Level,
+        message: &'a str,
+        access: &'a AccessRecord,
+    ) -> Self {
+        Self {
+            timestamp_millis,
+            level,
+            message,
+            request_id: access.request_id.as_deref(),
+            client: access.client,
+            duration: Some(access.duration),
+            access: Some(access),
+        }
+    }
+
+    /// One JSON object with dotted ECS field names, as the ECS logging
+    /// libraries write them.
+    pub fn to_json(&self) -> String {
+        let secs = (self.timestamp_millis / 1000) as u64;
+        let timestamp = json::rfc3339(UNIX_EPOCH + Duration::from_secs(secs));
+        let mut fields = vec![
+            (
+                "@timestamp",
+                json::string(&format!(
+                    "{}.{:03}Z",
+                    timestamp.trim_end_matches('Z'),
+                    self.timestamp_millis % 1000
+                )),
+            ),
+            (
+                "log.level",
+                json::string(&self.level.as_str().to_lowercase()),
+            ),
+            ("message", json::string(self.message)),
+            ("ecs.version", json::string(ECS_VERSION)),
+        ];
+        if let Some(request_id) = self.request_id {
+            fields.push(("http.request.id", json::string(request_id)));
+        }
+        if let Some(access) = self.access {
+            let (path, query) = access.url.split_once('?').unwrap_or((&access.url, ""));
+            fields.push(("http.request.method", json::string(&access.method)));
+            fields.push(("http.response.status_code", access.status.to_string()));
+            fields.push(("http.response.body.bytes", access.bytes.to_string()));
+            fields.push(("url.original", json::string(&access.url)));
+            fields.push(("url.path", json::string(path)));
+            if !query.is_empty() {
+                fields.push(("url.query", json::string(query)));
+            }
+        }
+        if let Some(client) = self.client {
+            fields.push(("client.ip", json::string(&client.to_string())));
+        }
+        if let Some(duration) = self.duration {
+            // ECS durations are in nanoseconds.
+            fields.push(("event.duration", duration.as_nanos().to_string()));
+        }
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|(name, value)| format!("{}:{}", json::string(name), value))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json::test::parse_object;
+
+    #[test]
+    fn access_json_test() {
+        let access = AccessRecord {
+            request_id: Some(String::from("req-1")),
+            method: String::from("GET"),
+            url: String::from("/docs/a%20b.txt?download"),
+            status: 200,
+            bytes: 5120,
+            client: Some("192.0.2.7".parse().unwrap()),
+            duration: Duration::from_micros(1500),
+        };
+        let json =
+            EcsLogRecord::access(1709214307123, Level::Info, "GET /docs 200", &access).to_json();
+        let fields = parse_object(&json).unwrap();
+        let field = |name: &str| fields.get(name).map(String::as_str);
+        assert_eq!(Some("2024-02-29T13:45:07.123Z"), field("@timestamp"));
+        assert_eq!(Some("info"), field("log.level"));
+        assert_eq!(Some("8.11.0"), field("ecs.version"));
+        assert_eq!(Some("req-1"), field("http.request.id"));
+        assert_eq!(Some("GET"), field("http.request.method"));
+        assert_eq!(Some("200"), field("http.response.status_code"));
+        assert_eq!(Some("5120"), field("http.response.body.bytes"));
+        assert_eq!(Some("/docs/a%20b.txt?download"), field("url.original"));
+        assert_eq!(Some("/docs/a%20b.txt"), field("url.path"));
+        assert_eq!(Some("download"), field("url.query"));
+        assert_eq!(Some("192.0.2.7"), field("client.ip"));
+        assert_eq!(Some("1500000"), field("event.duration"));
+    }
+
+    #[test]
+    fn line_json_test() {
+        let record = EcsLogRecord {
+            timestamp_millis: 0,
+            level: Level::Error,
+            message: "failed \"x\"",
+            request_id: None,
+            client: None,
+            duration: None,
+            access: None,
+        };
+        let fields = parse_object(&record.to_json()).unwrap();
+        assert_eq!("1970-01-01T00:00:00.000Z", fields["@timestamp"]);
+        assert_eq!("error", fields["log.level"]);
+        assert_eq!("failed \"x\"", fields["message"]);
+        assert!(!fields.contains_key("http.response.status_code"));
+        assert!(!fields.contains_key("http.request.id"));
+        assert_eq!(4, fields.len());
+    }
+}
@@ -0,0 +1,94 @@
+/// Content codings from an `Accept-Encoding` header with their weights,
+/// most preferred first. Names are lowercased; entries whose weight does
+/// not parse are dropped, while `q=0` entries are kept since they refuse a
+/// coding.
+pub fn parse_accept_encoding(accept_encoding: &str) -> Vec<(String, f32)> {
+    let mut weighted: Vec<(String, f32)> = accept_encoding
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let coding = parts.next()?.trim();
+            let q = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!coding.is_empty() && (0.0..=1.0).contains(&q))
+                .then(|| (coding.to_ascii_lowercase(), q))
+        })
+        .collect();
+    // A stable sort keeps header order between equal weights.
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    weighted
+}
+
+/// How much `accepted` wants `coding`: its own entry, else `*`. Without
+/// either, identity is still acceptable and any other coding is not.
+fn weight(accepted: &[(String, f32)], coding: &str) -> f32 {
+    let entry = |name: &str| {
+        accepted
+            .iter()
+            .find(|(accepted, _)| accepted == name)
+            .map(|(_, q)| *q)
+    };
+    entry(coding)
+        .or_else(|| entry("*"))
+        .unwrap_or(if coding == "identity" { 1.0 } else { 0.0 })
+}
+
+/// The most wanted of the `codings` the server can produce, listed in its
+/// own order of preference for ties. A request without the header gets
+/// identity. `None` when the client refuses all of them, which is a 406.
+pub fn negotiate<'a>(accept_encoding: Option<&str>, codings: &[&'a str]) -> Option<&'a str> {
+    let accepted = parse_accept_encoding(accept_encoding.unwrap_or(""));
+    let mut best: Option<(&str, f32)> = None;
+    for &coding in codings {
+        let q = weight(&accepted, coding);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((coding, q));
+        }
+    }
+    best.map(|(coding, _)| coding)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_accept_encoding_test() {
+        assert_eq!(
+            vec![
+                (String::from("br"), 1.0),
+                (String::from("gzip"), 0.8),
+                (String::from("identity"), 0.0),
+            ],
+            parse_accept_encoding("gzip;q=0.8, BR, identity;q=0, deflate;q=high")
+        );
+        assert!(parse_accept_encoding("").is_empty());
+    }
+
+    #[test]
+    fn negotiate_test() {
+        let codings = ["br", "gzip", "identity"];
+        assert_eq!(Some("identity"), negotiate(None, &codings));
+        assert_eq!(Some("identity"), negotiate(Some("identity"), &codings));
+        assert_eq!(Some("gzip"), negotiate(Some("gzip, br;q=0.5"), &codings));
+        // Everything refused.
+        assert_eq!(None, negotiate(Some("*;q=0"), &codings));
+        assert_eq!(
+            Some("identity"),
+            negotiate(Some("*;q=0, identity"), &codings)
+        );
+        // gzip refused, anything else welcome: the server's preference wins.
+        assert_eq!(Some("br"), negotiate(Some("gzip;q=0, *"), &codings));
+        assert_eq!(
+            Some("identity"),
+            negotiate(Some("gzip;q=0, *"), &["gzip", "identity"])
+        );
+        // Only identity is refused.
+        assert_eq!(
+            Some("gzip"),
+            negotiate(Some("gzip, identity;q=0"), &["gzip", "identity"])
+        );
+        assert_eq!(None, negotiate(Some("identity;q=0"), &["gzip", "identity"]));
+    }
+}
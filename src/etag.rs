@@ -0,0 +1,293 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::fs::Metadata;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EtagMode {
+    /// Derived from the modification time only.
+    #[default]
+    Mtime,
+    /// Derived from the modification time and the file size.
+    MtimeSize,
+    /// Derived from the file content, so it survives copies and mtime resets.
+    Hash,
+}
+
+impl EtagMode {
+    /// The `--etag-mode` value selecting this mode.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Mtime => "mtime",
+            Self::MtimeSize => "mtime-size",
+            Self::Hash => "hash",
+        }
+    }
+}
+
+impl FromStr for EtagMode {
+    type Err = ();
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "mtime" => Ok(Self::Mtime),
+            "mtime-size" => Ok(Self::MtimeSize),
+            "hash" => Ok(Self::Hash),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Files whose hash [`EtagCache`] keeps before it drops the least recently
+/// used one.
+const ETAG_CACHE_CAPACITY: usize = 4096;
+
+struct CachedEtag {
+    modified: SystemTime,
+    len: u64,
+    etag: String,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CachedEtags {
+    files: HashMap<PathBuf, CachedEtag>,
+    clock: u64,
+}
+
+/// Content hashes keyed by path, used while the file's mtime and size stay
+/// the same. Bounded, so serving many files doesn't grow it forever.
+pub struct EtagCache {
+    capacity: usize,
+    cached: Mutex<CachedEtags>,
+}
+
+impl Default for EtagCache {
+    fn default() -> Self {
+        Self {
+            capacity: ETAG_CACHE_CAPACITY,
+            cached: Mutex::default(),
+        }
+    }
+}
+
+impl EtagCache {
+    fn get_or_insert(
+        &self,
+        path: &Path,
+        modified: SystemTime,
+        len: u64,
+        hash: impl FnOnce() -> String,
+    ) -> String {
+        let mut cached = self.cached.lock().unwrap();
+        cached.clock += 1;
+        let now = cached.clock;
+        if let Some(entry) = cached.files.get_mut(path) {
+            if entry.modified == modified && entry.len == len {
+                entry.last_used = now;
+                return entry.etag.clone();
+            }
+        }
+        if cached.files.len() >= self.capacity && !cached.files.contains_key(path) {
+            let oldest = cached
+                .files
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                cached.files.remove(&oldest);
+            }
+        }
+        let etag = hash();
+        cached.files.insert(
+            path.to_path_buf(),
+            CachedEtag {
+                modified,
+                len,
+                etag: etag.clone(),
+                last_used: now,
+            },
+        );
+        etag
+    }
+
+    /// Drops `path` and everything below it, once it has been deleted or
+    /// moved away.
+    pub fn forget(&self, path: &Path) {
+        self.cached
+            .lock()
+            .unwrap()
+            .files
+            .retain(|cached, _| !cached.starts_with(path));
+    }
+}
+
+pub fn etag(
+    mode: EtagMode,
+    path: &Path,
+    metadata: &Metadata,
+    body: &[u8],
+    cache: &EtagCache,
+) -> String {
+    let modified = metadata.modified.unwrap_or(UNIX_EPOCH);
+    let mtime = modified
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    match mode {
+        EtagMode::Mtime => format!("\"{:x}\"", mtime),
+        EtagMode::MtimeSize => format!("\"{:x}-{:x}\"", mtime, metadata.len),
+        EtagMode::Hash => cache.get_or_insert(path, modified, metadata.len, || content_etag(body)),
+    }
+}
+
+/// Strong ETag for a generated body.
+pub fn content_etag(body: &[u8]) -> String {
+    format!("\"{:016x}\"", fnv1a(body))
+}
+
+/// Suffixes compressing proxies append to the ETag of an encoded variant,
+/// such as Apache's `-gzip`.
+const ENCODING_SUFFIXES: [&str; 3] = ["-gzip", "-gz", "-br"];
+
+/// Marks `etag` as weak, for a body whose bytes may differ between
+/// responses that mean the same thing.
+pub fn weak(etag: &str) -> String {
+    match etag.strip_prefix("W/") {
+        Some(_) => String::from(etag),
+        None => format!("W/{}", etag),
+    }
+}
+
+/// `etag` with any `W/` prefix and encoding suffix dropped, lowercased,
+/// so every variant of one representation compares equal.
+fn weak_key(etag: &str) -> String {
+    let etag = etag.strip_prefix("W/").unwrap_or(etag);
+    let opaque = etag.trim_matches('"');
+    let opaque = ENCODING_SUFFIXES
+        .iter()
+        .find_map(|suffix| opaque.strip_suffix(suffix))
+        .unwrap_or(opaque);
+    opaque.to_ascii_lowercase()
+}
+
+/// Whether an `If-None-Match` header value matches `etag`. As RFC 9110
+/// requires for `If-None-Match` this is a weak comparison, which here also
+/// treats the gzip and brotli variants of a tag as equal to it.
+pub fn matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = weak_key(etag);
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || weak_key(candidate) == etag)
+}
+
+/// 64-bit FNV-1a, cheap and stable across builds and restarts.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{fs::File, time::Duration};
+
+    #[test]
+    fn etag_mode_copy_test() {
+        let dir = std::env::temp_dir().join(format!("serve-dir-etag-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let original = dir.join("original.txt");
+        let copy = dir.join("copy.txt");
+        std::fs::write(&original, "same content").unwrap();
+        std::fs::copy(&original, &copy).unwrap();
+        File::options()
+            .write(true)
+            .open(&copy)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(1_000_000))
+            .unwrap();
+
+        let cache = EtagCache::default();
+        let etag_of = |mode, path: &Path| {
+            let metadata = std::fs::metadata(path).unwrap().into();
+            etag(mode, path, &metadata, b"same content", &cache)
+        };
+        for mode in [EtagMode::Mtime, EtagMode::MtimeSize] {
+            assert_ne!(etag_of(mode, &original), etag_of(mode, &copy));
+        }
+        assert_eq!(
+            etag_of(EtagMode::Hash, &original),
+            etag_of(EtagMode::Hash, &copy)
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn etag_cache_test() {
+        let cache = EtagCache {
+            capacity: 2,
+            cached: Mutex::default(),
+        };
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let etag_of = |path: &str, len: u64, body: &[u8]| {
+            let metadata = Metadata {
+                len,
+                modified: Some(modified),
+                is_dir: false,
+            };
+            etag(EtagMode::Hash, Path::new(path), &metadata, body, &cache)
+        };
+        let first = etag_of("/srv/a.txt", 1, b"a");
+        // Cached while mtime and size are unchanged...
+        assert_eq!(first, etag_of("/srv/a.txt", 1, b"b"));
+        // ...and hashed again when either changes.
+        assert_eq!(content_etag(b"bb"), etag_of("/srv/a.txt", 2, b"bb"));
+
+        etag_of("/srv/sub/b.txt", 1, b"b");
+        etag_of("/srv/a.txt", 2, b"bb");
+        etag_of("/srv/c.txt", 1, b"c");
+        let cached = |path: &str| {
+            cache
+                .cached
+                .lock()
+                .unwrap()
+                .files
+                .contains_key(Path::new(path))
+        };
+        assert_eq!(2, cache.cached.lock().unwrap().files.len());
+        // The least recently used entry made room.
+        assert!(!cached("/srv/sub/b.txt"));
+        assert!(cached("/srv/a.txt"));
+
+        etag_of("/srv/sub/b.txt", 1, b"b");
+        cache.forget(Path::new("/srv/sub"));
+        assert!(!cached("/srv/sub/b.txt"));
+        assert_eq!(1, cache.cached.lock().unwrap().files.len());
+    }
+
+    #[test]
+    fn if_none_match_test() {
+        assert!(matches("\"a\", \"b\"", "\"b\""));
+        assert!(matches("*", "\"b\""));
+        assert!(!matches("\"a\"", "\"b\""));
+    }
+
+    #[test]
+    fn weak_etag_test() {
+        assert_eq!("W/\"ab\"", weak("\"ab\""));
+        assert_eq!("W/\"ab\"", weak("W/\"ab\""));
+        for variant in ["W/\"ab\"", "\"ab-gz\"", "W/\"AB-br\"", "\"ab-gzip\""] {
+            assert!(matches(variant, "\"ab\""), "{}", variant);
+            assert!(matches(variant, "W/\"ab\""), "{}", variant);
+        }
+        assert!(!matches("W/\"ab-zz\"", "\"ab\""));
+        assert!(!matches("\"abc\"", "W/\"ab\""));
+    }
+}
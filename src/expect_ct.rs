@@ -0,0 +1,43 @@
+/// `Expect-CT` policy. The header is deprecated: Chrome has required
+/// Certificate Transparency for every certificate since 2018 and ignores
+/// it, so it only matters to older clients.
+pub struct ExpectCtConfig {
+    pub max_age: u64,
+    pub enforce: bool,
+    pub report_uri: Option<String>,
+}
+
+impl ExpectCtConfig {
+    /// `Expect-CT` value: `max-age=86400, enforce, report-uri="..."`.
+    pub fn header(&self) -> String {
+        let mut value = format!("max-age={}", self.max_age);
+        if self.enforce {
+            value.push_str(", enforce");
+        }
+        if let Some(report_uri) = &self.report_uri {
+            value.push_str(&format!(", report-uri=\"{}\"", report_uri));
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn header_test() {
+        let mut config = ExpectCtConfig {
+            max_age: 86400,
+            enforce: false,
+            report_uri: None,
+        };
+        assert_eq!("max-age=86400", config.header());
+        config.enforce = true;
+        config.report_uri = Some(String::from("https://report.example.com/ct"));
+        assert_eq!(
+            "max-age=86400, enforce, report-uri=\"https://report.example.com/ct\"",
+            config.header()
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use std::path::{Path, PathBuf};
+
+use crate::glob;
+
+/// For a file name matching `glob`, returns the path with the fingerprint
+/// (the dot separated segment before the extension) removed, so
+/// `app.abc123.js` falls back to `app.js`.
+pub fn fallback(glob: &str, path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    if !glob::matches(glob, name) {
+        return None;
+    }
+    let (rest, extension) = name.rsplit_once('.')?;
+    let (stem, _fingerprint) = rest.rsplit_once('.')?;
+    if stem.is_empty() {
+        return None;
+    }
+    Some(path.with_file_name(format!("{}.{}", stem, extension)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fallback_test() {
+        assert_eq!(
+            Some(PathBuf::from("dist/app.js")),
+            fallback("*.*.js", Path::new("dist/app.abc123.js"))
+        );
+        assert_eq!(
+            Some(PathBuf::from("vendor.min.js")),
+            fallback("*.*.js", Path::new("vendor.min.abc.js"))
+        );
+        assert_eq!(None, fallback("*.*.js", Path::new("app.js")));
+        assert_eq!(None, fallback("*.*.css", Path::new("app.abc123.js")));
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    future::Future,
+    io,
+    ops::Deref,
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    time::SystemTime,
+};
+
+pub type FsFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+/// The parts of a file's metadata the server looks at, so a
+/// [`FileSystem`] other than the disk can produce them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metadata {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub is_dir: bool,
+}
+
+impl From<std::fs::Metadata> for Metadata {
+    fn from(metadata: std::fs::Metadata) -> Self {
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            is_dir: metadata.is_dir(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DirEntry {
+    pub name: String,
+    /// Of the symlink target, or of the link itself when it is broken.
+    pub metadata: Metadata,
+    pub symlink: Option<Symlink>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symlink {
+    /// Where the link points, as stored in it.
+    pub target: PathBuf,
+    pub broken: bool,
+}
+
+/// `--follow-symlinks`: which symlinks below the served directory are
+/// followed. Checked on disk, whatever the [`FileSystem`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FollowSymlinks {
+    #[default]
+    All,
+    /// Only links whose target stays inside the served directory.
+    Safe,
+    Never,
+}
+
+impl FollowSymlinks {
+    /// The `--follow-symlinks` value selecting this mode.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Safe => "safe",
+            Self::Never => "never",
+        }
+    }
+
+    /// Whether `path`, inside `root`, may be served. A path that does not
+    /// exist is allowed, as it is a 404 either way.
+    pub fn allows(self, root: &Path, path: &Path) -> bool {
+        match self {
+            Self::All => true,
+            Self::Safe => match (root.canonicalize(), path.canonicalize()) {
+                (Ok(root), Ok(path)) => path.starts_with(root),
+                _ => true,
+            },
+            Self::Never => {
+                let Ok(relative) = path.strip_prefix(root) else {
+                    return true;
+                };
+                let mut current = root.to_path_buf();
+                !relative.components().any(|component| {
+                    current.push(component);
+                    current
+                        .symlink_metadata()
+                        .is_ok_and(|metadata| metadata.file_type().is_symlink())
+                })
+            }
+        }
+    }
+}
+
+impl FromStr for FollowSymlinks {
+    type Err = ();
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "all" => Ok(Self::All),
+            "safe" => Ok(Self::Safe),
+            "never" => Ok(Self::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Where served files are read from. Writes (`--allow-put`,
+/// `--allow-delete`) and the background indexers still go to disk.
+pub trait FileSystem: Send + Sync {
+    fn read_file<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Vec<u8>>;
+
+    fn metadata<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Metadata>;
+
+    /// Entries of `path`, including hidden ones, in no particular order.
+    fn read_dir<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Vec<DirEntry>>;
+
+    fn is_file(&self, path: &Path) -> bool;
+}
+
+/// Reads from the local disk through `tokio::fs`.
+pub struct LocalFileSystem;
+
+impl FileSystem for LocalFileSystem {
+    fn read_file<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Vec<u8>> {
+        Box::pin(tokio::fs::read(path))
+    }
+
+    fn metadata<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Metadata> {
+        Box::pin(async move { tokio::fs::metadata(path).await.map(Metadata::from) })
+    }
+
+    fn read_dir<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Vec<DirEntry>> {
+        Box::pin(async move {
+            let mut entries = Vec::new();
+            let mut read_dir = tokio::fs::read_dir(path).await?;
+            while let Some(entry) = read_dir.next_entry().await? {
+                let path = entry.path();
+                let is_symlink = entry
+                    .file_type()
+                    .await
+                    .is_ok_and(|file_type| file_type.is_symlink());
+                let target = if is_symlink {
+                    Some(tokio::fs::read_link(&path).await?)
+                } else {
+                    None
+                };
+                // Follows symlinks, like serving the entry would.
+                let (metadata, broken) = match tokio::fs::metadata(&path).await {
+                    Ok(metadata) => (metadata, false),
+                    Err(_) if is_symlink => (tokio::fs::symlink_metadata(&path).await?, true),
+                    Err(_) => continue,
+                };
+                entries.push(DirEntry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    metadata: metadata.into(),
+                    symlink: target.map(|target| Symlink { target, broken }),
+                });
+            }
+            Ok(entries)
+        })
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+}
+
+/// Shared handle to the configured [`FileSystem`], the disk by default.
+#[derive(Clone)]
+pub struct Fs(Arc<dyn FileSystem>);
+
+impl Fs {
+    pub fn new(fs: impl FileSystem + 'static) -> Self {
+        Self(Arc::new(fs))
+    }
+}
+
+impl Default for Fs {
+    fn default() -> Self {
+        Self::new(LocalFileSystem)
+    }
+}
+
+impl Deref for Fs {
+    type Target = dyn FileSystem;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::{
+        collections::BTreeMap,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    /// In-memory files keyed by absolute path; directories are implied by
+    /// the files below them.
+    #[derive(Default)]
+    pub struct MockFileSystem {
+        files: BTreeMap<PathBuf, Vec<u8>>,
+    }
+
+    impl MockFileSystem {
+        pub fn with_file(mut self, path: impl Into<PathBuf>, data: impl Into<Vec<u8>>) -> Self {
+            self.files.insert(path.into(), data.into());
+            self
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            self.files
+                .keys()
+                .any(|file| file != path && file.starts_with(path))
+        }
+
+        fn file_metadata(data: &[u8]) -> Metadata {
+            Metadata {
+                len: data.len() as u64,
+                modified: Some(UNIX_EPOCH + Duration::from_secs(1_000_000)),
+                is_dir: false,
+            }
+        }
+    }
+
+    fn not_found() -> io::Error {
+        io::Error::from(io::ErrorKind::NotFound)
+    }
+
+    impl FileSystem for MockFileSystem {
+        fn read_file<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Vec<u8>> {
+            let data = self.files.get(path).cloned().ok_or_else(not_found);
+            Box::pin(async move { data })
+        }
+
+        fn metadata<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Metadata> {
+            let metadata = match self.files.get(path) {
+                Some(data) => Ok(Self::file_metadata(data)),
+                None if self.is_dir(path) => Ok(Metadata {
+                    len: 0,
+                    modified: None,
+                    is_dir: true,
+                }),
+                None => Err(not_found()),
+            };
+            Box::pin(async move { metadata })
+        }
+
+        fn read_dir<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Vec<DirEntry>> {
+            Box::pin(async move {
+                if !self.is_dir(path) {
+                    return Err(not_found());
+                }
+                let mut entries: Vec<DirEntry> = Vec::new();
+                for (file, data) in &self.files {
+                    let Ok(relative) = file.strip_prefix(path) else {
+                        continue;
+                    };
+                    let mut components = relative.components();
+                    let name = match components.next() {
+                        Some(name) => name.as_os_str().to_string_lossy().into_owned(),
+                        None => continue,
+                    };
+                    if entries.iter().any(|entry| entry.name == name) {
+                        continue;
+                    }
+                    let metadata = if components.next().is_some() {
+                        Metadata {
+                            len: 0,
+                            modified: None,
+                            is_dir: true,
+                        }
+                    } else {
+                        Self::file_metadata(data)
+                    };
+                    entries.push(DirEntry {
+                        name,
+                        metadata,
+                        symlink: None,
+                    });
+                }
+                Ok(entries)
+            })
+        }
+
+        fn is_file(&self, path: &Path) -> bool {
+            self.files.contains_key(path)
+        }
+    }
+
+    #[tokio::test]
+    async fn mock_file_system_test() {
+        let fs = MockFileSystem::default()
+            .with_file("/srv/a.txt", "a")
+            .with_file("/srv/sub/b.txt", "bb");
+        assert!(fs.is_file(Path::new("/srv/a.txt")));
+        assert!(!fs.is_file(Path::new("/srv/sub")));
+        assert!(fs.metadata(Path::new("/srv/sub")).await.unwrap().is_dir);
+        assert_eq!(
+            2,
+            fs.metadata(Path::new("/srv/sub/b.txt")).await.unwrap().len
+        );
+        let mut names: Vec<_> = fs
+            .read_dir(Path::new("/srv"))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.metadata.is_dir))
+            .collect();
+        names.sort();
+        assert_eq!(
+            vec![(String::from("a.txt"), false), (String::from("sub"), true)],
+            names
+        );
+        assert!(fs.read_file(Path::new("/srv/missing")).await.is_err());
+    }
+}
@@ -0,0 +1,43 @@
+/// Matches `name` against a shell style glob where `*` is any run of
+/// characters and `?` is any single character.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matches_test() {
+        assert!(matches("*.*.js", "app.abc123.js"));
+        assert!(matches("*.js", "app.js"));
+        assert!(matches("app.??.css", "app.v2.css"));
+        assert!(!matches("*.*.js", "app.js"));
+        assert!(!matches("*.js", "app.css"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use hyper::header::{HeaderName, HeaderValue};
+
+/// `--header-file`: response headers kept out of the command line, re-read
+/// on SIGHUP.
+pub struct HeaderFile {
+    pub path: PathBuf,
+    headers: RwLock<Vec<(String, String)>>,
+}
+
+/// Parses one `name: value` per line, skipping blank lines and `#`
+/// comments. Unlike `--header`, a line must have exactly one colon.
+pub fn parse(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut headers = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || format!("line {}: expected name: value, got {:?}", number + 1, line);
+        if line.matches(':').count() != 1 {
+            return Err(invalid());
+        }
+        let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+        let (name, value) = (name.trim(), value.trim());
+        if HeaderName::from_bytes(name.as_bytes()).is_err() || HeaderValue::from_str(value).is_err()
+        {
+            return Err(invalid());
+        }
+        headers.push((String::from(name), String::from(value)));
+    }
+    Ok(headers)
+}
+
+impl HeaderFile {
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let headers = Self::read(&path)?;
+        Ok(Self {
+            path,
+            headers: RwLock::new(headers),
+        })
+    }
+
+    fn read(path: &Path) -> Result<Vec<(String, String)>, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {} {}", path.display(), err))?;
+        parse(&contents).map_err(|err| format!("{} {}", path.display(), err))
+    }
+
+    /// Re-reads the file, keeping the previous headers if it is now
+    /// invalid. Returns how many headers were loaded.
+    pub fn reload(&self) -> Result<usize, String> {
+        let headers = Self::read(&self.path)?;
+        let count = headers.len();
+        *self.headers.write().unwrap() = headers;
+        Ok(count)
+    }
+
+    /// The file's headers that `cli` does not set; those on the command
+    /// line win.
+    pub fn headers_not_in(&self, cli: &[(String, String)]) -> Vec<(String, String)> {
+        self.headers
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| !cli.iter().any(|(key, _)| key.eq_ignore_ascii_case(name)))
+            .cloned()
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.headers
+            .read()
+            .unwrap()
+            .iter()
+            .any(|(key, _)| key.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Reloads `header_file` whenever the process receives SIGHUP.
+#[cfg(unix)]
+pub fn reload_on_sighup(header_file: std::sync::Arc<HeaderFile>) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match header_file.reload() {
+                Ok(count) => log::info!(
+                    "reloaded {} headers from {}",
+                    count,
+                    header_file.path.display()
+                ),
+                Err(err) => log::error!("failed to reload headers, keeping the old ones: {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pairs(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(name, value)| (String::from(*name), String::from(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(
+            Ok(pairs(&[("x-api-key", "secret"), ("x-team", "web")])),
+            parse("# keys\nx-api-key: secret\n\n  x-team:web  \n")
+        );
+        assert!(parse("x-api-key secret").is_err());
+        assert_eq!(
+            Err(String::from(
+                "line 2: expected name: value, got \"link: http://a\""
+            )),
+            parse("# ok\nlink: http://a")
+        );
+        assert!(parse("bad name: value").is_err());
+    }
+
+    #[test]
+    fn merge_and_reload_test() {
+        let path = PathBuf::from(format!(
+            "{}headers.txt",
+            crate::test::temp_dir("header-file")
+        ));
+        std::fs::write(&path, "x-from-file: 1\nx-both: file\n").unwrap();
+        let header_file = HeaderFile::load(path.clone()).unwrap();
+        let cli = pairs(&[("X-Both", "cli")]);
+        assert_eq!(
+            pairs(&[("x-from-file", "1")]),
+            header_file.headers_not_in(&cli)
+        );
+
+        std::fs::write(&path, "x-reloaded: 2\n").unwrap();
+        assert_eq!(Ok(1), header_file.reload());
+        assert_eq!(
+            pairs(&[("x-reloaded", "2")]),
+            header_file.headers_not_in(&cli)
+        );
+
+        std::fs::write(&path, "broken").unwrap();
+        assert!(header_file.reload().is_err());
+        assert!(header_file.contains("X-Reloaded"));
+    }
+}
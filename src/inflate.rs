@@ -0,0 +1,281 @@
+//! A DEFLATE (RFC 1951) decoder for `--zip` entries. The server never
+//! needs to compress; what it writes uses stored blocks.
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order code length code lengths are sent in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Reads bits least significant first, as DEFLATE packs them.
+struct Bits<'a> {
+    data: &'a [u8],
+    at: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u8) -> Option<u32> {
+        let n = u32::from(n);
+        while self.count < n {
+            let byte = *self.data.get(self.at)?;
+            self.at += 1;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Some(value)
+    }
+
+    /// Drops the rest of the current byte; fewer than 8 bits are ever
+    /// buffered, so `at` is then the next unread byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code: how many codes there are of each length and
+/// the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Option<Self> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        // More codes of a length than fit is a broken stream; fewer is
+        // allowed, as a single distance code is.
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return None;
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+        Some(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Option<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return self.symbols.get((index + code - first) as usize).copied();
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+/// Decodes a raw DEFLATE stream. `None` when it is malformed or would
+/// grow past `limit` bytes, the size the archive claims.
+pub fn inflate(data: &[u8], limit: usize) -> Option<Vec<u8>> {
+    let mut bits = Bits {
+        data,
+        at: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut out = Vec::with_capacity(limit.min(1 << 24));
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = data.get(bits.at..bits.at + 4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                if len != !u16::from_le_bytes([header[2], header[3]]) {
+                    return None;
+                }
+                let start = bits.at + 4;
+                out.extend_from_slice(data.get(start..start + usize::from(len))?);
+                bits.at = start + usize::from(len);
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths)?;
+                let distances = Huffman::new(&[5; 30])?;
+                codes(&mut bits, &mut out, &literals, &distances, limit)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut out, &literals, &distances, limit)?;
+            }
+            _ => return None,
+        }
+        if out.len() > limit {
+            return None;
+        }
+        if last {
+            return Some(out);
+        }
+    }
+}
+
+/// Reads the code lengths a dynamic block starts with.
+fn dynamic_codes(bits: &mut Bits) -> Option<(Huffman, Huffman)> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_length_count = bits.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return None;
+    }
+    let mut code_lengths = [0u8; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[symbol] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let total = literal_count + distance_count;
+    let mut lengths = Vec::with_capacity(total);
+    while lengths.len() < total {
+        let symbol = code_lengths.decode(bits)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last()?, 3 + bits.bits(2)?),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > total {
+            return None;
+        }
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    // Without an end-of-block code the block could never finish.
+    if lengths[256] == 0 {
+        return None;
+    }
+    let literals = Huffman::new(&lengths[..literal_count])?;
+    let distances = Huffman::new(&lengths[literal_count..])?;
+    Some((literals, distances))
+}
+
+/// Decodes the symbols of one compressed block into `out`.
+fn codes(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+    limit: usize,
+) -> Option<()> {
+    loop {
+        let symbol = usize::from(literals.decode(bits)?);
+        match symbol {
+            0..=255 if out.len() < limit => out.push(symbol as u8),
+            0..=255 => return None,
+            256 => return Some(()),
+            _ => {
+                let index = symbol - 257;
+                let length = usize::from(*LENGTH_BASE.get(index)?)
+                    + bits.bits(LENGTH_EXTRA[index])? as usize;
+                let index = usize::from(distances.decode(bits)?);
+                let distance = usize::from(*DISTANCE_BASE.get(index)?)
+                    + bits.bits(DISTANCE_EXTRA[index])? as usize;
+                if distance > out.len() || out.len() + length > limit {
+                    return None;
+                }
+                // Byte by byte, as a copy may overlap what it produces.
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::archive::GzipWriter;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|at| u8::from_str_radix(&text[at..at + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn fixed_block_test() {
+        let compressed = hex("cb48cdc9c957c8c020cbf38b7252b800");
+        assert_eq!(
+            Some(b"hello hello hello hello world\n".to_vec()),
+            inflate(&compressed, 100)
+        );
+        // Larger than the archive said it would be.
+        assert_eq!(None, inflate(&compressed, 10));
+        assert_eq!(None, inflate(&compressed[..8], 100));
+    }
+
+    #[test]
+    fn dynamic_block_test() {
+        let compressed = hex(concat!(
+            "ed8f4b1283201044afd2d9a73c4796a98a1780f09104194501e1f4193587c822ebee796fba1f34e6",
+            "e49e6fc84825c0d086571aa7059475c4cab117ad4291ed7017dc1b2b24978a5b07189735474d0778",
+            "37278a7c6b970e372ac87a73c1fafac52b6156342da3580ec1058f697061031948bf83e724e2daae",
+            "9c2aab774da6d2a1e70f0e0d4b99c7de26a23a1927dad7b3f59ff12b333e"
+        ));
+        let sentence = "The quick brown fox jumps over the lazy dog. Pack my box with five dozen \
+                        liquor jugs. How vexingly quick daft zebras jump! Sphinx of black \
+                        quartz, judge my vow. The five boxing wizards jump quickly. ";
+        assert_eq!(
+            Some(sentence.repeat(3).into_bytes()),
+            inflate(&compressed, 1000)
+        );
+    }
+
+    #[test]
+    fn stored_block_test() {
+        let data: Vec<u8> = (0..70000u32).map(|i| (i % 251) as u8).collect();
+        let mut gzip = GzipWriter::default();
+        let mut stream = gzip.write(&data);
+        stream.extend(gzip.finish());
+        // Past the gzip header, up to the CRC and size trailer.
+        let deflate = &stream[10..stream.len() - 8];
+        assert_eq!(Some(data), inflate(deflate, 70000));
+        assert_eq!(None, inflate(&[0, 1, 0, 0, 0, b'x'], 10));
+    }
+}
@@ -0,0 +1,38 @@
+/// Largest HTML file `--inject-html` rewrites unless `--inject-max-size`
+/// says otherwise; bigger ones are served untouched.
+pub const DEFAULT_MAX_SIZE: u64 = 1 << 20;
+
+/// `html` with `snippet` inserted before its last `</body>`, matched
+/// case-insensitively. `None` when there is no `</body>`, since a fragment
+/// is better left alone than given a trailing script.
+pub fn before_body_end(html: &[u8], snippet: &str) -> Option<Vec<u8>> {
+    let end_of_body = html
+        .windows(7)
+        .rposition(|window| window.eq_ignore_ascii_case(b"</body>"))?;
+    let mut out = Vec::with_capacity(html.len() + snippet.len());
+    out.extend_from_slice(&html[..end_of_body]);
+    out.extend_from_slice(snippet.as_bytes());
+    out.extend_from_slice(&html[end_of_body..]);
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn before_body_end_test() {
+        let snippet = "<script src=/a.js></script>";
+        assert_eq!(
+            b"<body><p>hi</p><script src=/a.js></script></BODY></html>".to_vec(),
+            before_body_end(b"<body><p>hi</p></BODY></html>", snippet).unwrap()
+        );
+        // The last one wins, so a `</body>` inside a comment earlier on is
+        // skipped.
+        assert_eq!(
+            b"<!-- </body> --><body>x<script src=/a.js></script></body>".to_vec(),
+            before_body_end(b"<!-- </body> --><body>x</body>", snippet).unwrap()
+        );
+        assert_eq!(None, before_body_end(b"<p>fragment</p>", snippet));
+    }
+}
@@ -0,0 +1,91 @@
+use std::net::IpAddr;
+
+/// Every address assigned to a network interface, as `(name, address)`
+/// pairs in the order `getifaddrs` reports them.
+#[cfg(unix)]
+pub fn interfaces() -> std::io::Result<Vec<(String, IpAddr)>> {
+    use std::{
+        ffi::CStr,
+        net::{Ipv4Addr, Ipv6Addr},
+    };
+
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs fills `head` with a list we free below.
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut interfaces = Vec::new();
+    let mut cursor = head;
+    while !cursor.is_null() {
+        // SAFETY: `cursor` is a node of the list returned by getifaddrs and
+        // the address is only read as the type its family says it is.
+        unsafe {
+            let entry = &*cursor;
+            cursor = entry.ifa_next;
+            if entry.ifa_addr.is_null() {
+                continue;
+            }
+            let address = match (*entry.ifa_addr).sa_family as libc::c_int {
+                libc::AF_INET => {
+                    let address = &*(entry.ifa_addr as *const libc::sockaddr_in);
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)))
+                }
+                libc::AF_INET6 => {
+                    let address = &*(entry.ifa_addr as *const libc::sockaddr_in6);
+                    IpAddr::V6(Ipv6Addr::from(address.sin6_addr.s6_addr))
+                }
+                _ => continue,
+            };
+            let name = CStr::from_ptr(entry.ifa_name)
+                .to_string_lossy()
+                .into_owned();
+            interfaces.push((name, address));
+        }
+    }
+    // SAFETY: `head` came from a successful getifaddrs call.
+    unsafe { libc::freeifaddrs(head) };
+    Ok(interfaces)
+}
+
+#[cfg(not(unix))]
+pub fn interfaces() -> std::io::Result<Vec<(String, IpAddr)>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "listing interfaces is only supported on unix",
+    ))
+}
+
+/// The primary address of interface `name`: its first IPv4 address, or its
+/// first IPv6 address when it has no IPv4 one.
+pub fn interface_addr(name: &str) -> std::io::Result<Option<IpAddr>> {
+    let addresses: Vec<IpAddr> = interfaces()?
+        .into_iter()
+        .filter(|(interface, _)| interface == name)
+        .map(|(_, address)| address)
+        .collect();
+    Ok(addresses
+        .iter()
+        .find(|address| address.is_ipv4())
+        .or(addresses.first())
+        .copied())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bind_interface_test() {
+        let interfaces = interfaces().unwrap();
+        let (name, _) = interfaces
+            .iter()
+            .find(|(name, _)| name == "lo")
+            .or(interfaces.first())
+            .expect("no network interfaces");
+        let address = interface_addr(name).unwrap().unwrap();
+        let listener = std::net::TcpListener::bind((address, 0)).unwrap();
+        assert_eq!(address, listener.local_addr().unwrap().ip());
+
+        assert_eq!(None, interface_addr("does-not-exist0").unwrap());
+    }
+}
@@ -0,0 +1,130 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Quotes and escapes `value` as a JSON string literal.
+pub fn string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp such as `2024-01-01T00:00:00Z`.
+pub fn rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::{
+        collections::HashMap,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    /// Reads a flat JSON object of strings and numbers, as the loggers
+    /// write them, into its fields. Numbers are kept as their text.
+    pub fn parse_object(text: &str) -> Option<HashMap<String, String>> {
+        let mut rest = text.strip_prefix('{')?.strip_suffix('}')?;
+        let mut fields = HashMap::new();
+        while !rest.is_empty() {
+            let (name, after) = parse_string(rest)?;
+            let after = after.strip_prefix(':')?;
+            let (value, after) = if after.starts_with('"') {
+                parse_string(after)?
+            } else {
+                let end = after.find(',').unwrap_or(after.len());
+                (String::from(&after[..end]), &after[end..])
+            };
+            fields.insert(name, value);
+            rest = after.strip_prefix(',').unwrap_or(after);
+        }
+        Some(fields)
+    }
+
+    /// The string literal `text` starts with, unescaped, and what follows.
+    fn parse_string(text: &str) -> Option<(String, &str)> {
+        let mut chars = text.strip_prefix('"')?.char_indices();
+        let mut value = String::new();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => return Some((value, &text[index + 2..])),
+                '\\' => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    'r' => value.push('\r'),
+                    't' => value.push('\t'),
+                    'u' => {
+                        let digits: String = (0..4)
+                            .filter_map(|_| chars.next())
+                            .map(|(_, c)| c)
+                            .collect();
+                        value.push(char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?);
+                    }
+                    escaped => value.push(escaped),
+                },
+                c => value.push(c),
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn string_escape_test() {
+        assert_eq!(r#""a\"b\\c\n\u0001""#, super::string("a\"b\\c\n\u{1}"));
+    }
+
+    #[test]
+    fn rfc3339_test() {
+        assert_eq!("1970-01-01T00:00:00Z", super::rfc3339(UNIX_EPOCH));
+        assert_eq!(
+            "2024-02-29T13:45:07Z",
+            super::rfc3339(UNIX_EPOCH + Duration::from_secs(1709214307))
+        );
+    }
+
+    #[test]
+    fn parse_object_test() {
+        let text = format!(
+            "{{{}:{},\"n\":12}}",
+            super::string("a,b"),
+            super::string("x\"y\\z\n\u{1}")
+        );
+        let fields = parse_object(&text).unwrap();
+        assert_eq!("x\"y\\z\n\u{1}", fields["a,b"]);
+        assert_eq!("12", fields["n"]);
+        assert_eq!(Some(HashMap::new()), parse_object("{}"));
+        assert_eq!(None, parse_object("[]"));
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use hyper::{
+    header::{HeaderValue, CONNECTION},
+    HeaderMap,
+};
+
+/// Which request on its connection this is, counting from 1. Set by the
+/// connection's service for `--keep-alive-max-requests`.
+#[derive(Clone, Copy, Debug)]
+pub struct RequestCount(pub usize);
+
+/// `--keep-alive-timeout` and `--keep-alive-max-requests`, announced to
+/// HTTP/1.1 clients in a `Keep-Alive` header.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeepAliveConfig {
+    /// How long an idle connection stays open, `None` without a limit.
+    pub timeout: Option<Duration>,
+    /// Requests served on one connection before it is closed.
+    pub max_requests: Option<usize>,
+}
+
+impl KeepAliveConfig {
+    /// Adds `Keep-Alive` to the response to the `served`th request, with
+    /// `max` counting the requests still allowed after it. The last one
+    /// allowed gets `Connection: close` instead.
+    pub fn apply(&self, served: usize, headers: &mut HeaderMap) {
+        let closing = headers
+            .get(CONNECTION)
+            .is_some_and(|connection| connection.as_bytes().eq_ignore_ascii_case(b"close"));
+        if closing {
+            return;
+        }
+        if self.max_requests.is_some_and(|max| served >= max) {
+            headers.insert(CONNECTION, HeaderValue::from_static("close"));
+            return;
+        }
+        let mut params = Vec::new();
+        // Rounded down, so a client never waits past the server's timeout.
+        if let Some(timeout) = self.timeout.filter(|timeout| timeout.as_secs() > 0) {
+            params.push(format!("timeout={}", timeout.as_secs()));
+        }
+        if let Some(max) = self.max_requests {
+            params.push(format!("max={}", max - served));
+        }
+        if params.is_empty() {
+            return;
+        }
+        if let Ok(value) = HeaderValue::from_str(&params.join(", ")) {
+            headers.insert("keep-alive", value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apply_test() {
+        let config = KeepAliveConfig {
+            timeout: Some(Duration::from_millis(5500)),
+            max_requests: Some(3),
+        };
+        let mut headers = HeaderMap::new();
+        config.apply(1, &mut headers);
+        assert_eq!("timeout=5, max=2", headers["keep-alive"]);
+        assert!(!headers.contains_key(CONNECTION));
+
+        let mut headers = HeaderMap::new();
+        config.apply(3, &mut headers);
+        assert_eq!("close", headers[CONNECTION]);
+        assert!(!headers.contains_key("keep-alive"));
+
+        let mut headers = HeaderMap::new();
+        KeepAliveConfig::default().apply(1, &mut headers);
+        assert!(headers.is_empty());
+        KeepAliveConfig {
+            timeout: Some(Duration::from_millis(500)),
+            ..Default::default()
+        }
+        .apply(1, &mut headers);
+        assert!(headers.is_empty());
+    }
+}
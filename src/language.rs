@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+/// Languages from an `Accept-Language` header, most preferred first.
+/// Regional tags are followed by their primary language so `fr-CA` can
+/// still find an `fr` variant; `*` and `q=0` entries are dropped.
+pub fn preferred_languages(accept_language: &str) -> Vec<String> {
+    let mut weighted: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let q = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && q > 0.0).then_some((tag, q))
+        })
+        .collect();
+    // A stable sort keeps header order between equal weights.
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut languages = Vec::<String>::new();
+    for (tag, _) in weighted {
+        let primary = tag.split('-').next().unwrap_or(tag);
+        for language in [tag, primary] {
+            if !languages
+                .iter()
+                .any(|seen| seen.eq_ignore_ascii_case(language))
+            {
+                languages.push(String::from(language));
+            }
+        }
+    }
+    languages
+}
+
+/// `about.html` with `fr` becomes `about.fr.html`.
+pub fn variant(path: &Path, language: &str) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let extension = path.extension()?.to_str()?;
+    Some(path.with_file_name(format!("{}.{}.{}", stem, language, extension)))
+}
+
+/// The first existing variant of `path` in preference order.
+pub fn localized(path: &Path, accept_language: &str) -> Option<PathBuf> {
+    preferred_languages(accept_language)
+        .iter()
+        .filter_map(|language| variant(path, language))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preferred_languages_test() {
+        assert_eq!(vec!["fr", "en"], preferred_languages("fr,en;q=0.9"));
+        assert_eq!(
+            vec!["en", "de-CH", "de"],
+            preferred_languages("de-CH;q=0.5, en, *;q=0.1")
+        );
+        assert_eq!(vec!["en"], preferred_languages("fr;q=0, en"));
+        assert!(preferred_languages("").is_empty());
+    }
+
+    #[test]
+    fn variant_test() {
+        assert_eq!(
+            Some(PathBuf::from("dir/about.fr.html")),
+            variant(Path::new("dir/about.html"), "fr")
+        );
+        assert_eq!(None, variant(Path::new("LICENSE"), "fr"));
+    }
+}
@@ -0,0 +1,518 @@
+use std::{
+    future::Future,
+    net::{SocketAddr, TcpListener},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use hyper::server::{
+    accept::Accept,
+    conn::{AddrIncoming, AddrStream},
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{Instant, Sleep},
+};
+
+/// Unanswered keep-alive probes before a connection is dropped.
+const KEEPALIVE_RETRIES: u32 = 3;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ListenOptions {
+    /// Let several processes bind the same port, with the kernel spreading
+    /// connections between them.
+    pub reuse_port: bool,
+    /// Idle time before the first keep-alive probe, and between probes.
+    /// `None` leaves keep-alive to the OS.
+    pub keepalive: Option<Duration>,
+    pub nodelay: bool,
+    /// Longest request or header line, in bytes, before the connection is
+    /// dropped.
+    pub max_request_line: Option<usize>,
+    /// How long a connection may go without reading or writing a byte, such
+    /// as between keep-alive requests, before it is closed. Time spent
+    /// handling a request does not count.
+    pub idle_timeout: Option<Duration>,
+}
+
+/// Binds a listening socket by hand so options that must be set before
+/// `bind` can be applied; hyper then takes it over with `Server::from_tcp`.
+pub fn bind(addr: SocketAddr, options: ListenOptions) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    if options.reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+/// Hands `listener` to hyper, which applies the per-connection options to
+/// every socket it accepts.
+pub fn incoming(listener: TcpListener, options: ListenOptions) -> std::io::Result<LimitedIncoming> {
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let mut incoming = AddrIncoming::from_listener(listener).map_err(std::io::Error::other)?;
+    incoming.set_nodelay(options.nodelay);
+    if let Some(keepalive) = options.keepalive {
+        incoming
+            .set_keepalive(Some(keepalive))
+            .set_keepalive_interval(Some(keepalive))
+            .set_keepalive_retries(Some(KEEPALIVE_RETRIES));
+    }
+    Ok(LimitedIncoming {
+        inner: incoming,
+        max_request_line: options.max_request_line,
+        idle_timeout: options.idle_timeout,
+    })
+}
+
+/// Accepts connections from [`AddrIncoming`], wrapping each in a
+/// [`LimitedReader`].
+pub struct LimitedIncoming {
+    inner: AddrIncoming,
+    max_request_line: Option<usize>,
+    idle_timeout: Option<Duration>,
+}
+
+impl LimitedIncoming {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.inner.local_addr()
+    }
+}
+
+impl Accept for LimitedIncoming {
+    type Conn = LimitedReader<AddrStream>;
+    type Error = std::io::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let max_request_line = self.max_request_line;
+        let idle_timeout = self.idle_timeout;
+        Pin::new(&mut self.inner).poll_accept(cx).map_ok(|stream| {
+            LimitedReader::new(stream, max_request_line).with_idle_timeout(idle_timeout)
+        })
+    }
+}
+
+/// The requests of one connection that are being handled. While there are
+/// any the connection is waiting on the server, not idle, however long a
+/// handler takes to answer.
+#[derive(Clone, Debug, Default)]
+pub struct InFlight(Arc<Requests>);
+
+#[derive(Debug, Default)]
+struct Requests {
+    count: AtomicUsize,
+    /// The reader that left its idle timer off while they ran, to start it
+    /// again once the last one is done.
+    idle_reader: Mutex<Option<Waker>>,
+}
+
+/// Ends one request of an [`InFlight`] when dropped, however its future
+/// finishes.
+struct InFlightGuard(Arc<Requests>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            if let Some(waker) = self.0.idle_reader.lock().unwrap().take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl InFlight {
+    /// Counts `handler` as in flight from now until it has finished.
+    pub fn track<F: Future>(&self, handler: F) -> impl Future<Output = F::Output> {
+        self.0.count.fetch_add(1, Ordering::AcqRel);
+        let guard = InFlightGuard(self.0.clone());
+        async move {
+            let output = handler.await;
+            drop(guard);
+            output
+        }
+    }
+
+    /// Whether requests are running, asking to be woken once they are not.
+    fn is_busy(&self, cx: &Context<'_>) -> bool {
+        if self.0.count.load(Ordering::Acquire) == 0 {
+            return false;
+        }
+        *self.0.idle_reader.lock().unwrap() = Some(cx.waker().clone());
+        // The last one may have finished before the waker was stored.
+        self.0.count.load(Ordering::Acquire) > 0
+    }
+}
+
+/// Fails reads with `ConnectionReset` once a line of the first request head
+/// runs past the limit without a `\n`, so hyper drops the connection instead
+/// of buffering it. Counting stops at the blank line ending the head: body
+/// bytes are not lines, and later requests on the connection are bounded by
+/// hyper's own buffer limit.
+///
+/// With an idle timeout, reads also fail with `TimedOut` once no byte has
+/// gone either way for that long while no request is [`InFlight`]. The
+/// timeout starts over when the last one finishes.
+pub struct LimitedReader<R> {
+    inner: R,
+    limit: usize,
+    line_len: usize,
+    seen_line: bool,
+    head_done: bool,
+    idle: Option<(Duration, Pin<Box<Sleep>>)>,
+    in_flight: InFlight,
+    was_busy: bool,
+}
+
+impl<R> LimitedReader<R> {
+    pub fn new(inner: R, limit: Option<usize>) -> Self {
+        Self {
+            inner,
+            limit: limit.unwrap_or(usize::MAX),
+            line_len: 0,
+            seen_line: false,
+            head_done: limit.is_none(),
+            idle: None,
+            in_flight: InFlight::default(),
+            was_busy: false,
+        }
+    }
+
+    pub fn with_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle = timeout.map(|timeout| (timeout, Box::pin(tokio::time::sleep(timeout))));
+        self
+    }
+
+    fn touch(&mut self) {
+        if let Some((timeout, sleep)) = &mut self.idle {
+            sleep.as_mut().reset(Instant::now() + *timeout);
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// What the connection's requests are tracked with, so the idle timeout
+    /// leaves them alone.
+    pub fn in_flight(&self) -> &InFlight {
+        &self.in_flight
+    }
+
+    fn count(&mut self, data: &[u8]) -> std::io::Result<()> {
+        for &byte in data {
+            if self.head_done {
+                break;
+            }
+            match byte {
+                b'\n' => {
+                    // An empty line (or a lone `\r`) after the request line
+                    // ends the head; empty lines before it are ignored.
+                    if self.line_len <= 1 && self.seen_line {
+                        self.head_done = true;
+                    }
+                    self.seen_line |= self.line_len > 1;
+                    self.line_len = 0;
+                }
+                _ => self.line_len += 1,
+            }
+            if self.line_len > self.limit {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionReset,
+                    "request line too long",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for LimitedReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                self.touch();
+                let counted = self.count(&buf.filled()[filled..]);
+                if counted.is_err() {
+                    buf.set_filled(filled);
+                }
+                Poll::Ready(counted)
+            }
+            Poll::Pending => {
+                if self.in_flight.is_busy(cx) {
+                    self.was_busy = true;
+                    return Poll::Pending;
+                }
+                if std::mem::take(&mut self.was_busy) {
+                    self.touch();
+                }
+                let idle = self
+                    .idle
+                    .as_mut()
+                    .is_some_and(|(_, sleep)| sleep.as_mut().poll(cx).is_ready());
+                if idle {
+                    return Poll::Ready(Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "connection idle",
+                    )));
+                }
+                Poll::Pending
+            }
+            other => other,
+        }
+    }
+}
+
+impl<R: AsyncWrite + Unpin> AsyncWrite for LimitedReader<R> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let written = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(1..)) = written {
+            self.touch();
+        }
+        written
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+fn set_reuse_port(socket: &Socket) -> std::io::Result<()> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+fn set_reuse_port(_socket: &Socket) -> std::io::Result<()> {
+    eprintln!("SO_REUSEPORT is not supported on this platform, ignoring --reuseport");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    #[test]
+    fn reuse_port_test() {
+        let options = ListenOptions {
+            reuse_port: true,
+            ..Default::default()
+        };
+        let first = bind("127.0.0.1:0".parse().unwrap(), options).unwrap();
+        let addr = first.local_addr().unwrap();
+        let second = bind(addr, options).unwrap();
+        assert_eq!(addr, second.local_addr().unwrap());
+
+        assert!(bind(addr, ListenOptions::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn limited_reader_test() {
+        use tokio::io::AsyncReadExt;
+
+        let read = |input: &'static [u8], limit: Option<usize>| async move {
+            let mut out = Vec::new();
+            LimitedReader::new(input, limit)
+                .read_to_end(&mut out)
+                .await
+                .map(|_| out.len())
+        };
+        let head: &[u8] = b"\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n";
+        assert_eq!(head.len(), read(head, Some(16)).await.unwrap());
+        let err = read(b"GET /a-long-path HTTP/1.1\r\n", Some(16))
+            .await
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::ConnectionReset, err.kind());
+        assert!(
+            read(b"GET / HTTP/1.1\r\nX-Long: aaaaaaaaaaaaaaaa\r\n", Some(16))
+                .await
+                .is_err()
+        );
+        // The body after the head is not counted.
+        let request: &[u8] = b"PUT / HTTP/1.1\r\n\r\naaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        assert!(read(request, Some(16)).await.is_ok());
+        assert!(read(b"GET /a-long-path HTTP/1.1\r\n", None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn request_line_reset_test() {
+        use hyper::{service::service_fn, Body, Response, Server};
+        use std::io::{Read, Write};
+
+        let options = ListenOptions {
+            max_request_line: Some(8192),
+            ..Default::default()
+        };
+        let listener = bind("127.0.0.1:0".parse().unwrap(), options).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let make_service = hyper::service::make_service_fn(|_: &LimitedReader<AddrStream>| async {
+            Ok::<_, std::convert::Infallible>(service_fn(|_| async {
+                Ok::<_, std::convert::Infallible>(Response::new(Body::from("ok")))
+            }))
+        });
+        tokio::spawn(Server::builder(incoming(listener, options).unwrap()).serve(make_service));
+
+        let response = tokio::task::spawn_blocking(move || {
+            let mut ok = std::net::TcpStream::connect(addr).unwrap();
+            ok.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+            let mut response = String::new();
+            ok.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+
+            let mut long = std::net::TcpStream::connect(addr).unwrap();
+            // Writing may already fail once the server has reset.
+            let _ = long.write_all(&[b'a'; 100_000]);
+            let mut response = Vec::new();
+            let _ = long.read_to_end(&mut response);
+            response
+        })
+        .await
+        .unwrap();
+        assert!(response.is_empty());
+    }
+
+    #[tokio::test]
+    async fn idle_timeout_test() {
+        use hyper::{service::service_fn, Body, Response, Server};
+        use std::io::{Read, Write};
+
+        let options = ListenOptions {
+            idle_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let listener = bind("127.0.0.1:0".parse().unwrap(), options).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let make_service = hyper::service::make_service_fn(|_: &LimitedReader<AddrStream>| async {
+            Ok::<_, std::convert::Infallible>(service_fn(|_| async {
+                Ok::<_, std::convert::Infallible>(Response::new(Body::from("ok")))
+            }))
+        });
+        tokio::spawn(Server::builder(incoming(listener, options).unwrap()).serve(make_service));
+
+        let (response, elapsed) = tokio::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+                .unwrap();
+            let started = std::time::Instant::now();
+            // The server keeps the connection open after answering, until
+            // it has been idle for the timeout.
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            (response, started.elapsed())
+        })
+        .await
+        .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(elapsed >= Duration::from_millis(150), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn slow_handler_idle_timeout_test() {
+        use hyper::{service::service_fn, Body, Response, Server};
+        use std::io::{Read, Write};
+
+        let options = ListenOptions {
+            idle_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let listener = bind("127.0.0.1:0".parse().unwrap(), options).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let make_service = hyper::service::make_service_fn(|conn: &LimitedReader<AddrStream>| {
+            let in_flight = conn.in_flight().clone();
+            async move {
+                Ok::<_, std::convert::Infallible>(service_fn(move |_| {
+                    in_flight.track(async {
+                        // Longer than the idle timeout before the first byte.
+                        tokio::time::sleep(Duration::from_millis(600)).await;
+                        Ok::<_, std::convert::Infallible>(Response::new(Body::from("slow")))
+                    })
+                }))
+            }
+        });
+        tokio::spawn(Server::builder(incoming(listener, options).unwrap()).serve(make_service));
+
+        let (response, elapsed) = tokio::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+                .unwrap();
+            let started = std::time::Instant::now();
+            let mut response = String::new();
+            let _ = stream.read_to_string(&mut response);
+            (response, started.elapsed())
+        })
+        .await
+        .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{:?}", response);
+        assert!(response.ends_with("slow"), "{:?}", response);
+        // Once answered, the connection times out as an idle one would.
+        assert!(elapsed < Duration::from_secs(3), "{:?}", elapsed);
+    }
+
+    #[tokio::test]
+    async fn socket_options_test() {
+        use hyper::server::accept::Accept;
+
+        let options = ListenOptions {
+            keepalive: Some(Duration::from_secs(60)),
+            nodelay: true,
+            ..Default::default()
+        };
+        let listener = bind("127.0.0.1:0".parse().unwrap(), options).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut incoming = incoming(listener, options).unwrap();
+        let _client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let accepted = std::future::poll_fn(|cx| std::pin::Pin::new(&mut incoming).poll_accept(cx))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let socket = socket2::SockRef::from(accepted.get_ref());
+        assert!(socket.nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(Duration::from_secs(60), socket.keepalive_time().unwrap());
+            assert_eq!(
+                Duration::from_secs(60),
+                socket.keepalive_interval().unwrap()
+            );
+            assert_eq!(KEEPALIVE_RETRIES, socket.keepalive_retries().unwrap());
+        }
+    }
+}
@@ -0,0 +1,599 @@
+use std::{
+    cmp::Ordering,
+    path::Path,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{
+    header::{ACCEPT, CONTENT_TYPE, ETAG, LAST_MODIFIED, LOCATION, VARY},
+    http::response::Builder,
+    Body, Request, Response,
+};
+use log::{error, info};
+
+use crate::{
+    digest, error_response, etag,
+    fs::{FileSystem, FollowSymlinks},
+    json, query_param, ServeConfig,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortOrder {
+    #[default]
+    NameAsc,
+    NameDesc,
+    MtimeDesc,
+    MtimeAsc,
+    SizeDesc,
+    SizeAsc,
+}
+
+impl SortOrder {
+    /// The `--sort` value selecting this order.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NameAsc => "name-asc",
+            Self::NameDesc => "name-desc",
+            Self::MtimeDesc => "mtime-desc",
+            Self::MtimeAsc => "mtime-asc",
+            Self::SizeDesc => "size-desc",
+            Self::SizeAsc => "size-asc",
+        }
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = ();
+
+    fn from_str(order: &str) -> Result<Self, Self::Err> {
+        match order {
+            "name-asc" => Ok(Self::NameAsc),
+            "name-desc" => Ok(Self::NameDesc),
+            "mtime-desc" => Ok(Self::MtimeDesc),
+            "mtime-asc" => Ok(Self::MtimeAsc),
+            "size-desc" => Ok(Self::SizeDesc),
+            "size-asc" => Ok(Self::SizeAsc),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How `--fingerprint-links` versions file links in listings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LinkFingerprint {
+    /// The modification time, which needs no read of the file.
+    #[default]
+    Mtime,
+    /// A truncated SHA-256 of the contents.
+    Hash,
+}
+
+impl FromStr for LinkFingerprint {
+    type Err = ();
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "mtime" => Ok(Self::Mtime),
+            "hash" => Ok(Self::Hash),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct Entry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: SystemTime,
+    /// Cache busting `?v=` value appended to the link, for files only.
+    pub version: Option<String>,
+    pub symlink: Option<Symlink>,
+}
+
+/// A symlinked entry, listed with its target.
+pub struct Symlink {
+    pub target: String,
+    pub state: LinkState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkState {
+    Followed,
+    /// Not served under `--follow-symlinks`, so listed without a link.
+    Blocked,
+    Broken,
+}
+
+impl LinkState {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Followed => "followed",
+            Self::Blocked => "blocked",
+            Self::Broken => "broken",
+        }
+    }
+}
+
+impl Entry {
+    fn href(&self) -> String {
+        let mut href = percent_encode(&self.name);
+        if self.is_dir {
+            href.push('/');
+        }
+        if let Some(version) = &self.version {
+            href.push_str("?v=");
+            href.push_str(version);
+        }
+        href
+    }
+}
+
+/// Sets the `version` of every file in `dir` according to `mode`.
+pub async fn fingerprint_entries(
+    fs: &dyn FileSystem,
+    dir: &Path,
+    entries: &mut [Entry],
+    mode: LinkFingerprint,
+) {
+    let servable = |entry: &&mut Entry| {
+        !entry.is_dir
+            && entry
+                .symlink
+                .as_ref()
+                .is_none_or(|link| link.state == LinkState::Followed)
+    };
+    for entry in entries.iter_mut().filter(servable) {
+        entry.version = match mode {
+            LinkFingerprint::Mtime => Some(format!(
+                "{:x}",
+                entry
+                    .modified
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+            )),
+            LinkFingerprint::Hash => fs.read_file(&dir.join(&entry.name)).await.ok().map(|body| {
+                digest::sha256(&body)[..6]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect()
+            }),
+        };
+    }
+}
+
+/// Reads the visible entries of `dir`; hidden files are never served, so
+/// they are not listed either. Symlinks are marked blocked when `follow`
+/// does not allow serving them from `root`.
+pub async fn read_entries(
+    fs: &dyn FileSystem,
+    dir: &Path,
+    root: &Path,
+    follow: FollowSymlinks,
+) -> std::io::Result<Vec<Entry>> {
+    Ok(fs
+        .read_dir(dir)
+        .await?
+        .into_iter()
+        .filter(|entry| !entry.name.starts_with('.'))
+        .map(|entry| {
+            let symlink = entry.symlink.map(|link| Symlink {
+                target: link.target.to_string_lossy().into_owned(),
+                state: if link.broken {
+                    LinkState::Broken
+                } else if follow.allows(root, &dir.join(&entry.name)) {
+                    LinkState::Followed
+                } else {
+                    LinkState::Blocked
+                },
+            });
+            Entry {
+                name: entry.name,
+                is_dir: entry.metadata.is_dir,
+                size: entry.metadata.len,
+                modified: entry.metadata.modified.unwrap_or(UNIX_EPOCH),
+                version: None,
+                symlink,
+            }
+        })
+        .collect())
+}
+
+/// Sorts directories before files, then by `order`, falling back to the name
+/// so the listing is stable.
+pub fn sort_entries(entries: &mut [Entry], order: SortOrder) {
+    entries.sort_by(|a, b| {
+        let by_order = match order {
+            SortOrder::NameAsc => Ordering::Equal,
+            SortOrder::NameDesc => b.name.cmp(&a.name),
+            SortOrder::MtimeDesc => b.modified.cmp(&a.modified),
+            SortOrder::MtimeAsc => a.modified.cmp(&b.modified),
+            SortOrder::SizeDesc => b.size.cmp(&a.size),
+            SortOrder::SizeAsc => a.size.cmp(&b.size),
+        };
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then(by_order)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+pub fn render_html(uri_path: &str, entries: &[Entry]) -> String {
+    let title = html_escape(uri_path);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n<body>\n<h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    if uri_path != "/" {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for entry in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        let name = format!("{}{}", html_escape(&entry.name), suffix);
+        let Some(symlink) = &entry.symlink else {
+            html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                html_escape(&entry.href()),
+                name
+            ));
+            continue;
+        };
+        let target = html_escape(&symlink.target);
+        html.push_str(&match symlink.state {
+            LinkState::Followed => format!(
+                "<li><a href=\"{}\">{}</a> -&gt; {}</li>\n",
+                html_escape(&entry.href()),
+                name,
+                target
+            ),
+            LinkState::Blocked => format!("<li>{} -&gt; {} (not followed)</li>\n", name, target),
+            LinkState::Broken => format!("<li>{} -&gt; {} (broken link)</li>\n", name, target),
+        });
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    html
+}
+
+pub fn render_json(entries: &[Entry]) -> String {
+    let items: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let href = match entry.version {
+                Some(_) => format!(",\"href\":{}", json::string(&entry.href())),
+                None => String::new(),
+            };
+            let symlink = match &entry.symlink {
+                Some(symlink) => format!(
+                    ",\"symlink\":{{\"target\":{},\"state\":{}}}",
+                    json::string(&symlink.target),
+                    json::string(symlink.state.as_str())
+                ),
+                None => String::new(),
+            };
+            format!(
+                "{{\"name\":{},\"size\":{},\"mtime\":{},\"type\":{}{}{}}}",
+                json::string(&entry.name),
+                entry.size,
+                json::string(&json::rfc3339(entry.modified)),
+                json::string(if entry.is_dir { "directory" } else { "file" }),
+                href,
+                symlink
+            )
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+/// The `--index-json` document: followable files with their size and
+/// modification time, and directory names ending in `/`. Blocked and broken
+/// symlinks are left out since they cannot be fetched.
+pub fn render_index_json(entries: &[Entry]) -> String {
+    let servable = entries.iter().filter(|entry| {
+        entry
+            .symlink
+            .as_ref()
+            .is_none_or(|link| link.state == LinkState::Followed)
+    });
+    let (dirs, files): (Vec<&Entry>, Vec<&Entry>) = servable.partition(|entry| entry.is_dir);
+    let files: Vec<String> = files
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"name\":{},\"size\":{},\"mtime\":{}}}",
+                json::string(&entry.name),
+                entry.size,
+                json::string(&json::rfc3339(entry.modified))
+            )
+        })
+        .collect();
+    let dirs: Vec<String> = dirs
+        .iter()
+        .map(|entry| json::string(&format!("{}/", entry.name)))
+        .collect();
+    format!(
+        "{{\"files\":[{}],\"dirs\":[{}]}}",
+        files.join(","),
+        dirs.join(",")
+    )
+}
+
+/// With both `--index-json` and `--list-directories`, whether to answer
+/// with HTML: for `?format=html`, or an `Accept` header naming `text/html`
+/// as browsers send. Other clients get JSON.
+fn prefers_html(request: &Request<Body>) -> bool {
+    match query_param(request.uri().query(), "format") {
+        Some("json") => return false,
+        Some("html") => return true,
+        _ => {}
+    }
+    request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Whether the client asked for JSON, via `?format=json` or an `Accept`
+/// header that names `application/json` but not `text/html`.
+fn wants_json(request: &Request<Body>) -> bool {
+    match query_param(request.uri().query(), "format") {
+        Some("json") => return true,
+        Some("html") => return false,
+        _ => {}
+    }
+    request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json") && !accept.contains("text/html"))
+}
+
+pub async fn handle_listing(
+    request: &Request<Body>,
+    shared_data: &ServeConfig,
+    response_builder: Builder,
+    dir: &Path,
+    time_of_request: u128,
+) -> Response<Body> {
+    let (uri, method) = (request.uri(), request.method());
+    if !uri.path().ends_with('/') {
+        let location = match uri.query() {
+            Some(query) => format!("{}/?{}", uri.path(), query),
+            None => format!("{}/", uri.path()),
+        };
+        let location = shared_data
+            .proxy
+            .as_ref()
+            .and_then(|proxy| proxy.absolute_location(request, &location))
+            .unwrap_or(location);
+        info!(
+            "{}: [301] [{}] {} redirected to {}",
+            time_of_request, method, uri, location
+        );
+        return response_builder
+            .status(301)
+            .header(LOCATION, location)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let root = Path::new(&shared_data.directory_path);
+    let follow = shared_data.follow_symlinks;
+    let mut entries = match read_entries(&*shared_data.fs, dir, root, follow).await {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+            return error_response(
+                shared_data,
+                response_builder,
+                500,
+                "Something Went Wrong :(",
+            )
+            .await;
+        }
+    };
+    let order = query_param(uri.query(), "sort")
+        .filter(|_| shared_data.allow_sort_param)
+        .and_then(|order| order.parse().ok())
+        .unwrap_or(shared_data.default_sort);
+    sort_entries(&mut entries, order);
+    if let Some(mode) = shared_data.fingerprint_links {
+        fingerprint_entries(&*shared_data.fs, dir, &mut entries, mode).await;
+    }
+
+    info!(
+        "{}: [200] [{}] {} listed directory",
+        time_of_request, method, uri
+    );
+    let response_builder = response_builder.header(VARY, "Accept");
+    let json = if shared_data.index_json {
+        (!shared_data.list_directories || !prefers_html(request))
+            .then(|| render_index_json(&entries))
+    } else {
+        wants_json(request).then(|| render_json(&entries))
+    };
+    if let Some(body) = json {
+        let mut response_builder = response_builder
+            .header(CONTENT_TYPE, "application/json")
+            .header(ETAG, etag::content_etag(body.as_bytes()));
+        let modified = shared_data.fs.metadata(dir).await.ok();
+        if let Some(modified) = modified.and_then(|metadata| metadata.modified) {
+            response_builder =
+                response_builder.header(LAST_MODIFIED, httpdate::fmt_http_date(modified));
+        }
+        return response_builder.body(Body::from(body)).unwrap();
+    }
+    response_builder
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(render_html(uri.path(), &entries)))
+        .unwrap()
+}
+
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Percent-encodes a single path segment for use in an `href`.
+pub fn percent_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fs::LocalFileSystem;
+    use std::time::Duration;
+
+    fn entries() -> Vec<Entry> {
+        let entry = |name: &str, is_dir, size, secs| Entry {
+            name: String::from(name),
+            is_dir,
+            size,
+            modified: UNIX_EPOCH + Duration::from_secs(secs),
+            version: None,
+            symlink: None,
+        };
+        vec![
+            entry("b.txt", false, 30, 1),
+            entry("a.txt", false, 10, 3),
+            entry("dir", true, 0, 2),
+            entry("c.txt", false, 20, 2),
+        ]
+    }
+
+    fn sorted(order: &str) -> Vec<String> {
+        let mut entries = entries();
+        sort_entries(&mut entries, order.parse().unwrap());
+        entries.into_iter().map(|entry| entry.name).collect()
+    }
+
+    #[test]
+    fn sort_order_test() {
+        assert_eq!(vec!["dir", "a.txt", "b.txt", "c.txt"], sorted("name-asc"));
+        assert_eq!(vec!["dir", "c.txt", "b.txt", "a.txt"], sorted("name-desc"));
+        assert_eq!(vec!["dir", "a.txt", "c.txt", "b.txt"], sorted("mtime-desc"));
+        assert_eq!(vec!["dir", "b.txt", "c.txt", "a.txt"], sorted("mtime-asc"));
+        assert_eq!(vec!["dir", "b.txt", "c.txt", "a.txt"], sorted("size-desc"));
+        assert_eq!(vec!["dir", "a.txt", "c.txt", "b.txt"], sorted("size-asc"));
+        assert!("newest".parse::<SortOrder>().is_err());
+    }
+
+    #[test]
+    fn render_html_test() {
+        let html = render_html(
+            "/docs/",
+            &[Entry {
+                name: String::from("a b&<c>.txt"),
+                is_dir: false,
+                size: 1,
+                modified: UNIX_EPOCH,
+                version: None,
+                symlink: None,
+            }],
+        );
+        assert!(html.contains("<a href=\"../\">"));
+        assert!(html.contains("<a href=\"a%20b%26%3Cc%3E.txt\">a b&amp;&lt;c&gt;.txt</a>"));
+    }
+
+    #[tokio::test]
+    async fn fingerprint_links_test() {
+        let dir = crate::test::temp_dir("fingerprint-links");
+        std::fs::write(format!("{}app.js", dir), "abc").unwrap();
+        std::fs::create_dir(format!("{}assets", dir)).unwrap();
+        let fs = LocalFileSystem;
+        let dir = Path::new(&dir);
+        let mut entries = read_entries(&fs, dir, dir, FollowSymlinks::All)
+            .await
+            .unwrap();
+        sort_entries(&mut entries, SortOrder::NameAsc);
+        fingerprint_entries(&fs, dir, &mut entries, LinkFingerprint::Hash).await;
+
+        // SHA-256 of "abc" starts ba7816bf8f01.
+        let html = render_html("/", &entries);
+        assert!(html.contains("<a href=\"app.js?v=ba7816bf8f01\">app.js</a>"));
+        assert!(html.contains("<a href=\"assets/\">assets/</a>"));
+        let json = render_json(&entries);
+        assert!(json.contains(r#""href":"app.js?v=ba7816bf8f01""#));
+
+        fingerprint_entries(&fs, dir, &mut entries, LinkFingerprint::Mtime).await;
+        let html = render_html("/", &entries);
+        assert!(html.contains("<a href=\"app.js?v="));
+        assert!(!html.contains("ba7816bf8f01"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlink_entries_test() {
+        let root = crate::test::temp_dir("symlink-entries");
+        let outside = crate::test::temp_dir("symlink-entries-outside");
+        std::fs::create_dir(format!("{}inner", root)).unwrap();
+        let link = |target: &str, name: &str| {
+            std::os::unix::fs::symlink(target, format!("{}{}", root, name)).unwrap()
+        };
+        link("inner", "inside");
+        link(&outside, "outside");
+        link("missing.txt", "dangling");
+        let root = Path::new(&root);
+
+        let html = |follow| async move {
+            let mut entries = read_entries(&LocalFileSystem, root, root, follow)
+                .await
+                .unwrap();
+            sort_entries(&mut entries, SortOrder::NameAsc);
+            render_html("/", &entries)
+        };
+        let safe = html(FollowSymlinks::Safe).await;
+        assert!(safe.contains("<li><a href=\"inside/\">inside/</a> -&gt; inner</li>"));
+        assert!(safe.contains(&format!(
+            "<li>outside/ -&gt; {} (not followed)</li>",
+            outside
+        )));
+        assert!(safe.contains("<li>dangling -&gt; missing.txt (broken link)</li>"));
+        assert!(safe.contains("<li><a href=\"inner/\">inner/</a></li>"));
+
+        let all = html(FollowSymlinks::All).await;
+        assert!(all.contains("<li><a href=\"outside/\">outside/</a> -&gt; "));
+        let never = html(FollowSymlinks::Never).await;
+        assert!(never.contains("<li>inside/ -&gt; inner (not followed)</li>"));
+    }
+
+    #[test]
+    fn render_index_json_test() {
+        let mut entries = entries();
+        let last = entries.pop().unwrap();
+        entries.push(Entry {
+            symlink: Some(Symlink {
+                target: String::from("missing"),
+                state: LinkState::Broken,
+            }),
+            ..last
+        });
+        sort_entries(&mut entries, SortOrder::NameAsc);
+        assert_eq!(
+            r#"{"files":[{"name":"a.txt","size":10,"mtime":"1970-01-01T00:00:03Z"},{"name":"b.txt","size":30,"mtime":"1970-01-01T00:00:01Z"}],"dirs":["dir/"]}"#,
+            render_index_json(&entries)
+        );
+    }
+
+    #[test]
+    fn render_json_test() {
+        let json = render_json(&entries()[1..3]);
+        assert_eq!(
+            r#"[{"name":"a.txt","size":10,"mtime":"1970-01-01T00:00:03Z","type":"file"},{"name":"dir","size":0,"mtime":"1970-01-01T00:00:02Z","type":"directory"}]"#,
+            json
+        );
+    }
+}
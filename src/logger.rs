@@ -0,0 +1,554 @@
+use std::{
+    future::Future,
+    io::{BufWriter, Write},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::{
+    ecs::{AccessRecord, EcsLogRecord},
+    syslog::Syslog,
+};
+
+struct RequestContext {
+    start: Instant,
+    client: Option<IpAddr>,
+    id: String,
+}
+
+tokio::task_local! {
+    static REQUEST: RequestContext;
+}
+
+/// The logger [`Logger::init`] installed, for records `log` can't carry.
+static INSTALLED: OnceLock<&'static Logger> = OnceLock::new();
+
+/// Runs `future` as one request: every line it logs ends with
+/// `duration_us=`, the time since it started, and carries the `client`
+/// address after its timestamp. ECS documents also get `request_id`.
+pub async fn timed<F: Future>(client: Option<IpAddr>, request_id: String, future: F) -> F::Output {
+    let context = RequestContext {
+        start: Instant::now(),
+        client,
+        id: request_id,
+    };
+    REQUEST.scope(context, future).await
+}
+
+/// An id for a request that didn't bring its own `X-Request-Id`. The time
+/// keeps ids from repeating across restarts and the counter within one
+/// millisecond.
+pub fn next_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:x}", crate::now_millis(), count)
+}
+
+/// Id of the request being handled; `None` outside [`timed`].
+pub fn request_id() -> Option<String> {
+    REQUEST.try_with(|context| context.id.clone()).ok()
+}
+
+/// Logs a finished request through the installed logger.
+pub fn access(record: &AccessRecord) {
+    if let Some(logger) = INSTALLED.get() {
+        logger.log_access(record);
+    }
+}
+
+/// Time since the request being handled started; `None` outside
+/// [`timed`].
+pub fn elapsed() -> Option<Duration> {
+    REQUEST.try_with(|context| context.start.elapsed()).ok()
+}
+
+pub fn client() -> Option<IpAddr> {
+    REQUEST.try_with(|context| context.client).ok().flatten()
+}
+
+/// Inserts `client` after the `{timestamp}: ` that starts request lines;
+/// other lines are returned unchanged.
+fn with_client(line: String, client: IpAddr) -> String {
+    let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 || !line[digits..].starts_with(": ") {
+        return line;
+    }
+    let (timestamp, rest) = line.split_at(digits + 2);
+    format!("{}{} {}", timestamp, client, rest)
+}
+
+/// Whether access log lines are colored by their status.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorMode {
+    /// Only when writing to a terminal and `NO_COLOR` is unset.
+    #[default]
+    Auto,
+    Never,
+    Always,
+}
+
+impl ColorMode {
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            Self::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none(),
+            Self::Never => false,
+            Self::Always => true,
+        }
+    }
+}
+
+/// `--log-format`: how the access log writes a line.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One Elastic Common Schema JSON document per line.
+    Ecs,
+}
+
+impl LogFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "text" => Some(Self::Text),
+            "ecs" => Some(Self::Ecs),
+            _ => None,
+        }
+    }
+}
+
+/// `--error-log`: records at or above `level` go here instead of the
+/// access log. Writes are buffered until [`Log::flush`].
+pub struct ErrorLog {
+    level: LevelFilter,
+    output: Mutex<BufWriter<Box<dyn Write + Send>>>,
+}
+
+impl ErrorLog {
+    pub fn new(level: LevelFilter, output: Box<dyn Write + Send>) -> Self {
+        Self {
+            level,
+            output: Mutex::new(BufWriter::new(output)),
+        }
+    }
+
+    fn accepts(&self, level: Level) -> bool {
+        level <= self.level
+    }
+
+    fn write(&self, line: &str) {
+        let _ = self.output.lock().unwrap().write_all(line.as_bytes());
+    }
+
+    /// Records a server event such as startup or shutdown, whatever the
+    /// level.
+    pub fn event(&self, message: &str) {
+        self.write(&format!("{}: {}\n", crate::now_millis(), message));
+    }
+
+    pub fn flush(&self) {
+        let _ = self.output.lock().unwrap().flush();
+    }
+}
+
+/// Writes log records on their own line, dropping those above `level`.
+pub struct Logger {
+    level: LevelFilter,
+    output: Mutex<Box<dyn Write + Send>>,
+    syslog: Option<Syslog>,
+    error_log: Option<Arc<ErrorLog>>,
+    color: bool,
+    format: LogFormat,
+}
+
+impl Logger {
+    pub fn new(level: LevelFilter, output: Box<dyn Write + Send>) -> Self {
+        Self {
+            level,
+            output: Mutex::new(output),
+            syslog: None,
+            error_log: None,
+            color: false,
+            format: LogFormat::Text,
+        }
+    }
+
+    /// Colors lines carrying a `[status]` with ANSI escapes. Syslog always
+    /// gets the plain line.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Writes access log lines in `format`. Syslog and the error log keep
+    /// the plain lines.
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Also sends every record to syslog.
+    pub fn with_syslog(mut self, syslog: Syslog) -> Self {
+        self.syslog = Some(syslog);
+        self
+    }
+
+    /// Sends the records `error_log` accepts there rather than to the
+    /// access log.
+    pub fn with_error_log(mut self, error_log: Arc<ErrorLog>) -> Self {
+        self.error_log = Some(error_log);
+        self
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        let error_level = self.error_log.as_ref().map(|error_log| error_log.level);
+        self.level.max(error_level.unwrap_or(LevelFilter::Off))
+    }
+
+    /// Installs `self` as the global `log` backend and the target of
+    /// [`access`].
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let level = self.max_level();
+        let logger: &'static Logger = Box::leak(Box::new(self));
+        log::set_logger(logger)?;
+        let _ = INSTALLED.set(logger);
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    /// Writes the ECS document for a finished request. Text logs already
+    /// have the line the handler wrote, so they get nothing more.
+    pub fn log_access(&self, record: &AccessRecord) {
+        let level = access_level(record.status);
+        if self.format != LogFormat::Ecs || level > self.level {
+            return;
+        }
+        let message = format!("{} {} {}", record.method, record.url, record.status);
+        let ecs = EcsLogRecord::access(crate::now_millis(), level, &message, record);
+        let _ = self
+            .output
+            .lock()
+            .unwrap()
+            .write_all(format!("{}\n", ecs.to_json()).as_bytes());
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        let error_log = self
+            .error_log
+            .as_ref()
+            .filter(|error_log| error_log.accepts(record.level()));
+        if error_log.is_some() || record.level() <= self.level {
+            // One write per line, so a rotating file never splits a line.
+            let line = match elapsed() {
+                Some(elapsed) => format!("{} duration_us={}\n", record.args(), elapsed.as_micros()),
+                None => format!("{}\n", record.args()),
+            };
+            let line = match client() {
+                Some(client) => with_client(line, client),
+                None => line,
+            };
+            if let Some(error_log) = error_log {
+                error_log.write(&line);
+            } else if self.format == LogFormat::Ecs {
+                let message = record.args().to_string();
+                let request_id = request_id();
+                let ecs = EcsLogRecord {
+                    timestamp_millis: crate::now_millis(),
+                    level: record.level(),
+                    message: &message,
+                    request_id: request_id.as_deref(),
+                    client: client(),
+                    duration: elapsed(),
+                    access: None,
+                };
+                let _ = self
+                    .output
+                    .lock()
+                    .unwrap()
+                    .write_all(format!("{}\n", ecs.to_json()).as_bytes());
+            } else {
+                let colored = match status_of(&line).filter(|_| self.color) {
+                    Some(status) => {
+                        format!("{}{}\x1b[0m\n", status_color(status), line.trim_end())
+                    }
+                    None => line.clone(),
+                };
+                let _ = self.output.lock().unwrap().write_all(colored.as_bytes());
+            }
+            if let Some(syslog) = &self.syslog {
+                syslog.send(record.level(), line.trim_end());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.output.lock().unwrap().flush();
+        if let Some(error_log) = &self.error_log {
+            error_log.flush();
+        }
+    }
+}
+
+/// Level of the access log line for a response status: 5xx are errors, 4xx
+/// are warnings and everything else is informational.
+pub fn access_level(status: u16) -> Level {
+    match status {
+        500.. => Level::Error,
+        400..=499 => Level::Warn,
+        _ => Level::Info,
+    }
+}
+
+/// The first `[NNN]` status in an access log line.
+fn status_of(line: &str) -> Option<u16> {
+    line.match_indices('[').find_map(|(start, _)| {
+        let status = line.get(start + 1..start + 5)?.strip_suffix(']')?;
+        if !status.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        status.parse().ok()
+    })
+}
+
+/// Green for 2xx, cyan for 3xx, yellow for 4xx and red for 5xx.
+fn status_color(status: u16) -> &'static str {
+    match status {
+        500.. => "\x1b[31m",
+        400..=499 => "\x1b[33m",
+        300..=399 => "\x1b[36m",
+        _ => "\x1b[32m",
+    }
+}
+
+pub fn parse_level(level: &str) -> Option<LevelFilter> {
+    match level.to_ascii_lowercase().as_str() {
+        "debug" => Some(LevelFilter::Debug),
+        "info" => Some(LevelFilter::Info),
+        "warn" => Some(LevelFilter::Warn),
+        "error" => Some(LevelFilter::Error),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::sync::{Arc, OnceLock};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// The access log and the error log of the global test logger.
+    fn captured() -> &'static (Buffer, Buffer) {
+        static BUFFERS: OnceLock<(Buffer, Buffer)> = OnceLock::new();
+        BUFFERS.get_or_init(|| {
+            let (access, errors) = (Buffer::default(), Buffer::default());
+            let error_log = ErrorLog::new(LevelFilter::Error, Box::new(errors.clone()));
+            log::set_boxed_logger(Box::new(
+                Logger::new(LevelFilter::Debug, Box::new(access.clone()))
+                    .with_error_log(Arc::new(error_log)),
+            ))
+            .unwrap();
+            log::set_max_level(LevelFilter::Debug);
+            (access, errors)
+        })
+    }
+
+    /// Installs a global debug-level logger that records into memory and
+    /// returns everything logged so far by any test. Errors go to
+    /// [`captured_error_logs`] instead.
+    pub fn captured_logs() -> String {
+        String::from_utf8(captured().0 .0.lock().unwrap().clone()).unwrap()
+    }
+
+    /// The error log of the [`captured_logs`] logger, flushed.
+    pub fn captured_error_logs() -> String {
+        log::logger().flush();
+        String::from_utf8(captured().1 .0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn warn_level_test() {
+        let buffer = Buffer::default();
+        let logger = Logger::new(parse_level("warn").unwrap(), Box::new(buffer.clone()));
+        for status in [200, 304, 404, 500] {
+            logger.log(
+                &Record::builder()
+                    .level(access_level(status))
+                    .args(format_args!("[{}] [GET] /", status))
+                    .build(),
+            );
+        }
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!("[404] [GET] /\n[500] [GET] /\n", output);
+    }
+
+    #[test]
+    fn color_test() {
+        let log = |color: ColorMode| {
+            let buffer = Buffer::default();
+            let logger = Logger::new(LevelFilter::Info, Box::new(buffer.clone()))
+                .with_color(color.enabled(false));
+            for status in [200, 301, 404, 500] {
+                logger.log(
+                    &Record::builder()
+                        .level(access_level(status))
+                        .args(format_args!("1: [{}] [GET] /", status))
+                        .build(),
+                );
+            }
+            logger.log(&Record::builder().args(format_args!("started")).build());
+            let output = buffer.0.lock().unwrap().clone();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(
+            "\x1b[32m1: [200] [GET] /\x1b[0m\n\
+             \x1b[36m1: [301] [GET] /\x1b[0m\n\
+             \x1b[33m1: [404] [GET] /\x1b[0m\n\
+             \x1b[31m1: [500] [GET] /\x1b[0m\n\
+             started\n",
+            log(ColorMode::Always)
+        );
+        let plain = log(ColorMode::Never);
+        assert!(!plain.contains('\x1b'));
+        assert_eq!(plain, log(ColorMode::Auto));
+        assert_eq!(None, status_of("[GET] [12] [abcd]"));
+    }
+
+    #[test]
+    fn error_log_test() {
+        let (access, errors) = (Buffer::default(), Buffer::default());
+        let error_log = Arc::new(ErrorLog::new(
+            parse_level("warn").unwrap(),
+            Box::new(errors.clone()),
+        ));
+        let logger = Logger::new(LevelFilter::Info, Box::new(access.clone()))
+            .with_error_log(error_log.clone());
+        for status in [200, 404, 500] {
+            logger.log(
+                &Record::builder()
+                    .level(access_level(status))
+                    .args(format_args!("[{}] [GET] /", status))
+                    .build(),
+            );
+        }
+        error_log.event("stopping");
+        // Buffered until flushed.
+        assert!(errors.0.lock().unwrap().is_empty());
+        logger.flush();
+        let errors = String::from_utf8(errors.0.lock().unwrap().clone()).unwrap();
+        assert!(errors.starts_with("[404] [GET] /\n[500] [GET] /\n"));
+        assert!(errors.ends_with(": stopping\n"));
+        let access = String::from_utf8(access.0.lock().unwrap().clone()).unwrap();
+        assert_eq!("[200] [GET] /\n", access);
+    }
+
+    #[tokio::test]
+    async fn ecs_format_test() {
+        let access = Buffer::default();
+        let logger =
+            Logger::new(LevelFilter::Info, Box::new(access.clone())).with_format(LogFormat::Ecs);
+        let client = "10.0.0.7".parse().ok();
+        timed(client, String::from("req-7"), async {
+            logger.log(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .args(format_args!("1709214307123: [404] [GET] /a.txt not found"))
+                    .build(),
+            );
+        })
+        .await;
+        logger.log_access(&AccessRecord {
+            request_id: Some(String::from("req-7")),
+            method: String::from("GET"),
+            url: String::from("/a.txt"),
+            status: 404,
+            bytes: 9,
+            client,
+            duration: Duration::from_millis(2),
+        });
+        logger.log_access(&AccessRecord {
+            status: 200,
+            ..Default::default()
+        });
+
+        let access = String::from_utf8(access.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = access
+            .lines()
+            .map(|line| crate::json::test::parse_object(line).unwrap())
+            .collect();
+        assert_eq!(3, lines.len());
+        let line = &lines[0];
+        assert_eq!("warn", line["log.level"]);
+        assert_eq!(
+            "1709214307123: [404] [GET] /a.txt not found",
+            line["message"]
+        );
+        assert_eq!("req-7", line["http.request.id"]);
+        assert_eq!("10.0.0.7", line["client.ip"]);
+        // Handler lines aren't mined for fields they merely mention.
+        assert!(!line.contains_key("http.response.status_code"));
+
+        let finished = &lines[1];
+        assert_eq!("warn", finished["log.level"]);
+        assert_eq!("req-7", finished["http.request.id"]);
+        assert_eq!("GET", finished["http.request.method"]);
+        assert_eq!("404", finished["http.response.status_code"]);
+        assert_eq!("9", finished["http.response.body.bytes"]);
+        assert_eq!("/a.txt", finished["url.path"]);
+        assert_eq!("2000000", finished["event.duration"]);
+        assert_eq!("info", lines[2]["log.level"]);
+
+        // Text logs have the handler's line, and levels still filter.
+        let quiet = Buffer::default();
+        Logger::new(LevelFilter::Info, Box::new(quiet.clone()))
+            .log_access(&AccessRecord::default());
+        Logger::new(LevelFilter::Warn, Box::new(quiet.clone()))
+            .with_format(LogFormat::Ecs)
+            .log_access(&AccessRecord {
+                status: 200,
+                ..Default::default()
+            });
+        assert!(quiet.0.lock().unwrap().is_empty());
+        assert_eq!(Some(LogFormat::Ecs), LogFormat::parse("ecs"));
+        assert_eq!(None, LogFormat::parse("combined"));
+    }
+
+    #[test]
+    fn with_client_test() {
+        let client: IpAddr = "10.0.0.7".parse().unwrap();
+        assert_eq!(
+            "1700000000000: 10.0.0.7 [200] [GET] / served",
+            with_client(String::from("1700000000000: [200] [GET] / served"), client)
+        );
+        assert_eq!(
+            "started: listening",
+            with_client(String::from("started: listening"), client)
+        );
+        assert_eq!(
+            "1700000000000 no colon",
+            with_client(String::from("1700000000000 no colon"), client)
+        );
+    }
+}
@@ -1,16 +1,216 @@
 use std::{
-    convert::Infallible, net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc, time::UNIX_EPOCH,
+    collections::HashMap,
+    convert::Infallible,
+    io::IsTerminal,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::UNIX_EPOCH,
 };
 
+mod archive;
+mod audit;
+mod banner;
+mod base64;
+mod body_count;
+mod body_log;
+mod cache_control;
+mod clear_site_data;
+mod config;
+mod cors;
+mod csp;
+mod daemon;
+mod digest;
+mod dir_config;
+mod disposition;
+mod ecs;
+mod encoding;
+mod etag;
+mod expect_ct;
+mod fingerprint;
+mod fs;
+mod glob;
+mod header_file;
+mod inflate;
+mod inject;
+mod interface;
+mod json;
+mod keep_alive;
+mod language;
+mod listener;
+mod listing;
+mod logger;
+mod media;
+mod multipart;
+mod nel;
+mod openapi;
+mod permissions;
+mod proxy;
+mod regex;
+mod rewrite;
+mod robots;
+mod rotate;
+mod routes;
+mod search;
+mod sniff;
+mod stats;
+mod syslog;
+mod systemd;
+mod transform;
+mod tree;
+mod upload;
+mod webdav;
+mod write;
+mod zip_fs;
+
+use archive::ArchiveConfig;
+use audit::AuditLogger;
+use clear_site_data::ClearSiteDataRule;
+use config::ServeConfigBuilder;
+use cors::{CorsConfig, IsolationMode};
+use dir_config::DirConfig;
+use etag::{EtagCache, EtagMode};
+use expect_ct::ExpectCtConfig;
+use fs::{FollowSymlinks, Fs};
+use header_file::HeaderFile;
 use hyper::{
-    header::CONTENT_TYPE,
+    body::HttpBody,
+    header::{
+        HeaderValue, ACCEPT_ENCODING, ACCEPT_LANGUAGE, ACCEPT_RANGES, ALLOW, AUTHORIZATION,
+        CACHE_CONTROL, CONNECTION, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_SECURITY_POLICY,
+        CONTENT_TYPE, ETAG, IF_NONE_MATCH, ORIGIN, PROXY_AUTHORIZATION, RANGE, SERVER, VARY,
+        WWW_AUTHENTICATE,
+    },
+    http::response::Builder,
     server::conn::AddrStream,
     service::{make_service_fn, service_fn},
-    Body, Method, Request, Response, Server,
+    Body, Method, Request, Response, Server, Version,
 };
+use keep_alive::{KeepAliveConfig, RequestCount};
+use listener::{LimitedReader, ListenOptions};
+use listing::{LinkFingerprint, SortOrder};
+use log::{debug, error, info, warn, LevelFilter};
+use logger::{ColorMode, ErrorLog, LogFormat, Logger};
+use nel::NelConfig;
+use openapi::OpenApiConfig;
+use proxy::ProxyConfig;
+use rewrite::RewriteRule;
+use robots::XRobotsRule;
+use rotate::{RotatingFile, RotationPolicy};
+use routes::{effective_routes, format_routes_json, format_routes_table};
+use search::SearchConfig;
+use stats::Stats;
+use syslog::Syslog;
+use transform::TransformRule;
+use upload::{UploadTracker, UPLOAD_PROGRESS_PREFIX};
+use zip_fs::ZipFileSystem;
 
 fn print_usage() {
-    println!("usage: serve-dir [directory_path] ...[options]\nset host: --host='127.0.0.1' or -h='127.0.0.1'\nset port: --port=8080 or -p=8080\nset header: --header=x-custom-header:x-custom-value or -H=x-custom-header:x-custom-value\nremove default headers([access-control-allow-origin:*]): --no-default-headers\nhelp: --help");
+    println!(
+        "usage: serve-dir [directory_path] ...[options]\n\
+         set host: --host='127.0.0.1' or -h='127.0.0.1'\n\
+         set port: --port=8080 or -p=8080, --port=0 picks a free one and prints SERVE_DIR_PORT=<port>\n\
+         set header: --header=x-custom-header:x-custom-value or -H=x-custom-header:x-custom-value\n\
+         read name: value headers from a file, re-read on SIGHUP, --header taking precedence: --header-file=headers.txt\n\
+         remove default headers([access-control-allow-origin:*]): --no-default-headers\n\
+         rewrite a path without redirecting: --rewrite=/old:./new/path or --rewrite=/old/*:./new/dir/\n\
+         rewrite with a regular expression and $N groups: --rewrite-regex=^/v\\d+/(.*):/$1\n\
+         set etag mode: --etag-mode=mtime (default), --etag-mode=mtime-size or --etag-mode=hash\n\
+         send weak etags (W/\"...\") for bodies a proxy compresses: --weak-etag\n\
+         require basic auth: --auth=user:password\n\
+         accept uploads via PUT (requires --auth): --allow-put\n\
+         stream upload progress: send x-upload-id:<id> with the PUT and read /_upload-progress/<id>\n\
+         accept multipart/form-data POSTs into a directory (requires --auth): --allow-upload (add --allow-overwrite to replace files)\n\
+         reject PUT and POST bodies larger than a size: --max-body-size=100MB\n\
+         accept DELETE for files (requires --auth): --allow-delete (add --allow-delete-dirs for directories)\n\
+         serve as a WebDAV share with PROPFIND, MKCOL, COPY, MOVE, DELETE and LOCK (requires --auth): --webdav\n\
+         echo TRACE requests back, without authorization headers: --allow-trace\n\
+         answer 405 to methods even when another flag enables them: --disable-method=POST,PUT,DELETE\n\
+         record PUT and DELETE operations as JSON lines: --audit-log=audit.log\n\
+         add a Digest: SHA-256=<hash> header to file responses: --response-digest\n\
+         add X-Content-Duration and Content-Duration to MP4, WebM, Ogg and MP3 files: --media-headers\n\
+         serve a file for 404s: --404=./404.html, or for paths under a prefix (repeatable): --404=/docs:./docs/404.html\n\
+         serve a custom body for an error status (repeatable): --error-body=403:./errors/403.html\n\
+         set log level: --log-level=debug, info (default), warn or error\n\
+         write the access log as Elastic Common Schema JSON: --log-format=ecs, or text (default)\n\
+         at debug level, log the start of POST and PUT bodies with passwords redacted: --log-body=1024\n\
+         warn about requests slower than a threshold (each line logs duration_us): --slow-log-threshold=500ms\n\
+         allow specific CORS origins (comma separated, sets vary:origin): --cors-origin=https://example.com\n\
+         send access-control-allow-credentials (not with origin *): --cors-credentials\n\
+         add vary:origin to responses with CORS headers: --cors-vary-origin\n\
+         expose resource timing cross-origin (defaults to matching --cors-origin): --timing-allow-origin=https://analytics.example.com or --timing-allow-origin=*\n\
+         try index files in order for directory paths, serving non-HTML ones as text/plain: --index-fallback=index.html,index.htm,README.md\n\
+         list directories without an index.html (JSON for accept:application/json or ?format=json): --list-directories\n\
+         answer directories without an index.html with a JSON index of files and dirs, still HTML for browsers with --list-directories: --index-json\n\
+         set listing order: --sort=name-asc (default), name-desc, mtime-desc, mtime-asc, size-desc or size-asc\n\
+         allow ?sort= to override the listing order per request: --allow-sort-param\n\
+         set cache-control for files: --cache-control='public, max-age=60'\n\
+         add stale directives to cache-control: --stale-while-revalidate=3600 --stale-if-error=86400\n\
+         write the access log to a file: --log-file=access.log\n\
+         color log lines by status (default on a terminal, honoring NO_COLOR): --color, never: --no-color, even in a pipe: --force-color\n\
+         rotate the log file: --log-rotate-size=100MB and/or --log-rotate-daily, keep N old files: --log-rotate-keep=7\n\
+         answer connectivity checks, bypassing auth and headers: --ping-path=/_ping --ping-body=pong\n\
+         log stripped cache busting query strings: --strip-fingerprint-query\n\
+         serve app.js for a missing app.abc123.js: --fingerprint-glob=*.*.js\n\
+         allow cross-origin loads of font files regardless of CORS settings: --font-cors\n\
+         set the server header: --server-name=MyServer/1.0, or strip it even if set by --header: --hide-server\n\
+         build absolute redirects from X-Forwarded-Proto/-Host, from any peer: --behind-proxy or listed ones: --behind-proxy=10.0.0.1\n\
+         set X-Forwarded-Proto on requests without a trusted one: --inject-forwarded-proto=https\n\
+         restrict browser features: --permissions-policy-camera=() --permissions-policy-microphone=(self)\n\
+         or all at once: --permissions-policy=camera=(),microphone=(self)\n\
+         set Document-Policy: --document-policy=force-load-at-top or only report violations: --document-policy-report-only=force-load-at-top\n\
+         add nosniff, frame, referrer and permissions policy headers: --security-headers\n\
+         add only x-content-type-options:nosniff: --no-mime-sniff\n\
+         enable SharedArrayBuffer with COOP, COEP, CORP and Origin-Agent-Cluster headers: --cross-origin-isolated\n\
+         or with a credentialless embedder policy: --cross-origin-isolated-mode=permissive\n\
+         add a per-response script-src nonce to the CSP and a csp-nonce meta tag to HTML files: --csp-nonce\n\
+         insert a snippet before </body> in HTML files up to a size (default 1MB): --inject-html='<script src=/debug.js></script>' --inject-max-size=1MB\n\
+         replace text in files of a content type (repeatable): --transform=text/html:__BASE_URL__:https://example.com\n\
+         warn when a file's content does not match its extension's content type: --validate-content-type\n\
+         ask browsers to report network errors: --nel-endpoint=https://report.example.com/nel\n\
+         tune error reports: --nel-max-age=86400 --nel-failure-fraction=0.1 --nel-include-subdomains --nel-group=network-errors\n\
+         add the deprecated Expect-CT header, ignored by current browsers: --expect-ct-max-age=86400\n\
+         enforce it and report failures: --expect-ct-enforce --expect-ct-report-uri=https://report.example.com/ct\n\
+         add X-Robots-Tag to every response: --x-robots-tag=noindex,nofollow or to matching paths: --x-robots-tag=/private/*:noindex\n\
+         clear browser data on every response: --clear-site-data=cookies,cache,storage or * for everything\n\
+         or on matching paths, such as a logout page: --clear-site-data-path=/logout:cookies\n\
+         serve about.fr.html for /about.html when Accept-Language prefers French: --localize\n\
+         bind to the address of a network interface instead of --host: --bind-interface=eth0\n\
+         refuse to serve files by extension: --forbid-ext=php,py,rb,env\n\
+         refuse to serve hidden files anywhere in the path: --forbid-hidden\n\
+         refuse to serve files more than N path segments deep (1 is root files only): --max-depth=10\n\
+         follow symlinks only to targets inside the directory, or not at all: --follow-symlinks=safe or --follow-symlinks=never\n\
+         add ?v= to file links in listings: --fingerprint-links or --fingerprint-links=hash\n\
+         log 5xx errors and startup and shutdown events to their own file, optionally down to warn or debug: --error-log=errors.log --error-log-level=warn\n\
+         send log lines to the local syslog, alongside --log-file if set: --log-syslog --log-syslog-facility=local0\n\
+         leave requested paths out of 404 log lines: --hide-404-path\n\
+         find files by name as JSON at /_search?q=*.js: --search-endpoint=/_search\n\
+         limit searches: --search-max-depth=5 --search-max-results=50\n\
+         download directories as zip or tar.gz, e.g. /_archive?path=/dir&format=zip: --archive-endpoint=/_archive\n\
+         limit archives: --archive-max-files=1000\n\
+         serve the files of a ZIP archive, read once at startup, instead of the directory: --zip=./dist.zip\n\
+         serve an OpenAPI spec at /openapi.yaml with a docs page at /docs/: --openapi=./openapi.yaml\n\
+         title and path of the docs page: --openapi-title=\"My API\" --openapi-path=/swagger\n\
+         do not log requests: --no-access-log, also hide the startup banner: --silent\n\
+         print a single Serving line instead of the configuration banner: --no-banner\n\
+         read .servedir files overriding auth, headers, cache_control and cors_origin per directory: --dir-config\n\
+         run in the background once bound, output going to --log-file: --daemon --pid-file=serve-dir.pid\n\
+         stop a background instance: serve-dir --stop --pid-file=serve-dir.pid\n\
+         share the port with other serve-dir processes (SO_REUSEPORT): --reuseport\n\
+         probe idle connections: --tcp-keepalive=60s, disable Nagle's algorithm: --tcp-nodelay\n\
+         close connections idle for a while, 0 to disable keep-alive: --keep-alive-timeout=30s\n\
+         close a connection after a number of requests: --keep-alive-max-requests=1000\n\
+         drop connections sending a request or header line longer than N bytes: --max-request-line=8192\n\
+         print the files that would be served and exit: --list-only or --list-only-json, filtered by --list-filter=*.js\n\
+         serve request statistics as JSON at a path, off by default, readable without auth, reset with ?reset (needs --auth): --stats-path=/_stats\n\
+         reject requests with a query string: --forbid-query, or only log them: --warn-query\n\
+         do not send Content-Disposition (inline for pages, PDFs and media, attachment for executables): --no-content-disposition\n\
+         send HTTP/1.0 clients a Content-Length and close the connection: --http10-compat\n\
+         refuse partial downloads with Range: --no-range-requests\n\
+         print routing rules and exit: --print-routes (add --json for JSON output)\n\
+         print the effective value and source (cli or default) of the main options and exit: --print-config\n\
+         help: --help"
+    );
 }
 
 trait Update<T> {
@@ -19,7 +219,8 @@ trait Update<T> {
 
 impl Update<(String, String)> for Vec<(String, String)> {
     fn update(&mut self, value: (String, String)) -> bool {
-        if let Some(index) = self.iter().position(|x| x.0 == value.0) {
+        // Header names are case-insensitive, so `-H=X-A:1` replaces `x-a`.
+        if let Some(index) = self.iter().position(|x| x.0.eq_ignore_ascii_case(&value.0)) {
             self[index].1 = value.1;
             true
         } else {
@@ -29,221 +230,5278 @@ impl Update<(String, String)> for Vec<(String, String)> {
     }
 }
 
-struct SharedData {
+/// Parses `name:value`, splitting at the first colon only so values such as
+/// URLs keep theirs. Whitespace around the separator is ignored.
+fn parse_header(header: &str) -> Option<(String, String)> {
+    let mut parts = header.splitn(2, ':');
+    let key = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    hyper::header::HeaderName::from_bytes(key.as_bytes()).ok()?;
+    hyper::header::HeaderValue::from_str(value).ok()?;
+    Some((String::from(key), String::from(value)))
+}
+
+/// What goes in the `Server` header. hyper sends none of its own, so by
+/// default only a `--header=server:...` would set one.
+#[derive(Default)]
+enum ServerNameConfig {
+    #[default]
+    Unchanged,
+    Custom(HeaderValue),
+    /// Strip the header even when `--header` or a `.servedir` set it.
+    Hidden,
+}
+
+impl ServerNameConfig {
+    fn apply(&self, headers: &mut hyper::HeaderMap) {
+        match self {
+            Self::Unchanged => {}
+            Self::Custom(name) => {
+                headers.insert(SERVER, name.clone());
+            }
+            Self::Hidden => {
+                headers.remove(SERVER);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct ServeConfig {
     headers: Vec<(String, String)>,
+    header_file: Option<Arc<HeaderFile>>,
+    /// `--log-body`: how much of a POST or PUT body to log at debug level.
+    log_body_bytes: Option<usize>,
     directory_path: String,
+    /// Address to listen on, unless `--bind-interface` or systemd decide.
+    addr: Option<SocketAddr>,
     not_found_file_path: Option<String>,
+    /// `--404=/prefix:file` pages, longest prefix first, tried before
+    /// `not_found_file_path`.
+    not_found_prefixes: Vec<(String, PathBuf)>,
+    /// Where served files and listings are read from.
+    fs: Fs,
+    rewrites: Vec<RewriteRule>,
+    etag_mode: EtagMode,
+    /// Send ETags as `W/"..."`, for when a proxy re-encodes the bodies.
+    weak_etag: bool,
+    etag_cache: EtagCache,
+    /// Expected `Authorization` header value when `--auth` is set.
+    auth: Option<String>,
+    auth_user: Option<String>,
+    allow_put: bool,
+    /// Accept `multipart/form-data` uploads via POST.
+    allow_upload: bool,
+    allow_overwrite: bool,
+    max_body_size: Option<u64>,
+    allow_delete: bool,
+    allow_delete_dirs: bool,
+    /// Answer the WebDAV methods in [`webdav::METHODS`] and DELETE.
+    webdav: bool,
+    /// Answer TRACE by echoing the request head.
+    allow_trace: bool,
+    /// Methods answered with 405 before anything else looks at them.
+    disabled_methods: Vec<Method>,
+    audit_logger: Option<Arc<Mutex<AuditLogger>>>,
+    uploads: UploadTracker,
+    response_digest: bool,
+    media_headers: bool,
+    error_bodies: HashMap<u16, PathBuf>,
+    cors: CorsConfig,
+    list_directories: bool,
+    index_json: bool,
+    default_sort: SortOrder,
+    allow_sort_param: bool,
+    /// `Cache-Control` for file responses, with stale directives merged in.
+    cache_control: Option<String>,
+    /// Path answered with `ping_body` ahead of auth and every other check.
+    ping_path: String,
+    ping_body: String,
+    strip_fingerprint_query: bool,
+    /// File names matching this glob fall back to their unfingerprinted name.
+    fingerprint_glob: Option<String>,
+    /// Index files tried in order for directory paths; empty means just
+    /// `index.html`.
+    index_fallback: Vec<String>,
+    font_cors: bool,
+    server_name: ServerNameConfig,
+    /// Trust in `X-Forwarded-*`, for absolute redirects behind a proxy.
+    proxy: Option<ProxyConfig>,
+    /// Feature to allowlist, sent as `Permissions-Policy` and `Feature-Policy`.
+    permissions_policy: HashMap<String, String>,
+    document_policy: Option<String>,
+    document_policy_report_only: Option<String>,
+    nel: Option<NelConfig>,
+    expect_ct: Option<ExpectCtConfig>,
+    x_robots_tag: Vec<XRobotsRule>,
+    clear_site_data: Vec<ClearSiteDataRule>,
+    /// Prefer `name.<lang>.ext` variants chosen by `Accept-Language`.
+    localize: bool,
+    /// Extensions answered with 403 even when the file exists.
+    forbid_ext: Vec<String>,
+    forbid_hidden: bool,
+    /// Deepest request path, in segments, that files are served from.
+    max_depth: Option<usize>,
+    follow_symlinks: FollowSymlinks,
+    fingerprint_links: Option<LinkFingerprint>,
+    /// Log 404s as `<redacted>` so the log does not record probed paths.
+    hide_404_path: bool,
+    search: Option<SearchConfig>,
+    archive: Option<ArchiveConfig>,
+    openapi: Option<OpenApiConfig>,
+    stats: Arc<Stats>,
+    /// Path of the statistics endpoint, empty when disabled.
+    stats_path: String,
+    /// Reject requests carrying a query string with 400.
+    forbid_query: bool,
+    /// Log requests carrying a query string, but serve them.
+    warn_query: bool,
+    no_content_disposition: bool,
+    /// Give each HTML response a fresh CSP nonce, in the header and a meta tag.
+    csp_nonce: bool,
+    /// Snippet inserted before `</body>` in HTML files of at most
+    /// `inject_max_size` bytes.
+    inject_html: Option<String>,
+    inject_max_size: u64,
+    /// `--transform` replacements, applied in order.
+    transforms: Vec<TransformRule>,
+    /// Warn when a file's magic bytes contradict its `Content-Type`.
+    validate_content_type: bool,
+    /// Buffer responses to HTTP/1.0 requests and close the connection.
+    http10_compat: bool,
+    keep_alive: KeepAliveConfig,
+    /// Warn about requests that take longer than this to answer.
+    slow_log_threshold: Option<std::time::Duration>,
+    /// Refuse `Range` requests with 400 and advertise `Accept-Ranges: none`.
+    no_range_requests: bool,
+    /// Merge `.servedir` overrides from the requested path into each request.
+    dir_config: bool,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let pid_file = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--pid-file="))
+        .unwrap_or(daemon::DEFAULT_PID_FILE);
+    if args.iter().any(|arg| arg == "--stop") {
+        match daemon::stop(Path::new(pid_file)) {
+            Ok(pid) => println!("Stopped serve-dir {}", pid),
+            Err(err) => {
+                eprintln!("failed to stop serve-dir from {} {}", pid_file, err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--daemon") {
+        let log_file = args.iter().find_map(|arg| arg.strip_prefix("--log-file="));
+        if let Err(err) = daemon::daemonize(Path::new(pid_file), log_file.map(Path::new)) {
+            eprintln!("failed to start in the background {}", err);
+            std::process::exit(1);
+        }
+    }
+    tokio::runtime::Runtime::new()
+        .expect("failed to start the async runtime")
+        .block_on(serve(args));
 }
 
-#[tokio::main]
-async fn main() {
-    let mut args = std::env::args().skip(1);
+async fn serve(args: Vec<String>) {
+    let mut args = args.into_iter();
 
-    let mut directory_path = args.next().expect("Not Enough Arguments");
+    let directory_path = args.next().expect("Not Enough Arguments");
     if directory_path == "--help" {
         print_usage();
         return;
     }
-    if PathBuf::from_str(&directory_path).is_err() {
-        eprintln!("Invalid Directory Path");
-        return;
-    }
-    if !directory_path.ends_with('/') && !directory_path.ends_with('\\') {
-        directory_path.push('/');
-    }
     let mut headers = Vec::<(String, String)>::with_capacity(10);
+    let mut header_file: Option<Arc<HeaderFile>> = None;
+    let mut log_body_bytes = None;
     let mut host: [u8; 4] = [127, 0, 0, 1];
     let mut is_host_filled = false;
     let mut port: u16 = 8080;
     let mut is_port_filled = false;
     let mut no_default_headers = false;
+    let mut print_routes = false;
+    let mut print_config = false;
+    let mut list_only = false;
+    let mut list_filter: Option<String> = None;
+    let mut json_output = false;
 
     let mut not_found_file_path: Option<String> = None;
+    let mut not_found_prefixes = Vec::new();
+    let mut rewrites = Vec::<RewriteRule>::new();
+    let mut etag_mode = EtagMode::default();
+    let mut weak_etag = false;
+    let mut auth: Option<String> = None;
+    let mut auth_user: Option<String> = None;
+    let mut audit_log_path: Option<String> = None;
+    let mut response_digest = false;
+    let mut media_headers = false;
+    let mut error_bodies = HashMap::<u16, PathBuf>::new();
+    let mut log_level = LevelFilter::Info;
+    let mut log_format = LogFormat::Text;
+    let mut color_mode = ColorMode::default();
+    let mut log_file: Option<String> = None;
+    let mut error_log_path: Option<String> = None;
+    let mut error_log_level = LevelFilter::Error;
+    let mut rotation = RotationPolicy::default();
+    let mut log_syslog = false;
+    let mut access_log = true;
+    let mut silent = false;
+    let mut no_banner = false;
+    let mut options = Vec::<String>::new();
+    let mut syslog_facility = syslog::parse_facility("daemon").unwrap();
+    let mut cors = CorsConfig::default();
+    let mut list_directories = false;
+    let mut index_json = false;
+    let mut default_sort = SortOrder::default();
+    let mut allow_sort_param = false;
+    let mut cache_control: Option<String> = None;
+    let mut stale_while_revalidate: Option<u64> = None;
+    let mut stale_if_error: Option<u64> = None;
+    let mut allow_put = false;
+    let mut allow_upload = false;
+    let mut allow_overwrite = false;
+    let mut max_body_size: Option<u64> = None;
+    let mut allow_delete = false;
+    let mut allow_delete_dirs = false;
+    let mut webdav = false;
+    let mut ping_path = String::from("/_ping");
+    let mut ping_body = String::from("pong");
+    let mut stats_path = String::new();
+    let mut forbid_query = false;
+    let mut warn_query = false;
+    let mut no_content_disposition = false;
+    let mut no_mime_sniff = false;
+    let mut cross_origin_isolated = None;
+    let mut csp_nonce = false;
+    let mut inject_html = None;
+    let mut inject_max_size = inject::DEFAULT_MAX_SIZE;
+    let mut transforms = Vec::new();
+    let mut allow_trace = false;
+    let mut disabled_methods: Vec<Method> = Vec::new();
+    let mut dir_config = false;
+    let mut validate_content_type = false;
+    let mut http10_compat = false;
+    let mut keep_alive = true;
+    let mut keep_alive_max_requests = None;
+    let mut slow_log_threshold = None;
+    let mut no_range_requests = false;
+    let mut strip_fingerprint_query = false;
+    let mut fingerprint_glob: Option<String> = None;
+    let mut index_fallback: Vec<String> = Vec::new();
+    let mut font_cors = false;
+    let mut server_name = ServerNameConfig::default();
+    let mut proxy: Option<ProxyConfig> = None;
+    let mut permissions_policy = HashMap::<String, String>::new();
+    let mut security_headers = false;
+    let mut document_policy: Option<String> = None;
+    let mut document_policy_report_only: Option<String> = None;
+    let mut nel: Option<NelConfig> = None;
+    let mut nel_max_age: Option<u64> = None;
+    let mut nel_failure_fraction: Option<f64> = None;
+    let mut nel_include_subdomains = false;
+    let mut nel_group: Option<String> = None;
+    let mut expect_ct_max_age: Option<u64> = None;
+    let mut x_robots_tag = Vec::<XRobotsRule>::new();
+    let mut clear_site_data = Vec::<ClearSiteDataRule>::new();
+    let mut expect_ct_enforce = false;
+    let mut expect_ct_report_uri: Option<String> = None;
+    let mut localize = false;
+    let mut bind_interface: Option<String> = None;
+    let mut listen_options = ListenOptions::default();
+    let mut forbid_ext = Vec::<String>::new();
+    let mut forbid_hidden = false;
+    let mut max_depth: Option<usize> = None;
+    let mut follow_symlinks = FollowSymlinks::default();
+    let mut fingerprint_links: Option<LinkFingerprint> = None;
+    let mut hide_404_path = false;
+    let mut search_endpoint: Option<String> = None;
+    let mut search_max_depth: usize = 5;
+    let mut search_max_results: usize = 50;
+    let mut archive_endpoint = String::new();
+    let mut archive_max_files: usize = 1000;
+    let mut zip_path: Option<String> = None;
+    let mut openapi_spec: Option<PathBuf> = None;
+    let mut openapi_title = String::from(openapi::DEFAULT_TITLE);
+    let mut openapi_path = String::from(openapi::DEFAULT_UI_PATH);
 
     for arg in args {
+        // Only names are kept, so values such as --auth credentials never
+        // reach the banner.
+        options.push(arg.split('=').next().unwrap_or_default().to_string());
         if arg == "--help" {
             print_usage();
             return;
         }
         if !is_host_filled {
-            if arg.starts_with("--host=") {
-                let host_addr = &arg[7..];
-                let mut i = 0;
-                for val in host_addr.split('.') {
+            if let Some(host_addr) = arg.strip_prefix("--host=") {
+                for (i, val) in host_addr.split('.').enumerate() {
                     host[i] = val.parse().expect("Host Address is invalid");
-                    i += 1;
                 }
                 is_host_filled = true;
-            } else if arg.starts_with("-h=") {
-                let host_addr = &arg[3..];
-                let mut i = 0;
-                for val in host_addr.split('.') {
+            } else if let Some(host_addr) = arg.strip_prefix("-h=") {
+                for (i, val) in host_addr.split('.').enumerate() {
                     host[i] = val.parse().expect("Host Address is invalid");
-                    i += 1;
                 }
                 is_host_filled = true;
             }
         }
         if !is_port_filled {
-            if arg.starts_with("--port=") {
-                let port_str = &arg[7..];
+            if let Some(port_str) = arg.strip_prefix("--port=") {
                 port = port_str.parse().expect("Port is Invalid");
                 is_port_filled = true;
-            } else if arg.starts_with("-p=") {
-                let port_str = &arg[3..];
+            } else if let Some(port_str) = arg.strip_prefix("-p=") {
                 port = port_str.parse().expect("Port is Invalid");
                 is_port_filled = true;
             }
         }
-        if not_found_file_path.is_none() {
-            if arg.starts_with("--404=") {
-                not_found_file_path = Some(String::from(&arg[6..]));
+        if let Some(path) = arg.strip_prefix("--404=") {
+            match parse_not_found_prefix(path) {
+                Some(prefixed) => not_found_prefixes.push(prefixed),
+                None if not_found_file_path.is_none() => {
+                    not_found_file_path = Some(String::from(path));
+                }
+                None => {}
             }
         }
-        if arg.starts_with("--header=") {
-            let header_str = &arg[9..];
-            let (key, value) = header_str.split_at(header_str.find(':').expect("Invalid Header"));
-            headers.update((String::from(key), String::from(value)));
-        } else if arg.starts_with("-H") {
-            let header_str = &arg[3..];
-            let (key, value) = header_str.split_at(header_str.find(':').expect("Invalid Header"));
-            headers.update((String::from(key), String::from(value)));
+        if let Some(header_str) = arg
+            .strip_prefix("--header=")
+            .or_else(|| arg.strip_prefix("-H="))
+        {
+            headers.update(parse_header(header_str).expect("Invalid Header"));
+        } else if let Some(path) = arg.strip_prefix("--header-file=") {
+            match HeaderFile::load(PathBuf::from(path)) {
+                Ok(file) => header_file = Some(Arc::new(file)),
+                Err(err) => {
+                    eprintln!("Invalid Header File, {}", err);
+                    return;
+                }
+            }
         } else if arg == "--no-default-headers" {
             no_default_headers = true;
+        } else if let Some(rule) = arg.strip_prefix("--rewrite=") {
+            rewrites.push(RewriteRule::parse(rule).expect("Invalid Rewrite Rule"));
+        } else if let Some(rule) = arg.strip_prefix("--rewrite-regex=") {
+            rewrites.push(RewriteRule::parse_regex(rule).expect("Invalid Rewrite Rule"));
+        } else if let Some(mode) = arg.strip_prefix("--etag-mode=") {
+            etag_mode = mode.parse().expect("Invalid ETag Mode");
+        } else if arg == "--weak-etag" {
+            weak_etag = true;
+        } else if let Some(credentials) = arg.strip_prefix("--auth=") {
+            if !credentials.contains(':') {
+                eprintln!("Invalid Auth, expected --auth=user:password");
+                return;
+            }
+            auth = Some(format!("Basic {}", base64::encode(credentials.as_bytes())));
+            auth_user = credentials.split(':').next().map(String::from);
+        } else if arg == "--allow-put" {
+            allow_put = true;
+        } else if arg == "--allow-upload" {
+            allow_upload = true;
+        } else if arg == "--allow-overwrite" {
+            allow_overwrite = true;
+        } else if let Some(size) = arg.strip_prefix("--max-body-size=") {
+            max_body_size = Some(parse_size(size).expect("Invalid Max Body Size"));
+        } else if arg == "--allow-delete" {
+            allow_delete = true;
+        } else if arg == "--allow-trace" {
+            allow_trace = true;
+        } else if let Some(methods) = arg.strip_prefix("--disable-method=") {
+            disabled_methods.extend(
+                methods
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|method| !method.is_empty())
+                    .map(|method| {
+                        Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                            .expect("Invalid Method")
+                    }),
+            );
+        } else if arg == "--allow-delete-dirs" {
+            allow_delete_dirs = true;
+        } else if arg == "--webdav" {
+            webdav = true;
+        } else if let Some(path) = arg.strip_prefix("--audit-log=") {
+            audit_log_path = Some(String::from(path));
+        } else if arg == "--response-digest" {
+            response_digest = true;
+        } else if arg == "--media-headers" {
+            media_headers = true;
+        } else if let Some(error_body) = arg.strip_prefix("--error-body=") {
+            let (status, path) = error_body.split_once(':').expect("Invalid Error Body");
+            let status = status.parse().expect("Invalid Error Body Status");
+            error_bodies.insert(status, PathBuf::from(path));
+        } else if let Some(level) = arg.strip_prefix("--log-level=") {
+            log_level = logger::parse_level(level).expect("Invalid Log Level");
+        } else if let Some(format) = arg.strip_prefix("--log-format=") {
+            log_format =
+                LogFormat::parse(format).expect("Invalid Log Format, expected text or ecs");
+        } else if let Some(size) = arg.strip_prefix("--log-body=") {
+            log_body_bytes = Some(parse_size(size).expect("Invalid Log Body Size") as usize);
+        } else if arg == "--color" {
+            color_mode = ColorMode::Auto;
+        } else if arg == "--no-color" {
+            color_mode = ColorMode::Never;
+        } else if arg == "--force-color" {
+            color_mode = ColorMode::Always;
+        } else if arg == "--no-access-log" {
+            access_log = false;
+        } else if arg == "--no-banner" {
+            no_banner = true;
+        } else if arg == "--silent" {
+            access_log = false;
+            silent = true;
+        } else if let Some(origins) = arg.strip_prefix("--cors-origin=") {
+            cors.allowed_origins
+                .extend(origins.split(',').map(|origin| String::from(origin.trim())));
+        } else if arg == "--cors-credentials" {
+            cors.credentials = true;
+        } else if arg == "--cors-vary-origin" {
+            cors.vary_origin = true;
+        } else if let Some(origins) = arg.strip_prefix("--timing-allow-origin=") {
+            cors.timing_allow_origin
+                .extend(origins.split(',').map(|origin| String::from(origin.trim())));
+        } else if arg == "--list-directories" {
+            list_directories = true;
+        } else if arg == "--index-json" {
+            index_json = true;
+        } else if let Some(sort) = arg.strip_prefix("--sort=") {
+            default_sort = sort.parse().expect("Invalid Sort Order");
+        } else if arg == "--allow-sort-param" {
+            allow_sort_param = true;
+        } else if let Some(value) = arg.strip_prefix("--cache-control=") {
+            cache_control = Some(String::from(value));
+        } else if let Some(secs) = arg.strip_prefix("--stale-while-revalidate=") {
+            stale_while_revalidate = Some(secs.parse().expect("Invalid stale-while-revalidate"));
+        } else if let Some(secs) = arg.strip_prefix("--stale-if-error=") {
+            stale_if_error = Some(secs.parse().expect("Invalid stale-if-error"));
+        } else if let Some(path) = arg.strip_prefix("--log-file=") {
+            log_file = Some(String::from(path));
+        } else if let Some(path) = arg.strip_prefix("--error-log=") {
+            error_log_path = Some(String::from(path));
+        } else if let Some(level) = arg.strip_prefix("--error-log-level=") {
+            error_log_level = logger::parse_level(level).expect("Invalid Error Log Level");
+        } else if let Some(size) = arg.strip_prefix("--log-rotate-size=") {
+            rotation.max_size = Some(parse_size(size).expect("Invalid Log Rotate Size"));
+        } else if arg == "--log-syslog" {
+            log_syslog = true;
+        } else if let Some(facility) = arg.strip_prefix("--log-syslog-facility=") {
+            syslog_facility = syslog::parse_facility(facility).expect("Invalid Syslog Facility");
+        } else if arg == "--log-rotate-daily" {
+            rotation.daily = true;
+        } else if let Some(keep) = arg.strip_prefix("--log-rotate-keep=") {
+            rotation.keep = Some(keep.parse().expect("Invalid Log Rotate Keep"));
+        } else if let Some(path) = arg.strip_prefix("--ping-path=") {
+            ping_path = String::from(path);
+        } else if let Some(body) = arg.strip_prefix("--ping-body=") {
+            ping_body = String::from(body);
+        } else if let Some(path) = arg.strip_prefix("--stats-path=") {
+            stats_path = String::from(path);
+        } else if arg == "--forbid-query" {
+            forbid_query = true;
+        } else if arg == "--warn-query" {
+            warn_query = true;
+        } else if arg == "--no-content-disposition" {
+            no_content_disposition = true;
+        } else if arg == "--http10-compat" {
+            http10_compat = true;
+        } else if let Some(threshold) = arg.strip_prefix("--slow-log-threshold=") {
+            slow_log_threshold =
+                Some(parse_duration(threshold).expect("Invalid Slow Log Threshold"));
+        } else if arg == "--no-range-requests" {
+            no_range_requests = true;
+        } else if arg == "--strip-fingerprint-query" {
+            strip_fingerprint_query = true;
+        } else if let Some(glob) = arg.strip_prefix("--fingerprint-glob=") {
+            fingerprint_glob = Some(String::from(glob));
+        } else if let Some(files) = arg.strip_prefix("--index-fallback=") {
+            index_fallback = files
+                .split(',')
+                .map(str::trim)
+                .filter(|file| !file.is_empty())
+                .map(String::from)
+                .collect();
+        } else if arg == "--font-cors" {
+            font_cors = true;
+        } else if let Some(name) = arg.strip_prefix("--server-name=") {
+            server_name =
+                ServerNameConfig::Custom(HeaderValue::from_str(name).expect("Invalid Server Name"));
+        } else if arg == "--hide-server" {
+            server_name = ServerNameConfig::Hidden;
+        } else if arg == "--behind-proxy" {
+            proxy.get_or_insert_with(ProxyConfig::default);
+        } else if let Some(trusted) = arg.strip_prefix("--behind-proxy=") {
+            proxy.get_or_insert_with(ProxyConfig::default).trusted =
+                proxy::parse_trusted(trusted).expect("Invalid Proxy Address");
+        } else if let Some(proto) = arg.strip_prefix("--inject-forwarded-proto=") {
+            proxy
+                .get_or_insert_with(ProxyConfig::default)
+                .forwarded_proto =
+                Some(HeaderValue::from_str(proto).expect("Invalid Forwarded Proto"));
+        } else if let Some(policy) = arg.strip_prefix("--permissions-policy=") {
+            for entry in policy.split(',').filter(|entry| !entry.trim().is_empty()) {
+                let (feature, allowlist) = entry
+                    .split_once('=')
+                    .expect("Invalid Permissions Policy, expected feature=(allowlist)");
+                permissions_policy
+                    .insert(String::from(feature.trim()), String::from(allowlist.trim()));
+            }
+        } else if let Some(endpoint) = arg.strip_prefix("--nel-endpoint=") {
+            nel = Some(NelConfig::new(String::from(endpoint)));
+        } else if let Some(max_age) = arg.strip_prefix("--nel-max-age=") {
+            nel_max_age = Some(max_age.parse().expect("Invalid NEL Max Age"));
+        } else if let Some(fraction) = arg.strip_prefix("--nel-failure-fraction=") {
+            nel_failure_fraction = Some(
+                fraction
+                    .parse()
+                    .ok()
+                    .filter(|fraction| (0.0..=1.0).contains(fraction))
+                    .expect("Invalid NEL Failure Fraction, expected 0 to 1"),
+            );
+        } else if arg == "--nel-include-subdomains" {
+            nel_include_subdomains = true;
+        } else if let Some(group) = arg.strip_prefix("--nel-group=") {
+            nel_group = Some(String::from(group));
+        } else if let Some(max_age) = arg.strip_prefix("--expect-ct-max-age=") {
+            expect_ct_max_age = Some(max_age.parse().expect("Invalid Expect-CT Max Age"));
+        } else if arg == "--expect-ct-enforce" {
+            expect_ct_enforce = true;
+        } else if let Some(report_uri) = arg.strip_prefix("--expect-ct-report-uri=") {
+            expect_ct_report_uri = Some(String::from(report_uri));
+        } else if let Some(rule) = arg.strip_prefix("--x-robots-tag=") {
+            x_robots_tag.push(XRobotsRule::parse(rule).expect("Invalid X-Robots-Tag Rule"));
+        } else if let Some(policy) = arg.strip_prefix("--document-policy=") {
+            document_policy = Some(String::from(policy));
+        } else if let Some(policy) = arg.strip_prefix("--document-policy-report-only=") {
+            document_policy_report_only = Some(String::from(policy));
+        } else if let Some(types) = arg.strip_prefix("--clear-site-data=") {
+            clear_site_data.push(
+                ClearSiteDataRule::parse(None, types).expect("Invalid Clear-Site-Data Types"),
+            );
+        } else if let Some(rule) = arg.strip_prefix("--clear-site-data-path=") {
+            clear_site_data.push(
+                ClearSiteDataRule::parse_path(rule).expect("Invalid Clear-Site-Data Path Rule"),
+            );
+        } else if arg == "--dir-config" {
+            dir_config = true;
+        } else if arg == "--csp-nonce" {
+            csp_nonce = true;
+        } else if let Some(snippet) = arg.strip_prefix("--inject-html=") {
+            inject_html = Some(String::from(snippet));
+        } else if let Some(size) = arg.strip_prefix("--inject-max-size=") {
+            inject_max_size = parse_size(size).expect("Invalid Inject Max Size");
+        } else if let Some(rule) = arg.strip_prefix("--transform=") {
+            transforms.push(
+                TransformRule::parse(rule)
+                    .expect("Invalid Transform, expected --transform=type/subtype:from:to"),
+            );
+        } else if arg == "--cross-origin-isolated" {
+            cross_origin_isolated = cross_origin_isolated.or(Some(IsolationMode::Strict));
+        } else if let Some(mode) = arg.strip_prefix("--cross-origin-isolated-mode=") {
+            cross_origin_isolated = Some(
+                IsolationMode::parse(mode)
+                    .expect("Invalid Cross Origin Isolated Mode, expected strict or permissive"),
+            );
+        } else if arg == "--no-mime-sniff" {
+            no_mime_sniff = true;
+        } else if arg == "--validate-content-type" {
+            validate_content_type = true;
+        } else if let Some(entry) = arg.strip_prefix("--permissions-policy-") {
+            let (feature, allowlist) = entry.split_once('=').expect(
+                "Invalid Permissions Policy, expected --permissions-policy-feature=(allowlist)",
+            );
+            permissions_policy.insert(String::from(feature), String::from(allowlist));
+        } else if arg == "--security-headers" {
+            security_headers = true;
+        } else if arg == "--localize" {
+            localize = true;
+        } else if arg == "--reuseport" {
+            listen_options.reuse_port = true;
+        } else if let Some(keepalive) = arg.strip_prefix("--tcp-keepalive=") {
+            let keepalive = parse_duration(keepalive).expect("Invalid TCP Keep-Alive");
+            listen_options.keepalive = Some(keepalive).filter(|keepalive| !keepalive.is_zero());
+        } else if arg == "--tcp-nodelay" {
+            listen_options.nodelay = true;
+        } else if let Some(timeout) = arg.strip_prefix("--keep-alive-timeout=") {
+            let timeout = parse_duration(timeout).expect("Invalid Keep-Alive Timeout");
+            keep_alive = !timeout.is_zero();
+            listen_options.idle_timeout = Some(timeout).filter(|_| keep_alive);
+        } else if let Some(max) = arg.strip_prefix("--keep-alive-max-requests=") {
+            let max: usize = max.parse().expect("Invalid Keep-Alive Max Requests");
+            keep_alive_max_requests = Some(max).filter(|max| *max > 0);
+        } else if let Some(limit) = arg.strip_prefix("--max-request-line=") {
+            listen_options.max_request_line =
+                Some(limit.parse().expect("Invalid Max Request Line"));
+        } else if let Some(name) = arg.strip_prefix("--bind-interface=") {
+            bind_interface = Some(String::from(name));
+        } else if let Some(extensions) = arg.strip_prefix("--forbid-ext=") {
+            forbid_ext.extend(
+                extensions
+                    .split(',')
+                    .map(|extension| extension.trim().trim_start_matches('.'))
+                    .filter(|extension| !extension.is_empty())
+                    .map(String::from),
+            );
+        } else if arg == "--forbid-hidden" {
+            forbid_hidden = true;
+        } else if let Some(depth) = arg.strip_prefix("--max-depth=") {
+            max_depth = Some(depth.parse().expect("Invalid Max Depth"));
+        } else if let Some(mode) = arg.strip_prefix("--follow-symlinks=") {
+            follow_symlinks = mode.parse().expect("Invalid Follow Symlinks Mode");
+        } else if arg == "--fingerprint-links" {
+            fingerprint_links = Some(LinkFingerprint::default());
+        } else if let Some(mode) = arg.strip_prefix("--fingerprint-links=") {
+            fingerprint_links = Some(mode.parse().expect("Invalid Fingerprint Links Mode"));
+        } else if arg == "--hide-404-path" {
+            hide_404_path = true;
+        } else if let Some(endpoint) = arg.strip_prefix("--search-endpoint=") {
+            search_endpoint = Some(String::from(endpoint));
+        } else if let Some(depth) = arg.strip_prefix("--search-max-depth=") {
+            search_max_depth = depth.parse().expect("Invalid Search Max Depth");
+        } else if let Some(results) = arg.strip_prefix("--search-max-results=") {
+            search_max_results = results.parse().expect("Invalid Search Max Results");
+        } else if let Some(endpoint) = arg.strip_prefix("--archive-endpoint=") {
+            archive_endpoint = String::from(endpoint);
+        } else if let Some(files) = arg.strip_prefix("--archive-max-files=") {
+            archive_max_files = files.parse().expect("Invalid Archive Max Files");
+        } else if let Some(path) = arg.strip_prefix("--zip=") {
+            zip_path = Some(String::from(path));
+        } else if let Some(spec) = arg.strip_prefix("--openapi=") {
+            openapi_spec = Some(PathBuf::from(spec));
+        } else if let Some(title) = arg.strip_prefix("--openapi-title=") {
+            openapi_title = String::from(title);
+        } else if let Some(path) = arg.strip_prefix("--openapi-path=") {
+            openapi_path = String::from(path);
+        } else if arg == "--list-only" {
+            list_only = true;
+        } else if arg == "--list-only-json" {
+            list_only = true;
+            json_output = true;
+        } else if let Some(glob) = arg.strip_prefix("--list-filter=") {
+            list_filter = Some(String::from(glob));
+        } else if arg == "--print-routes" {
+            print_routes = true;
+        } else if arg == "--print-config" {
+            print_config = true;
+        } else if arg == "--json" {
+            json_output = true;
         }
     }
-    if !no_default_headers {
+    let has_allow_origin = headers
+        .iter()
+        .any(|(key, _)| key.eq_ignore_ascii_case("access-control-allow-origin"))
+        || header_file
+            .as_ref()
+            .is_some_and(|file| file.contains("access-control-allow-origin"));
+    if !no_default_headers && !has_allow_origin && cors.allowed_origins.is_empty() {
         headers.push((
             String::from("access-control-allow-origin"),
             String::from("*"),
         ));
     }
+    let has_nosniff = headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("x-content-type-options"));
+    if no_mime_sniff && !has_nosniff {
+        headers.push((
+            String::from("x-content-type-options"),
+            String::from("nosniff"),
+        ));
+    }
+    if security_headers {
+        for (key, value) in [
+            ("x-content-type-options", "nosniff"),
+            ("x-frame-options", "DENY"),
+            ("referrer-policy", "strict-origin-when-cross-origin"),
+        ] {
+            if !headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(key))
+            {
+                headers.push((String::from(key), String::from(value)));
+            }
+        }
+        for (feature, allowlist) in permissions::SECURE_DEFAULTS {
+            permissions_policy
+                .entry(String::from(feature))
+                .or_insert_with(|| String::from(allowlist));
+        }
+    }
+    if let Some(mode) = cross_origin_isolated {
+        for (key, value) in cors::isolation_headers(mode) {
+            if !headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(key))
+            {
+                headers.push((String::from(key), String::from(value)));
+            }
+        }
+    }
+    if let Some((feature, allowlist)) = permissions_policy
+        .iter()
+        .find(|(_, allowlist)| !permissions::is_valid_allowlist(allowlist))
+    {
+        eprintln!(
+            "Invalid Permissions Policy allowlist {} for {}, expected * or (self ...)",
+            allowlist, feature
+        );
+        return;
+    }
+    if let Some(nel) = &mut nel {
+        nel.max_age = nel_max_age.unwrap_or(nel.max_age);
+        nel.failure_fraction = nel_failure_fraction.unwrap_or(nel.failure_fraction);
+        nel.include_subdomains = nel_include_subdomains;
+        nel.group = nel_group.unwrap_or_else(|| nel.group.clone());
+    }
+    if expect_ct_max_age.is_none() && (expect_ct_enforce || expect_ct_report_uri.is_some()) {
+        eprintln!("--expect-ct-enforce and --expect-ct-report-uri need --expect-ct-max-age");
+        return;
+    }
+    let expect_ct = expect_ct_max_age.map(|max_age| ExpectCtConfig {
+        max_age,
+        enforce: expect_ct_enforce,
+        report_uri: expect_ct_report_uri,
+    });
+    let has_wildcard_origin = headers.iter().any(|(key, value)| {
+        key.eq_ignore_ascii_case("access-control-allow-origin") && value.trim() == "*"
+    });
+    if cors.credentials && has_wildcard_origin && cors.allowed_origins.is_empty() {
+        eprintln!(
+            "--cors-credentials cannot be used with access-control-allow-origin:*, set --cors-origin or --no-default-headers"
+        );
+        return;
+    }
+
+    if stale_while_revalidate.is_some() || stale_if_error.is_some() {
+        cache_control = Some(cache_control::merge_cache_control(
+            cache_control.as_deref().unwrap_or_default(),
+            stale_while_revalidate,
+            stale_if_error,
+        ));
+    }
+
+    if allow_put && auth.is_none() {
+        eprintln!("--allow-put requires --auth, refusing to serve a writable directory");
+        return;
+    }
+    if allow_upload && auth.is_none() {
+        eprintln!("--allow-upload requires --auth, refusing to serve a writable directory");
+        return;
+    }
+    if webdav && auth.is_none() {
+        eprintln!("--webdav requires --auth, refusing to serve a writable directory");
+        return;
+    }
+    if allow_delete && auth.is_none() {
+        eprintln!("--allow-delete requires --auth, refusing to serve a writable directory");
+        return;
+    }
+
+    let syslog = if log_syslog {
+        match Syslog::connect_default(syslog_facility) {
+            Ok(syslog) => Some(syslog),
+            Err(err) => {
+                eprintln!("failed to connect to syslog, logging to stdout {}", err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let log_to_terminal = log_file.is_none() && syslog.is_none() && std::io::stdout().is_terminal();
+    let log_output: Box<dyn std::io::Write + Send> = match log_file {
+        Some(path) => match RotatingFile::open(&path, rotation) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("failed to open log file {} {}", path, err);
+                return;
+            }
+        },
+        None if syslog.is_some() => Box::new(std::io::sink()),
+        None => Box::new(std::io::stdout()),
+    };
+    // Everything logged through `log` is per request; startup output is
+    // printed directly.
+    if !access_log {
+        log_level = LevelFilter::Off;
+    }
+    let mut logger = Logger::new(log_level, log_output)
+        .with_color(color_mode.enabled(log_to_terminal))
+        .with_format(log_format);
+    if let Some(syslog) = syslog {
+        logger = logger.with_syslog(syslog);
+    }
+    let error_log = match error_log_path {
+        Some(path) => {
+            match std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+            {
+                Ok(file) => Some(Arc::new(ErrorLog::new(error_log_level, Box::new(file)))),
+                Err(err) => {
+                    eprintln!("failed to open error log {} {}", path, err);
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+    if let Some(error_log) = &error_log {
+        logger = logger.with_error_log(error_log.clone());
+    }
+    if let Err(err) = logger.init() {
+        eprintln!("failed to initialize logger {}", err);
+        return;
+    }
+
+    let audit_logger = match audit_log_path.map(AuditLogger::open).transpose() {
+        Ok(audit_logger) => audit_logger.map(|logger| Arc::new(Mutex::new(logger))),
+        Err(err) => {
+            eprintln!("failed to open audit log {}", err);
+            return;
+        }
+    };
+
+    // Most specific first; a stable sort keeps the command line order.
+    not_found_prefixes
+        .sort_by_key(|(prefix, _): &(String, PathBuf)| std::cmp::Reverse(prefix.len()));
+    let mut builder = ServeConfigBuilder::new()
+        .with_directory(directory_path)
+        .with_host(IpAddr::from(host))
+        .with_port(port);
+    for (key, value) in headers {
+        builder = builder.with_header(key, value);
+    }
+    if let Some(path) = not_found_file_path {
+        builder = builder.with_not_found_path(path);
+    }
+    let config = match builder.build() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
 
-    let addr = SocketAddr::from((host, port));
-    println!("Serving {} at {:?}", directory_path, addr);
+    // Entries are mounted at the directory, so the paths the server builds
+    // from it find them.
+    let fs = match zip_path {
+        Some(path) => {
+            match ZipFileSystem::open(Path::new(&path), Path::new(&config.directory_path)) {
+                Ok(zip) => Fs::new(zip),
+                Err(err) => {
+                    eprintln!("failed to open ZIP archive {} {}", path, err);
+                    return;
+                }
+            }
+        }
+        None => Fs::default(),
+    };
 
-    let shared_data = Arc::new(SharedData {
-        headers,
-        directory_path,
-        not_found_file_path,
+    let shared_data = Arc::new(ServeConfig {
+        fs,
+        header_file,
+        log_body_bytes,
+        rewrites,
+        etag_mode,
+        weak_etag,
+        etag_cache: EtagCache::default(),
+        auth,
+        auth_user,
+        allow_put,
+        allow_upload,
+        allow_overwrite,
+        max_body_size,
+        allow_delete,
+        allow_delete_dirs,
+        webdav,
+        allow_trace,
+        disabled_methods,
+        audit_logger,
+        uploads: UploadTracker::default(),
+        response_digest,
+        media_headers,
+        error_bodies,
+        not_found_prefixes,
+        cors,
+        list_directories,
+        index_json,
+        default_sort,
+        allow_sort_param,
+        cache_control,
+        ping_path,
+        ping_body,
+        strip_fingerprint_query,
+        fingerprint_glob,
+        index_fallback,
+        font_cors,
+        server_name,
+        proxy,
+        permissions_policy,
+        document_policy,
+        document_policy_report_only,
+        nel,
+        expect_ct,
+        x_robots_tag,
+        clear_site_data,
+        localize,
+        forbid_ext,
+        forbid_hidden,
+        max_depth,
+        follow_symlinks,
+        fingerprint_links,
+        hide_404_path,
+        search: search_endpoint.map(|endpoint| SearchConfig {
+            endpoint,
+            max_depth: search_max_depth,
+            max_results: search_max_results,
+        }),
+        archive: Some(archive_endpoint)
+            .filter(|endpoint| !endpoint.is_empty())
+            .map(|endpoint| ArchiveConfig {
+                endpoint,
+                max_files: archive_max_files,
+            }),
+        openapi: openapi_spec.map(|spec| OpenApiConfig::new(spec, &openapi_path, openapi_title)),
+        stats: Arc::default(),
+        stats_path,
+        forbid_query,
+        warn_query,
+        no_content_disposition,
+        csp_nonce,
+        inject_html,
+        inject_max_size,
+        transforms,
+        validate_content_type,
+        http10_compat,
+        // Without keep-alive hyper closes every connection anyway.
+        keep_alive: KeepAliveConfig {
+            timeout: listen_options.idle_timeout,
+            max_requests: keep_alive_max_requests.filter(|_| keep_alive),
+        },
+        slow_log_threshold,
+        no_range_requests,
+        dir_config,
+        ..config
     });
-    let make_service = make_service_fn(move |_: &AddrStream| {
+
+    let bind_address = match &bind_interface {
+        Some(name) => match interface::interface_addr(name) {
+            Ok(Some(address)) => address,
+            Ok(None) => {
+                eprintln!("network interface {} not found or has no address", name);
+                return;
+            }
+            Err(err) => {
+                eprintln!("failed to list network interfaces {}", err);
+                return;
+            }
+        },
+        None => shared_data
+            .addr
+            .map_or(config::DEFAULT_HOST, |addr| addr.ip()),
+    };
+
+    if list_only {
+        let entries = tree::list_directory_tree(
+            Path::new(&shared_data.directory_path),
+            list_filter.as_deref(),
+        )
+        .await;
+        match entries {
+            Ok(entries) if json_output => println!("{}", tree::render_json(&entries)),
+            Ok(entries) => print!("{}", tree::render_lines(&entries)),
+            Err(err) => eprintln!("failed to list {} {}", shared_data.directory_path, err),
+        }
+        return;
+    }
+
+    if print_config {
+        print!(
+            "{}",
+            config::format_config_table(&shared_data.explain(&options))
+        );
+        return;
+    }
+
+    if print_routes {
+        let routes = effective_routes(&shared_data);
+        if json_output {
+            println!("{}", format_routes_json(&routes));
+        } else {
+            print!("{}", format_routes_table(&routes));
+        }
+        return;
+    }
+
+    let banner_data = shared_data.clone();
+    let make_service = make_service_fn(move |conn: &LimitedReader<AddrStream>| {
         let data = shared_data.clone();
-        async move { Ok::<_, Infallible>(service_fn(move |req| request_handler(req, data.clone()))) }
+        let remote_addr = conn.get_ref().remote_addr();
+        let in_flight = conn.in_flight().clone();
+        let connection = data.stats.open_connection();
+        let mut served = 0;
+        async move {
+            Ok::<_, Infallible>(service_fn(move |mut req: Request<Body>| {
+                let _connection = &connection;
+                req.extensions_mut().insert(remote_addr);
+                served += 1;
+                req.extensions_mut().insert(RequestCount(served));
+                in_flight.track(timed_request_handler(req, data.clone()))
+            }))
+        }
     });
 
-    let server = Server::bind(&addr).serve(make_service);
+    let tcp_listener = match systemd::activated_listener() {
+        Some(Ok(listener)) => listener,
+        Some(Err(err)) => {
+            startup_error(
+                &error_log,
+                format!("failed to use socket from systemd {}", err),
+            );
+            return;
+        }
+        None => {
+            let port = banner_data
+                .addr
+                .map_or(config::DEFAULT_PORT, |addr| addr.port());
+            let addr = SocketAddr::new(bind_address, port);
+            match listener::bind(addr, listen_options) {
+                Ok(listener) => listener,
+                Err(err) => {
+                    startup_error(&error_log, format!("failed to bind {} {}", addr, err));
+                    return;
+                }
+            }
+        }
+    };
+    let incoming = match listener::incoming(tcp_listener, listen_options) {
+        Ok(incoming) => incoming,
+        Err(err) => {
+            startup_error(&error_log, format!("failed to listen {}", err));
+            return;
+        }
+    };
+    let local_addr = incoming.local_addr();
+    let server = Server::builder(incoming)
+        .http1_keepalive(keep_alive)
+        .serve(make_service);
+    if let Err(err) = daemon::detach() {
+        eprintln!("failed to detach from the terminal {}", err);
+        return;
+    }
+    #[cfg(unix)]
+    if let Some(header_file) = &banner_data.header_file {
+        if let Err(err) = header_file::reload_on_sighup(header_file.clone()) {
+            eprintln!("failed to watch for SIGHUP {}", err);
+            return;
+        }
+    }
+    if let Some(error_log) = &error_log {
+        #[cfg(unix)]
+        if let Err(err) = flush_on_sigterm(error_log.clone()) {
+            eprintln!("failed to watch for SIGTERM {}", err);
+            return;
+        }
+        error_log.event(&format!(
+            "serving {} at {}",
+            banner_data.directory_path, local_addr
+        ));
+    }
+    let directory_path = &banner_data.directory_path;
+    match &bind_interface {
+        _ if silent => {}
+        _ if !no_banner => print!(
+            "{}",
+            banner::render(&banner_rows(
+                &banner_data,
+                local_addr,
+                bind_interface.as_deref(),
+                &options
+            ))
+        ),
+        Some(name) => println!("Serving {} at {:?} on {}", directory_path, local_addr, name),
+        None => println!("Serving {} at {:?}", directory_path, local_addr),
+    }
+    // Printed even with --silent: whoever asked for a random port needs it.
+    if port == 0 {
+        println!("SERVE_DIR_PORT={}", local_addr.port());
+    }
+    if let Err(err) = daemon::notify_ready() {
+        eprintln!("failed to notify the parent process {}", err);
+        return;
+    }
+    if let Err(err) = systemd::notify_ready() {
+        eprintln!("failed to notify systemd {}", err);
+    }
 
     if let Err(e) = server.await {
-        eprintln!("server initialization error {}", e);
+        startup_error(&error_log, format!("server initialization error {}", e));
+    }
+}
+
+/// Prints a failure to start, also recording it in `--error-log`.
+fn startup_error(error_log: &Option<Arc<ErrorLog>>, message: String) {
+    eprintln!("{}", message);
+    if let Some(error_log) = error_log {
+        error_log.event(&message);
+        error_log.flush();
+    }
+}
+
+/// Records the shutdown and flushes the buffered logs when SIGTERM, as
+/// sent by `--stop`, arrives.
+#[cfg(unix)]
+fn flush_on_sigterm(error_log: Arc<ErrorLog>) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::spawn(async move {
+        if terminate.recv().await.is_some() {
+            error_log.event("stopping on SIGTERM");
+            log::logger().flush();
+            std::process::exit(0);
+        }
+    });
+    Ok(())
+}
+
+/// The `--index-fallback` chain, or `index.html` alone.
+fn index_files(shared_data: &ServeConfig) -> Vec<&str> {
+    if shared_data.index_fallback.is_empty() {
+        vec!["index.html"]
+    } else {
+        shared_data
+            .index_fallback
+            .iter()
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Milliseconds since the epoch, the timestamp at the start of log lines.
+fn now_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+fn is_ping(request: &Request<Body>, shared_data: &ServeConfig) -> bool {
+    !shared_data.ping_path.is_empty()
+        && request.uri().path() == shared_data.ping_path
+        && (request.method() == Method::GET || request.method() == Method::HEAD)
+}
+
+fn is_stats(request: &Request<Body>, shared_data: &ServeConfig) -> bool {
+    !shared_data.stats_path.is_empty() && request.uri().path() == shared_data.stats_path
+}
+
+async fn stats_response(request: &Request<Body>, shared_data: &ServeConfig) -> Response<Body> {
+    let time_of_request = now_millis();
+    let uri = request.uri();
+    if query_param(uri.query(), "reset").is_some() {
+        if shared_data.auth.is_none() {
+            warn!(
+                "{}: [403] [{}] {} stats reset needs --auth",
+                time_of_request,
+                request.method(),
+                uri
+            );
+            return error_response(
+                shared_data,
+                Response::builder(),
+                403,
+                "Resetting stats requires --auth",
+            )
+            .await;
+        }
+        if !is_authorized(request, shared_data) {
+            warn!(
+                "{}: [401] [{}] {} unauthorized",
+                time_of_request,
+                request.method(),
+                uri
+            );
+            let response_builder =
+                Response::builder().header(WWW_AUTHENTICATE, "Basic realm=\"serve-dir\"");
+            return error_response(shared_data, response_builder, 401, "Unauthorized").await;
+        }
+        shared_data.stats.reset();
+        info!(
+            "{}: [200] [{}] {} stats reset",
+            time_of_request,
+            request.method(),
+            uri
+        );
     }
+    Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .header(CACHE_CONTROL, "no-store")
+        .body(Body::from(shared_data.stats.to_json()))
+        .unwrap()
+}
+
+/// Collects a streamed body so it can go out with a `Content-Length`, for
+/// clients that cannot read chunked responses.
+async fn buffer_response(response: Response<Body>) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+    parts.headers.insert(CONTENT_LENGTH, body.len().into());
+    parts
+        .headers
+        .insert(CONNECTION, HeaderValue::from_static("close"));
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// HTTP/1.0 connections close after each response unless both sides say
+/// `Connection: keep-alive`, and hyper only keeps one open when the response
+/// does. A body without a known length is delimited by closing, so it
+/// cannot be kept alive either way.
+fn negotiate_http10_keep_alive(wants_keep_alive: bool, response: &mut Response<Body>) {
+    let keep_alive = wants_keep_alive && response.body().size_hint().exact().is_some();
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    response
+        .headers_mut()
+        .insert(CONNECTION, HeaderValue::from_static(connection));
+}
+
+/// Whether the request carries the `--auth` credentials, or none are needed.
+fn is_authorized(request: &Request<Body>, shared_data: &ServeConfig) -> bool {
+    is_authorized_for(
+        request,
+        shared_data,
+        request.extensions().get::<DirConfig>(),
+    )
+}
+
+/// Whether the request carries the credentials `config`, or else `--auth`,
+/// asks for.
+fn is_authorized_for(
+    request: &Request<Body>,
+    shared_data: &ServeConfig,
+    config: Option<&DirConfig>,
+) -> bool {
+    let auth = config
+        .and_then(|config| config.auth.as_ref())
+        .or(shared_data.auth.as_ref());
+    auth.is_none_or(|expected| {
+        request
+            .headers()
+            .get(AUTHORIZATION)
+            .is_some_and(|value| value == expected.as_str())
+    })
+}
+
+/// Runs [`request_handler`] with its start time in scope, so the lines it
+/// logs carry `duration_us`, and warns about responses slower than
+/// `--slow-log-threshold`.
+async fn timed_request_handler(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+) -> Result<Response<Body>, Infallible> {
+    let client = match &shared_data.proxy {
+        Some(proxy) => proxy.client_ip(&request),
+        None => request.extensions().get::<SocketAddr>().map(SocketAddr::ip),
+    };
+    // A proxy's id ties our lines to its own; anything unprintable or
+    // unreasonably long is replaced.
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .map_or_else(logger::next_request_id, String::from);
+    logger::timed(client, request_id, async move {
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+        let response = request_handler(request, shared_data.clone()).await?;
+        let threshold = shared_data.slow_log_threshold;
+        let elapsed = logger::elapsed().unwrap_or_default();
+        if let Some(threshold) = threshold.filter(|threshold| elapsed > *threshold) {
+            warn!(
+                "{}: [{}] [{}] {} slower than {:?}",
+                now_millis(),
+                response.status().as_u16(),
+                method,
+                uri,
+                threshold
+            );
+        }
+        Ok(response)
+    })
+    .await
 }
 
 async fn request_handler(
     request: Request<Body>,
-    shared_data: Arc<SharedData>,
+    shared_data: Arc<ServeConfig>,
 ) -> Result<Response<Body>, Infallible> {
-    let mut response_builder = Response::builder();
-    for (key, value) in &shared_data.headers {
-        response_builder = response_builder.header(key, value);
+    let method = request.method().clone();
+    let url = request.uri().to_string();
+    let request_id = logger::request_id();
+    let client = logger::client();
+    // Health checks and the stats page itself would only skew the numbers.
+    let in_stats = !is_ping(&request, &shared_data) && !is_stats(&request, &shared_data);
+    let started = std::time::Instant::now();
+    let response = route_request(request, shared_data.clone()).await?;
+    let status = response.status().as_u16();
+    let elapsed = started.elapsed();
+    Ok(body_count::count(response, move |bytes| {
+        if in_stats {
+            shared_data.stats.record(&method, status, bytes, elapsed);
+        }
+        logger::access(&ecs::AccessRecord {
+            request_id,
+            method: method.to_string(),
+            url,
+            status,
+            bytes,
+            client,
+            duration: started.elapsed(),
+        });
+    }))
+}
+
+async fn route_request(
+    mut request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+) -> Result<Response<Body>, Infallible> {
+    if let Some(proxy) = &shared_data.proxy {
+        proxy.inject(&mut request);
+    }
+    if is_ping(&request, &shared_data) {
+        let body = if request.method() == Method::HEAD {
+            Body::empty()
+        } else {
+            Body::from(shared_data.ping_body.clone())
+        };
+        let response = Response::builder()
+            .header(CONTENT_TYPE, "text/plain")
+            .header(CONTENT_LENGTH, shared_data.ping_body.len())
+            .body(body)
+            .unwrap();
+        return Ok(response);
     }
 
-    let uri = request.uri();
-    let time_of_request = std::time::SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or(std::time::Duration::default())
-        .as_millis();
-    match request.method() {
-        &Method::GET => {
-            let mut uri_path = &uri.path()[1..];
-            if uri_path.is_empty() {
-                uri_path = "index.html";
-            } else {
-                if uri_path.starts_with('.') {
-                    println!(
-                        "{}: [403] [GET] {} requested invalid path",
-                        time_of_request, uri
-                    );
-                    return Ok(response_builder
-                        .status(403)
-                        .body(Body::from("Invalid Path"))
-                        .unwrap());
-                }
-            }
-            let file_path = PathBuf::from(format!("{}{}", shared_data.directory_path, uri_path));
+    if is_stats(&request, &shared_data) {
+        return Ok(stats_response(&request, &shared_data).await);
+    }
 
-            if file_path.is_file() {
-                match tokio::fs::read(&file_path).await {
-                    Ok(body) => {
-                        println!(
-                            "{}: [200] [GET] {} requested file path",
-                            time_of_request, uri
-                        );
-                        // let content_type = tree_magic::from_u8(&body);
-                        let content_type =
-                            mime_guess::MimeGuess::from_path(file_path).first_or_octet_stream();
-                        return Ok(response_builder
-                            .header("content-type", content_type.to_string())
-                            .body(Body::from(body))
-                            .unwrap());
-                    }
-                    Err(err) => {
-                        println!("{}: [500] [GET] {} {} ", time_of_request, uri, err);
-                        return Ok(response_builder
-                            .status(500)
-                            .body(Body::from("Something Went Wrong :("))
-                            .unwrap());
-                    }
-                };
+    if let Some(query) = request.uri().query() {
+        if shared_data.forbid_query || shared_data.warn_query {
+            let time_of_request = now_millis();
+            if shared_data.forbid_query {
+                warn!(
+                    "{}: [400] [{}] {} query strings are forbidden",
+                    time_of_request,
+                    request.method(),
+                    request.uri()
+                );
+                let response =
+                    error_response(&shared_data, Response::builder(), 400, "400 Bad Request").await;
+                return Ok(response);
             }
+            warn!(
+                "{}: [{}] {} unexpected query string {}",
+                time_of_request,
+                request.method(),
+                request.uri().path(),
+                query
+            );
         }
-        &Method::OPTIONS => {
-            println!("{}: [200] [OPTIONS] {}", time_of_request, uri);
-            return Ok(response_builder.body(Body::empty()).unwrap());
-        }
-        _ => {}
-    };
+    }
 
-    let (body, is_from_file) = not_found_body(&shared_data.not_found_file_path).await;
+    if shared_data.no_range_requests && request.headers().contains_key(RANGE) {
+        warn!(
+            "{}: [400] [{}] {} range requests are disabled",
+            now_millis(),
+            request.method(),
+            request.uri()
+        );
+        let response_builder = Response::builder().header(ACCEPT_RANGES, "none");
+        let response = error_response(&shared_data, response_builder, 400, "400 Bad Request").await;
+        return Ok(response);
+    }
 
-    let response = response_builder
-        .header(
-            CONTENT_TYPE,
-            if is_from_file {
-                "text/html"
-            } else {
-                "plain/text"
-            },
-        )
-        .status(404)
-        .body(body)
-        .unwrap();
-    println!(
-        "{}: [404] [GET] {} requested address not found",
-        time_of_request, uri
-    );
-    return Ok(response);
+    if dir_config::is_config_path(request.uri().path()) {
+        warn!(
+            "{}: [403] [{}] {} requested directory config",
+            now_millis(),
+            request.method(),
+            request.uri()
+        );
+        return Ok(error_response(&shared_data, Response::builder(), 403, "Forbidden").await);
+    }
+    if shared_data.dir_config {
+        // An archive is governed by the directory it packs, not its endpoint.
+        let target = match &shared_data.archive {
+            Some(archive) if request.uri().path() == archive.endpoint => {
+                String::from(archive::archived_path(request.uri()))
+            }
+            _ => String::from(request.uri().path()),
+        };
+        match dir_config::load(&shared_data.directory_path, &target).await {
+            Ok(config) => {
+                request.extensions_mut().insert(config);
+            }
+            Err(err) => {
+                error!(
+                    "{}: [500] [{}] {} invalid {}",
+                    now_millis(),
+                    request.method(),
+                    request.uri(),
+                    err
+                );
+                let response = error_response(
+                    &shared_data,
+                    Response::builder(),
+                    500,
+                    "Something Went Wrong :(",
+                )
+                .await;
+                return Ok(response);
+            }
+        }
+    }
+
+    let origin = request.headers().get(ORIGIN).cloned();
+    let cors_origins = request
+        .extensions()
+        .get::<DirConfig>()
+        .and_then(|config| config.cors_origins.clone());
+    let path = String::from(request.uri().path());
+    let method = request.method().clone();
+    let is_http10 = request.version() == Version::HTTP_10;
+    let is_http11 = request.version() == Version::HTTP_11;
+    let served = request.extensions().get::<RequestCount>().copied();
+    let wants_keep_alive = request
+        .headers()
+        .get(CONNECTION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("keep-alive"))
+        });
+    let mut response = handle_request(request, shared_data.clone()).await?;
+    if method == Method::HEAD {
+        strip_body(&mut response);
+    }
+    if shared_data.http10_compat && is_http10 {
+        response = buffer_response(response).await;
+    } else if is_http10 {
+        negotiate_http10_keep_alive(wants_keep_alive, &mut response);
+    } else if let Some(RequestCount(served)) = served.filter(|_| is_http11) {
+        shared_data.keep_alive.apply(served, response.headers_mut());
+    }
+    match cors_origins {
+        Some(allowed_origins) => CorsConfig {
+            allowed_origins,
+            ..shared_data.cors.clone()
+        }
+        .apply(origin.as_ref(), response.headers_mut()),
+        None => shared_data
+            .cors
+            .apply(origin.as_ref(), response.headers_mut()),
+    }
+    if shared_data.font_cors {
+        cors::apply_font_cors(&path, response.headers_mut());
+    }
+    shared_data.server_name.apply(response.headers_mut());
+    Ok(response)
 }
 
-async fn not_found_body(path: &Option<String>) -> (Body, bool) {
-    const NOT_FOUND: &str = "404 Not Found";
-    if let Some(val) = path {
-        if let Ok(data) = tokio::fs::read(val).await {
-            return (Body::from(data), true);
+/// Empties a response to HEAD, keeping the `Content-Length` GET would have
+/// sent.
+fn strip_body(response: &mut Response<Body>) {
+    if let Some(length) = response.body().size_hint().exact() {
+        if !response.headers().contains_key(CONTENT_LENGTH) {
+            response.headers_mut().insert(CONTENT_LENGTH, length.into());
         }
     }
-    return (Body::from(NOT_FOUND), false);
+    *response.body_mut() = Body::empty();
 }
 
-#[cfg(test)]
-mod test {
+async fn handle_request(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+) -> Result<Response<Body>, Infallible> {
+    let mut response_builder = Response::builder();
+    if let Some(header_file) = &shared_data.header_file {
+        for (key, value) in header_file.headers_not_in(&shared_data.headers) {
+            response_builder = response_builder.header(key, value);
+        }
+    }
+    for (key, value) in &shared_data.headers {
+        response_builder = response_builder.header(key, value);
+    }
+    if !shared_data.permissions_policy.is_empty() {
+        response_builder = response_builder
+            .header(
+                "permissions-policy",
+                permissions::permissions_policy(&shared_data.permissions_policy),
+            )
+            .header(
+                "feature-policy",
+                permissions::feature_policy(&shared_data.permissions_policy),
+            );
+    }
+    if let Some(config) = request.extensions().get::<DirConfig>() {
+        if let Some(headers) = response_builder.headers_mut() {
+            for (name, value) in &config.headers {
+                headers.insert(name, value.clone());
+            }
+        }
+    }
+    if let Some(policy) = &shared_data.document_policy {
+        response_builder = response_builder.header("document-policy", policy);
+    }
+    if let Some(policy) = &shared_data.document_policy_report_only {
+        response_builder = response_builder.header("document-policy-report-only", policy);
+    }
+    if let Some(nel) = &shared_data.nel {
+        response_builder = response_builder
+            .header("nel", nel.nel())
+            .header("report-to", nel.report_to());
+    }
+    if let Some(expect_ct) = &shared_data.expect_ct {
+        response_builder = response_builder.header("expect-ct", expect_ct.header());
+    }
+    if let Some(value) = robots::x_robots_tag(&shared_data.x_robots_tag, request.uri().path()) {
+        response_builder = response_builder.header("x-robots-tag", value);
+    }
+    if let Some(value) =
+        clear_site_data::clear_site_data(&shared_data.clear_site_data, request.uri().path())
+    {
+        response_builder = response_builder.header("clear-site-data", value);
+    }
 
-    #[test]
-    fn mime_type_from_binary_test() {
-        let path = "./package.json";
-        let mime = mime_guess::MimeGuess::from_path(path)
-            .first_or_octet_stream()
-            .to_string();
-        assert_eq!("application/json", mime);
+    let uri = request.uri().clone();
+    let method = request.method().clone();
+    let time_of_request = now_millis();
+    debug!(
+        "{}: [{}] {} request headers {:?}",
+        time_of_request,
+        method,
+        uri,
+        request.headers()
+    );
+    let request = match shared_data.log_body_bytes {
+        Some(limit)
+            if (method == Method::POST || method == Method::PUT)
+                && log::log_enabled!(log::Level::Debug) =>
+        {
+            let (request, body) = body_log::peek(request, limit).await;
+            debug!(
+                "{}: [{}] {} request body {:?}",
+                time_of_request, method, uri, body
+            );
+            request
+        }
+        _ => request,
+    };
+
+    if shared_data.disabled_methods.contains(&method) {
+        warn!(
+            "{}: [405] [{}] {} method disabled",
+            time_of_request, method, uri
+        );
+        return Ok(method_not_allowed(&shared_data, response_builder).await);
+    }
+
+    if !is_authorized(&request, &shared_data) && method != Method::OPTIONS {
+        warn!(
+            "{}: [401] [{}] {} unauthorized",
+            time_of_request, method, uri
+        );
+        let response_builder =
+            response_builder.header(WWW_AUTHENTICATE, "Basic realm=\"serve-dir\"");
+        return Ok(error_response(&shared_data, response_builder, 401, "Unauthorized").await);
+    }
+
+    match method {
+        // HEAD goes through the same lookup; the body is dropped afterwards.
+        Method::GET | Method::HEAD => {
+            if shared_data.allow_put {
+                if let Some(id) = uri.path().strip_prefix(UPLOAD_PROGRESS_PREFIX) {
+                    if let Some(receiver) = shared_data.uploads.subscribe(id) {
+                        info!(
+                            "{}: [200] [{}] {} upload progress",
+                            time_of_request, method, uri
+                        );
+                        return Ok(response_builder
+                            .header(CONTENT_TYPE, "text/event-stream")
+                            .header(CACHE_CONTROL, "no-cache")
+                            .body(upload::progress_events(receiver))
+                            .unwrap());
+                    }
+                }
+            }
+            if shared_data.strip_fingerprint_query {
+                if let Some(query) = uri.query() {
+                    debug!(
+                        "{}: [{}] {} stripped query {}",
+                        time_of_request,
+                        method,
+                        uri.path(),
+                        query
+                    );
+                }
+            }
+            if let Some(search) = &shared_data.search {
+                if uri.path() == search.endpoint {
+                    return Ok(search::handle_search(
+                        &request,
+                        &shared_data,
+                        search,
+                        response_builder,
+                        time_of_request,
+                    )
+                    .await);
+                }
+            }
+            if let Some(openapi) = &shared_data.openapi {
+                if openapi.matches(uri.path()) {
+                    return Ok(openapi::handle_openapi(
+                        &request,
+                        &shared_data,
+                        openapi,
+                        response_builder,
+                        time_of_request,
+                    )
+                    .await);
+                }
+            }
+            if let Some(archive) = &shared_data.archive {
+                if uri.path() == archive.endpoint {
+                    return Ok(archive::handle_archive(
+                        &request,
+                        &shared_data,
+                        archive,
+                        response_builder,
+                        time_of_request,
+                    )
+                    .await);
+                }
+            }
+            let uri_path = percent_decode(uri.path());
+            let relative_path = uri_path.trim_start_matches('/');
+            if relative_path.starts_with('.') || relative_path.split('/').any(|s| s == "..") {
+                warn!(
+                    "{}: [403] [{}] {} requested invalid path",
+                    time_of_request, method, uri
+                );
+                return Ok(
+                    error_response(&shared_data, response_builder, 403, "Invalid Path").await,
+                );
+            }
+            let rewritten_path = shared_data
+                .rewrites
+                .iter()
+                .find_map(|rule| rule.apply(&uri_path, &shared_data.directory_path));
+            if let Some(path) = rewritten_path {
+                debug!(
+                    "{}: [{}] {} rewritten to {}",
+                    time_of_request,
+                    method,
+                    uri,
+                    path.display()
+                );
+                if shared_data.fs.is_file(&path) {
+                    return Ok(serve_file(
+                        &request,
+                        &shared_data,
+                        response_builder,
+                        &path,
+                        time_of_request,
+                    )
+                    .await);
+                }
+            } else {
+                let path =
+                    PathBuf::from(format!("{}{}", shared_data.directory_path, relative_path));
+                let is_directory = relative_path.is_empty() || relative_path.ends_with('/');
+                let candidates: Vec<PathBuf> = if is_directory {
+                    index_files(&shared_data)
+                        .iter()
+                        .map(|file| path.join(file))
+                        .collect()
+                } else {
+                    vec![path.clone()]
+                };
+                let accept_language = if shared_data.localize {
+                    response_builder = response_builder.header(VARY, "Accept-Language");
+                    request
+                        .headers()
+                        .get(ACCEPT_LANGUAGE)
+                        .and_then(|value| value.to_str().ok())
+                } else {
+                    None
+                };
+                let localized = |candidate: &PathBuf| {
+                    accept_language
+                        .and_then(|accept_language| language::localized(candidate, accept_language))
+                        .unwrap_or_else(|| candidate.clone())
+                };
+                let index_path = localized(&candidates[0]);
+                let found = candidates
+                    .iter()
+                    .map(localized)
+                    .find(|candidate| shared_data.fs.is_file(candidate));
+                if let Some(found) = found {
+                    let mut response = serve_file(
+                        &request,
+                        &shared_data,
+                        response_builder,
+                        &found,
+                        time_of_request,
+                    )
+                    .await;
+                    let is_html = mime_guess::from_path(&found)
+                        .first()
+                        .is_some_and(|mime| mime.essence_str() == "text/html");
+                    // A README in the chain is shown as is rather than
+                    // downloaded or rendered.
+                    if is_directory && !is_html && response.status() == 200 {
+                        response.headers_mut().insert(
+                            CONTENT_TYPE,
+                            HeaderValue::from_static("text/plain; charset=utf-8"),
+                        );
+                    }
+                    return Ok(response);
+                }
+                let fingerprint_fallback = shared_data
+                    .fingerprint_glob
+                    .as_ref()
+                    .and_then(|glob| fingerprint::fallback(glob, &index_path))
+                    .filter(|path| shared_data.fs.is_file(path));
+                if let Some(path) = fingerprint_fallback {
+                    debug!(
+                        "{}: [{}] {} fingerprint fallback to {}",
+                        time_of_request,
+                        method,
+                        uri,
+                        path.display()
+                    );
+                    return Ok(serve_file(
+                        &request,
+                        &shared_data,
+                        response_builder,
+                        &path,
+                        time_of_request,
+                    )
+                    .await);
+                }
+                let is_dir = (shared_data.list_directories || shared_data.index_json)
+                    && shared_data
+                        .fs
+                        .metadata(&path)
+                        .await
+                        .is_ok_and(|metadata| metadata.is_dir);
+                let root = Path::new(&shared_data.directory_path);
+                if is_dir && !shared_data.follow_symlinks.allows(root, &path) {
+                    warn!(
+                        "{}: [403] [{}] {} refused to follow symlink",
+                        time_of_request, method, uri
+                    );
+                    return Ok(
+                        error_response(&shared_data, response_builder, 403, "Forbidden").await,
+                    );
+                }
+                if is_dir {
+                    return Ok(listing::handle_listing(
+                        &request,
+                        &shared_data,
+                        response_builder,
+                        &path,
+                        time_of_request,
+                    )
+                    .await);
+                }
+            }
+        }
+        Method::PUT if shared_data.allow_put => {
+            return Ok(
+                write::handle_put(request, shared_data, response_builder, time_of_request).await,
+            );
+        }
+        Method::POST if shared_data.allow_upload => {
+            return Ok(write::handle_upload(
+                request,
+                shared_data,
+                response_builder,
+                time_of_request,
+            )
+            .await);
+        }
+        Method::TRACE if shared_data.allow_trace => {
+            info!("{}: [200] [TRACE] {}", time_of_request, uri);
+            return Ok(response_builder
+                .header(CONTENT_TYPE, "message/http")
+                .body(Body::from(trace_message(&request)))
+                .unwrap());
+        }
+        _ if shared_data.webdav && webdav::is_webdav_method(&method) => {
+            return Ok(webdav::handle_webdav(
+                request,
+                shared_data,
+                response_builder,
+                time_of_request,
+            )
+            .await);
+        }
+        Method::DELETE if shared_data.allow_delete || shared_data.webdav => {
+            return Ok(write::handle_delete(
+                request,
+                shared_data,
+                response_builder,
+                time_of_request,
+            )
+            .await);
+        }
+        Method::OPTIONS => {
+            info!("{}: [200] [OPTIONS] {}", time_of_request, uri);
+            let response_builder = if shared_data.webdav {
+                response_builder
+                    .header(&webdav::DAV, webdav::COMPLIANCE)
+                    .header(ALLOW, allowed_methods(&shared_data))
+            } else {
+                response_builder
+            };
+            return Ok(response_builder.body(Body::empty()).unwrap());
+        }
+        // Only GET and HEAD go on to look for a file; a path that exists
+        // would not be served to any other method either.
+        _ => {
+            warn!(
+                "{}: [405] [{}] {} method not allowed",
+                time_of_request, method, uri
+            );
+            return Ok(method_not_allowed(&shared_data, response_builder).await);
+        }
+    };
+
+    let (body, is_from_file) = not_found_body(&shared_data, uri.path()).await;
+
+    let response = response_builder
+        .header(
+            CONTENT_TYPE,
+            if is_from_file {
+                "text/html"
+            } else {
+                "plain/text"
+            },
+        )
+        .status(404)
+        .body(body)
+        .unwrap();
+    warn!(
+        "{}: [404] [{}] {} requested address not found",
+        time_of_request,
+        method,
+        not_found_uri(&shared_data, &uri)
+    );
+    Ok(response)
+}
+
+/// Effective configuration shown in the startup banner.
+fn banner_rows(
+    shared_data: &ServeConfig,
+    addr: SocketAddr,
+    interface: Option<&str>,
+    options: &[String],
+) -> Vec<(&'static str, String)> {
+    let on_off = |enabled: bool| String::from(if enabled { "on" } else { "off" });
+    let cors = if shared_data.cors.allowed_origins.is_empty() {
+        shared_data
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("access-control-allow-origin"))
+            .map(|(_, value)| format!("origin {}", value))
+            .unwrap_or_else(|| on_off(false))
+    } else {
+        format!("origins {}", shared_data.cors.allowed_origins.join(", "))
+    };
+    let auth = match (&shared_data.auth, &shared_data.auth_user) {
+        (Some(_), Some(user)) => format!("basic, user {}", user),
+        (Some(_), None) => String::from("basic"),
+        (None, _) => on_off(false),
+    };
+    let address = match interface {
+        Some(name) => format!("{} on {}", addr, name),
+        None => addr.to_string(),
+    };
+    let options: Vec<&str> = options
+        .iter()
+        .map(String::as_str)
+        .filter(|option| option.starts_with('-'))
+        .collect();
+    vec![
+        ("directory", shared_data.directory_path.clone()),
+        ("address", address),
+        // TLS, compression and rate limiting are left to a reverse proxy.
+        ("tls", on_off(false)),
+        ("cors", cors),
+        ("compression", on_off(false)),
+        ("auth", auth),
+        ("rate limit", on_off(false)),
+        ("headers", shared_data.headers.len().to_string()),
+        (
+            "404 page",
+            shared_data
+                .not_found_file_path
+                .clone()
+                .unwrap_or_else(|| String::from("default")),
+        ),
+        (
+            "options",
+            if options.is_empty() {
+                String::from("none")
+            } else {
+                options.join(" ")
+            },
+        ),
+    ]
+}
+
+/// Number of segments in a request path, ignoring empty ones, so `/a.js` is
+/// 1 and `/a//b/c.js` is 3.
+fn path_depth(uri_path: &str) -> usize {
+    percent_decode(uri_path)
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .count()
+}
+
+/// Keeps the walked `entries` of `dir`, at `dir_uri` below the served root,
+/// that a GET would serve to `request`: not forbidden, not behind a refused
+/// symlink, and allowed by the `.servedir` files above each of them.
+async fn retain_servable(
+    request: &Request<Body>,
+    shared_data: &ServeConfig,
+    dir_uri: &str,
+    dir: &Path,
+    entries: &mut Vec<tree::FileEntry>,
+) {
+    let root = Path::new(&shared_data.directory_path);
+    let mut authorized_dirs: HashMap<String, bool> = HashMap::new();
+    let mut servable = Vec::with_capacity(entries.len());
+    for entry in entries.drain(..) {
+        let uri_path = format!("{}{}", dir_uri.trim_end_matches('/'), entry.path);
+        let file_path = dir.join(&entry.path[1..]);
+        if is_forbidden(shared_data, &uri_path, &file_path)
+            || !shared_data.follow_symlinks.allows(root, &file_path)
+        {
+            continue;
+        }
+        if shared_data.dir_config {
+            let parent = uri_path.rsplit_once('/').map_or("", |(parent, _)| parent);
+            let authorized = match authorized_dirs.get(parent) {
+                Some(authorized) => *authorized,
+                None => {
+                    // An unreadable `.servedir` hides its files rather than
+                    // exposing them.
+                    let authorized = dir_config::load(&shared_data.directory_path, parent)
+                        .await
+                        .is_ok_and(|config| is_authorized_for(request, shared_data, Some(&config)));
+                    authorized_dirs.insert(String::from(parent), authorized);
+                    authorized
+                }
+            };
+            if !authorized {
+                continue;
+            }
+        }
+        servable.push(entry);
+    }
+    *entries = servable;
+}
+
+/// Whether `--forbid-ext`, `--forbid-hidden` or `--max-depth` rule out
+/// serving `file_path`.
+fn is_forbidden(shared_data: &ServeConfig, uri_path: &str, file_path: &Path) -> bool {
+    let file_name = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    // Matching on the name rather than `Path::extension` also catches `.env`.
+    let forbidden_ext = shared_data
+        .forbid_ext
+        .iter()
+        .any(|extension| file_name.ends_with(&format!(".{}", extension.to_ascii_lowercase())));
+    let hidden = shared_data.forbid_hidden
+        && (file_name.starts_with('.')
+            || percent_decode(uri_path)
+                .split('/')
+                .any(|segment| segment.starts_with('.')));
+    let too_deep = shared_data
+        .max_depth
+        .is_some_and(|max_depth| path_depth(uri_path) > max_depth);
+    forbidden_ext || hidden || too_deep
+}
+
+async fn serve_file(
+    request: &Request<Body>,
+    shared_data: &ServeConfig,
+    mut response_builder: Builder,
+    file_path: &Path,
+    time_of_request: u128,
+) -> Response<Body> {
+    let (uri, method) = (request.uri(), request.method());
+    // let content_type = tree_magic::from_u8(&body);
+    let content_type = mime_guess::MimeGuess::from_path(file_path).first_or_octet_stream();
+    if is_forbidden(shared_data, uri.path(), file_path) {
+        warn!(
+            "{}: [403] [{}] {} requested forbidden file",
+            time_of_request, method, uri
+        );
+        return error_response(shared_data, response_builder, 403, "Forbidden").await;
+    }
+    let root = Path::new(&shared_data.directory_path);
+    if !shared_data.follow_symlinks.allows(root, file_path) {
+        warn!(
+            "{}: [403] [{}] {} refused to follow symlink",
+            time_of_request, method, uri
+        );
+        return error_response(shared_data, response_builder, 403, "Forbidden").await;
+    }
+    let accept_encoding = request
+        .headers()
+        .get(ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok());
+    // Files go out as they are stored, so identity is the only coding.
+    if encoding::negotiate(accept_encoding, &["identity"]).is_none() {
+        warn!(
+            "{}: [406] [{}] {} refused every content encoding",
+            time_of_request, method, uri
+        );
+        return error_response(shared_data, response_builder, 406, "Not Acceptable").await;
+    }
+    let cache_control = request
+        .extensions()
+        .get::<DirConfig>()
+        .and_then(|config| config.cache_control.as_ref())
+        .or(shared_data.cache_control.as_ref());
+    if let Some(cache_control) = cache_control {
+        response_builder = response_builder.header(CACHE_CONTROL, cache_control);
+    }
+    if shared_data.no_range_requests {
+        response_builder = response_builder.header(ACCEPT_RANGES, "none");
+    }
+    if !shared_data.no_content_disposition {
+        if let Some(disposition) = disposition::content_disposition(file_path, &content_type) {
+            response_builder = response_builder.header(CONTENT_DISPOSITION, disposition);
+        }
+    }
+    let mut body = match shared_data.fs.read_file(file_path).await {
+        Ok(body) => body,
+        Err(err) => {
+            error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+            return error_response(
+                shared_data,
+                response_builder,
+                500,
+                "Something Went Wrong :(",
+            )
+            .await;
+        }
+    };
+    let is_html = content_type.essence_str() == "text/html";
+    if shared_data.csp_nonce && is_html {
+        let nonce = match csp::nonce() {
+            Ok(nonce) => nonce,
+            Err(err) => {
+                error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+                return error_response(
+                    shared_data,
+                    response_builder,
+                    500,
+                    "Something Went Wrong :(",
+                )
+                .await;
+            }
+        };
+        if let Some(headers) = response_builder.headers_mut() {
+            let existing = headers
+                .get(CONTENT_SECURITY_POLICY)
+                .and_then(|policy| policy.to_str().ok());
+            let policy = csp::with_nonce(existing, &nonce);
+            headers.insert(
+                CONTENT_SECURITY_POLICY,
+                HeaderValue::from_str(&policy).unwrap(),
+            );
+            // A cached copy or a 304 would pair an old nonce with a new header.
+            headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        }
+        if let Some(injected) = csp::inject_meta(&body, &nonce) {
+            body = injected;
+        }
+    }
+    let snippet = shared_data
+        .inject_html
+        .as_deref()
+        .filter(|_| is_html && body.len() as u64 <= shared_data.inject_max_size);
+    let injected = match snippet.and_then(|snippet| inject::before_body_end(&body, snippet)) {
+        Some(injected) => {
+            body = injected;
+            true
+        }
+        None => false,
+    };
+    let transformed =
+        match transform::apply(&shared_data.transforms, content_type.essence_str(), &body) {
+            Some(transformed) => {
+                body = transformed;
+                true
+            }
+            None => false,
+        };
+    let metadata = if shared_data.csp_nonce && is_html {
+        None
+    } else {
+        shared_data.fs.metadata(file_path).await.ok()
+    };
+    if let Some(metadata) = metadata {
+        // The snippet and replacements can change between runs while the
+        // file's mtime does not, so a rewritten body is tagged by its content.
+        let etag = if injected || transformed {
+            etag::content_etag(&body)
+        } else {
+            etag::etag(
+                shared_data.etag_mode,
+                file_path,
+                &metadata,
+                &body,
+                &shared_data.etag_cache,
+            )
+        };
+        let etag = if shared_data.weak_etag {
+            etag::weak(&etag)
+        } else {
+            etag
+        };
+        let is_not_modified = request
+            .headers()
+            .get(IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| etag::matches(value, &etag));
+        if is_not_modified {
+            info!(
+                "{}: [304] [{}] {} requested file not modified",
+                time_of_request, method, uri
+            );
+            return response_builder
+                .status(304)
+                .header(ETAG, etag)
+                .body(Body::empty())
+                .unwrap();
+        }
+        response_builder = response_builder.header(ETAG, etag);
+    }
+    info!(
+        "{}: [200] [{}] {} requested file path",
+        time_of_request, method, uri
+    );
+    if shared_data.response_digest {
+        response_builder = response_builder.header(digest::DIGEST, digest::digest_header(&body));
+    }
+    let is_media = matches!(content_type.type_().as_str(), "audio" | "video");
+    let duration = (shared_data.media_headers && is_media)
+        .then(|| media::duration(&body))
+        .flatten();
+    if let Some(duration) = duration {
+        // Content-Duration (RFC 3803) takes whole seconds.
+        response_builder = response_builder
+            .header("x-content-duration", format!("{:.3}", duration))
+            .header("content-duration", format!("{:.0}", duration.round()));
+    }
+    if shared_data.validate_content_type {
+        if let Some(detected) = sniff::mismatch(content_type.essence_str(), &body) {
+            warn!(
+                "{}: [{}] {} served as {} but content looks like {}",
+                time_of_request,
+                method,
+                uri,
+                content_type.essence_str(),
+                detected
+            );
+        }
+    }
+    response_builder
+        .header("content-type", content_type.to_string())
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// A 405 listing the methods that are answered in `Allow`.
+async fn method_not_allowed(
+    shared_data: &ServeConfig,
+    response_builder: Builder,
+) -> Response<Body> {
+    let response_builder = response_builder.header(ALLOW, allowed_methods(shared_data));
+    error_response(shared_data, response_builder, 405, "405 Method Not Allowed").await
+}
+
+fn allowed_methods(shared_data: &ServeConfig) -> String {
+    let mut methods = vec!["GET", "HEAD", "OPTIONS"];
+    if shared_data.allow_put {
+        methods.push("PUT");
+    }
+    if shared_data.allow_upload {
+        methods.push("POST");
+    }
+    if shared_data.allow_delete || shared_data.webdav {
+        methods.push("DELETE");
+    }
+    if shared_data.webdav {
+        methods.extend(webdav::METHODS);
+    }
+    if shared_data.allow_trace {
+        methods.push("TRACE");
+    }
+    methods.retain(|method| {
+        !shared_data
+            .disabled_methods
+            .iter()
+            .any(|disabled| disabled.as_str() == *method)
+    });
+    methods.join(", ")
+}
+
+/// The request head echoed back by TRACE, without credentials.
+fn trace_message(request: &Request<Body>) -> String {
+    let mut message = format!(
+        "{} {} {:?}\r\n",
+        request.method(),
+        request.uri(),
+        request.version()
+    );
+    for (name, value) in request.headers() {
+        if name == AUTHORIZATION || name == PROXY_AUTHORIZATION {
+            continue;
+        }
+        let name: Vec<String> = name
+            .as_str()
+            .split('-')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            })
+            .collect();
+        message.push_str(&format!(
+            "{}: {}\r\n",
+            name.join("-"),
+            String::from_utf8_lossy(value.as_bytes())
+        ));
+    }
+    message.push_str("\r\n");
+    message
+}
+
+/// Parses a byte size such as `512`, `10KB`, `100MB` or `1GB` (powers of 1024).
+fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Parses a duration such as `500ms`, `60s`, `5m` or `1h`; bare numbers
+/// are seconds.
+fn parse_duration(duration: &str) -> Option<std::time::Duration> {
+    let duration = duration.trim();
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (number, unit) = duration.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let millis = match unit.trim() {
+        "ms" => 1,
+        "" | "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        _ => return None,
+    };
+    Some(std::time::Duration::from_millis(
+        number.checked_mul(millis)?,
+    ))
+}
+
+/// Returns the first value of `name` in a `a=1&b=2` query string.
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Decodes `%XX` escapes in a request path. Invalid escapes are kept as is.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Maps a request path onto the served directory, rejecting empty paths and
+/// any segment starting with `.` (hidden files and `..` traversal).
+fn resolve_path(directory_path: &str, uri_path: &str) -> Option<PathBuf> {
+    let uri_path = percent_decode(uri_path);
+    let relative_path = uri_path.trim_start_matches('/');
+    if relative_path.is_empty() || relative_path.split('/').any(|s| s.starts_with('.')) {
+        return None;
+    }
+    Some(PathBuf::from(format!(
+        "{}{}",
+        directory_path, relative_path
+    )))
+}
+
+/// Like [`resolve_path`], but the served directory itself is allowed as
+/// well, for requests that operate on a directory.
+fn resolve_collection(directory_path: &str, uri_path: &str) -> Option<PathBuf> {
+    if percent_decode(uri_path).trim_start_matches('/').is_empty() {
+        return Some(PathBuf::from(directory_path));
+    }
+    resolve_path(directory_path, uri_path)
+}
+
+/// The URI as it should appear in a 404 log line.
+fn not_found_uri(shared_data: &ServeConfig, uri: &hyper::Uri) -> String {
+    if shared_data.hide_404_path {
+        String::from("<redacted>")
+    } else {
+        uri.to_string()
+    }
+}
+
+/// Splits `--404=/docs:./docs/404.html` into its path prefix and file;
+/// `None` for a plain global `--404` file.
+fn parse_not_found_prefix(value: &str) -> Option<(String, PathBuf)> {
+    let (prefix, path) = value.split_once(':')?;
+    if !prefix.starts_with('/') || path.is_empty() {
+        return None;
+    }
+    let prefix = format!("/{}", prefix.trim_matches('/'));
+    Some((prefix, PathBuf::from(path)))
+}
+
+/// The page for a 404 at `uri_path`: the longest matching `--404` prefix,
+/// whole segments only, then the global `--404` or `--error-body=404`.
+/// The flag is whether a page was read rather than the built-in text.
+async fn not_found_body(shared_data: &ServeConfig, uri_path: &str) -> (Body, bool) {
+    const NOT_FOUND: &str = "404 Not Found";
+    let prefixed = shared_data
+        .not_found_prefixes
+        .iter()
+        .find(|(prefix, _)| {
+            prefix == "/"
+                || uri_path
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .map(|(_, path)| path.clone());
+    let global = shared_data
+        .not_found_file_path
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| shared_data.error_bodies.get(&404).cloned());
+    // An unreadable prefix page falls back to the global one.
+    for path in [prefixed, global].into_iter().flatten() {
+        if let Ok(data) = tokio::fs::read(path).await {
+            return (Body::from(data), true);
+        }
+    }
+    (Body::from(NOT_FOUND), false)
+}
+
+/// Builds an error response, using the `--error-body` file configured for
+/// `status` when there is one and it can be read.
+async fn error_response(
+    shared_data: &ServeConfig,
+    response_builder: Builder,
+    status: u16,
+    default_body: impl Into<Body>,
+) -> Response<Body> {
+    let response_builder = response_builder.status(status);
+    if let Some(path) = shared_data.error_bodies.get(&status) {
+        if let Ok(data) = tokio::fs::read(path).await {
+            let content_type = mime_guess::MimeGuess::from_path(path).first_or_octet_stream();
+            return response_builder
+                .header(CONTENT_TYPE, content_type.to_string())
+                .body(Body::from(data))
+                .unwrap();
+        }
+    }
+    response_builder.body(default_body.into()).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hyper::header::{ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_RANGE};
+
+    /// A fresh, empty directory under the system temp dir, with a trailing `/`
+    /// like the served directory path.
+    pub fn temp_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("serve-dir-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        format!("{}/", dir.display())
+    }
+
+    pub async fn send(shared_data: &Arc<ServeConfig>, request: Request<Body>) -> Response<Body> {
+        request_handler(request, shared_data.clone()).await.unwrap()
+    }
+
+    fn writable_data(directory_path: String) -> Arc<ServeConfig> {
+        Arc::new(ServeConfig {
+            directory_path,
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            allow_put: true,
+            allow_delete: true,
+            ..Default::default()
+        })
+    }
+
+    fn put(path: &str, body: &'static str) -> Request<Body> {
+        Request::put(path)
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn delete(path: &str) -> Request<Body> {
+        Request::delete(path)
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn put_create_and_overwrite_test() {
+        let dir = temp_dir("put");
+        let shared_data = writable_data(dir.clone());
+
+        let response = send(&shared_data, put("/nested/file.txt", "first")).await;
+        assert_eq!(201, response.status());
+        assert_eq!(
+            "first",
+            std::fs::read_to_string(format!("{}nested/file.txt", dir)).unwrap()
+        );
+
+        let response = send(&shared_data, put("/nested/file.txt", "second")).await;
+        assert_eq!(204, response.status());
+        assert_eq!(
+            "second",
+            std::fs::read_to_string(format!("{}nested/file.txt", dir)).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn put_path_traversal_test() {
+        let shared_data = writable_data(temp_dir("put-traversal"));
+        for path in ["/../escape.txt", "/a/../../escape.txt", "/.hidden"] {
+            let response = send(&shared_data, put(path, "data")).await;
+            assert_eq!(403, response.status());
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn put_policy_test() {
+        let dir = temp_dir("put-policy");
+        let outside = temp_dir("put-policy-outside");
+        std::os::unix::fs::symlink(&outside, format!("{}escape", dir)).unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            allow_put: true,
+            follow_symlinks: FollowSymlinks::Never,
+            forbid_ext: vec![String::from("php")],
+            ..Default::default()
+        });
+        for path in ["/escape/file.txt", "/escape/new/file.txt", "/shell.php"] {
+            let response = send(&shared_data, put(path, "data")).await;
+            assert_eq!(403, response.status(), "{}", path);
+        }
+        assert!(std::fs::read_dir(&outside).unwrap().next().is_none());
+        assert!(!std::path::Path::new(&format!("{}shell.php", dir)).exists());
+        assert_eq!(
+            201,
+            send(&shared_data, put("/file.txt", "data")).await.status()
+        );
+    }
+
+    #[tokio::test]
+    async fn put_unauthenticated_test() {
+        let dir = temp_dir("put-unauthenticated");
+        let shared_data = writable_data(dir.clone());
+        let request = Request::put("/file.txt").body(Body::from("data")).unwrap();
+        let response = send(&shared_data, request).await;
+        assert_eq!(401, response.status());
+        assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+        assert!(!std::path::Path::new(&format!("{}file.txt", dir)).exists());
+    }
+
+    #[test]
+    fn mime_type_from_binary_test() {
+        let path = "./package.json";
+        let mime = mime_guess::MimeGuess::from_path(path)
+            .first_or_octet_stream()
+            .to_string();
+        assert_eq!("application/json", mime);
+    }
+
+    #[tokio::test]
+    async fn delete_file_test() {
+        let dir = temp_dir("delete");
+        std::fs::write(format!("{}file.txt", dir), "data").unwrap();
+        let shared_data = writable_data(dir.clone());
+
+        let response = send(&shared_data, delete("/file.txt")).await;
+        assert_eq!(204, response.status());
+        assert!(!std::path::Path::new(&format!("{}file.txt", dir)).exists());
+
+        let response = send(&shared_data, delete("/file.txt")).await;
+        assert_eq!(404, response.status());
+    }
+
+    #[tokio::test]
+    async fn delete_directory_test() {
+        let dir = temp_dir("delete-dir");
+        std::fs::create_dir(format!("{}sub", dir)).unwrap();
+        let shared_data = writable_data(dir.clone());
+        let response = send(&shared_data, delete("/sub")).await;
+        assert_eq!(403, response.status());
+
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            auth: shared_data.auth.clone(),
+            allow_delete: true,
+            allow_delete_dirs: true,
+            ..Default::default()
+        });
+        let response = send(&shared_data, delete("/sub")).await;
+        assert_eq!(204, response.status());
+        assert!(!std::path::Path::new(&format!("{}sub", dir)).exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn delete_policy_test() {
+        let dir = temp_dir("delete-policy");
+        let outside = temp_dir("delete-policy-outside");
+        std::fs::write(format!("{}kept.txt", outside), "data").unwrap();
+        std::fs::write(format!("{}config.env", dir), "secret").unwrap();
+        std::os::unix::fs::symlink(&outside, format!("{}escape", dir)).unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            allow_delete: true,
+            allow_delete_dirs: true,
+            follow_symlinks: FollowSymlinks::Safe,
+            forbid_ext: vec![String::from("env")],
+            ..Default::default()
+        });
+        for path in ["/escape/kept.txt", "/escape", "/config.env"] {
+            let response = send(&shared_data, delete(path)).await;
+            assert_eq!(403, response.status(), "{}", path);
+        }
+        assert!(std::path::Path::new(&format!("{}kept.txt", outside)).exists());
+        assert!(std::path::Path::new(&format!("{}config.env", dir)).exists());
+    }
+
+    #[tokio::test]
+    async fn delete_auth_and_method_test() {
+        let dir = temp_dir("delete-auth");
+        std::fs::write(format!("{}file.txt", dir), "data").unwrap();
+        let shared_data = writable_data(dir.clone());
+        let request = Request::delete("/file.txt").body(Body::empty()).unwrap();
+        assert_eq!(401, send(&shared_data, request).await.status());
+        assert_eq!(
+            403,
+            send(&shared_data, delete("/../file.txt")).await.status()
+        );
+
+        let read_only = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            ..Default::default()
+        });
+        let response = send(&read_only, delete("/file.txt")).await;
+        assert_eq!(405, response.status());
+        assert_eq!("GET, HEAD, OPTIONS", response.headers()[ALLOW]);
+        assert!(std::path::Path::new(&format!("{}file.txt", dir)).exists());
+    }
+
+    #[tokio::test]
+    async fn audit_log_test() {
+        let dir = temp_dir("audit");
+        let audit_log_path = format!("{}../serve-dir-audit-{}.log", dir, std::process::id());
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            auth_user: Some(String::from("user")),
+            allow_put: true,
+            allow_delete: true,
+            audit_logger: Some(Arc::new(Mutex::new(
+                AuditLogger::open(&audit_log_path).unwrap(),
+            ))),
+            ..Default::default()
+        });
+
+        send(&shared_data, put("/file.txt", "12345")).await;
+        send(&shared_data, put("/file.txt", "123")).await;
+        send(&shared_data, delete("/file.txt")).await;
+        send(&shared_data, delete("/file.txt")).await;
+
+        let log = std::fs::read_to_string(&audit_log_path).unwrap();
+        std::fs::remove_file(&audit_log_path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(4, lines.len());
+        assert!(lines.iter().all(|line| line.contains(r#""user":"user""#)));
+        assert!(lines[0].contains(r#""operation":"PUT","path":"/file.txt","size_before":null,"size_after":5,"success":true"#));
+        assert!(lines[1].contains(r#""size_before":5,"size_after":3,"success":true"#));
+        assert!(lines[2].contains(r#""operation":"DELETE","path":"/file.txt","size_before":3,"size_after":null,"success":true"#));
+        assert!(lines[3].contains(r#""size_before":null,"size_after":null,"success":false"#));
+
+        // The user is the one the request got in as, if any.
+        std::fs::create_dir(format!("{}private", dir)).unwrap();
+        std::fs::write(
+            format!("{}private/{}", dir, dir_config::FILE_NAME),
+            "auth = \"editor:secret\"\n",
+        )
+        .unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            dir_config: true,
+            allow_put: true,
+            audit_logger: Some(Arc::new(Mutex::new(
+                AuditLogger::open(&audit_log_path).unwrap(),
+            ))),
+            ..Default::default()
+        });
+        let request = Request::put("/private/file.txt")
+            .header(
+                AUTHORIZATION,
+                format!("Basic {}", base64::encode(b"editor:secret")),
+            )
+            .body(Body::from("x"))
+            .unwrap();
+        assert_eq!(201, send(&shared_data, request).await.status());
+        let request = Request::put("/open.txt").body(Body::from("x")).unwrap();
+        assert_eq!(201, send(&shared_data, request).await.status());
+        let log = std::fs::read_to_string(&audit_log_path).unwrap();
+        std::fs::remove_file(&audit_log_path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert!(
+            lines[0].contains(r#""user":"editor","operation":"PUT","path":"/private/file.txt""#)
+        );
+        assert!(lines[1].contains(r#""user":null,"operation":"PUT","path":"/open.txt""#));
+    }
+
+    #[tokio::test]
+    async fn upload_progress_test() {
+        let dir = temp_dir("upload-progress");
+        let shared_data = writable_data(dir.clone());
+        let (mut body_sender, body) = Body::channel();
+        let request = Request::put("/upload.bin")
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .header(upload::UPLOAD_ID_HEADER, "abc")
+            .header(hyper::header::CONTENT_LENGTH, "8")
+            .body(body)
+            .unwrap();
+        let upload = tokio::spawn({
+            let shared_data = shared_data.clone();
+            async move { send(&shared_data, request).await }
+        });
+
+        body_sender.send_data("1234".into()).await.unwrap();
+        while shared_data.uploads.subscribe("abc").is_none() {
+            tokio::task::yield_now().await;
+        }
+        let progress_request = Request::get("/_upload-progress/abc")
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .body(Body::empty())
+            .unwrap();
+        let progress = send(&shared_data, progress_request).await;
+        assert_eq!("text/event-stream", progress.headers()[CONTENT_TYPE]);
+
+        body_sender.send_data("5678".into()).await.unwrap();
+        drop(body_sender);
+        assert_eq!(201, upload.await.unwrap().status());
+
+        let events = hyper::body::to_bytes(progress.into_body()).await.unwrap();
+        let events = String::from_utf8(events.to_vec()).unwrap();
+        assert!(events.starts_with("data: {\"uploaded\":"));
+        assert!(events.contains("data: {\"uploaded\":8,\"total\":8,\"percent\":100,"));
+        assert!(events.contains("\"bytes_per_second\":"));
+        assert!(shared_data.uploads.subscribe("abc").is_none());
+    }
+
+    fn upload(path: &str, files: &[(&str, &str)]) -> Request<Body> {
+        let mut body = String::new();
+        for (name, data) in files {
+            body.push_str(&format!(
+                "--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\r\n{}\r\n",
+                name, data
+            ));
+        }
+        body.push_str("--XYZ--\r\n");
+        Request::post(path)
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .header(CONTENT_TYPE, "multipart/form-data; boundary=XYZ")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn uploadable_data(directory_path: String) -> ServeConfig {
+        ServeConfig {
+            directory_path,
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            allow_upload: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_test() {
+        let dir = temp_dir("upload");
+        std::fs::create_dir_all(format!("{}sub", dir)).unwrap();
+        let shared_data = Arc::new(uploadable_data(dir.clone()));
+
+        let response = send(&shared_data, upload("/", &[("../a.txt", "hello")])).await;
+        assert_eq!(201, response.status());
+        assert_eq!("application/json", response.headers()[CONTENT_TYPE]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(r#"{"files":[{"name":"a.txt","size":5}]}"#, body);
+        assert_eq!(
+            "hello",
+            std::fs::read_to_string(format!("{}a.txt", dir)).unwrap()
+        );
+
+        let files = [("b.txt", "bb"), ("c.bin", "line\r\nbreak")];
+        let response = send(&shared_data, upload("/sub", &files)).await;
+        assert_eq!(201, response.status());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            r#"{"files":[{"name":"b.txt","size":2},{"name":"c.bin","size":11}]}"#,
+            body
+        );
+        assert_eq!(
+            "line\r\nbreak",
+            std::fs::read_to_string(format!("{}sub/c.bin", dir)).unwrap()
+        );
+
+        assert_eq!(
+            400,
+            send(&shared_data, upload("/", &[(".env", "x")]))
+                .await
+                .status()
+        );
+        assert_eq!(
+            404,
+            send(&shared_data, upload("/missing/", &[("d", "x")]))
+                .await
+                .status()
+        );
+        let mut request = upload("/", &[("d", "x")]);
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert_eq!(415, send(&shared_data, request).await.status());
+        let unauthorized = Request::post("/").body(Body::empty()).unwrap();
+        assert_eq!(401, send(&shared_data, unauthorized).await.status());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn upload_policy_test() {
+        let dir = temp_dir("upload-policy");
+        let outside = temp_dir("upload-policy-outside");
+        std::os::unix::fs::symlink(&outside, format!("{}escape", dir)).unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            follow_symlinks: FollowSymlinks::Never,
+            forbid_ext: vec![String::from("php")],
+            ..uploadable_data(dir.clone())
+        });
+        let response = send(&shared_data, upload("/escape/", &[("a.txt", "x")])).await;
+        assert_eq!(403, response.status());
+        assert!(std::fs::read_dir(&outside).unwrap().next().is_none());
+        let files = [("a.txt", "x"), ("shell.php", "x")];
+        assert_eq!(403, send(&shared_data, upload("/", &files)).await.status());
+        assert!(!std::path::Path::new(&format!("{}a.txt", dir)).exists());
+        assert_eq!(
+            201,
+            send(&shared_data, upload("/", &files[..1])).await.status()
+        );
+    }
+
+    #[tokio::test]
+    async fn upload_overwrite_test() {
+        let dir = temp_dir("upload-overwrite");
+        std::fs::write(format!("{}taken.txt", dir), "old").unwrap();
+        let shared_data = Arc::new(uploadable_data(dir.clone()));
+
+        let files = [("new.txt", "new"), ("taken.txt", "replaced")];
+        let response = send(&shared_data, upload("/", &files)).await;
+        assert_eq!(409, response.status());
+        assert_eq!(
+            "old",
+            std::fs::read_to_string(format!("{}taken.txt", dir)).unwrap()
+        );
+        assert!(!std::path::Path::new(&format!("{}new.txt", dir)).exists());
+
+        let shared_data = Arc::new(ServeConfig {
+            allow_overwrite: true,
+            ..uploadable_data(dir.clone())
+        });
+        assert_eq!(201, send(&shared_data, upload("/", &files)).await.status());
+        assert_eq!(
+            "replaced",
+            std::fs::read_to_string(format!("{}taken.txt", dir)).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn max_body_size_test() {
+        let dir = temp_dir("max-body-size");
+        let shared_data = Arc::new(ServeConfig {
+            max_body_size: Some(64),
+            allow_put: true,
+            ..uploadable_data(dir.clone())
+        });
+        let big = "x".repeat(100);
+        let response = send(&shared_data, upload("/", &[("big.txt", &big)])).await;
+        assert_eq!(413, response.status());
+        assert!(!std::path::Path::new(&format!("{}big.txt", dir)).exists());
+
+        // Without a Content-Length the limit is enforced while reading.
+        let (mut body_sender, body) = Body::channel();
+        let request = Request::put("/streamed.txt")
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .body(body)
+            .unwrap();
+        let streamed = tokio::spawn({
+            let shared_data = shared_data.clone();
+            async move { send(&shared_data, request).await }
+        });
+        body_sender.send_data(big.into()).await.unwrap();
+        assert_eq!(413, streamed.await.unwrap().status());
+        assert!(!std::path::Path::new(&format!("{}streamed.txt", dir)).exists());
+        assert_eq!(
+            201,
+            send(&shared_data, put("/small.txt", "ok")).await.status()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn streamed_put_test() {
+        let dir = temp_dir("streamed-put");
+        let path = format!("{}big.bin", dir);
+        std::fs::write(&path, "old").unwrap();
+        let shared_data = writable_data(dir.clone());
+        let resident = || {
+            let status = std::fs::read_to_string("/proc/self/status").unwrap();
+            let kb = status
+                .lines()
+                .find_map(|line| line.strip_prefix("VmRSS:"))
+                .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+                .unwrap();
+            kb * 1024
+        };
+        let streamed_put = || {
+            let (body_sender, body) = Body::channel();
+            let request = Request::put("/big.bin")
+                .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+                .body(body)
+                .unwrap();
+            let response = tokio::spawn({
+                let shared_data = shared_data.clone();
+                async move { send(&shared_data, request).await }
+            });
+            (body_sender, response)
+        };
+        let entries = || std::fs::read_dir(&dir).unwrap().count();
+
+        // An upload that breaks off leaves the existing file alone.
+        let (mut body_sender, response) = streamed_put();
+        body_sender.send_data("partial".into()).await.unwrap();
+        body_sender.abort();
+        assert_eq!(500, response.await.unwrap().status());
+        assert_eq!("old", std::fs::read_to_string(&path).unwrap());
+        assert_eq!(1, entries());
+
+        const MB: usize = 1 << 20;
+        let chunk = hyper::body::Bytes::from(vec![7u8; MB]);
+        let (mut body_sender, response) = streamed_put();
+        let before = resident();
+        let mut peak = before;
+        for i in 0..500 {
+            body_sender.send_data(chunk.clone()).await.unwrap();
+            if i % 25 == 0 {
+                peak = peak.max(resident());
+            }
+            if i == 250 {
+                assert_eq!("old", std::fs::read_to_string(&path).unwrap());
+            }
+        }
+        drop(body_sender);
+        assert_eq!(204, response.await.unwrap().status());
+        assert_eq!(500 * MB as u64, std::fs::metadata(&path).unwrap().len());
+        assert_eq!(1, entries());
+        let growth = peak.saturating_sub(before);
+        assert!(growth < 100 * MB as u64, "grew by {} bytes", growth);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn dav(method: &str, path: &str, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn webdav_propfind_test() {
+        let dir = temp_dir("webdav-propfind");
+        std::fs::create_dir_all(format!("{}sub dir", dir)).unwrap();
+        std::fs::write(format!("{}a.txt", dir), "hello").unwrap();
+        std::fs::write(format!("{}.hidden", dir), "secret").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            webdav: true,
+            ..Default::default()
+        });
+
+        let response = send(&shared_data, dav("PROPFIND", "/", &[("depth", "1")])).await;
+        assert_eq!(207, response.status());
+        assert_eq!(
+            "application/xml; charset=utf-8",
+            response.headers()[CONTENT_TYPE]
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let xml = String::from_utf8(body.to_vec()).unwrap();
+        let hrefs: Vec<&str> = xml
+            .split("<D:href>")
+            .skip(1)
+            .map(|rest| rest.split_once("</D:href>").unwrap().0)
+            .collect();
+        assert_eq!(vec!["/", "/a.txt", "/sub%20dir/"], hrefs);
+        assert!(xml.contains("<D:displayname>a.txt</D:displayname><D:resourcetype/><D:getcontentlength>5</D:getcontentlength>"));
+        assert!(xml.contains("<D:displayname>sub dir</D:displayname><D:resourcetype><D:collection/></D:resourcetype>"));
+
+        let response = send(&shared_data, dav("PROPFIND", "/a.txt", &[("depth", "0")])).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let xml = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(1, xml.matches("<D:response>").count());
+        assert!(xml.contains("<D:getetag>&quot;"));
+        assert!(xml.contains("<D:getlastmodified>"));
+
+        let response = send(&shared_data, dav("PROPFIND", "/", &[])).await;
+        assert_eq!(403, response.status());
+        let response = send(&shared_data, dav("PROPFIND", "/missing", &[("depth", "0")])).await;
+        assert_eq!(404, response.status());
+
+        let response = send(&shared_data, dav("OPTIONS", "/", &[])).await;
+        assert_eq!("1, 2", response.headers()["dav"]);
+        assert!(response.headers()[ALLOW]
+            .to_str()
+            .unwrap()
+            .contains("PROPFIND"));
+    }
+
+    #[tokio::test]
+    async fn webdav_write_test() {
+        let dir = temp_dir("webdav-write");
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            webdav: true,
+            ..Default::default()
+        });
+        let status = |request| {
+            let shared_data = shared_data.clone();
+            async move { send(&shared_data, request).await.status() }
+        };
+
+        assert_eq!(201, status(dav("MKCOL", "/docs", &[])).await);
+        assert_eq!(405, status(dav("MKCOL", "/docs", &[])).await);
+        assert_eq!(409, status(dav("MKCOL", "/missing/docs", &[])).await);
+        std::fs::write(format!("{}docs/a.txt", dir), "a").unwrap();
+
+        let copy = dav("COPY", "/docs", &[("destination", "http://localhost/copy")]);
+        assert_eq!(201, status(copy).await);
+        assert_eq!(
+            "a",
+            std::fs::read_to_string(format!("{}copy/a.txt", dir)).unwrap()
+        );
+        let copy = dav(
+            "COPY",
+            "/docs/a.txt",
+            &[("destination", "/copy/a.txt"), ("overwrite", "F")],
+        );
+        assert_eq!(412, status(copy).await);
+        let copy = dav("COPY", "/docs", &[("destination", "/docs/inner")]);
+        assert_eq!(403, status(copy).await);
+
+        let moved = dav("MOVE", "/copy/a.txt", &[("destination", "/docs/a.txt")]);
+        assert_eq!(204, status(moved).await);
+        assert!(!std::path::Path::new(&format!("{}copy/a.txt", dir)).exists());
+
+        let response = send(&shared_data, dav("LOCK", "/docs/a.txt", &[])).await;
+        assert_eq!(200, response.status());
+        let token = response.headers()["lock-token"].to_str().unwrap();
+        assert!(token.starts_with("<opaquelocktoken:"));
+        assert_eq!(204, status(dav("UNLOCK", "/docs/a.txt", &[])).await);
+
+        assert_eq!(204, status(dav("DELETE", "/docs", &[])).await);
+        assert!(!std::path::Path::new(&format!("{}docs", dir)).exists());
+        assert_eq!(
+            401,
+            status(
+                Request::builder()
+                    .method("PROPFIND")
+                    .uri("/")
+                    .body(Body::empty())
+                    .unwrap()
+            )
+            .await
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn webdav_policy_test() {
+        let dir = temp_dir("webdav-policy");
+        let outside = temp_dir("webdav-policy-outside");
+        std::fs::write(format!("{}secret.txt", outside), "secret").unwrap();
+        std::fs::write(format!("{}public.txt", dir), "public").unwrap();
+        std::fs::write(format!("{}keys.env", dir), "secret").unwrap();
+        std::fs::create_dir_all(format!("{}docs", dir)).unwrap();
+        std::fs::write(format!("{}docs/a.txt", dir), "a").unwrap();
+        std::os::unix::fs::symlink(&outside, format!("{}docs/escape", dir)).unwrap();
+        std::os::unix::fs::symlink(&outside, format!("{}escape", dir)).unwrap();
+        std::fs::create_dir_all(format!("{}private", dir)).unwrap();
+        std::fs::write(
+            format!("{}private/{}", dir, dir_config::FILE_NAME),
+            "auth = \"other:secret\"\n",
+        )
+        .unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            webdav: true,
+            dir_config: true,
+            forbid_ext: vec![String::from("env")],
+            follow_symlinks: FollowSymlinks::Safe,
+            ..Default::default()
+        });
+        let status = |request| {
+            let shared_data = shared_data.clone();
+            async move { send(&shared_data, request).await.status() }
+        };
+
+        let response = send(&shared_data, dav("PROPFIND", "/", &[("depth", "1")])).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let xml = String::from_utf8(body.to_vec()).unwrap();
+        let hrefs: Vec<&str> = xml
+            .split("<D:href>")
+            .skip(1)
+            .map(|rest| rest.split_once("</D:href>").unwrap().0)
+            .collect();
+        assert_eq!(vec!["/", "/docs/", "/public.txt"], hrefs);
+        let propfind = dav("PROPFIND", "/keys.env", &[("depth", "0")]);
+        assert_eq!(403, status(propfind).await);
+        let propfind = dav("PROPFIND", "/escape/", &[("depth", "1")]);
+        assert_eq!(403, status(propfind).await);
+
+        let copy = dav("COPY", "/docs", &[("destination", "/copy")]);
+        assert_eq!(201, status(copy).await);
+        assert!(std::path::Path::new(&format!("{}copy/a.txt", dir)).exists());
+        assert!(!std::path::Path::new(&format!("{}copy/escape", dir)).exists());
+        let copy = dav("COPY", "/public.txt", &[("destination", "/private/p.txt")]);
+        assert_eq!(403, status(copy).await);
+        let moved = dav("MOVE", "/public.txt", &[("destination", "/public.env")]);
+        assert_eq!(403, status(moved).await);
+        let moved = dav("MOVE", "/public.txt", &[("destination", "/escape/p.txt")]);
+        assert_eq!(403, status(moved).await);
+        assert!(std::path::Path::new(&format!("{}public.txt", dir)).exists());
+    }
+
+    #[tokio::test]
+    async fn put_checksum_test() {
+        let dir = temp_dir("put-checksum");
+        let shared_data = writable_data(dir.clone());
+        let put_with = |header: &'static str, value: &'static str| {
+            let mut request = put("/file.txt", "hello");
+            request
+                .headers_mut()
+                .insert(header, hyper::header::HeaderValue::from_static(value));
+            request
+        };
+
+        let response = send(
+            &shared_data,
+            put_with(digest::CONTENT_MD5, "AAAAAAAAAAAAAAAAAAAAAA=="),
+        )
+        .await;
+        assert_eq!(400, response.status());
+        assert!(!std::path::Path::new(&format!("{}file.txt", dir)).exists());
+
+        let request = put_with(
+            digest::DIGEST,
+            "SHA-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=",
+        );
+        assert_eq!(201, send(&shared_data, request).await.status());
+        assert_eq!(
+            204,
+            send(&shared_data, put("/file.txt", "no checksum"))
+                .await
+                .status()
+        );
+    }
+
+    #[tokio::test]
+    async fn error_body_test() {
+        let dir = temp_dir("error-body");
+        std::fs::write(format!("{}403.html", dir), "<h1>forbidden</h1>").unwrap();
+        std::fs::write(format!("{}500.txt", dir), "custom 500").unwrap();
+        let error_bodies = HashMap::from([
+            (403, PathBuf::from(format!("{}403.html", dir))),
+            (500, PathBuf::from(format!("{}500.txt", dir))),
+            (405, PathBuf::from(format!("{}missing.html", dir))),
+        ]);
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            error_bodies,
+            ..Default::default()
+        });
+        let body = |response: Response<Body>| async {
+            hyper::body::to_bytes(response.into_body()).await.unwrap()
+        };
+
+        let request = Request::get("/.secret").body(Body::empty()).unwrap();
+        let response = send(&shared_data, request).await;
+        assert_eq!(403, response.status());
+        assert_eq!("text/html", response.headers()[CONTENT_TYPE]);
+        assert_eq!("<h1>forbidden</h1>", body(response).await);
+
+        let response = error_response(&shared_data, Response::builder(), 500, "default").await;
+        assert_eq!("custom 500", body(response).await);
+
+        let request = Request::delete("/file").body(Body::empty()).unwrap();
+        let response = send(&shared_data, request).await;
+        assert_eq!(405, response.status());
+        assert_eq!("405 Method Not Allowed", body(response).await);
+
+        let response = error_response(&shared_data, Response::builder(), 502, "default").await;
+        assert_eq!("default", body(response).await);
+    }
+
+    #[tokio::test]
+    async fn not_found_prefix_test() {
+        let dir = temp_dir("not-found-prefix");
+        for (name, page) in [
+            ("docs.html", "docs"),
+            ("api.html", "api v2"),
+            ("global.html", "global"),
+        ] {
+            std::fs::write(format!("{}{}", dir, name), page).unwrap();
+        }
+        let mut not_found_prefixes: Vec<(String, PathBuf)> = [
+            "/docs:{}docs.html",
+            "/docs/api/v2/:{}api.html",
+            "/gone:{}missing.html",
+        ]
+        .iter()
+        .map(|arg| parse_not_found_prefix(&arg.replace("{}", &dir)).unwrap())
+        .collect();
+        not_found_prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        let not_found = |global: Option<&str>| {
+            Arc::new(ServeConfig {
+                directory_path: dir.clone(),
+                not_found_file_path: global.map(|name| format!("{}{}", dir, name)),
+                not_found_prefixes: not_found_prefixes.clone(),
+                ..Default::default()
+            })
+        };
+        let body = |shared_data: Arc<ServeConfig>, path: &'static str| async move {
+            let request = Request::get(path).body(Body::empty()).unwrap();
+            let response = send(&shared_data, request).await;
+            assert_eq!(404, response.status());
+            hyper::body::to_bytes(response.into_body()).await.unwrap()
+        };
+
+        let shared_data = not_found(Some("global.html"));
+        assert_eq!("docs", body(shared_data.clone(), "/docs").await);
+        assert_eq!("docs", body(shared_data.clone(), "/docs/api/v1/x").await);
+        assert_eq!("api v2", body(shared_data.clone(), "/docs/api/v2/x").await);
+        // Prefixes match whole segments.
+        assert_eq!("global", body(shared_data.clone(), "/docsy").await);
+        assert_eq!("global", body(shared_data.clone(), "/other").await);
+        // An unreadable prefix page falls back to the global one, then to
+        // the built-in text.
+        assert_eq!("global", body(shared_data, "/gone/x").await);
+        assert_eq!("404 Not Found", body(not_found(None), "/gone/x").await);
+        assert_eq!("404 Not Found", body(not_found(None), "/other").await);
+
+        assert_eq!(None, parse_not_found_prefix("./404.html"));
+        assert_eq!(None, parse_not_found_prefix("/srv/404.html"));
+    }
+
+    #[tokio::test]
+    async fn logged_method_test() {
+        logger::test::captured_logs();
+        let dir = temp_dir("logged-method");
+        std::fs::write(format!("{}logged-method-test.txt", dir), "head").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            ..Default::default()
+        });
+        for method in [Method::OPTIONS, Method::POST, Method::DELETE, Method::GET] {
+            let request = Request::builder()
+                .method(method)
+                .uri("/logged-method-test")
+                .body(Body::empty())
+                .unwrap();
+            send(&shared_data, request).await;
+        }
+        let head = Request::head("/logged-method-test.txt")
+            .body(Body::empty())
+            .unwrap();
+        send(&shared_data, head).await;
+        let logs = logger::test::captured_logs();
+        let line = |method: &str| {
+            logs.lines()
+                .find(|line| line.contains(&format!("] [{}] /logged-method-test", method)))
+                .map(|line| line.split_once(": ").unwrap().1.to_string())
+        };
+        assert_eq!(
+            Some("[200] [OPTIONS] /logged-method-test"),
+            line("OPTIONS").as_deref()
+        );
+        assert_eq!(
+            Some("[405] [POST] /logged-method-test method not allowed"),
+            line("POST").as_deref()
+        );
+        assert_eq!(
+            Some("[405] [DELETE] /logged-method-test method not allowed"),
+            line("DELETE").as_deref()
+        );
+        assert_eq!(
+            Some("[404] [GET] /logged-method-test requested address not found"),
+            line("GET").as_deref()
+        );
+        // HEAD is served by the GET code, but logged as what it is.
+        assert_eq!(
+            Some("[200] [HEAD] /logged-method-test.txt requested file path"),
+            line("HEAD").as_deref()
+        );
+    }
+
+    #[tokio::test]
+    async fn stats_test() {
+        let dir = temp_dir("stats");
+        std::fs::write(format!("{}index.html", dir), "hello").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            auth: Some(String::from("Basic dXNlcjpwYXNz")),
+            stats_path: String::from("/_stats"),
+            archive: Some(ArchiveConfig {
+                endpoint: String::from("/_archive"),
+                max_files: 10,
+            }),
+            ..Default::default()
+        });
+        let request = |method: &str, path: &str, authorized: bool| {
+            let mut builder = Request::builder().method(method).uri(path);
+            if authorized {
+                builder = builder.header(AUTHORIZATION, "Basic dXNlcjpwYXNz");
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+        // Bytes are counted as the body is written, so each one is read.
+        let mut sent = 0;
+        for (method, path, authorized) in [
+            ("GET", "/", true),
+            ("GET", "/", true),
+            ("GET", "/missing", true),
+            ("OPTIONS", "/", false),
+            ("GET", "/", false),
+            ("GET", "/_archive?path=/&format=zip", true),
+        ] {
+            let response = send(&shared_data, request(method, path, authorized)).await;
+            sent += hyper::body::to_bytes(response.into_body())
+                .await
+                .unwrap()
+                .len();
+        }
+
+        let response = send(&shared_data, request("GET", "/_stats", false)).await;
+        assert_eq!(200, response.status());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#""total_requests":6,"#), "{}", body);
+        // The streamed archive has no size up front but still counts.
+        assert!(sent > 100);
+        assert!(
+            body.contains(&format!(r#""total_bytes":{},"#, sent)),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains(r#""status_codes":{"200":4,"401":1,"404":1}"#),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains(r#""methods":{"GET":5,"OPTIONS":1}"#),
+            "{}",
+            body
+        );
+
+        let response = send(&shared_data, request("GET", "/_stats?reset", false)).await;
+        assert_eq!(401, response.status());
+        let response = send(&shared_data, request("GET", "/_stats?reset", true)).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains(r#""total_requests":0,"#));
+
+        // Off unless asked for, so a real file by that name is served.
+        let dir = temp_dir("stats-off");
+        std::fs::write(format!("{}_stats", dir), "mine").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            ..Default::default()
+        });
+        let response = send(&shared_data, request("GET", "/_stats", false)).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("mine", body);
+    }
+
+    #[tokio::test]
+    async fn query_string_test() {
+        let dir = temp_dir("query-string");
+        std::fs::write(format!("{}index.html", dir), "hello").unwrap();
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let forbid = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            forbid_query: true,
+            ..Default::default()
+        });
+        assert_eq!(400, send(&forbid, get("/?v=1")).await.status());
+        assert_eq!(400, send(&forbid, get("/?")).await.status());
+        assert_eq!(200, send(&forbid, get("/")).await.status());
+
+        logger::test::captured_logs();
+        let warn = Arc::new(ServeConfig {
+            directory_path: dir,
+            warn_query: true,
+            ..Default::default()
+        });
+        assert_eq!(200, send(&warn, get("/?warn-query-test")).await.status());
+        assert_eq!(200, send(&warn, get("/")).await.status());
+        let logs = logger::test::captured_logs();
+        assert!(logs.contains("[GET] / unexpected query string warn-query-test"));
+    }
+
+    #[tokio::test]
+    async fn csp_nonce_test() {
+        let dir = temp_dir("csp-nonce");
+        std::fs::write(
+            format!("{}index.html", dir),
+            "<html><head><title>t</title></head><body></body></html>",
+        )
+        .unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            headers: vec![(
+                String::from("content-security-policy"),
+                String::from("default-src 'self'"),
+            )],
+            csp_nonce: true,
+            ..Default::default()
+        });
+        let mut nonces = Vec::new();
+        for _ in 0..2 {
+            let response = send(&shared_data, Request::get("/").body(Body::empty()).unwrap()).await;
+            assert_eq!(200, response.status());
+            assert_eq!("no-store", response.headers()[CACHE_CONTROL]);
+            assert!(!response.headers().contains_key(ETAG));
+            let policy = response.headers()[CONTENT_SECURITY_POLICY]
+                .to_str()
+                .unwrap();
+            let nonce = policy
+                .strip_prefix("default-src 'self'; script-src 'nonce-")
+                .and_then(|rest| rest.strip_suffix('\''))
+                .unwrap()
+                .to_string();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            let meta = format!("<meta name=\"csp-nonce\" content=\"{}\"></head>", nonce);
+            assert!(String::from_utf8_lossy(&body).contains(&meta));
+            nonces.push(nonce);
+        }
+        assert_ne!(nonces[0], nonces[1]);
+    }
+
+    #[tokio::test]
+    async fn client_ip_log_test() {
+        logger::test::captured_logs();
+        let dir = temp_dir("client-ip-log");
+        std::fs::write(format!("{}client-ip-log.txt", dir), "hi").unwrap();
+        let behind_proxy = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            proxy: Some(proxy::ProxyConfig::default()),
+            ..Default::default()
+        });
+        let direct = Arc::new(ServeConfig {
+            directory_path: dir,
+            ..Default::default()
+        });
+        for (shared_data, path) in [
+            (direct, "/client-ip-log.txt"),
+            (behind_proxy, "/client-ip-log.txt?proxied"),
+        ] {
+            let mut request = Request::get(path)
+                .header("x-forwarded-for", "203.0.113.9")
+                .body(Body::empty())
+                .unwrap();
+            request
+                .extensions_mut()
+                .insert(SocketAddr::from(([192, 0, 2, 1], 40000)));
+            timed_request_handler(request, shared_data).await.unwrap();
+        }
+
+        let logs = logger::test::captured_logs();
+        let line = |path: &str| {
+            logs.lines()
+                .find(|line| line.contains(&format!("[GET] {} ", path)) && line.contains("[200]"))
+                .map(|line| line.split_once(": ").unwrap().1.to_string())
+                .unwrap()
+        };
+        assert!(line("/client-ip-log.txt").starts_with("192.0.2.1 [200] [GET] "));
+        assert!(line("/client-ip-log.txt?proxied").starts_with("203.0.113.9 [200] [GET] "));
+    }
+
+    #[tokio::test]
+    async fn slow_log_threshold_test() {
+        struct SlowFileSystem;
+
+        impl fs::FileSystem for SlowFileSystem {
+            fn read_file<'a>(&'a self, path: &'a Path) -> fs::FsFuture<'a, Vec<u8>> {
+                Box::pin(async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                    fs::LocalFileSystem.read_file(path).await
+                })
+            }
+
+            fn metadata<'a>(&'a self, path: &'a Path) -> fs::FsFuture<'a, fs::Metadata> {
+                fs::LocalFileSystem.metadata(path)
+            }
+
+            fn read_dir<'a>(&'a self, path: &'a Path) -> fs::FsFuture<'a, Vec<fs::DirEntry>> {
+                fs::LocalFileSystem.read_dir(path)
+            }
+
+            fn is_file(&self, path: &Path) -> bool {
+                fs::LocalFileSystem.is_file(path)
+            }
+        }
+
+        let dir = temp_dir("slow-log");
+        std::fs::write(format!("{}slow-log-test.txt", dir), "slow").unwrap();
+        std::fs::write(format!("{}fast-log-test.txt", dir), "fast").unwrap();
+        logger::test::captured_logs();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            fs: Fs::new(SlowFileSystem),
+            slow_log_threshold: Some(std::time::Duration::from_millis(10)),
+            ..Default::default()
+        });
+        let request = Request::get("/slow-log-test.txt")
+            .body(Body::empty())
+            .unwrap();
+        let response = timed_request_handler(request, shared_data).await.unwrap();
+        assert_eq!(200, response.status());
+        let fast_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            slow_log_threshold: Some(std::time::Duration::from_secs(10)),
+            ..Default::default()
+        });
+        let request = Request::get("/fast-log-test.txt")
+            .body(Body::empty())
+            .unwrap();
+        timed_request_handler(request, fast_data).await.unwrap();
+
+        let logs = logger::test::captured_logs();
+        let lines = |name: &str| {
+            logs.lines()
+                .filter(|line| line.contains(name) && line.contains("[200]"))
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        let slow = lines("/slow-log-test.txt");
+        assert_eq!(2, slow.len(), "{:?}", slow);
+        assert!(slow[0].contains("[200] [GET] /slow-log-test.txt requested file path"));
+        assert!(slow[1].contains("[200] [GET] /slow-log-test.txt slower than 10ms"));
+        for line in &slow {
+            let micros: u128 = line.rsplit("duration_us=").next().unwrap().parse().unwrap();
+            assert!(micros >= 20_000, "{}", line);
+        }
+        let fast = lines("/fast-log-test.txt");
+        assert_eq!(1, fast.len(), "{:?}", fast);
+        assert!(fast[0].contains(" duration_us="));
+    }
+
+    #[tokio::test]
+    async fn server_name_test() {
+        let dir = temp_dir("server-name");
+        let server_of = |server_name| {
+            let shared_data = Arc::new(ServeConfig {
+                directory_path: dir.clone(),
+                headers: vec![(String::from("server"), String::from("from-header"))],
+                server_name,
+                ..Default::default()
+            });
+            async move {
+                let request = Request::get("/missing").body(Body::empty()).unwrap();
+                let response = send(&shared_data, request).await;
+                response.headers().get(SERVER).cloned()
+            }
+        };
+        assert_eq!(
+            Some(HeaderValue::from_static("from-header")),
+            server_of(ServerNameConfig::Unchanged).await
+        );
+        assert_eq!(
+            Some(HeaderValue::from_static("MyServer/1.0")),
+            server_of(ServerNameConfig::Custom(HeaderValue::from_static(
+                "MyServer/1.0"
+            )))
+            .await
+        );
+        assert_eq!(None, server_of(ServerNameConfig::Hidden).await);
+    }
+
+    #[tokio::test]
+    async fn weak_etag_test() {
+        let dir = temp_dir("weak-etag");
+        std::fs::write(format!("{}app.js", dir), "let a = 1;").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            etag_mode: EtagMode::Hash,
+            weak_etag: true,
+            ..Default::default()
+        });
+        let get = |if_none_match: Option<&str>| {
+            let mut request = Request::get("/app.js");
+            if let Some(if_none_match) = if_none_match {
+                request = request.header(IF_NONE_MATCH, if_none_match);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = send(&shared_data, get(None)).await;
+        assert_eq!(200, response.status());
+        let etag = response.headers()[ETAG].to_str().unwrap().to_string();
+        let opaque = etag.strip_prefix("W/\"").unwrap().trim_end_matches('"');
+        for if_none_match in [
+            etag.clone(),
+            format!("\"{}\"", opaque),
+            format!("W/\"{}-gz\"", opaque),
+            format!("\"{}-br\"", opaque),
+        ] {
+            let response = send(&shared_data, get(Some(&if_none_match))).await;
+            assert_eq!(304, response.status(), "{}", if_none_match);
+            assert_eq!(etag, response.headers()[ETAG]);
+        }
+        let response = send(&shared_data, get(Some("W/\"other-gz\""))).await;
+        assert_eq!(200, response.status());
+    }
+
+    #[tokio::test]
+    async fn transform_test() {
+        let dir = temp_dir("transform");
+        let page = "<a href=\"{{BASE_URL}}/docs/\">{{BASE_URL}}</a>";
+        std::fs::write(format!("{}index.html", dir), page).unwrap();
+        std::fs::write(format!("{}notes.txt", dir), "{{BASE_URL}}").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            transforms: vec![
+                TransformRule::parse("text/html:{{BASE_URL}}:https://example.com").unwrap(),
+            ],
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let response = send(&shared_data, get("/index.html")).await;
+        let expected = "<a href=\"https://example.com/docs/\">https://example.com</a>";
+        assert_eq!(
+            expected.len().to_string(),
+            response.headers()[CONTENT_LENGTH]
+        );
+        assert_ne!(page.len(), expected.len());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(expected, body);
+
+        let response = send(&shared_data, get("/notes.txt")).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("{{BASE_URL}}", body);
+
+        // As sent to an HTTP/1.0 client, with the header written out.
+        let response = buffer_response(send(&shared_data, get("/index.html")).await).await;
+        assert_eq!(
+            expected.len().to_string(),
+            response.headers()[CONTENT_LENGTH]
+        );
+    }
+
+    #[tokio::test]
+    async fn inject_html_test() {
+        let dir = temp_dir("inject-html");
+        std::fs::write(format!("{}index.html", dir), "<body><p>hi</p></body>").unwrap();
+        std::fs::write(format!("{}fragment.html", dir), "<p>hi</p>").unwrap();
+        std::fs::write(
+            format!("{}big.html", dir),
+            "<body>too big to rewrite</body>",
+        )
+        .unwrap();
+        std::fs::write(format!("{}data.txt", dir), "</body>").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            inject_html: Some(String::from("<script></script>")),
+            inject_max_size: 24,
+            ..Default::default()
+        });
+        for (path, expected) in [
+            ("/", "<body><p>hi</p><script></script></body>"),
+            ("/fragment.html", "<p>hi</p>"),
+            ("/big.html", "<body>too big to rewrite</body>"),
+            ("/data.txt", "</body>"),
+        ] {
+            let response = send(
+                &shared_data,
+                Request::get(path).body(Body::empty()).unwrap(),
+            )
+            .await;
+            assert_eq!(200, response.status());
+            let length = response.headers()[CONTENT_LENGTH].clone();
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(expected, body);
+            assert_eq!(body.len().to_string(), length);
+        }
+    }
+
+    #[tokio::test]
+    async fn validate_content_type_test() {
+        let dir = temp_dir("validate-content-type");
+        std::fs::write(format!("{}fake.png", dir), "%PDF-1.7 not an image").unwrap();
+        std::fs::write(format!("{}real.pdf", dir), "%PDF-1.7 a document").unwrap();
+        logger::test::captured_logs();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            validate_content_type: true,
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let response = send(&shared_data, get("/fake.png")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("image/png", response.headers()[CONTENT_TYPE]);
+        send(&shared_data, get("/real.pdf")).await;
+        let logs = logger::test::captured_logs();
+        assert!(logs.contains(
+            "[GET] /fake.png served as image/png but content looks like application/pdf"
+        ));
+        assert!(!logs.contains("/real.pdf served as"));
+    }
+
+    #[tokio::test]
+    async fn content_disposition_test() {
+        let dir = temp_dir("content-disposition");
+        std::fs::write(format!("{}report.pdf", dir), "%PDF").unwrap();
+        std::fs::write(format!("{}setup.exe", dir), "MZ").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let response = send(&shared_data, get("/report.pdf")).await;
+        assert_eq!(
+            "inline; filename=\"report.pdf\"",
+            response.headers()[CONTENT_DISPOSITION]
+        );
+        let response = send(&shared_data, get("/setup.exe")).await;
+        assert_eq!(
+            "attachment; filename=\"setup.exe\"",
+            response.headers()[CONTENT_DISPOSITION]
+        );
+
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            no_content_disposition: true,
+            ..Default::default()
+        });
+        let response = send(&shared_data, get("/report.pdf")).await;
+        assert!(!response.headers().contains_key(CONTENT_DISPOSITION));
+    }
+
+    #[tokio::test]
+    async fn http10_compat_test() {
+        let dir = temp_dir("http10");
+        std::fs::write(format!("{}index.html", dir), "hello").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            http10_compat: true,
+            ..Default::default()
+        });
+        let request = |version| {
+            Request::get("/")
+                .version(version)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = send(&shared_data, request(Version::HTTP_10)).await;
+        assert_eq!("5", response.headers()[CONTENT_LENGTH]);
+        assert_eq!("close", response.headers()[CONNECTION]);
+        let response = send(&shared_data, request(Version::HTTP_11)).await;
+        assert!(!response.headers().contains_key(CONNECTION));
+    }
+
+    #[tokio::test]
+    async fn keep_alive_max_requests_test() {
+        use std::io::{Read, Write};
+
+        let dir = temp_dir("keep-alive-max");
+        std::fs::write(format!("{}index.html", dir), "hello").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            keep_alive: KeepAliveConfig {
+                timeout: Some(std::time::Duration::from_secs(5)),
+                max_requests: Some(2),
+            },
+            ..Default::default()
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut served = 0;
+            let service = service_fn(move |mut req: Request<Body>| {
+                served += 1;
+                req.extensions_mut().insert(RequestCount(served));
+                timed_request_handler(req, shared_data.clone())
+            });
+            hyper::server::conn::Http::new()
+                .serve_connection(stream, service)
+                .await
+        });
+
+        // Three pipelined requests where only two are allowed.
+        let response = tokio::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(&b"GET / HTTP/1.1\r\nHost: a\r\n\r\n".repeat(3))
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response.to_ascii_lowercase()
+        })
+        .await
+        .unwrap();
+        let responses: Vec<&str> = response.split("http/1.1 ").skip(1).collect();
+        assert_eq!(2, responses.len(), "{}", response);
+        assert!(responses[0].contains("keep-alive: timeout=5, max=1"));
+        assert!(!responses[0].contains("connection: close"));
+        assert!(responses[1].contains("connection: close"));
+        assert!(!responses[1].contains("keep-alive:"));
+    }
+
+    #[tokio::test]
+    async fn http10_keep_alive_test() {
+        let dir = temp_dir("http10-keep-alive");
+        std::fs::write(format!("{}index.html", dir), "hello").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            ..Default::default()
+        });
+        let connection_of = |version, connection: Option<&'static str>| {
+            let mut request = Request::get("/").version(version);
+            if let Some(connection) = connection {
+                request = request.header(CONNECTION, connection);
+            }
+            let shared_data = shared_data.clone();
+            async move {
+                let response = send(&shared_data, request.body(Body::empty()).unwrap()).await;
+                response.headers().get(CONNECTION).cloned()
+            }
+        };
+        assert_eq!(
+            Some(HeaderValue::from_static("keep-alive")),
+            connection_of(Version::HTTP_10, Some("Keep-Alive")).await
+        );
+        assert_eq!(
+            Some(HeaderValue::from_static("close")),
+            connection_of(Version::HTTP_10, None).await
+        );
+        assert_eq!(None, connection_of(Version::HTTP_11, None).await);
+
+        let (_sender, body) = Body::channel();
+        let mut streamed = Response::new(body);
+        negotiate_http10_keep_alive(true, &mut streamed);
+        assert_eq!("close", streamed.headers()[CONNECTION]);
+    }
+
+    #[tokio::test]
+    async fn no_range_requests_test() {
+        let dir = temp_dir("no-range");
+        std::fs::write(format!("{}video.mp4", dir), "frames").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            no_range_requests: true,
+            ..Default::default()
+        });
+        let response = send(
+            &shared_data,
+            Request::get("/video.mp4").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(200, response.status());
+        assert_eq!("none", response.headers()[ACCEPT_RANGES]);
+
+        let ranged = Request::get("/video.mp4")
+            .header(RANGE, "bytes=0-1")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(400, send(&shared_data, ranged).await.status());
+    }
+
+    #[tokio::test]
+    async fn disable_method_test() {
+        let dir = temp_dir("disable-method");
+        std::fs::write(format!("{}file.txt", dir), "x").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            allow_put: true,
+            allow_delete: true,
+            disabled_methods: vec![Method::POST, Method::PUT, Method::DELETE],
+            ..uploadable_data(dir)
+        });
+        for method in [Method::POST, Method::PUT, Method::DELETE] {
+            // Disabled methods are refused before credentials are checked.
+            let request = Request::builder()
+                .method(method.clone())
+                .uri("/file.txt")
+                .body(Body::empty())
+                .unwrap();
+            let response = send(&shared_data, request).await;
+            assert_eq!(405, response.status(), "{}", method);
+            assert_eq!("GET, HEAD, OPTIONS", response.headers()[ALLOW]);
+        }
+        let get = Request::get("/file.txt")
+            .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(200, send(&shared_data, get).await.status());
+
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: temp_dir("disable-method-missing"),
+            ..Default::default()
+        });
+        let patch = Request::patch("/missing.txt").body(Body::empty()).unwrap();
+        assert_eq!(405, send(&shared_data, patch).await.status());
+        let get = Request::get("/missing.txt").body(Body::empty()).unwrap();
+        assert_eq!(404, send(&shared_data, get).await.status());
+    }
+
+    #[tokio::test]
+    async fn head_test() {
+        let dir = temp_dir("head");
+        std::fs::write(format!("{}file.txt", dir), "hello").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            ..Default::default()
+        });
+        let head = |path: &str| Request::head(path).body(Body::empty()).unwrap();
+        let response = send(&shared_data, head("/file.txt")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("5", response.headers()[CONTENT_LENGTH]);
+        assert_eq!("text/plain", response.headers()[CONTENT_TYPE]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.is_empty());
+
+        let response = send(&shared_data, head("/missing.txt")).await;
+        assert_eq!(404, response.status());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn extension_method_test() {
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: temp_dir("extension-method"),
+            ..Default::default()
+        });
+        for method in ["PROPFIND", "MKCOL", "COPY", "MOVE", "LOCK", "UNLOCK"] {
+            let request = Request::builder()
+                .method(method)
+                .uri("/file.txt")
+                .body(Body::empty())
+                .unwrap();
+            let response = send(&shared_data, request).await;
+            assert_eq!(405, response.status(), "{}", method);
+            assert_eq!("GET, HEAD, OPTIONS", response.headers()[ALLOW]);
+        }
+    }
+
+    #[tokio::test]
+    async fn hide_404_path_test() {
+        logger::test::captured_logs();
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let hidden = Arc::new(ServeConfig {
+            directory_path: temp_dir("hide-404"),
+            hide_404_path: true,
+            ..Default::default()
+        });
+        let response = send(&hidden, get("/secret-admin-panel")).await;
+        assert_eq!(404, response.status());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("secret-admin-panel"));
+        let shown = Arc::new(ServeConfig {
+            directory_path: temp_dir("show-404"),
+            ..Default::default()
+        });
+        send(&shown, get("/public-missing-page")).await;
+
+        let logs = logger::test::captured_logs();
+        let not_found = |path: &str| {
+            logs.lines()
+                .any(|line| line.contains("[404]") && line.contains(path))
+        };
+        assert!(!not_found("/secret-admin-panel"));
+        assert!(not_found("/public-missing-page"));
+        assert!(logs.contains("[404] [GET] <redacted> requested address not found"));
+    }
+
+    #[tokio::test]
+    async fn behind_proxy_redirect_test() {
+        let dir = temp_dir("behind-proxy");
+        std::fs::create_dir(format!("{}docs", dir)).unwrap();
+        let redirect = |proxy: Option<ProxyConfig>, forwarded_proto: Option<&'static str>| {
+            let shared_data = Arc::new(ServeConfig {
+                directory_path: dir.clone(),
+                list_directories: true,
+                proxy,
+                ..Default::default()
+            });
+            let mut request = Request::get("/docs").header(hyper::header::HOST, "example.com");
+            if let Some(proto) = forwarded_proto {
+                request = request.header("x-forwarded-proto", proto);
+            }
+            async move {
+                let response = send(&shared_data, request.body(Body::empty()).unwrap()).await;
+                assert_eq!(301, response.status());
+                response.headers()[hyper::header::LOCATION].clone()
+            }
+        };
+        let behind_proxy = || Some(ProxyConfig::default());
+        assert_eq!(
+            "https://example.com/docs/",
+            redirect(behind_proxy(), Some("https")).await
+        );
+        assert_eq!("/docs/", redirect(behind_proxy(), None).await);
+        assert_eq!("/docs/", redirect(None, Some("https")).await);
+        let injected = Some(ProxyConfig {
+            forwarded_proto: Some(HeaderValue::from_static("https")),
+            ..Default::default()
+        });
+        assert_eq!("https://example.com/docs/", redirect(injected, None).await);
+    }
+
+    #[tokio::test]
+    async fn directory_listing_test() {
+        let dir = temp_dir("listing");
+        std::fs::create_dir(format!("{}sub", dir)).unwrap();
+        std::fs::write(format!("{}sub/small.txt", dir), "1").unwrap();
+        std::fs::write(format!("{}sub/large.txt", dir), "12345").unwrap();
+        std::fs::write(format!("{}sub/.hidden", dir), "").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            list_directories: true,
+            allow_sort_param: true,
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let response = send(&shared_data, get("/sub?sort=size-desc")).await;
+        assert_eq!(301, response.status());
+        assert_eq!(
+            "/sub/?sort=size-desc",
+            response.headers()[hyper::header::LOCATION]
+        );
+
+        let response = send(&shared_data, get("/sub/?sort=size-desc")).await;
+        assert_eq!(200, response.status());
+        let html = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let html = String::from_utf8(html.to_vec()).unwrap();
+        assert!(!html.contains(".hidden"));
+        assert!(html.find("large.txt").unwrap() < html.find("small.txt").unwrap());
+
+        let response = send(&shared_data, get("/sub/%2e%2e/")).await;
+        assert_eq!(403, response.status());
+
+        let request = Request::get("/sub/")
+            .header(hyper::header::ACCEPT, "application/json")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&shared_data, request).await;
+        assert_eq!("application/json", response.headers()[CONTENT_TYPE]);
+        assert!(response.headers().contains_key(ETAG));
+        assert!(response
+            .headers()
+            .contains_key(hyper::header::LAST_MODIFIED));
+        let json = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(json.starts_with(br#"[{"name":"large.txt","size":5,"#));
+
+        let response = send(&shared_data, get("/sub/?format=json")).await;
+        assert_eq!("application/json", response.headers()[CONTENT_TYPE]);
+        let response = send(&shared_data, get("/sub/")).await;
+        assert_eq!("text/html; charset=utf-8", response.headers()[CONTENT_TYPE]);
+    }
+
+    #[tokio::test]
+    async fn index_json_test() {
+        let dir = temp_dir("index-json");
+        std::fs::create_dir_all(format!("{}sub/nested", dir)).unwrap();
+        std::fs::write(format!("{}sub/a.txt", dir), "12345").unwrap();
+        let index_json = |list_directories| {
+            Arc::new(ServeConfig {
+                directory_path: dir.clone(),
+                list_directories,
+                index_json: true,
+                ..Default::default()
+            })
+        };
+        let get = |accept: Option<&str>| {
+            let mut request = Request::get("/sub/");
+            if let Some(accept) = accept {
+                request = request.header(hyper::header::ACCEPT, accept);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+        let browser = Some("text/html,application/xhtml+xml,*/*;q=0.8");
+
+        let response = send(&index_json(false), get(browser)).await;
+        assert_eq!(200, response.status());
+        assert_eq!("application/json", response.headers()[CONTENT_TYPE]);
+        let json = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let json = String::from_utf8(json.to_vec()).unwrap();
+        assert!(json.starts_with(r#"{"files":[{"name":"a.txt","size":5,"mtime":""#));
+        assert!(json.ends_with(r#"],"dirs":["nested/"]}"#));
+
+        let both = index_json(true);
+        let response = send(&both, get(browser)).await;
+        assert_eq!("text/html; charset=utf-8", response.headers()[CONTENT_TYPE]);
+        for accept in [Some("application/json"), Some("*/*"), None] {
+            let response = send(&both, get(accept)).await;
+            assert_eq!("application/json", response.headers()[CONTENT_TYPE]);
+            assert_eq!("Accept", response.headers()[hyper::header::VARY]);
+        }
+        let request = Request::get("/sub/?format=html")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(&both, request).await;
+        assert_eq!("text/html; charset=utf-8", response.headers()[CONTENT_TYPE]);
+    }
+
+    #[tokio::test]
+    async fn ping_test() {
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: temp_dir("ping"),
+            auth: Some(String::from("Basic dXNlcjpwYXNz")),
+            ping_path: String::from("/_ping"),
+            ping_body: String::from("pong"),
+            ..Default::default()
+        });
+        let response = send(
+            &shared_data,
+            Request::get("/_ping").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(200, response.status());
+        assert_eq!("text/plain", response.headers()[CONTENT_TYPE]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("pong", body);
+
+        let head = Request::head("/_ping").body(Body::empty()).unwrap();
+        let response = send(&shared_data, head).await;
+        assert_eq!(200, response.status());
+        assert_eq!("4", response.headers()[CONTENT_LENGTH]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.is_empty());
+
+        let other = Request::get("/other").body(Body::empty()).unwrap();
+        assert_eq!(401, send(&shared_data, other).await.status());
+    }
+
+    #[tokio::test]
+    async fn fingerprint_fallback_test() {
+        let dir = temp_dir("fingerprint");
+        std::fs::write(format!("{}app.js", dir), "plain").unwrap();
+        std::fs::write(format!("{}app.def456.js", dir), "fingerprinted").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            fingerprint_glob: Some(String::from("*.*.js")),
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let response = send(&shared_data, get("/app.abc123.js")).await;
+        assert_eq!(200, response.status());
+        let etag = response.headers()[ETAG].clone();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("plain", body);
+        let response = send(&shared_data, get("/app.js?v=abc123")).await;
+        assert_eq!(etag, response.headers()[ETAG]);
+
+        let response = send(&shared_data, get("/app.def456.js")).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("fingerprinted", body);
+
+        let response = send(&shared_data, get("/other.abc123.js")).await;
+        assert_eq!(404, response.status());
+        let response = send(&shared_data, get("/app.abc123.css")).await;
+        assert_eq!(404, response.status());
+    }
+
+    #[tokio::test]
+    async fn font_cors_test() {
+        let dir = temp_dir("font-cors");
+        std::fs::write(format!("{}font.woff2", dir), "font").unwrap();
+        std::fs::write(format!("{}style.css", dir), "css").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            font_cors: true,
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let response = send(&shared_data, get("/font.woff2")).await;
+        assert_eq!("*", response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN]);
+        assert_eq!(
+            "cross-origin",
+            response.headers()["cross-origin-resource-policy"]
+        );
+        let response = send(&shared_data, get("/style.css")).await;
+        assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert!(!response
+            .headers()
+            .contains_key("cross-origin-resource-policy"));
+    }
+
+    #[tokio::test]
+    async fn cross_origin_isolated_test() {
+        let dir = temp_dir("cross-origin-isolated");
+        std::fs::write(format!("{}worker.js", dir), "js").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            headers: cors::isolation_headers(IsolationMode::Strict)
+                .iter()
+                .map(|(name, value)| (String::from(*name), String::from(*value)))
+                .collect(),
+            ..Default::default()
+        });
+        let response = send(
+            &shared_data,
+            Request::get("/worker.js").body(Body::empty()).unwrap(),
+        )
+        .await;
+        let headers = response.headers();
+        assert_eq!("same-origin", headers["cross-origin-opener-policy"]);
+        assert_eq!("require-corp", headers["cross-origin-embedder-policy"]);
+        assert_eq!("cross-origin", headers["cross-origin-resource-policy"]);
+        assert_eq!("?1", headers["origin-agent-cluster"]);
+    }
+
+    #[tokio::test]
+    async fn localize_test() {
+        let dir = temp_dir("localize");
+        std::fs::write(format!("{}about.html", dir), "hello").unwrap();
+        std::fs::write(format!("{}about.fr.html", dir), "bonjour").unwrap();
+        std::fs::write(format!("{}contact.html", dir), "contact").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            localize: true,
+            ..Default::default()
+        });
+        let get = |path: &str, accept_language: &str| {
+            Request::get(path)
+                .header(ACCEPT_LANGUAGE, accept_language)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = send(&shared_data, get("/about.html", "fr,en;q=0.9")).await;
+        assert_eq!("Accept-Language", response.headers()[VARY]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("bonjour", body);
+        let response = send(&shared_data, get("/about.html", "en")).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("hello", body);
+        let response = send(&shared_data, get("/contact.html", "fr")).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("contact", body);
+    }
+
+    #[tokio::test]
+    async fn forbid_ext_test() {
+        let dir = temp_dir("forbid-ext");
+        for name in ["index.php", "tool.py", "app.RB", ".env", "page.html"] {
+            std::fs::write(format!("{}{}", dir, name), "secret").unwrap();
+        }
+        std::fs::create_dir(format!("{}sub", dir)).unwrap();
+        std::fs::write(format!("{}sub/.secret", dir), "secret").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            forbid_ext: ["php", "py", "rb", "env"].map(String::from).to_vec(),
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        for path in ["/index.php", "/tool.py", "/app.RB"] {
+            assert_eq!(
+                403,
+                send(&shared_data, get(path)).await.status(),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(200, send(&shared_data, get("/page.html")).await.status());
+        assert_eq!(200, send(&shared_data, get("/sub/.secret")).await.status());
+
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            rewrites: vec![RewriteRule::parse(&format!(
+                "/config:{}.env",
+                shared_data.directory_path
+            ))
+            .unwrap()],
+            forbid_ext: vec![String::from("env")],
+            forbid_hidden: true,
+            ..Default::default()
+        });
+        assert_eq!(403, send(&shared_data, get("/config")).await.status());
+        assert_eq!(403, send(&shared_data, get("/sub/.secret")).await.status());
+        assert_eq!(200, send(&shared_data, get("/page.html")).await.status());
+    }
+
+    #[tokio::test]
+    async fn regex_rewrite_request_test() {
+        let dir = temp_dir("regex-rewrite");
+        std::fs::create_dir(format!("{}js", dir)).unwrap();
+        std::fs::write(format!("{}js/app.js", dir), "app").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            rewrites: vec![RewriteRule::parse_regex(r"^/v\d+/(.*):/$1").unwrap()],
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let response = send(&shared_data, get("/v3/js/app.js")).await;
+        assert_eq!(200, response.status());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("app", body);
+        assert_eq!(
+            404,
+            send(&shared_data, get("/v3/js/missing.js")).await.status()
+        );
+        assert_eq!(200, send(&shared_data, get("/js/app.js")).await.status());
+    }
+
+    #[tokio::test]
+    async fn document_policy_test() {
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: temp_dir("document-policy"),
+            permissions_policy: HashMap::from([(String::from("camera"), String::from("()"))]),
+            document_policy: Some(String::from("force-load-at-top")),
+            document_policy_report_only: Some(String::from("oversized-images=2.0")),
+            ..Default::default()
+        });
+        let response = send(&shared_data, Request::get("/").body(Body::empty()).unwrap()).await;
+        let headers = response.headers();
+        assert_eq!("force-load-at-top", headers["document-policy"]);
+        assert_eq!(
+            "oversized-images=2.0",
+            headers["document-policy-report-only"]
+        );
+        assert_eq!("camera=()", headers["permissions-policy"]);
+    }
+
+    #[tokio::test]
+    async fn nel_headers_test() {
+        let dir = temp_dir("nel-headers");
+        std::fs::write(format!("{}index.html", dir), "hello").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            nel: Some(NelConfig {
+                failure_fraction: 0.1,
+                ..NelConfig::new(String::from("https://report.example.com/nel"))
+            }),
+            ..Default::default()
+        });
+        for path in ["/", "/missing"] {
+            let response = send(
+                &shared_data,
+                Request::get(path).body(Body::empty()).unwrap(),
+            )
+            .await;
+            assert_eq!(
+                r#"{"report_to":"network-errors","max_age":86400,"include_subdomains":false,"failure_fraction":0.1}"#,
+                response.headers()["nel"]
+            );
+            let report_to = response.headers()["report-to"].to_str().unwrap();
+            assert!(report_to.starts_with(r#"{"group":"network-errors","#));
+            assert!(
+                report_to.ends_with(r#""endpoints":[{"url":"https://report.example.com/nel"}]}"#)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn expect_ct_header_test() {
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: temp_dir("expect-ct-header"),
+            expect_ct: Some(ExpectCtConfig {
+                max_age: 86400,
+                enforce: true,
+                report_uri: None,
+            }),
+            ..Default::default()
+        });
+        let response = send(&shared_data, Request::get("/").body(Body::empty()).unwrap()).await;
+        assert_eq!("max-age=86400, enforce", response.headers()["expect-ct"]);
+    }
+
+    #[tokio::test]
+    async fn x_robots_tag_test() {
+        let dir = temp_dir("x-robots-tag");
+        std::fs::create_dir_all(format!("{}private", dir)).unwrap();
+        std::fs::write(format!("{}private/a.html", dir), "a").unwrap();
+        std::fs::write(format!("{}b.html", dir), "b").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            x_robots_tag: vec![XRobotsRule::parse("/private/*:noindex").unwrap()],
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let response = send(&shared_data, get("/private/a.html")).await;
+        assert_eq!("noindex", response.headers()["x-robots-tag"]);
+        let response = send(&shared_data, get("/b.html")).await;
+        assert!(!response.headers().contains_key("x-robots-tag"));
+    }
+
+    #[tokio::test]
+    async fn clear_site_data_test() {
+        let dir = temp_dir("clear-site-data");
+        std::fs::write(format!("{}logout", dir), "bye").unwrap();
+        std::fs::write(format!("{}index.html", dir), "hello").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            clear_site_data: vec![ClearSiteDataRule::parse_path("/logout:cookies,storage").unwrap()],
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let response = send(&shared_data, get("/logout")).await;
+        assert_eq!(
+            "\"cookies\", \"storage\"",
+            response.headers()["clear-site-data"]
+        );
+        let response = send(&shared_data, get("/index.html")).await;
+        assert!(!response.headers().contains_key("clear-site-data"));
+    }
+
+    #[tokio::test]
+    async fn dir_config_test() {
+        let dir = temp_dir("dir-config");
+        std::fs::create_dir_all(format!("{}private/deeper", dir)).unwrap();
+        std::fs::write(format!("{}index.html", dir), "public").unwrap();
+        std::fs::write(format!("{}private/a.txt", dir), "a").unwrap();
+        std::fs::write(format!("{}private/deeper/b.txt", dir), "b").unwrap();
+        std::fs::write(
+            format!("{}.servedir", dir),
+            "[headers]\nx-area = \"public\"\nx-root = \"yes\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            format!("{}private/.servedir", dir),
+            "auth = \"user:pass\"\ncache_control = \"no-store\"\n[headers]\nx-area = \"private\"\n",
+        )
+        .unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            headers: vec![(String::from("x-area"), String::from("global"))],
+            cache_control: Some(String::from("max-age=60")),
+            dir_config: true,
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let authorized = |path: &str| {
+            Request::get(path)
+                .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = send(&shared_data, get("/index.html")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("public", response.headers()["x-area"]);
+        assert_eq!("max-age=60", response.headers()[CACHE_CONTROL]);
+
+        assert_eq!(
+            401,
+            send(&shared_data, get("/private/a.txt")).await.status()
+        );
+        assert_eq!(
+            401,
+            send(&shared_data, get("/private/deeper/b.txt"))
+                .await
+                .status()
+        );
+        let response = send(&shared_data, authorized("/private/deeper/b.txt")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("private", response.headers()["x-area"]);
+        assert_eq!("yes", response.headers()["x-root"]);
+        assert_eq!("no-store", response.headers()[CACHE_CONTROL]);
+
+        assert_eq!(403, send(&shared_data, get("/.servedir")).await.status());
+        assert_eq!(
+            403,
+            send(&shared_data, authorized("/private/.servedir"))
+                .await
+                .status()
+        );
+    }
+
+    #[tokio::test]
+    async fn dir_config_walk_test() {
+        let dir = temp_dir("dir-config-walk");
+        std::fs::create_dir_all(format!("{}private/deeper", dir)).unwrap();
+        std::fs::write(format!("{}index.txt", dir), "public").unwrap();
+        std::fs::write(format!("{}private/secret.txt", dir), "topsecret").unwrap();
+        std::fs::write(format!("{}private/deeper/secret.txt", dir), "deeper").unwrap();
+        std::fs::write(
+            format!("{}private/.servedir", dir),
+            "auth = \"user:pass\"\n",
+        )
+        .unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            dir_config: true,
+            archive: Some(ArchiveConfig {
+                endpoint: String::from("/_archive"),
+                max_files: 10,
+            }),
+            search: Some(SearchConfig {
+                endpoint: String::from("/_search"),
+                max_depth: 5,
+                max_results: 10,
+            }),
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let authorized = |path: &str| {
+            Request::get(path)
+                .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let body = |request| {
+            let shared_data = shared_data.clone();
+            async move {
+                let response = send(&shared_data, request).await;
+                assert_eq!(200, response.status());
+                hyper::body::to_bytes(response.into_body()).await.unwrap()
+            }
+        };
+        let names = |zip: &[u8]| {
+            archive::test::read_zip(zip)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            401,
+            send(&shared_data, get("/private/secret.txt"))
+                .await
+                .status()
+        );
+        for path in ["/_archive?path=/private", "/_archive?path=/private/deeper"] {
+            assert_eq!(
+                401,
+                send(&shared_data, get(path)).await.status(),
+                "{}",
+                path
+            );
+        }
+        let zip = body(get("/_archive?path=/")).await;
+        assert_eq!(vec!["index.txt"], names(&zip));
+        let zip = body(authorized("/_archive?path=/")).await;
+        assert_eq!(
+            vec![
+                "index.txt",
+                "private/deeper/secret.txt",
+                "private/secret.txt"
+            ],
+            names(&zip)
+        );
+        let zip = body(authorized("/_archive?path=/private")).await;
+        assert_eq!(vec!["deeper/secret.txt", "secret.txt"], names(&zip));
+
+        assert_eq!("[]", body(get("/_search?q=secret")).await);
+        let results = body(authorized("/_search?q=secret")).await;
+        assert!(String::from_utf8_lossy(&results).contains("/private/deeper/secret.txt"));
+    }
+
+    #[tokio::test]
+    async fn trace_test() {
+        let request = || {
+            Request::builder()
+                .method(Method::TRACE)
+                .uri("/some/path?q=1")
+                .header("x-custom-header", "value")
+                .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: temp_dir("trace"),
+            auth: Some(String::from("Basic dXNlcjpwYXNz")),
+            allow_trace: true,
+            ..Default::default()
+        });
+        let response = send(&shared_data, request()).await;
+        assert_eq!(200, response.status());
+        assert_eq!("message/http", response.headers()[CONTENT_TYPE]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            "TRACE /some/path?q=1 HTTP/1.1\r\nX-Custom-Header: value\r\n\r\n",
+            body
+        );
+
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: temp_dir("trace"),
+            ..Default::default()
+        });
+        assert_eq!(405, send(&shared_data, request()).await.status());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn follow_symlinks_test() {
+        let dir = temp_dir("follow-symlinks");
+        let outside = temp_dir("follow-symlinks-outside");
+        std::fs::write(format!("{}secret.txt", outside), "secret").unwrap();
+        std::fs::write(format!("{}public.txt", dir), "public").unwrap();
+        std::os::unix::fs::symlink(&outside, format!("{}escape", dir)).unwrap();
+        std::os::unix::fs::symlink("public.txt", format!("{}alias.txt", dir)).unwrap();
+        let status = |follow_symlinks, path: &'static str| {
+            let shared_data = Arc::new(ServeConfig {
+                directory_path: dir.clone(),
+                list_directories: true,
+                follow_symlinks,
+                ..Default::default()
+            });
+            let request = Request::get(path).body(Body::empty()).unwrap();
+            async move { send(&shared_data, request).await.status() }
+        };
+
+        assert_eq!(200, status(FollowSymlinks::All, "/escape/secret.txt").await);
+        assert_eq!(
+            403,
+            status(FollowSymlinks::Safe, "/escape/secret.txt").await
+        );
+        assert_eq!(403, status(FollowSymlinks::Safe, "/escape/").await);
+        assert_eq!(200, status(FollowSymlinks::Safe, "/alias.txt").await);
+        assert_eq!(403, status(FollowSymlinks::Never, "/alias.txt").await);
+        assert_eq!(200, status(FollowSymlinks::Never, "/public.txt").await);
+    }
+
+    #[tokio::test]
+    async fn max_depth_test() {
+        let dir = temp_dir("max-depth");
+        std::fs::create_dir_all(format!("{}a/b/c", dir)).unwrap();
+        std::fs::write(format!("{}root.js", dir), "x").unwrap();
+        std::fs::write(format!("{}a/b/file.js", dir), "x").unwrap();
+        std::fs::write(format!("{}a/b/c/file.js", dir), "x").unwrap();
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            max_depth: Some(3),
+            ..Default::default()
+        });
+        assert_eq!(200, send(&shared_data, get("/root.js")).await.status());
+        assert_eq!(200, send(&shared_data, get("/a/b/file.js")).await.status());
+        assert_eq!(
+            403,
+            send(&shared_data, get("/a/b/c/file.js")).await.status()
+        );
+        assert_eq!(
+            403,
+            send(&shared_data, get("/a//b/c/file.js")).await.status()
+        );
+
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            ..Default::default()
+        });
+        assert_eq!(
+            200,
+            send(&shared_data, get("/a/b/c/file.js")).await.status()
+        );
+    }
+
+    #[test]
+    fn banner_rows_test() {
+        let shared_data = ServeConfig {
+            directory_path: String::from("./public/"),
+            headers: vec![(
+                String::from("access-control-allow-origin"),
+                String::from("*"),
+            )],
+            auth: Some(String::from("Basic dXNlcjpwYXNz")),
+            auth_user: Some(String::from("user")),
+            ..Default::default()
+        };
+        let options = [String::from("--auth"), String::from("--font-cors")];
+        let rows = banner_rows(
+            &shared_data,
+            "127.0.0.1:8080".parse().unwrap(),
+            Some("eth0"),
+            &options,
+        );
+        let row = |key: &str| {
+            rows.iter()
+                .find(|(name, _)| *name == key)
+                .unwrap()
+                .1
+                .as_str()
+        };
+        assert_eq!("./public/", row("directory"));
+        assert_eq!("127.0.0.1:8080 on eth0", row("address"));
+        assert_eq!("off", row("tls"));
+        assert_eq!("origin *", row("cors"));
+        assert_eq!("basic, user user", row("auth"));
+        assert_eq!("1", row("headers"));
+        assert_eq!("default", row("404 page"));
+        assert_eq!("--auth --font-cors", row("options"));
+    }
+
+    #[test]
+    fn path_depth_test() {
+        assert_eq!(1, path_depth("/file.js"));
+        assert_eq!(7, path_depth("/a/b/c/d/e/f/file.js"));
+        assert_eq!(2, path_depth("/a//b"));
+        assert_eq!(2, path_depth("/a%2Fb"));
+    }
+
+    #[tokio::test]
+    async fn search_endpoint_test() {
+        let dir = temp_dir("search-endpoint");
+        std::fs::write(format!("{}app.js", dir), "x").unwrap();
+        std::fs::write(format!("{}other.js", dir), "x").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            auth: Some(String::from("Basic dXNlcjpwYXNz")),
+            search: Some(search::SearchConfig {
+                endpoint: String::from("/_search"),
+                max_depth: 5,
+                max_results: 1,
+            }),
+            ..Default::default()
+        });
+        let get = |path: &str| {
+            Request::get(path)
+                .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = send(&shared_data, get("/_search?q=*.js")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("application/json", response.headers()[CONTENT_TYPE]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.starts_with(r#"[{"path":"/app.js","size":1,"mtime":""#));
+        assert!(!body.contains("other.js"));
+
+        assert_eq!(400, send(&shared_data, get("/_search")).await.status());
+        let unauthenticated = Request::get("/_search?q=app").body(Body::empty()).unwrap();
+        assert_eq!(401, send(&shared_data, unauthenticated).await.status());
+    }
+
+    #[tokio::test]
+    async fn openapi_test() {
+        let dir = temp_dir("openapi");
+        let spec = format!("{}openapi.yaml", dir);
+        std::fs::write(&spec, "openapi: 3.0.0\n").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            openapi: Some(OpenApiConfig::new(
+                PathBuf::from(spec),
+                "/swagger",
+                String::from("<My API>"),
+            )),
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let response = send(&shared_data, get("/openapi.yaml")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("application/yaml", response.headers()[CONTENT_TYPE]);
+        assert_eq!("public, max-age=60", response.headers()[CACHE_CONTROL]);
+
+        let response = send(&shared_data, get("/swagger/")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("text/html; charset=utf-8", response.headers()[CONTENT_TYPE]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let html = String::from_utf8_lossy(&body);
+        assert!(html.contains("<title>&lt;My API&gt;</title>"));
+        assert!(html.contains("data-url=\"/openapi.yaml\""));
+        let script = format!("/swagger/ui.js?v={}", env!("CARGO_PKG_VERSION"));
+        assert!(html.contains(&script));
+
+        let response = send(&shared_data, get(&script)).await;
+        assert_eq!(
+            "text/javascript; charset=utf-8",
+            response.headers()[CONTENT_TYPE]
+        );
+        assert_eq!(
+            "public, max-age=31536000, immutable",
+            response.headers()[CACHE_CONTROL]
+        );
+        let response = send(&shared_data, get("/swagger")).await;
+        assert_eq!(301, response.status());
+        assert_eq!("/swagger/", response.headers()[hyper::header::LOCATION]);
+    }
+
+    #[tokio::test]
+    async fn archive_endpoint_test() {
+        let dir = temp_dir("archive-endpoint");
+        std::fs::create_dir_all(format!("{}sub/nested", dir)).unwrap();
+        std::fs::write(format!("{}sub/a.txt", dir), "alpha").unwrap();
+        std::fs::write(format!("{}sub/nested/b.txt", dir), "beta").unwrap();
+        std::fs::write(format!("{}outside.txt", dir), "x").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            auth: Some(String::from("Basic dXNlcjpwYXNz")),
+            archive: Some(ArchiveConfig {
+                endpoint: String::from("/_archive"),
+                max_files: 2,
+            }),
+            ..Default::default()
+        });
+        let get = |path: &str| {
+            Request::get(path)
+                .header(AUTHORIZATION, "Basic dXNlcjpwYXNz")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let expected = vec![
+            (String::from("a.txt"), b"alpha".to_vec()),
+            (String::from("nested/b.txt"), b"beta".to_vec()),
+        ];
+
+        let response = send(&shared_data, get("/_archive?path=/sub&format=zip")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("application/zip", response.headers()[CONTENT_TYPE]);
+        assert_eq!(
+            "attachment; filename=\"sub.zip\"",
+            response.headers()[CONTENT_DISPOSITION]
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(expected, archive::test::read_zip(&body));
+
+        let response = send(&shared_data, get("/_archive?path=/sub&format=tar.gz")).await;
+        assert_eq!(
+            "attachment; filename=\"sub.tar.gz\"",
+            response.headers()[CONTENT_DISPOSITION]
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let tar = archive::test::read_gzip(&body);
+        assert_eq!(expected, archive::test::read_tar(&tar));
+
+        let zip = send(&shared_data, get("/_archive?path=/sub&format=zip")).await;
+        assert_eq!("bytes", zip.headers()[ACCEPT_RANGES]);
+        let etag = zip.headers()[ETAG].clone();
+        let last_modified = zip.headers()[hyper::header::LAST_MODIFIED].clone();
+        let zip = hyper::body::to_bytes(zip.into_body()).await.unwrap();
+        let ranged = |path: &str, range: &str| {
+            let mut request = get(path);
+            request
+                .headers_mut()
+                .insert(RANGE, HeaderValue::from_str(range).unwrap());
+            request
+        };
+        let response = send(&shared_data, ranged("/_archive?path=/sub", "bytes=10-99")).await;
+        assert_eq!(206, response.status());
+        assert_eq!(
+            format!("bytes 10-99/{}", zip.len()),
+            response.headers()[CONTENT_RANGE]
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(zip[10..100], body);
+        let response = send(&shared_data, ranged("/_archive?path=/sub", "bytes=-22")).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(zip[zip.len() - 22..], body);
+        let response = send(
+            &shared_data,
+            ranged("/_archive?path=/sub", &format!("bytes={}-", zip.len())),
+        )
+        .await;
+        assert_eq!(416, response.status());
+        assert_eq!(
+            format!("bytes */{}", zip.len()),
+            response.headers()[CONTENT_RANGE]
+        );
+        let response = send(
+            &shared_data,
+            ranged("/_archive?path=/sub&format=tar.gz", "bytes=0-9"),
+        )
+        .await;
+        assert_eq!(416, response.status());
+        assert_eq!("bytes */*", response.headers()[CONTENT_RANGE]);
+
+        // A resume only gets a range of the archive it started with.
+        let resumed = |if_range: HeaderValue| {
+            let mut request = ranged("/_archive?path=/sub", "bytes=10-99");
+            request
+                .headers_mut()
+                .insert(hyper::header::IF_RANGE, if_range);
+            let shared_data = shared_data.clone();
+            async move { send(&shared_data, request).await }
+        };
+        let response = resumed(etag.clone()).await;
+        assert_eq!(206, response.status());
+        assert_eq!(etag, response.headers()[ETAG]);
+        assert_eq!(206, resumed(last_modified.clone()).await.status());
+        let response = resumed(HeaderValue::from_static("\"stale\"")).await;
+        assert_eq!(200, response.status());
+        assert!(!response.headers().contains_key(CONTENT_RANGE));
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(zip, body);
+        let weak = HeaderValue::from_str(&format!("W/{}", etag.to_str().unwrap())).unwrap();
+        assert_eq!(200, resumed(weak).await.status());
+        std::fs::write(
+            format!("{}sub/a.txt", shared_data.directory_path),
+            "changed",
+        )
+        .unwrap();
+        let response = resumed(etag.clone()).await;
+        assert_eq!(200, response.status());
+        assert_ne!(etag, response.headers()[ETAG]);
+
+        assert_eq!(
+            413,
+            send(&shared_data, get("/_archive?path=/")).await.status()
+        );
+        assert_eq!(
+            404,
+            send(&shared_data, get("/_archive?path=/../sub"))
+                .await
+                .status()
+        );
+        assert_eq!(
+            400,
+            send(&shared_data, get("/_archive?path=/sub&format=rar"))
+                .await
+                .status()
+        );
+        let unauthenticated = Request::get("/_archive?path=/sub")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(401, send(&shared_data, unauthenticated).await.status());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn archive_forbidden_test() {
+        let dir = temp_dir("archive-forbidden");
+        std::fs::create_dir_all(format!("{}a/b", dir)).unwrap();
+        std::fs::write(format!("{}index.html", dir), "public").unwrap();
+        std::fs::write(format!("{}config.php", dir), "<?php secret").unwrap();
+        std::fs::write(format!("{}a/b/deep.txt", dir), "deep").unwrap();
+        std::os::unix::fs::symlink("index.html", format!("{}alias.html", dir)).unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            forbid_ext: vec![String::from("php")],
+            follow_symlinks: FollowSymlinks::Never,
+            max_depth: Some(2),
+            archive: Some(ArchiveConfig {
+                endpoint: String::from("/_archive"),
+                max_files: 10,
+            }),
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        for path in ["/config.php", "/alias.html", "/a/b/deep.txt"] {
+            assert_eq!(
+                403,
+                send(&shared_data, get(path)).await.status(),
+                "{}",
+                path
+            );
+        }
+
+        let response = send(&shared_data, get("/_archive?path=/")).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(
+            vec![(String::from("index.html"), b"public".to_vec())],
+            archive::test::read_zip(&body)
+        );
+        let response = send(&shared_data, get("/_archive?path=/a")).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(archive::test::read_zip(&body).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn header_file_test() {
+        let dir = temp_dir("header-file-reload");
+        let path = format!("{}headers.txt", dir);
+        std::fs::write(&path, "# secrets\nx-api-key: one\nx-team: file\n").unwrap();
+        let header_file = Arc::new(HeaderFile::load(PathBuf::from(&path)).unwrap());
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            headers: vec![(String::from("X-Team"), String::from("cli"))],
+            header_file: Some(header_file.clone()),
+            ..Default::default()
+        });
+        let get = || Request::get("/").body(Body::empty()).unwrap();
+
+        let response = send(&shared_data, get()).await;
+        assert_eq!("one", response.headers()["x-api-key"]);
+        let teams: Vec<_> = response.headers().get_all("x-team").iter().collect();
+        assert_eq!(vec!["cli"], teams);
+
+        header_file::reload_on_sighup(header_file.clone()).unwrap();
+        std::fs::write(&path, "x-api-key: two\n").unwrap();
+        assert_eq!(0, unsafe { libc::raise(libc::SIGHUP) });
+        for _ in 0..100 {
+            if header_file.headers_not_in(&[]) == [(String::from("x-api-key"), String::from("two"))]
+            {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let response = send(&shared_data, get()).await;
+        assert_eq!("two", response.headers()["x-api-key"]);
+    }
+
+    #[tokio::test]
+    async fn accept_encoding_test() {
+        let dir = temp_dir("accept-encoding");
+        std::fs::write(format!("{}a.txt", dir), "plain").unwrap();
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir,
+            ..Default::default()
+        });
+        let status = |accept_encoding: &'static str| {
+            let shared_data = shared_data.clone();
+            async move {
+                let request = Request::get("/a.txt")
+                    .header(ACCEPT_ENCODING, accept_encoding)
+                    .body(Body::empty())
+                    .unwrap();
+                send(&shared_data, request).await.status()
+            }
+        };
+        assert_eq!(200, status("identity").await);
+        assert_eq!(200, status("gzip;q=0, *").await);
+        assert_eq!(200, status("gzip, br").await);
+        assert_eq!(406, status("*;q=0").await);
+        assert_eq!(406, status("identity;q=0").await);
+    }
+
+    #[tokio::test]
+    async fn error_log_test() {
+        logger::test::captured_logs();
+        let dir = temp_dir("error-log");
+        std::fs::write(format!("{}plain.txt", dir), "not a directory").unwrap();
+        let shared_data = writable_data(dir);
+        let response = send(&shared_data, put("/plain.txt/under-a-file.txt", "x")).await;
+        assert_eq!(500, response.status());
+
+        let line = "[500] [PUT] /plain.txt/under-a-file.txt";
+        assert!(logger::test::captured_error_logs().contains(line));
+        assert!(!logger::test::captured_logs().contains(line));
+    }
+
+    #[tokio::test]
+    async fn log_body_test() {
+        logger::test::captured_logs();
+        let dir = temp_dir("log-body");
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: dir.clone(),
+            auth: Some(format!("Basic {}", base64::encode(b"user:pass"))),
+            allow_put: true,
+            log_body_bytes: Some(16),
+            ..Default::default()
+        });
+        let body = "password=hunter2&note=the rest is not logged";
+        let response = send(&shared_data, put("/form.txt", body)).await;
+        assert_eq!(201, response.status());
+        assert_eq!(
+            body,
+            std::fs::read_to_string(format!("{}form.txt", dir)).unwrap()
+        );
+        let logs = logger::test::captured_logs();
+        assert!(logs.contains("[PUT] /form.txt request body \"password=[REDACTED]\""));
+        assert!(!logs.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn media_headers_test() {
+        let dir = temp_dir("media-headers");
+        std::fs::write(format!("{}song.mp3", dir), media::test::mp3(125)).unwrap();
+        std::fs::write(format!("{}notes.txt", dir), media::test::mp3(125)).unwrap();
+        let media_headers = |media_headers| {
+            Arc::new(ServeConfig {
+                directory_path: dir.clone(),
+                media_headers,
+                ..Default::default()
+            })
+        };
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let response = send(&media_headers(true), get("/song.mp3")).await;
+        assert_eq!(200, response.status());
+        assert_eq!("3.000", response.headers()["x-content-duration"]);
+        assert_eq!("3", response.headers()["content-duration"]);
+        let response = send(&media_headers(true), get("/notes.txt")).await;
+        assert!(!response.headers().contains_key("x-content-duration"));
+        let response = send(&media_headers(false), get("/song.mp3")).await;
+        assert!(!response.headers().contains_key("x-content-duration"));
+    }
+
+    #[tokio::test]
+    async fn index_fallback_test() {
+        let dir = temp_dir("index-fallback");
+        for (path, body) in [
+            ("all/index.html", "html"),
+            ("all/index.htm", "htm"),
+            ("all/README.md", "# readme"),
+            ("htm/index.htm", "htm"),
+            ("htm/README.md", "# readme"),
+            ("readme/README.md", "# readme"),
+            ("none/other.txt", "other"),
+        ] {
+            let path = format!("{}{}", dir, path);
+            std::fs::create_dir_all(Path::new(&path).parent().unwrap()).unwrap();
+            std::fs::write(path, body).unwrap();
+        }
+        let index_fallback = |files: &[&str]| {
+            Arc::new(ServeConfig {
+                directory_path: dir.clone(),
+                index_fallback: files.iter().map(|file| String::from(*file)).collect(),
+                ..Default::default()
+            })
+        };
+        let chain = index_fallback(&["index.html", "index.htm", "README.md"]);
+        let get = |shared_data: Arc<ServeConfig>, path: &'static str| async move {
+            let response = send(
+                &shared_data,
+                Request::get(path).body(Body::empty()).unwrap(),
+            )
+            .await;
+            let status = response.status().as_u16();
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .map(|value| String::from(value.to_str().unwrap()));
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            (
+                status,
+                content_type,
+                String::from_utf8(body.to_vec()).unwrap(),
+            )
+        };
+
+        let (status, content_type, body) = get(chain.clone(), "/all/").await;
+        assert_eq!((200, "html"), (status, body.as_str()));
+        assert_eq!(Some("text/html"), content_type.as_deref());
+        let (_, content_type, body) = get(chain.clone(), "/htm/").await;
+        assert_eq!("htm", body);
+        assert_eq!(Some("text/html"), content_type.as_deref());
+        let (status, content_type, body) = get(chain.clone(), "/readme/").await;
+        assert_eq!((200, "# readme"), (status, body.as_str()));
+        assert_eq!(Some("text/plain; charset=utf-8"), content_type.as_deref());
+        assert_eq!(404, get(chain.clone(), "/none/").await.0);
+        // Asking for the file itself keeps its own type.
+        let (_, content_type, _) = get(chain, "/readme/README.md").await;
+        assert_ne!(Some("text/plain; charset=utf-8"), content_type.as_deref());
+
+        let default = index_fallback(&[]);
+        assert_eq!("html", get(default.clone(), "/all/").await.2);
+        assert_eq!(404, get(default, "/readme/").await.0);
+    }
+
+    #[test]
+    fn parse_header_test() {
+        let header = |key: &str, value: &str| Some((String::from(key), String::from(value)));
+        assert_eq!(
+            header("x-redirect-to", "https://example.com/path"),
+            parse_header("x-redirect-to:https://example.com/path")
+        );
+        assert_eq!(
+            header("x-times", "10:30:45"),
+            parse_header("x-times:10:30:45")
+        );
+        assert_eq!(
+            header("x-custom", "value"),
+            parse_header(" x-custom : value ")
+        );
+        assert_eq!(None, parse_header("no-separator"));
+        assert_eq!(None, parse_header("bad key:value"));
+        assert_eq!(None, parse_header("x-caf\u{e9}:value"));
+
+        let mut headers = vec![(String::from("X-Custom"), String::from("a"))];
+        assert!(headers.update((String::from("x-custom"), String::from("b"))));
+        assert_eq!(vec![(String::from("X-Custom"), String::from("b"))], headers);
+    }
+
+    #[test]
+    fn parse_duration_test() {
+        use std::time::Duration;
+        assert_eq!(Some(Duration::from_secs(60)), parse_duration("60s"));
+        assert_eq!(Some(Duration::from_secs(60)), parse_duration("60"));
+        assert_eq!(Some(Duration::from_secs(300)), parse_duration("5m"));
+        assert_eq!(Some(Duration::from_millis(500)), parse_duration("500ms"));
+        assert_eq!(None, parse_duration("s"));
+        assert_eq!(None, parse_duration("10d"));
+    }
+
+    #[test]
+    fn parse_size_test() {
+        assert_eq!(Some(512), parse_size("512"));
+        assert_eq!(Some(100 << 20), parse_size("100MB"));
+        assert_eq!(Some(10 << 10), parse_size("10kb"));
+        assert_eq!(None, parse_size("MB"));
+        assert_eq!(None, parse_size("10XB"));
+    }
+
+    #[test]
+    fn percent_decode_test() {
+        assert_eq!("/a b/%zz/é", percent_decode("/a%20b/%zz/%C3%A9"));
+        assert_eq!(Some("2"), query_param(Some("a=1&b=2&b=3"), "b"));
+        assert_eq!(Some(""), query_param(Some("flag"), "flag"));
+        assert_eq!(None, query_param(None, "a"));
+    }
+
+    #[tokio::test]
+    async fn mock_file_system_test() {
+        let fs = fs::test::MockFileSystem::default()
+            .with_file("/srv/index.html", "<h1>home</h1>")
+            .with_file("/srv/secret.key", "key")
+            .with_file("/srv/docs/a.txt", "a");
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: String::from("/srv/"),
+            fs: Fs::new(fs),
+            forbid_ext: vec![String::from("key")],
+            list_directories: true,
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let response = send(&shared_data, get("/")).await;
+        assert_eq!(200, response.status());
+        assert!(response.headers().contains_key(ETAG));
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("<h1>home</h1>", body);
+
+        assert_eq!(404, send(&shared_data, get("/missing.txt")).await.status());
+        assert_eq!(403, send(&shared_data, get("/secret.key")).await.status());
+
+        let response = send(&shared_data, get("/docs/")).await;
+        assert_eq!(200, response.status());
+        let html = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&html).contains("<a href=\"a.txt\">a.txt</a>"));
+    }
+
+    #[tokio::test]
+    async fn zip_file_system_test() {
+        let zip = zip_fs::test::stored_zip(&[
+            ("index.html", "<h1>zipped</h1>"),
+            ("assets/app.js", "alert(1)"),
+            ("assets/nested.zip", "PK"),
+        ]);
+        let shared_data = Arc::new(ServeConfig {
+            directory_path: String::from("/srv/"),
+            fs: Fs::new(ZipFileSystem::new(zip, Path::new("/srv/")).unwrap()),
+            list_directories: true,
+            ..Default::default()
+        });
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+
+        let response = send(&shared_data, get("/")).await;
+        assert_eq!(200, response.status());
+        assert!(response.headers().contains_key(ETAG));
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("<h1>zipped</h1>", body);
+
+        let response = send(&shared_data, get("/assets/app.js")).await;
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!("alert(1)", body);
+        assert_eq!(404, send(&shared_data, get("/missing.txt")).await.status());
+
+        let response = send(&shared_data, get("/assets/")).await;
+        assert_eq!(200, response.status());
+        let html = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let html = String::from_utf8_lossy(&html);
+        assert!(html.contains("<a href=\"app.js\">app.js</a>"), "{}", html);
+        // Served as a file, not opened.
+        assert!(
+            html.contains("<a href=\"nested.zip\">nested.zip</a>"),
+            "{}",
+            html
+        );
     }
 }
@@ -0,0 +1,288 @@
+//! Just enough of the MP4, WebM, Ogg and MP3 containers to read a
+//! duration for `--media-headers`.
+
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |value, &byte| value << 8 | u64::from(byte))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u64> {
+    Some(be_uint(data.get(offset..offset + 4)?))
+}
+
+fn be_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(be_uint(data.get(offset..offset + 8)?))
+}
+
+fn le_uint(data: &[u8], offset: usize, len: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + len)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| value << 8 | u64::from(byte)),
+    )
+}
+
+/// The duration in seconds of the audio or video in `data`, recognised by
+/// its leading bytes. `None` when the format is not one of the supported
+/// containers or the duration is not recorded.
+pub fn duration(data: &[u8]) -> Option<f64> {
+    if data.get(4..8) == Some(b"ftyp") {
+        mp4_duration(data)
+    } else if data.starts_with(b"\x1a\x45\xdf\xa3") {
+        webm_duration(data)
+    } else if data.starts_with(b"OggS") {
+        ogg_duration(data)
+    } else {
+        mp3_duration(data)
+    }
+}
+
+/// The contents of the first `kind` box among the boxes in `data`.
+fn mp4_box<'a>(mut data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    while data.len() >= 8 {
+        let (header, size) = match be_u32(data, 0)? {
+            1 => (16, be_u64(data, 8)?),
+            0 => (8, data.len() as u64),
+            size => (8, size),
+        };
+        if size < header || size > data.len() as u64 {
+            return None;
+        }
+        if &data[4..8] == kind {
+            return Some(&data[header as usize..size as usize]);
+        }
+        data = &data[size as usize..];
+    }
+    None
+}
+
+/// The movie header's duration over its timescale.
+fn mp4_duration(data: &[u8]) -> Option<f64> {
+    let mvhd = mp4_box(mp4_box(data, b"moov")?, b"mvhd")?;
+    // Version 1 widens the creation and modification times to 64 bits.
+    let (timescale, duration) = match mvhd.first()? {
+        1 => (be_u32(mvhd, 20)?, be_u64(mvhd, 24)?),
+        _ => (be_u32(mvhd, 12)?, be_u32(mvhd, 16)?),
+    };
+    (timescale > 0).then(|| duration as f64 / timescale as f64)
+}
+
+/// An EBML variable length integer and its length. IDs keep their length
+/// marker bit; sizes do not.
+fn ebml_vint(data: &[u8], keep_marker: bool) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || data.len() < len {
+        return None;
+    }
+    let first = if keep_marker {
+        u64::from(first)
+    } else {
+        u64::from(first) & (0xff >> len)
+    };
+    let value = data[1..len]
+        .iter()
+        .fold(first, |value, &byte| value << 8 | u64::from(byte));
+    Some((value, len))
+}
+
+/// The body of the first `wanted` element among the elements in `data`.
+/// An element of unknown size, as streamed Segments are, runs to the end.
+fn ebml_element(mut data: &[u8], wanted: u64) -> Option<&[u8]> {
+    while !data.is_empty() {
+        let (id, id_len) = ebml_vint(data, true)?;
+        let (size, size_len) = ebml_vint(&data[id_len..], false)?;
+        let start = id_len + size_len;
+        let unknown = size == (1 << (7 * size_len)) - 1;
+        let end = if unknown {
+            data.len()
+        } else {
+            usize::try_from(size)
+                .ok()
+                .and_then(|size| start.checked_add(size))?
+                .min(data.len())
+        };
+        if id == wanted {
+            return Some(&data[start..end]);
+        }
+        data = &data[end..];
+    }
+    None
+}
+
+const EBML_SEGMENT: u64 = 0x1853_8067;
+const EBML_INFO: u64 = 0x1549_a966;
+const EBML_TIMECODE_SCALE: u64 = 0x2a_d7b1;
+const EBML_DURATION: u64 = 0x4489;
+
+/// The Segment Info duration, in units of its timecode scale in
+/// nanoseconds.
+fn webm_duration(data: &[u8]) -> Option<f64> {
+    let info = ebml_element(ebml_element(data, EBML_SEGMENT)?, EBML_INFO)?;
+    let scale = ebml_element(info, EBML_TIMECODE_SCALE).map_or(1_000_000, be_uint);
+    let duration = match ebml_element(info, EBML_DURATION)? {
+        bytes @ [_, _, _, _] => f64::from(f32::from_bits(be_uint(bytes) as u32)),
+        bytes @ [_, _, _, _, _, _, _, _] => f64::from_bits(be_uint(bytes)),
+        _ => return None,
+    };
+    Some(duration * scale as f64 / 1e9)
+}
+
+/// The granule position of the last page over the sample rate from the
+/// Vorbis or Opus identification header on the first.
+fn ogg_duration(data: &[u8]) -> Option<f64> {
+    let segments = usize::from(*data.get(26)?);
+    let packet = data.get(27 + segments..)?;
+    let (rate, pre_skip) = if packet.starts_with(b"\x01vorbis") {
+        (le_uint(packet, 12, 4)?, 0)
+    } else if packet.starts_with(b"OpusHead") {
+        // Opus granules always count 48 kHz samples.
+        (48_000, le_uint(packet, 10, 2)?)
+    } else {
+        return None;
+    };
+    let last_page = data.windows(4).rposition(|window| window == b"OggS")?;
+    let granule = le_uint(data, last_page + 6, 8)?;
+    (rate > 0).then(|| granule.saturating_sub(pre_skip) as f64 / rate as f64)
+}
+
+const MP3_BITRATES_V1: [u64; 15] = [
+    0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+const MP3_BITRATES_V2: [u64; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+/// MPEG audio layer III: the frame count of a Xing or Info header if the
+/// first frame has one, otherwise the size at the first frame's bitrate.
+fn mp3_duration(data: &[u8]) -> Option<f64> {
+    let mut start = 0;
+    if data.starts_with(b"ID3") {
+        // The tag size is syncsafe: 7 bits per byte.
+        let size = data
+            .get(6..10)?
+            .iter()
+            .fold(0, |size, &byte| size << 7 | usize::from(byte & 0x7f));
+        let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+        start = 10 + size + footer;
+    }
+    let header = data.get(start..start + 4)?;
+    let (version, layer) = ((header[1] >> 3) & 3, (header[1] >> 1) & 3);
+    if header[0] != 0xff || header[1] & 0xe0 != 0xe0 || version == 1 || layer != 1 {
+        return None;
+    }
+    let mpeg1 = version == 3;
+    let bitrate = *if mpeg1 {
+        &MP3_BITRATES_V1
+    } else {
+        &MP3_BITRATES_V2
+    }
+    .get(usize::from(header[2] >> 4))?;
+    let base_rate = [44_100, 48_000, 32_000].get(usize::from((header[2] >> 2) & 3))?;
+    let sample_rate = match version {
+        3 => *base_rate,
+        2 => base_rate / 2,
+        _ => base_rate / 4,
+    };
+    let samples_per_frame = if mpeg1 { 1152 } else { 576 };
+    let mono = header[3] >> 6 == 3;
+    let side_info = match (mpeg1, mono) {
+        (true, true) => 17,
+        (true, false) => 32,
+        (false, true) => 9,
+        (false, false) => 17,
+    };
+    let xing = start + 4 + side_info;
+    let has_frame_count = matches!(data.get(xing..xing + 4), Some(b"Xing" | b"Info"))
+        && be_u32(data, xing + 4).is_some_and(|flags| flags & 1 != 0);
+    if has_frame_count {
+        let frames = be_u32(data, xing + 8)?;
+        return Some((frames * samples_per_frame) as f64 / sample_rate as f64);
+    }
+    (bitrate > 0).then(|| (data.len() - start) as f64 * 8.0 / (bitrate * 1000) as f64)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn boxed(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(body);
+        data
+    }
+
+    /// A constant bitrate MPEG-1 layer III stream of 48 kHz stereo frames
+    /// with an Info header counting `frames`, so it lasts `frames * 24ms`.
+    pub fn mp3(frames: u32) -> Vec<u8> {
+        let mut data = b"ID3\x03\x00\x00\x00\x00\x00\x02\x00\x00".to_vec();
+        let mut frame = vec![0xff, 0xfb, 0x94, 0x00];
+        frame.extend_from_slice(&[0; 32]);
+        frame.extend_from_slice(b"Info\x00\x00\x00\x01");
+        frame.extend_from_slice(&frames.to_be_bytes());
+        frame.resize(416, 0);
+        data.extend_from_slice(&frame);
+        data
+    }
+
+    #[test]
+    fn mp4_duration_test() {
+        let mut mvhd = vec![0; 12];
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&2500u32.to_be_bytes());
+        let mut data = boxed(b"ftyp", b"isom\x00\x00\x02\x00");
+        data.extend(boxed(b"mdat", &[0; 20]));
+        data.extend(boxed(b"moov", &boxed(b"mvhd", &mvhd)));
+        assert_eq!(Some(2.5), duration(&data));
+        assert_eq!(None, duration(&boxed(b"ftyp", b"isom")));
+    }
+
+    #[test]
+    fn webm_duration_test() {
+        let mut info = vec![0x2a, 0xd7, 0xb1, 0x83, 0x0f, 0x42, 0x40];
+        info.extend_from_slice(&[0x44, 0x89, 0x84]);
+        info.extend_from_slice(&1500f32.to_be_bytes());
+        let mut data = vec![0x1a, 0x45, 0xdf, 0xa3, 0x80];
+        // A Segment of unknown size, then its Info.
+        data.extend_from_slice(&[
+            0x18, 0x53, 0x80, 0x67, 0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ]);
+        data.extend_from_slice(&[0x15, 0x49, 0xa9, 0x66, 0x80 | info.len() as u8]);
+        data.extend_from_slice(&info);
+        assert_eq!(Some(1.5), duration(&data));
+    }
+
+    #[test]
+    fn ogg_duration_test() {
+        let page = |granule: u64, packet: &[u8]| {
+            let mut page = b"OggS\x00\x02".to_vec();
+            page.extend_from_slice(&granule.to_le_bytes());
+            page.resize(26, 0);
+            page.push(1);
+            page.push(packet.len() as u8);
+            page.extend_from_slice(packet);
+            page
+        };
+        let mut vorbis = b"\x01vorbis\x00\x00\x00\x00\x02".to_vec();
+        vorbis.extend_from_slice(&44_100u32.to_le_bytes());
+        let mut data = page(0, &vorbis);
+        data.extend(page(88_200, &[0; 10]));
+        assert_eq!(Some(2.0), duration(&data));
+
+        let mut data = page(0, b"OpusHead\x01\x02\x38\x01");
+        data.extend(page(48_000 + 312, &[0; 10]));
+        assert_eq!(Some(1.0), duration(&data));
+    }
+
+    #[test]
+    fn mp3_duration_test() {
+        assert_eq!(Some(3.0), duration(&mp3(125)));
+        // Without the Info header, 128 kb/s makes 16000 bytes a second.
+        let mut cbr = vec![0xff, 0xfb, 0x90, 0x00];
+        cbr.resize(16_000, 0);
+        assert_eq!(Some(1.0), duration(&cbr));
+        assert_eq!(None, duration(b"plain text"));
+    }
+}
@@ -0,0 +1,186 @@
+/// One part of a `multipart/form-data` body.
+#[derive(Debug, PartialEq)]
+pub struct Part<'a> {
+    pub name: Option<String>,
+    pub filename: Option<String>,
+    pub data: &'a [u8],
+}
+
+/// The boundary of a `multipart/form-data` content type, `None` for any
+/// other type.
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        let value = value.trim().trim_matches('"');
+        (!value.is_empty()).then(|| String::from(value))
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Splits `body` into its parts. Headers other than `Content-Disposition`
+/// are skipped.
+pub fn parse<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<Part<'a>>, &'static str> {
+    let delimiter = format!("--{}", boundary);
+    let close = format!("\r\n--{}", boundary);
+    let start = find(body, delimiter.as_bytes()).ok_or("Missing multipart boundary")?;
+    let mut rest = &body[start + delimiter.len()..];
+    let mut parts = Vec::new();
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .ok_or("Malformed multipart boundary")?;
+        let headers_end = find(rest, b"\r\n\r\n").ok_or("Malformed multipart headers")?;
+        let headers =
+            std::str::from_utf8(&rest[..headers_end]).map_err(|_| "Malformed multipart headers")?;
+        rest = &rest[headers_end + 4..];
+        let data_end = find(rest, close.as_bytes()).ok_or("Unterminated multipart body")?;
+
+        let mut part = Part {
+            name: None,
+            filename: None,
+            data: &rest[..data_end],
+        };
+        for header in headers.split("\r\n") {
+            let Some((name, value)) = header.split_once(':') else {
+                continue;
+            };
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                for (key, value) in disposition_params(value) {
+                    match key.to_ascii_lowercase().as_str() {
+                        "name" => part.name = Some(value),
+                        "filename" => part.filename = Some(value),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        parts.push(part);
+        rest = &rest[data_end + close.len()..];
+    }
+}
+
+/// The `key=value` parameters after `form-data`, unquoting quoted values.
+fn disposition_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = value.chars().peekable();
+    // Skip the disposition type.
+    for c in chars.by_ref() {
+        if c == ';' {
+            break;
+        }
+    }
+    loop {
+        let key: String = chars.by_ref().take_while(|&c| c != '=').collect();
+        let key = key.trim().to_string();
+        if key.is_empty() {
+            return params;
+        }
+        while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => value.extend(chars.next()),
+                    c => value.push(c),
+                }
+            }
+            for c in chars.by_ref() {
+                if c == ';' {
+                    break;
+                }
+            }
+        } else {
+            value = chars.by_ref().take_while(|&c| c != ';').collect();
+            value = value.trim().to_string();
+        }
+        params.push((key, value));
+    }
+}
+
+/// The file name a client sent, reduced to its last path component. `None`
+/// for names that are empty, hidden, or contain control characters.
+pub fn sanitize_filename(filename: &str) -> Option<&str> {
+    let name = filename.rsplit(['/', '\\']).next()?.trim();
+    if name.is_empty() || name.starts_with('.') || name.chars().any(char::is_control) {
+        return None;
+    }
+    Some(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn boundary_test() {
+        assert_eq!(
+            Some(String::from("abc")),
+            boundary("multipart/form-data; boundary=abc")
+        );
+        assert_eq!(
+            Some(String::from("a b")),
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b\"")
+        );
+        assert_eq!(None, boundary("text/plain; boundary=abc"));
+        assert_eq!(None, boundary("multipart/form-data"));
+    }
+
+    #[test]
+    fn parse_test() {
+        let body = b"preamble\r\n--XX\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a;\\\"b\\\".txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            line one\r\nline two\r\n--XX\r\n\
+            content-disposition: form-data; name=note\r\n\r\n\
+            hi\r\n--XX--\r\n";
+        let parts = parse(body, "XX").unwrap();
+        assert_eq!(
+            vec![
+                Part {
+                    name: Some(String::from("file")),
+                    filename: Some(String::from("a;\"b\".txt")),
+                    data: b"line one\r\nline two",
+                },
+                Part {
+                    name: Some(String::from("note")),
+                    filename: None,
+                    data: b"hi",
+                },
+            ],
+            parts
+        );
+        assert!(parse(b"--XX\r\n\r\nno end", "XX").is_err());
+        assert!(parse(b"nothing here", "XX").is_err());
+    }
+
+    #[test]
+    fn sanitize_filename_test() {
+        assert_eq!(Some("report.pdf"), sanitize_filename("report.pdf"));
+        assert_eq!(Some("passwd"), sanitize_filename("../../etc/passwd"));
+        assert_eq!(
+            Some("file.txt"),
+            sanitize_filename("C:\\Users\\me\\file.txt")
+        );
+        assert_eq!(None, sanitize_filename(".."));
+        assert_eq!(None, sanitize_filename(".htaccess"));
+        assert_eq!(None, sanitize_filename("dir/"));
+        assert_eq!(None, sanitize_filename("a\nb"));
+    }
+}
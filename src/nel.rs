@@ -0,0 +1,75 @@
+use crate::json;
+
+/// Network Error Logging: asks browsers to report failed requests to
+/// `endpoint` through the Reporting API.
+pub struct NelConfig {
+    pub endpoint: String,
+    /// Reporting group name tying `NEL` to its `Report-To` entry.
+    pub group: String,
+    pub max_age: u64,
+    pub include_subdomains: bool,
+    /// Share of failed requests to report, from 0 to 1.
+    pub failure_fraction: f64,
+}
+
+impl NelConfig {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            group: String::from("network-errors"),
+            max_age: 86400,
+            include_subdomains: false,
+            failure_fraction: 1.0,
+        }
+    }
+
+    /// `NEL` value: `{"report_to":"network-errors","max_age":86400,...}`.
+    pub fn nel(&self) -> String {
+        format!(
+            r#"{{"report_to":{},"max_age":{},"include_subdomains":{},"failure_fraction":{}}}"#,
+            json::string(&self.group),
+            self.max_age,
+            self.include_subdomains,
+            self.failure_fraction
+        )
+    }
+
+    /// `Report-To` value naming the endpoint for the group.
+    pub fn report_to(&self) -> String {
+        format!(
+            r#"{{"group":{},"max_age":{},"include_subdomains":{},"endpoints":[{{"url":{}}}]}}"#,
+            json::string(&self.group),
+            self.max_age,
+            self.include_subdomains,
+            json::string(&self.endpoint)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn headers_test() {
+        let config = NelConfig {
+            include_subdomains: true,
+            failure_fraction: 0.1,
+            ..NelConfig::new(String::from("https://report.example.com/nel"))
+        };
+        assert_eq!(
+            r#"{"report_to":"network-errors","max_age":86400,"include_subdomains":true,"failure_fraction":0.1}"#,
+            config.nel()
+        );
+        assert_eq!(
+            concat!(
+                r#"{"group":"network-errors","max_age":86400,"include_subdomains":true,"#,
+                r#""endpoints":[{"url":"https://report.example.com/nel"}]}"#
+            ),
+            config.report_to()
+        );
+        assert!(NelConfig::new(String::new())
+            .nel()
+            .ends_with(r#""failure_fraction":1}"#));
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use hyper::{
+    header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION},
+    http::response::Builder,
+    Body, Request, Response,
+};
+use log::{error, info};
+
+use crate::{error_response, listing::html_escape, ServeConfig};
+
+const INDEX_HTML: &[u8] = include_bytes!("../assets/openapi/index.html");
+const UI_JS: &[u8] = include_bytes!("../assets/openapi/ui.js");
+/// Versioned so the script can be cached for good.
+const UI_JS_NAME: &str = concat!("ui.js?v=", env!("CARGO_PKG_VERSION"));
+
+pub const DEFAULT_UI_PATH: &str = "/docs/";
+pub const DEFAULT_TITLE: &str = "API Documentation";
+
+/// `--openapi`: the spec file, served next to a documentation page.
+pub struct OpenApiConfig {
+    pub spec: PathBuf,
+    /// Path of the spec, `/` followed by its file name.
+    pub spec_path: String,
+    /// Path of the documentation page, ending in `/`.
+    pub ui_path: String,
+    pub title: String,
+}
+
+impl OpenApiConfig {
+    pub fn new(spec: PathBuf, ui_path: &str, title: String) -> Self {
+        let spec_path = format!(
+            "/{}",
+            spec.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        );
+        let ui_path = format!("/{}/", ui_path.trim_matches('/')).replace("//", "/");
+        Self {
+            spec,
+            spec_path,
+            ui_path,
+            title,
+        }
+    }
+
+    fn script_path(&self) -> String {
+        format!("{}ui.js", self.ui_path)
+    }
+
+    /// Whether `path` is the spec or part of the documentation page.
+    pub fn matches(&self, path: &str) -> bool {
+        path == self.spec_path
+            || path == self.ui_path
+            || path == self.ui_path.trim_end_matches('/')
+            || path == self.script_path()
+    }
+
+    fn spec_content_type(&self) -> String {
+        match self
+            .spec
+            .extension()
+            .and_then(|extension| extension.to_str())
+        {
+            Some("yaml" | "yml") => String::from("application/yaml"),
+            _ => mime_guess::from_path(&self.spec)
+                .first_or_octet_stream()
+                .to_string(),
+        }
+    }
+}
+
+pub async fn handle_openapi(
+    request: &Request<Body>,
+    shared_data: &ServeConfig,
+    config: &OpenApiConfig,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let (uri, method) = (request.uri(), request.method());
+    let path = uri.path();
+    if path == config.spec_path {
+        // Re-read on every request so edits show up; cached only briefly.
+        return match tokio::fs::read(&config.spec).await {
+            Ok(spec) => {
+                info!(
+                    "{}: [200] [{}] {} served OpenAPI spec",
+                    time_of_request, method, uri
+                );
+                response_builder
+                    .header(CONTENT_TYPE, config.spec_content_type())
+                    .header(CACHE_CONTROL, "public, max-age=60")
+                    .body(Body::from(spec))
+                    .unwrap()
+            }
+            Err(err) => {
+                error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+                error_response(
+                    shared_data,
+                    response_builder,
+                    500,
+                    "Something Went Wrong :(",
+                )
+                .await
+            }
+        };
+    }
+    if path == config.script_path() {
+        info!(
+            "{}: [200] [{}] {} served OpenAPI UI",
+            time_of_request, method, uri
+        );
+        return response_builder
+            .header(CONTENT_TYPE, "text/javascript; charset=utf-8")
+            .header(CACHE_CONTROL, "public, max-age=31536000, immutable")
+            .body(Body::from(UI_JS))
+            .unwrap();
+    }
+    if path != config.ui_path {
+        info!(
+            "{}: [301] [{}] {} redirected to {}",
+            time_of_request, method, uri, config.ui_path
+        );
+        return response_builder
+            .status(301)
+            .header(LOCATION, &config.ui_path)
+            .body(Body::empty())
+            .unwrap();
+    }
+    let html = String::from_utf8_lossy(INDEX_HTML)
+        .replace("{{title}}", &html_escape(&config.title))
+        .replace("{{spec_url}}", &html_escape(&config.spec_path))
+        .replace(
+            "{{script_url}}",
+            &html_escape(&format!("{}{}", config.ui_path, UI_JS_NAME)),
+        );
+    info!(
+        "{}: [200] [{}] {} served OpenAPI UI",
+        time_of_request, method, uri
+    );
+    response_builder
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(Body::from(html))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn openapi_config_test() {
+        let config = OpenApiConfig::new(
+            PathBuf::from("./api/openapi.yaml"),
+            "swagger",
+            String::from(DEFAULT_TITLE),
+        );
+        assert_eq!("/openapi.yaml", config.spec_path);
+        assert_eq!("/swagger/", config.ui_path);
+        assert!(config.matches("/swagger"));
+        assert!(config.matches("/swagger/ui.js"));
+        assert!(!config.matches("/swagger/other.js"));
+        assert_eq!("application/yaml", config.spec_content_type());
+        assert_eq!(
+            "/",
+            OpenApiConfig::new(PathBuf::new(), "/", String::new()).ui_path
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+/// Features restricted by `--security-headers`: none of them are needed to
+/// view static files.
+pub const SECURE_DEFAULTS: [(&str, &str); 6] = [
+    ("camera", "()"),
+    ("geolocation", "()"),
+    ("microphone", "()"),
+    ("payment", "()"),
+    ("usb", "()"),
+    ("interest-cohort", "()"),
+];
+
+/// Checks an allowlist is `*` or a parenthesised list such as `(self)`.
+pub fn is_valid_allowlist(allowlist: &str) -> bool {
+    allowlist == "*" || (allowlist.starts_with('(') && allowlist.ends_with(')'))
+}
+
+fn sorted(policy: &HashMap<String, String>) -> Vec<(&String, &String)> {
+    let mut features: Vec<_> = policy.iter().collect();
+    features.sort();
+    features
+}
+
+/// `Permissions-Policy` value: `camera=(), microphone=(self)`.
+pub fn permissions_policy(policy: &HashMap<String, String>) -> String {
+    sorted(policy)
+        .into_iter()
+        .map(|(feature, allowlist)| format!("{}={}", feature, allowlist))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Legacy `Feature-Policy` value: `camera 'none'; microphone 'self'`.
+pub fn feature_policy(policy: &HashMap<String, String>) -> String {
+    sorted(policy)
+        .into_iter()
+        .map(|(feature, allowlist)| {
+            let origins: Vec<&str> = allowlist
+                .trim_start_matches('(')
+                .trim_end_matches(')')
+                .split_whitespace()
+                .map(|origin| match origin {
+                    "self" => "'self'",
+                    origin => origin.trim_matches('"'),
+                })
+                .collect();
+            if origins.is_empty() {
+                format!("{} 'none'", feature)
+            } else {
+                format!("{} {}", feature, origins.join(" "))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn single_feature_test() {
+        let policy = HashMap::from([(String::from("camera"), String::from("()"))]);
+        assert_eq!("camera=()", permissions_policy(&policy));
+        assert_eq!("camera 'none'", feature_policy(&policy));
+    }
+
+    #[test]
+    fn multiple_features_test() {
+        let policy = HashMap::from([
+            (String::from("microphone"), String::from("(self)")),
+            (String::from("camera"), String::from("()")),
+            (
+                String::from("geolocation"),
+                String::from("(self \"https://maps.example.com\")"),
+            ),
+            (String::from("fullscreen"), String::from("*")),
+        ]);
+        assert_eq!(
+            "camera=(), fullscreen=*, geolocation=(self \"https://maps.example.com\"), microphone=(self)",
+            permissions_policy(&policy)
+        );
+        assert_eq!(
+            "camera 'none'; fullscreen *; geolocation 'self' https://maps.example.com; microphone 'self'",
+            feature_policy(&policy)
+        );
+    }
+
+    #[test]
+    fn allowlist_test() {
+        assert!(is_valid_allowlist("()"));
+        assert!(is_valid_allowlist("(self)"));
+        assert!(is_valid_allowlist("*"));
+        assert!(!is_valid_allowlist("self"));
+    }
+}
@@ -0,0 +1,389 @@
+use std::net::{IpAddr, SocketAddr};
+
+use hyper::{
+    header::{HeaderName, HeaderValue, FORWARDED, HOST},
+    Body, Request,
+};
+
+pub static X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+pub static X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+pub static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// One proxy hop of an RFC 7239 `Forwarded` header.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ForwardedHop {
+    /// The `for` node: an address with an optional port, `unknown`, or an
+    /// obfuscated `_identifier`.
+    pub for_node: Option<String>,
+    pub proto: Option<String>,
+    pub host: Option<String>,
+}
+
+impl ForwardedHop {
+    /// The address in `for`, without brackets or port. `None` for unknown
+    /// and obfuscated nodes.
+    pub fn for_ip(&self) -> Option<IpAddr> {
+        let node = self.for_node.as_deref()?;
+        if let Some(bracketed) = node.strip_prefix('[') {
+            return bracketed.split(']').next()?.parse().ok();
+        }
+        node.parse()
+            .ok()
+            .or_else(|| node.split(':').next()?.parse().ok())
+    }
+}
+
+/// Splits at `delimiter` outside of quoted strings.
+fn split_unquoted(value: &str, delimiter: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => {
+                parts.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return String::from(value);
+    };
+    let mut unquoted = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// The hops of a `Forwarded` header, nearest the client first. Parameter
+/// names are case-insensitive and unknown ones such as `by` are skipped.
+pub fn parse_forwarded_header(value: &str) -> Vec<ForwardedHop> {
+    split_unquoted(value, ',')
+        .into_iter()
+        .filter(|element| !element.trim().is_empty())
+        .map(|element| {
+            let mut hop = ForwardedHop::default();
+            for pair in split_unquoted(element, ';') {
+                let Some((name, value)) = pair.split_once('=') else {
+                    continue;
+                };
+                let value = Some(unquote(value.trim()));
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "for" => hop.for_node = value,
+                    "proto" => hop.proto = value,
+                    "host" => hop.host = value,
+                    _ => {}
+                }
+            }
+            hop
+        })
+        .collect()
+}
+
+/// `--behind-proxy` and `--inject-forwarded-proto`: which peers may set the
+/// `X-Forwarded-*` headers used to build absolute redirect URLs.
+#[derive(Clone, Debug, Default)]
+pub struct ProxyConfig {
+    /// Peers whose forwarded headers are believed; empty trusts any peer.
+    pub trusted: Vec<IpAddr>,
+    /// Scheme set on requests that arrive without a trusted one.
+    pub forwarded_proto: Option<HeaderValue>,
+}
+
+impl ProxyConfig {
+    fn is_trusted(&self, request: &Request<Body>) -> bool {
+        self.trusted.is_empty()
+            || request
+                .extensions()
+                .get::<SocketAddr>()
+                .is_some_and(|peer| self.trusted.contains(&peer.ip()))
+    }
+
+    /// Sets `X-Forwarded-Proto` to the configured scheme, unless a trusted
+    /// proxy already sent one. An untrusted peer's value is replaced.
+    pub fn inject(&self, request: &mut Request<Body>) {
+        let Some(proto) = &self.forwarded_proto else {
+            return;
+        };
+        if !self.is_trusted(request) || !request.headers().contains_key(&X_FORWARDED_PROTO) {
+            request
+                .headers_mut()
+                .insert(X_FORWARDED_PROTO.clone(), proto.clone());
+        }
+    }
+
+    /// Every `Forwarded` hop, across repeated headers.
+    fn forwarded_hops(&self, request: &Request<Body>) -> Vec<ForwardedHop> {
+        request
+            .headers()
+            .get_all(FORWARDED)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(parse_forwarded_header)
+            .collect()
+    }
+
+    /// The address of the client a trusted proxy forwarded the request for:
+    /// the last `Forwarded` or, without one, `X-Forwarded-For` entry that is
+    /// not itself a trusted proxy. Falls back to the peer address, also when
+    /// that entry is obfuscated.
+    pub fn client_ip(&self, request: &Request<Body>) -> Option<IpAddr> {
+        let peer = request.extensions().get::<SocketAddr>().map(SocketAddr::ip);
+        if !self.is_trusted(request) {
+            return peer;
+        }
+        let hops = self.forwarded_hops(request);
+        if !hops.is_empty() {
+            let addrs: Vec<Option<IpAddr>> = hops.iter().map(ForwardedHop::for_ip).collect();
+            let client = addrs
+                .iter()
+                .rev()
+                .find(|addr| addr.is_none_or(|addr| !self.trusted.contains(&addr)))
+                .or(addrs.first())
+                .copied()
+                .flatten();
+            return client.or(peer);
+        }
+        let forwarded: Vec<IpAddr> = request
+            .headers()
+            .get_all(&X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|addr| addr.trim().parse().ok())
+            .collect();
+        forwarded
+            .iter()
+            .rev()
+            .find(|addr| !self.trusted.contains(addr))
+            .or(forwarded.first())
+            .copied()
+            .or(peer)
+    }
+
+    /// `location` made absolute with the scheme and host the client used,
+    /// so a redirect behind a TLS-terminating proxy stays on https. `None`
+    /// when the scheme is not known, leaving the location relative.
+    pub fn absolute_location(&self, request: &Request<Body>, location: &str) -> Option<String> {
+        let trusted = self.is_trusted(request);
+        // After `inject` the header is ours whenever the peer is untrusted.
+        if !trusted && self.forwarded_proto.is_none() {
+            return None;
+        }
+        let forwarded = |name: &HeaderName| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .map(str::trim)
+        };
+        // A trusted proxy's `Forwarded` wins over the `X-Forwarded-*` pair.
+        let hop = self
+            .forwarded_hops(request)
+            .into_iter()
+            .next()
+            .filter(|_| trusted)
+            .unwrap_or_default();
+        let proto = hop
+            .proto
+            .as_deref()
+            .or_else(|| forwarded(&X_FORWARDED_PROTO))?
+            .to_ascii_lowercase();
+        if proto != "http" && proto != "https" {
+            return None;
+        }
+        let host = hop
+            .host
+            .as_deref()
+            .or_else(|| forwarded(&X_FORWARDED_HOST).filter(|_| trusted))
+            .or_else(|| request.headers().get(HOST)?.to_str().ok())?;
+        Some(format!("{}://{}{}", proto, host, location))
+    }
+}
+
+/// Parses the `--behind-proxy=` list of trusted proxy addresses.
+pub fn parse_trusted(list: &str) -> Option<Vec<IpAddr>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty())
+        .map(|addr| addr.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(peer: &str, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut request = Request::get("/docs");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(SocketAddr::new(peer.parse().unwrap(), 40000));
+        request
+    }
+
+    #[test]
+    fn absolute_location_test() {
+        let proxy = ProxyConfig {
+            trusted: parse_trusted("10.0.0.1").unwrap(),
+            ..Default::default()
+        };
+        let forwarded = [
+            ("host", "internal:8080"),
+            ("x-forwarded-proto", "HTTPS, http"),
+            ("x-forwarded-host", "example.com"),
+        ];
+        assert_eq!(
+            Some(String::from("https://example.com/docs/")),
+            proxy.absolute_location(&request("10.0.0.1", &forwarded), "/docs/")
+        );
+        // Without the header the scheme is unknown, so the location stays
+        // relative.
+        let plain = [("host", "internal:8080")];
+        assert_eq!(
+            None,
+            proxy.absolute_location(&request("10.0.0.1", &plain), "/docs/")
+        );
+        assert_eq!(
+            None,
+            proxy.absolute_location(&request("10.0.0.2", &forwarded), "/docs/")
+        );
+        let bogus = [("host", "a"), ("x-forwarded-proto", "javascript")];
+        assert_eq!(
+            None,
+            proxy.absolute_location(&request("10.0.0.1", &bogus), "/docs/")
+        );
+    }
+
+    #[test]
+    fn inject_test() {
+        let proxy = ProxyConfig {
+            trusted: parse_trusted("10.0.0.1").unwrap(),
+            forwarded_proto: Some(HeaderValue::from_static("https")),
+        };
+        let forwarded = [
+            ("host", "example.com"),
+            ("x-forwarded-proto", "http"),
+            ("x-forwarded-host", "evil.example"),
+        ];
+        let mut trusted = request("10.0.0.1", &forwarded);
+        proxy.inject(&mut trusted);
+        assert_eq!("http", trusted.headers()[&X_FORWARDED_PROTO]);
+
+        let mut untrusted = request("10.0.0.2", &forwarded);
+        proxy.inject(&mut untrusted);
+        assert_eq!("https", untrusted.headers()[&X_FORWARDED_PROTO]);
+        assert_eq!(
+            Some(String::from("https://example.com/docs/")),
+            proxy.absolute_location(&untrusted, "/docs/")
+        );
+        assert_eq!(None, parse_trusted("10.0.0.1,nope"));
+    }
+
+    #[test]
+    fn parse_forwarded_header_test() {
+        assert_eq!(
+            vec![
+                ForwardedHop {
+                    for_node: Some(String::from("192.0.2.60")),
+                    proto: Some(String::from("https")),
+                    host: Some(String::from("example.com")),
+                },
+                ForwardedHop {
+                    for_node: Some(String::from("198.51.100.17")),
+                    ..Default::default()
+                },
+            ],
+            parse_forwarded_header(
+                "for=192.0.2.60;Proto=https;by=203.0.113.43;host=\"example.com\", for=198.51.100.17"
+            )
+        );
+        let hops = parse_forwarded_header(
+            "For=\"[2001:db8:cafe::17]:4711\", for=\"192.0.2.43:47011\", for=_hidden, for=unknown",
+        );
+        let addrs: Vec<Option<IpAddr>> = hops.iter().map(ForwardedHop::for_ip).collect();
+        assert_eq!(
+            vec![
+                Some("2001:db8:cafe::17".parse().unwrap()),
+                Some("192.0.2.43".parse().unwrap()),
+                None,
+                None,
+            ],
+            addrs
+        );
+        assert_eq!(Some(String::from("_hidden")), hops[2].for_node);
+        // Delimiters inside quotes do not split.
+        let hops = parse_forwarded_header("for=\"_a,b;c\";proto=http");
+        assert_eq!(1, hops.len());
+        assert_eq!(Some(String::from("_a,b;c")), hops[0].for_node);
+    }
+
+    #[test]
+    fn forwarded_header_test() {
+        let proxy = ProxyConfig {
+            trusted: parse_trusted("10.0.0.1").unwrap(),
+            ..Default::default()
+        };
+        let ip = |addr: &str| Some(addr.parse::<IpAddr>().unwrap());
+        let headers = [
+            ("host", "internal:8080"),
+            (
+                "forwarded",
+                "for=\"[2001:db8::1]:4711\";proto=https;host=example.com",
+            ),
+            ("x-forwarded-for", "1.1.1.1"),
+            ("x-forwarded-proto", "http"),
+        ];
+        let trusted = request("10.0.0.1", &headers);
+        assert_eq!(ip("2001:db8::1"), proxy.client_ip(&trusted));
+        assert_eq!(
+            Some(String::from("https://example.com/docs/")),
+            proxy.absolute_location(&trusted, "/docs/")
+        );
+        let untrusted = request("10.0.0.2", &headers);
+        assert_eq!(ip("10.0.0.2"), proxy.client_ip(&untrusted));
+        assert_eq!(None, proxy.absolute_location(&untrusted, "/docs/"));
+        let hidden = request("10.0.0.1", &[("forwarded", "for=_gazonk, for=10.0.0.1")]);
+        assert_eq!(ip("10.0.0.1"), proxy.client_ip(&hidden));
+    }
+
+    #[test]
+    fn client_ip_test() {
+        let proxy = ProxyConfig {
+            trusted: parse_trusted("10.0.0.1, 10.0.0.2").unwrap(),
+            ..Default::default()
+        };
+        let ip = |addr: &str| Some(addr.parse::<IpAddr>().unwrap());
+        let forwarded = [("x-forwarded-for", "1.1.1.1, 2.2.2.2, 10.0.0.2")];
+        assert_eq!(
+            ip("2.2.2.2"),
+            proxy.client_ip(&request("10.0.0.1", &forwarded))
+        );
+        assert_eq!(
+            ip("10.0.0.9"),
+            proxy.client_ip(&request("10.0.0.9", &forwarded))
+        );
+        assert_eq!(ip("10.0.0.1"), proxy.client_ip(&request("10.0.0.1", &[])));
+    }
+}
@@ -0,0 +1,337 @@
+/// A small backtracking regular expression matcher for `--rewrite-regex`.
+///
+/// Supports `^` and `$`, `.`, the classes `\d`, `\w` and `\s` and their
+/// negations, bracket classes such as `[a-z_]` or `[^/]`, the greedy
+/// quantifiers `*`, `+` and `?`, alternation with `|` and capture groups.
+#[derive(Debug)]
+pub struct Regex {
+    alternatives: Vec<Vec<Node>>,
+    groups: usize,
+}
+
+#[derive(Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Group {
+        index: usize,
+        alternatives: Vec<Vec<Node>>,
+    },
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+impl Node {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Node::Char(expected) => c == *expected,
+            Node::Any => true,
+            Node::Class { ranges, negated } => {
+                ranges.iter().any(|&(low, high)| (low..=high).contains(&c)) != *negated
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The spans of each group in a match, group 0 being the whole match.
+type Captures = Vec<Option<(usize, usize)>>;
+
+struct Parser<'a> {
+    pattern: &'a [char],
+    at: usize,
+    groups: usize,
+}
+
+impl Parser<'_> {
+    fn alternatives(&mut self) -> Option<Vec<Vec<Node>>> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.pattern.get(self.at) == Some(&'|') {
+            self.at += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Some(alternatives)
+    }
+
+    fn sequence(&mut self) -> Option<Vec<Node>> {
+        let mut nodes = Vec::new();
+        while let Some(&c) = self.pattern.get(self.at) {
+            let (min, max) = match c {
+                '|' | ')' => break,
+                '*' => (0, None),
+                '+' => (1, None),
+                '?' => (0, Some(1)),
+                _ => {
+                    nodes.push(self.atom()?);
+                    continue;
+                }
+            };
+            self.at += 1;
+            let node = match nodes.pop()? {
+                Node::Start | Node::End | Node::Repeat { .. } => return None,
+                node => node,
+            };
+            nodes.push(Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            });
+        }
+        Some(nodes)
+    }
+
+    fn atom(&mut self) -> Option<Node> {
+        let c = *self.pattern.get(self.at)?;
+        self.at += 1;
+        Some(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                // Non-capturing and lookaround groups are not supported.
+                if self.pattern.get(self.at) == Some(&'?') {
+                    return None;
+                }
+                self.groups += 1;
+                let index = self.groups;
+                let alternatives = self.alternatives()?;
+                if self.pattern.get(self.at) != Some(&')') {
+                    return None;
+                }
+                self.at += 1;
+                Node::Group {
+                    index,
+                    alternatives,
+                }
+            }
+            '[' => self.class()?,
+            '\\' => self.escape()?,
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape(&mut self) -> Option<Node> {
+        let c = *self.pattern.get(self.at)?;
+        self.at += 1;
+        let (ranges, negated) = match c {
+            'd' | 'D' => (vec![('0', '9')], c == 'D'),
+            'w' | 'W' => (
+                vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+                c == 'W',
+            ),
+            's' | 'S' => (vec![(' ', ' '), ('\t', '\r')], c == 'S'),
+            c if c.is_ascii_alphanumeric() => return None,
+            c => return Some(Node::Char(c)),
+        };
+        Some(Node::Class { ranges, negated })
+    }
+
+    fn class(&mut self) -> Option<Node> {
+        let negated = self.pattern.get(self.at) == Some(&'^');
+        if negated {
+            self.at += 1;
+        }
+        let mut ranges = Vec::new();
+        loop {
+            let mut low = *self.pattern.get(self.at)?;
+            self.at += 1;
+            match low {
+                // A `]` first is a literal one.
+                ']' if !ranges.is_empty() => break,
+                '\\' => match self.escape()? {
+                    Node::Char(c) => low = c,
+                    Node::Class {
+                        ranges: escaped,
+                        negated: false,
+                    } => {
+                        ranges.extend(escaped);
+                        continue;
+                    }
+                    _ => return None,
+                },
+                _ => {}
+            }
+            let high = match self.pattern.get(self.at..self.at + 2) {
+                Some(&['-', high]) if high != ']' => {
+                    self.at += 2;
+                    high
+                }
+                _ => low,
+            };
+            if high < low {
+                return None;
+            }
+            ranges.push((low, high));
+        }
+        Some(Node::Class { ranges, negated })
+    }
+}
+
+type Then<'a> = dyn FnMut(usize, &mut Captures) -> bool + 'a;
+
+impl Regex {
+    /// `None` when `pattern` is malformed or uses syntax this matcher does
+    /// not support.
+    pub fn new(pattern: &str) -> Option<Self> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let mut parser = Parser {
+            pattern: &pattern,
+            at: 0,
+            groups: 0,
+        };
+        let alternatives = parser.alternatives()?;
+        if parser.at != pattern.len() {
+            return None;
+        }
+        Some(Self {
+            alternatives,
+            groups: parser.groups,
+        })
+    }
+
+    /// The groups of the leftmost match in `text`, group 0 being the whole
+    /// match and unmatched groups `None`.
+    pub fn captures(&self, text: &str) -> Option<Vec<Option<String>>> {
+        let text: Vec<char> = text.chars().collect();
+        for start in 0..=text.len() {
+            let mut captures = vec![None; self.groups + 1];
+            let mut end = None;
+            let matched = self.alternatives.iter().any(|nodes| {
+                Self::matches(nodes, &text, start, &mut captures, &mut |at, _| {
+                    end = Some(at);
+                    true
+                })
+            });
+            if let (true, Some(end)) = (matched, end) {
+                captures[0] = Some((start, end));
+                return Some(
+                    captures
+                        .into_iter()
+                        .map(|span| span.map(|(from, to)| text[from..to].iter().collect()))
+                        .collect(),
+                );
+            }
+        }
+        None
+    }
+
+    /// Whether `nodes` match `text` from `at` with `then` accepting where
+    /// they end, backtracking into earlier choices until it does.
+    fn matches(
+        nodes: &[Node],
+        text: &[char],
+        at: usize,
+        captures: &mut Captures,
+        then: &mut Then,
+    ) -> bool {
+        let Some((node, rest)) = nodes.split_first() else {
+            return then(at, captures);
+        };
+        match node {
+            Node::Start => at == 0 && Self::matches(rest, text, at, captures, then),
+            Node::End => at == text.len() && Self::matches(rest, text, at, captures, then),
+            Node::Group {
+                index,
+                alternatives,
+            } => alternatives.iter().any(|nodes| {
+                Self::matches(nodes, text, at, captures, &mut |end, captures| {
+                    let previous = captures[*index].replace((at, end));
+                    if Self::matches(rest, text, end, captures, then) {
+                        return true;
+                    }
+                    captures[*index] = previous;
+                    false
+                })
+            }),
+            Node::Repeat { node, min, max } => {
+                Self::repeat(node, *min, *max, 0, rest, text, at, captures, then)
+            }
+            node => {
+                text.get(at).is_some_and(|&c| node.matches(c))
+                    && Self::matches(rest, text, at + 1, captures, then)
+            }
+        }
+    }
+
+    /// Greedily matches `node` as many times as it can, giving repetitions
+    /// back one at a time while `rest` fails.
+    #[allow(clippy::too_many_arguments)]
+    fn repeat(
+        node: &Node,
+        min: usize,
+        max: Option<usize>,
+        count: usize,
+        rest: &[Node],
+        text: &[char],
+        at: usize,
+        captures: &mut Captures,
+        then: &mut Then,
+    ) -> bool {
+        if max.is_none_or(|max| count < max) {
+            let once = std::slice::from_ref(node);
+            let matched = Self::matches(once, text, at, captures, &mut |end, captures| {
+                // An empty repetition would go round forever.
+                end != at
+                    && Self::repeat(node, min, max, count + 1, rest, text, end, captures, then)
+            });
+            if matched {
+                return true;
+            }
+        }
+        count >= min && Self::matches(rest, text, at, captures, then)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn captures(pattern: &str, text: &str) -> Option<Vec<Option<String>>> {
+        Regex::new(pattern).unwrap().captures(text)
+    }
+
+    fn groups(values: &[Option<&str>]) -> Option<Vec<Option<String>>> {
+        Some(values.iter().map(|value| value.map(String::from)).collect())
+    }
+
+    #[test]
+    fn regex_test() {
+        assert_eq!(
+            groups(&[Some("/v12/app.js"), Some("app.js")]),
+            captures(r"^/v\d+/(.*)", "/v12/app.js")
+        );
+        assert_eq!(None, captures(r"^/v\d+/(.*)", "/va/app.js"));
+        assert_eq!(
+            groups(&[Some("b/c.txt"), Some("b"), Some("c"), Some("txt")]),
+            captures(r"([^/]+)/(\w+)\.(txt|md)$", "/a/b/c.txt")
+        );
+        // Backtracking gives the greedy group back what the rest needs.
+        assert_eq!(
+            groups(&[Some("a/b/c"), Some("a/b"), Some("c")]),
+            captures("(.*)/(.*)", "a/b/c")
+        );
+        assert_eq!(
+            groups(&[Some("/doc"), None]),
+            captures("^/doc(s)?$", "/doc")
+        );
+        assert_eq!(groups(&[Some("x-_9")]), captures("^[a-z_0-9-]+$", "x-_9"));
+        assert_eq!(None, captures("^a+$", "aab"));
+        assert_eq!(groups(&[Some("")]), captures("x*", "abc"));
+    }
+
+    #[test]
+    fn invalid_regex_test() {
+        for pattern in ["(", "a)", "*a", "[a", "[z-a]", "(?:a)", r"\k", "a**"] {
+            assert!(Regex::new(pattern).is_none(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::path::PathBuf;
+
+use crate::regex::Regex;
+
+/// Maps a request path to a different file without redirecting the client.
+///
+/// `/old:./new/path` rewrites exactly `/old`, while `/old/*:./new/` rewrites
+/// everything below `/old/`, appending the remainder to the destination.
+/// `--rewrite-regex=^/v\d+/(.*):/$1` matches a regular expression instead
+/// and substitutes its groups into a request path below the served
+/// directory; `/v2/app.js` is read from `app.js`.
+pub enum RewriteRule {
+    Exact {
+        pattern: String,
+        destination: String,
+    },
+    Prefix {
+        pattern: String,
+        destination: String,
+    },
+    Regex {
+        pattern: String,
+        regex: Regex,
+        destination: String,
+    },
+}
+
+/// Whether a path stays out of hidden files and parent directories.
+fn is_plain(path: &str) -> bool {
+    !path.split('/').any(|segment| segment.starts_with('.'))
+}
+
+impl RewriteRule {
+    pub fn parse(rule: &str) -> Option<Self> {
+        let (pattern, destination) = rule.split_once(':')?;
+        if !pattern.starts_with('/') || destination.is_empty() {
+            return None;
+        }
+        let (pattern, destination) = (String::from(pattern), String::from(destination));
+        Some(match pattern.strip_suffix('*') {
+            Some(prefix) => Self::Prefix {
+                pattern: String::from(prefix),
+                destination,
+            },
+            None => Self::Exact {
+                pattern,
+                destination,
+            },
+        })
+    }
+
+    /// `^/v\d+/(.*):/$1`, split at the last `:` so the expression may hold
+    /// one. The destination must be a request path.
+    pub fn parse_regex(rule: &str) -> Option<Self> {
+        let (pattern, destination) = rule.rsplit_once(':')?;
+        if !destination.starts_with('/') {
+            return None;
+        }
+        Some(Self::Regex {
+            pattern: String::from(pattern),
+            regex: Regex::new(pattern)?,
+            destination: String::from(destination),
+        })
+    }
+
+    /// The request paths the rule matches, as `--print-routes` shows them.
+    pub fn pattern(&self) -> String {
+        match self {
+            Self::Exact { pattern, .. } => pattern.clone(),
+            Self::Prefix { pattern, .. } => format!("{}*", pattern),
+            Self::Regex { pattern, .. } => pattern.clone(),
+        }
+    }
+
+    pub fn destination(&self) -> &str {
+        match self {
+            Self::Exact { destination, .. }
+            | Self::Prefix { destination, .. }
+            | Self::Regex { destination, .. } => destination,
+        }
+    }
+
+    /// The file `uri_path` is read from, regex destinations resolving
+    /// below `directory_path`.
+    pub fn apply(&self, uri_path: &str, directory_path: &str) -> Option<PathBuf> {
+        match self {
+            Self::Exact {
+                pattern,
+                destination,
+            } => (uri_path == pattern).then(|| PathBuf::from(destination)),
+            Self::Prefix {
+                pattern,
+                destination,
+            } => {
+                let rest = uri_path.strip_prefix(pattern.as_str())?;
+                is_plain(rest).then(|| PathBuf::from(format!("{}{}", destination, rest)))
+            }
+            Self::Regex {
+                regex, destination, ..
+            } => {
+                let captures = regex.captures(uri_path)?;
+                let path = substitute(destination, &captures);
+                let relative = path.trim_start_matches('/');
+                is_plain(relative).then(|| PathBuf::from(format!("{}{}", directory_path, relative)))
+            }
+        }
+    }
+}
+
+/// Replaces `$N` in `destination` with group `N`, an unmatched group with
+/// nothing. `$$` is a literal `$`.
+fn substitute(destination: &str, captures: &[Option<String>]) -> String {
+    let mut result = String::new();
+    let mut chars = destination.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        let mut digits = String::new();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            digits.push(digit);
+        }
+        match digits.parse::<usize>() {
+            Ok(group) => {
+                if let Some(Some(value)) = captures.get(group) {
+                    result.push_str(value);
+                }
+            }
+            Err(_) => {
+                chars.next_if_eq(&'$');
+                result.push('$');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exact_rewrite_test() {
+        let rule = RewriteRule::parse("/old:./new/path.html").unwrap();
+        assert_eq!(
+            Some(PathBuf::from("./new/path.html")),
+            rule.apply("/old", "./")
+        );
+        assert_eq!(None, rule.apply("/old/file", "./"));
+        assert_eq!(None, rule.apply("/older", "./"));
+    }
+
+    #[test]
+    fn prefix_rewrite_test() {
+        let rule = RewriteRule::parse("/assets/*:./static/").unwrap();
+        assert_eq!(
+            Some(PathBuf::from("./static/js/app.js")),
+            rule.apply("/assets/js/app.js", "./")
+        );
+        assert_eq!(None, rule.apply("/assets/../secret", "./"));
+        assert_eq!(None, rule.apply("/other/app.js", "./"));
+    }
+
+    #[test]
+    fn regex_rewrite_test() {
+        let rule = RewriteRule::parse_regex(r"^/v\d+/(.*):/$1").unwrap();
+        assert_eq!(
+            Some(PathBuf::from("./public/js/app.js")),
+            rule.apply("/v12/js/app.js", "./public/")
+        );
+        assert_eq!(None, rule.apply("/vx/js/app.js", "./public/"));
+        assert_eq!(None, rule.apply("/v1/../secret", "./public/"));
+        assert_eq!(None, rule.apply("/v1/.env", "./public/"));
+
+        let rule =
+            RewriteRule::parse_regex(r"^/(\w+)/([0-9]+)-([a-z]+)\.html$:/$3/$1-$2.html").unwrap();
+        assert_eq!(
+            Some(PathBuf::from("./posts/blog-42.html")),
+            rule.apply("/blog/42-posts.html", "./")
+        );
+        assert_eq!(None, rule.apply("/blog/42-posts.htm", "./"));
+
+        // An unmatched optional group substitutes nothing.
+        let rule = RewriteRule::parse_regex("^/docs(/latest)?/(.+)$:/manual/$2$1$$").unwrap();
+        assert_eq!(
+            Some(PathBuf::from("./manual/intro$")),
+            rule.apply("/docs/intro", "./")
+        );
+        assert_eq!(
+            Some(PathBuf::from("./manual/intro/latest$")),
+            rule.apply("/docs/latest/intro", "./")
+        );
+        assert_eq!("^/docs(/latest)?/(.+)$", rule.pattern());
+    }
+
+    #[test]
+    fn invalid_rewrite_test() {
+        assert!(RewriteRule::parse("old:./new").is_none());
+        assert!(RewriteRule::parse("/old:").is_none());
+        assert!(RewriteRule::parse("/old").is_none());
+        assert!(RewriteRule::parse_regex("^/(old:/new").is_none());
+        assert!(RewriteRule::parse_regex("^/old:./new").is_none());
+    }
+}
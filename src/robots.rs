@@ -0,0 +1,77 @@
+use crate::glob;
+
+/// An `X-Robots-Tag` value, sent on every response or only on paths
+/// matching a glob: `noindex,nofollow` or `/private/*:noindex`.
+pub struct XRobotsRule {
+    pub pattern: Option<String>,
+    pub value: String,
+}
+
+impl XRobotsRule {
+    pub fn parse(rule: &str) -> Option<Self> {
+        let (pattern, value) = match rule.split_once(':') {
+            Some((pattern, value)) if pattern.starts_with('/') => (Some(pattern), value),
+            _ => (None, rule),
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        Some(Self {
+            pattern: pattern.map(String::from),
+            value: String::from(value),
+        })
+    }
+
+    pub fn matches(&self, uri_path: &str) -> bool {
+        self.pattern
+            .as_ref()
+            .is_none_or(|pattern| glob::matches(pattern, uri_path))
+    }
+}
+
+/// Values of every rule matching `uri_path`, joined into one header value.
+pub fn x_robots_tag(rules: &[XRobotsRule], uri_path: &str) -> Option<String> {
+    let values: Vec<&str> = rules
+        .iter()
+        .filter(|rule| rule.matches(uri_path))
+        .map(|rule| rule.value.as_str())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let rule = XRobotsRule::parse("noindex,nofollow").unwrap();
+        assert_eq!(None, rule.pattern);
+        assert_eq!("noindex,nofollow", rule.value);
+        let rule = XRobotsRule::parse("/private/*:noindex").unwrap();
+        assert_eq!(Some(String::from("/private/*")), rule.pattern);
+        assert_eq!("noindex", rule.value);
+        // User agent prefixes are part of the value.
+        let rule = XRobotsRule::parse("googlebot: noarchive").unwrap();
+        assert_eq!("googlebot: noarchive", rule.value);
+        assert!(XRobotsRule::parse("/private/*:").is_none());
+    }
+
+    #[test]
+    fn x_robots_tag_test() {
+        let rules = [
+            XRobotsRule::parse("/private/*:noindex").unwrap(),
+            XRobotsRule::parse("/*.pdf:nosnippet").unwrap(),
+        ];
+        assert_eq!(
+            Some(String::from("noindex")),
+            x_robots_tag(&rules, "/private/a.html")
+        );
+        assert_eq!(
+            Some(String::from("noindex, nosnippet")),
+            x_robots_tag(&rules, "/private/doc.pdf")
+        );
+        assert_eq!(None, x_robots_tag(&rules, "/public/a.html"));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::json;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RotationPolicy {
+    /// Rotate before a write once the file has reached this many bytes.
+    pub max_size: Option<u64>,
+    /// Rotate on the first write after midnight UTC.
+    pub daily: bool,
+    /// Rotated files to keep; older ones are deleted.
+    pub keep: Option<usize>,
+}
+
+/// A log file that renames itself aside and starts over according to a
+/// [`RotationPolicy`]. Size-based rotations are numbered (`access.log.1`
+/// is the newest) and daily ones are dated (`access.2024-01-01.log`).
+pub struct RotatingFile {
+    path: PathBuf,
+    policy: RotationPolicy,
+    file: File,
+    size: u64,
+    day: u64,
+}
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86400
+}
+
+fn open(path: &Path) -> std::io::Result<(File, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((file, size))
+}
+
+/// `YYYY-MM-DD` naming a day that exists.
+fn is_valid_date(date: &str) -> bool {
+    let bytes = date.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return false;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<u32> {
+        let digits = &date[range];
+        digits
+            .bytes()
+            .all(|byte| byte.is_ascii_digit())
+            .then(|| digits.parse().ok())
+            .flatten()
+    };
+    let (Some(year), Some(month), Some(day)) = (number(0..4), number(5..7), number(8..10)) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&day)
+}
+
+impl RotatingFile {
+    pub fn open(path: impl Into<PathBuf>, policy: RotationPolicy) -> std::io::Result<Self> {
+        let path = path.into();
+        let (file, size) = open(&path)?;
+        Ok(Self {
+            path,
+            policy,
+            file,
+            size,
+            day: today(),
+        })
+    }
+
+    fn numbered(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn dated(&self, day: u64) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let date = &json::rfc3339(UNIX_EPOCH + std::time::Duration::from_secs(day * 86400))[..10];
+        let name = match self.path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, date, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, date),
+        };
+        self.path.with_file_name(name)
+    }
+
+    /// Whether `name` is one [`Self::dated`] produces for some valid day,
+    /// so `server.conf` beside `server.log` is never mistaken for one.
+    fn is_dated(&self, name: &str) -> bool {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let Some(rest) = name
+            .strip_prefix(stem.as_ref())
+            .and_then(|rest| rest.strip_prefix('.'))
+        else {
+            return false;
+        };
+        let (Some(date), Some(suffix)) = (rest.get(..10), rest.get(10..)) else {
+            return false;
+        };
+        let suffix_matches = match self.path.extension() {
+            Some(extension) => suffix
+                .strip_prefix('.')
+                .is_some_and(|suffix| suffix == extension.to_string_lossy()),
+            None => suffix.is_empty(),
+        };
+        suffix_matches && is_valid_date(date)
+    }
+
+    /// Shifts numbered files up by one, dropping the ones beyond `keep`.
+    fn rotate_numbered(&self) -> std::io::Result<()> {
+        let mut last = 1;
+        while self.numbered(last).exists() {
+            last += 1;
+        }
+        for n in (1..last).rev() {
+            if self.policy.keep.is_some_and(|keep| n >= keep) {
+                std::fs::remove_file(self.numbered(n))?;
+            } else {
+                std::fs::rename(self.numbered(n), self.numbered(n + 1))?;
+            }
+        }
+        if self.policy.keep != Some(0) {
+            std::fs::rename(&self.path, self.numbered(1))?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the oldest dated files beyond `keep`.
+    fn prune_dated(&self) -> std::io::Result<()> {
+        let keep = match self.policy.keep {
+            Some(keep) => keep,
+            None => return Ok(()),
+        };
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let current = self.path.file_name().unwrap_or_default();
+        let mut rotated: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let name = entry.file_name();
+                name != current && self.is_dated(&name.to_string_lossy())
+            })
+            .map(|entry| entry.path())
+            .collect();
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(keep);
+        for path in &rotated[..excess] {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn rotate_if_needed(&mut self) -> std::io::Result<()> {
+        let today = today();
+        if self.policy.daily && today != self.day {
+            // rename(2) is atomic, so the old content is never half moved.
+            std::fs::rename(&self.path, self.dated(self.day))?;
+            self.prune_dated()?;
+        } else if self.policy.max_size.is_some_and(|max| self.size >= max) {
+            self.rotate_numbered()?;
+        } else {
+            return Ok(());
+        }
+        let (file, size) = open(&self.path)?;
+        self.file = file;
+        self.size = size;
+        self.day = today;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.rotate_if_needed()?;
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn size_rotation_test() {
+        let dir = crate::test::temp_dir("rotate");
+        let path = PathBuf::from(format!("{}access.log", dir));
+        let policy = RotationPolicy {
+            max_size: Some(100),
+            keep: Some(2),
+            ..Default::default()
+        };
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        for i in 0..10 {
+            file.write_all(format!("{:029}\n", i).as_bytes()).unwrap();
+        }
+
+        let newest = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(format!("{:029}\n{:029}\n", 8, 9), newest);
+        let rotated = std::fs::read_to_string(format!("{}access.log.1", dir)).unwrap();
+        assert!(rotated.starts_with(&format!("{:029}\n", 4)));
+        assert!(PathBuf::from(format!("{}access.log.2", dir)).exists());
+        assert!(!PathBuf::from(format!("{}access.log.3", dir)).exists());
+    }
+
+    #[test]
+    fn dated_name_test() {
+        let file = RotatingFile::open(
+            format!("{}access.log", crate::test::temp_dir("rotate-dated")),
+            RotationPolicy::default(),
+        )
+        .unwrap();
+        assert!(file.dated(19723).ends_with("access.2024-01-01.log"));
+    }
+
+    #[test]
+    fn prune_dated_test() {
+        let dir = crate::test::temp_dir("rotate-prune");
+        let policy = RotationPolicy {
+            daily: true,
+            keep: Some(1),
+            ..Default::default()
+        };
+        let file = RotatingFile::open(format!("{}access.log", dir), policy).unwrap();
+        for name in [
+            "access.2024-01-01.log",
+            "access.2024-01-02.log",
+            "access.conf",
+            "access.2024-02-30.log",
+            "access.2024-01-01.log.bak",
+        ] {
+            std::fs::write(format!("{}{}", dir, name), "").unwrap();
+        }
+        file.prune_dated().unwrap();
+
+        let exists = |name: &str| PathBuf::from(format!("{}{}", dir, name)).exists();
+        assert!(!exists("access.2024-01-01.log"));
+        assert!(exists("access.2024-01-02.log"));
+        assert!(exists("access.log"));
+        assert!(exists("access.conf"));
+        assert!(exists("access.2024-02-30.log"));
+        assert!(exists("access.2024-01-01.log.bak"));
+        assert!(file.is_dated("access.2024-02-29.log"));
+        assert!(!file.is_dated("access.2023-02-29.log"));
+    }
+}
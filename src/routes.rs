@@ -0,0 +1,219 @@
+use crate::{json, upload::UPLOAD_PROGRESS_PREFIX, ServeConfig};
+
+pub struct Route {
+    pub kind: &'static str,
+    pub pattern: String,
+    pub destination: String,
+}
+
+/// Lists the routing rules in the order `request_handler` evaluates them.
+pub fn effective_routes(shared_data: &ServeConfig) -> Vec<Route> {
+    let mut routes = Vec::new();
+    if !shared_data.ping_path.is_empty() {
+        routes.push(Route {
+            kind: "PING",
+            pattern: shared_data.ping_path.clone(),
+            destination: format!("\"{}\"", shared_data.ping_body),
+        });
+    }
+    if !shared_data.stats_path.is_empty() {
+        routes.push(Route {
+            kind: "STATS",
+            pattern: shared_data.stats_path.clone(),
+            destination: String::from("(request counters)"),
+        });
+    }
+    if shared_data.allow_put {
+        routes.push(Route {
+            kind: "UPLOAD_PROGRESS",
+            pattern: format!("{}*", UPLOAD_PROGRESS_PREFIX),
+            destination: String::from("(event stream)"),
+        });
+    }
+    if let Some(search) = &shared_data.search {
+        routes.push(Route {
+            kind: "SEARCH",
+            pattern: search.endpoint.clone(),
+            destination: shared_data.directory_path.clone(),
+        });
+    }
+    if let Some(openapi) = &shared_data.openapi {
+        routes.push(Route {
+            kind: "OPENAPI",
+            pattern: openapi.spec_path.clone(),
+            destination: openapi.spec.to_string_lossy().into_owned(),
+        });
+        routes.push(Route {
+            kind: "OPENAPI_UI",
+            pattern: openapi.ui_path.clone(),
+            destination: String::from("(embedded)"),
+        });
+    }
+    if let Some(archive) = &shared_data.archive {
+        routes.push(Route {
+            kind: "ARCHIVE",
+            pattern: archive.endpoint.clone(),
+            destination: shared_data.directory_path.clone(),
+        });
+    }
+    routes.extend(shared_data.rewrites.iter().map(|rule| Route {
+        kind: "REWRITE",
+        pattern: rule.pattern(),
+        destination: String::from(rule.destination()),
+    }));
+    routes.push(Route {
+        kind: "MOUNT",
+        pattern: String::from("/"),
+        destination: shared_data.directory_path.clone(),
+    });
+    // Only for the WebDAV methods; GET and HEAD never get this far.
+    if shared_data.webdav {
+        routes.push(Route {
+            kind: "WEBDAV",
+            pattern: String::from("/"),
+            destination: shared_data.directory_path.clone(),
+        });
+    }
+    for (prefix, path) in &shared_data.not_found_prefixes {
+        routes.push(Route {
+            kind: "NOT_FOUND",
+            pattern: format!("{}/*", prefix.trim_end_matches('/')),
+            destination: path.display().to_string(),
+        });
+    }
+    if let Some(path) = &shared_data.not_found_file_path {
+        routes.push(Route {
+            kind: "NOT_FOUND",
+            pattern: String::from("*"),
+            destination: path.clone(),
+        });
+    }
+    routes
+}
+
+pub fn format_routes_table(routes: &[Route]) -> String {
+    const HEADINGS: [&str; 4] = ["ORDER", "TYPE", "PATTERN", "DESTINATION"];
+    let rows: Vec<[String; 4]> = routes
+        .iter()
+        .enumerate()
+        .map(|(i, route)| {
+            [
+                (i + 1).to_string(),
+                route.kind.to_string(),
+                route.pattern.clone(),
+                route.destination.clone(),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADINGS.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    let headings = HEADINGS.map(String::from);
+    for row in std::iter::once(&headings).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+pub fn format_routes_json(routes: &[Route]) -> String {
+    let entries: Vec<String> = routes
+        .iter()
+        .enumerate()
+        .map(|(i, route)| {
+            format!(
+                "{{\"order\":{},\"type\":{},\"pattern\":{},\"destination\":{}}}",
+                i + 1,
+                json::string(route.kind),
+                json::string(&route.pattern),
+                json::string(&route.destination)
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RewriteRule;
+
+    fn shared_data() -> ServeConfig {
+        ServeConfig {
+            directory_path: String::from("./public/"),
+            not_found_file_path: Some(String::from("./404.html")),
+            rewrites: vec![RewriteRule::parse("/old:./new.html").unwrap()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn routes_table_test() {
+        let table = format_routes_table(&effective_routes(&shared_data()));
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(4, lines.len());
+        assert!(lines[0].starts_with("ORDER"));
+        assert!(lines[1].starts_with("1") && lines[1].contains("REWRITE"));
+        assert!(lines[1].ends_with("./new.html"));
+        assert!(lines[2].starts_with("2") && lines[2].contains("MOUNT"));
+        assert!(lines[2].ends_with("./public/"));
+        assert!(lines[3].contains("NOT_FOUND") && lines[3].ends_with("./404.html"));
+    }
+
+    #[test]
+    fn routes_json_test() {
+        let json = format_routes_json(&effective_routes(&shared_data()));
+        assert_eq!(
+            concat!(
+                r#"[{"order":1,"type":"REWRITE","pattern":"/old","destination":"./new.html"},"#,
+                r#"{"order":2,"type":"MOUNT","pattern":"/","destination":"./public/"},"#,
+                r#"{"order":3,"type":"NOT_FOUND","pattern":"*","destination":"./404.html"}]"#
+            ),
+            json
+        );
+    }
+
+    #[test]
+    fn routes_order_test() {
+        let shared_data = ServeConfig {
+            ping_path: String::from("/_ping"),
+            stats_path: String::from("/_stats"),
+            allow_put: true,
+            webdav: true,
+            archive: Some(crate::ArchiveConfig {
+                endpoint: String::from("/_archive"),
+                max_files: 10,
+            }),
+            ..shared_data()
+        };
+        let routes: Vec<(&str, String)> = effective_routes(&shared_data)
+            .into_iter()
+            .map(|route| (route.kind, route.pattern))
+            .collect();
+        assert_eq!(
+            vec![
+                ("PING", String::from("/_ping")),
+                ("STATS", String::from("/_stats")),
+                ("UPLOAD_PROGRESS", String::from("/_upload-progress/*")),
+                ("ARCHIVE", String::from("/_archive")),
+                ("REWRITE", String::from("/old")),
+                ("MOUNT", String::from("/")),
+                ("WEBDAV", String::from("/")),
+                ("NOT_FOUND", String::from("*")),
+            ],
+            routes
+        );
+    }
+}
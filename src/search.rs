@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use hyper::{header::CONTENT_TYPE, http::response::Builder, Body, Request, Response};
+use log::{error, info, warn};
+
+use crate::{
+    error_response, glob, percent_decode, query_param, retain_servable,
+    tree::{self, FileEntry},
+    ServeConfig,
+};
+
+pub struct SearchConfig {
+    pub endpoint: String,
+    pub max_depth: usize,
+    pub max_results: usize,
+}
+
+/// How well `name` matches `query`: an exact name beats a prefix, which
+/// beats a substring, which beats any other glob match.
+fn rank(query: &str, name: &str) -> Option<u8> {
+    let query = query.to_lowercase();
+    let name = name.to_lowercase();
+    let is_glob = query.contains(['*', '?']);
+    if is_glob && !glob::matches(&query, &name) {
+        return None;
+    }
+    let literal = query.replace(['*', '?'], "");
+    if name == literal {
+        Some(0)
+    } else if name.starts_with(&literal) {
+        Some(1)
+    } else if name.contains(&literal) {
+        Some(2)
+    } else if is_glob {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+/// Files under `root` no more than `max_depth` levels deep whose name
+/// matches `query`, best matches first.
+pub fn search(root: &Path, query: &str, max_depth: usize) -> std::io::Result<Vec<FileEntry>> {
+    let mut results: Vec<(u8, FileEntry)> = tree::walk(root, Some(max_depth))?
+        .into_iter()
+        .filter_map(|entry| Some((rank(query, entry.name())?, entry)))
+        .collect();
+    // The walk is sorted by path, so a stable sort keeps that within a rank.
+    results.sort_by_key(|(rank, _)| *rank);
+    Ok(results.into_iter().map(|(_, entry)| entry).collect())
+}
+
+pub async fn handle_search(
+    request: &Request<Body>,
+    shared_data: &ServeConfig,
+    config: &SearchConfig,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let (uri, method) = (request.uri(), request.method());
+    let query = match query_param(uri.query(), "q").map(percent_decode) {
+        Some(query) if !query.is_empty() => query,
+        _ => {
+            warn!(
+                "{}: [400] [{}] {} missing search query",
+                time_of_request, method, uri
+            );
+            return error_response(shared_data, response_builder, 400, "Missing ?q=").await;
+        }
+    };
+    let root = PathBuf::from(&shared_data.directory_path);
+    let max_depth = config.max_depth;
+    let walk_root = root.clone();
+    let results = tokio::task::spawn_blocking(move || search(&walk_root, &query, max_depth))
+        .await
+        .unwrap_or_else(|err| Err(std::io::Error::other(err)));
+    let mut results = match results {
+        Ok(results) => results,
+        Err(err) => {
+            error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+            return error_response(
+                shared_data,
+                response_builder,
+                500,
+                "Something Went Wrong :(",
+            )
+            .await;
+        }
+    };
+    retain_servable(request, shared_data, "/", &root, &mut results).await;
+    results.truncate(config.max_results);
+    info!(
+        "{}: [200] [{}] {} search found {} files",
+        time_of_request,
+        method,
+        uri,
+        results.len()
+    );
+    response_builder
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(tree::render_json(&results)))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tree() -> String {
+        let dir = crate::test::temp_dir("search");
+        for path in ["a/b/c/d", "lib", ".git"] {
+            std::fs::create_dir_all(format!("{}{}", dir, path)).unwrap();
+        }
+        for path in [
+            "app.js",
+            "lib/app.js.map",
+            "lib/myapp.js",
+            "lib/vendor.js",
+            "a/b/c/d/deep.js",
+            ".git/app.js",
+        ] {
+            std::fs::write(format!("{}{}", dir, path), "x").unwrap();
+        }
+        dir
+    }
+
+    fn paths(results: &[FileEntry]) -> Vec<&str> {
+        results.iter().map(|result| result.path.as_str()).collect()
+    }
+
+    #[test]
+    fn pattern_test() {
+        let dir = tree();
+        let results = search(Path::new(&dir), "*.js", 5).unwrap();
+        assert_eq!(
+            vec![
+                "/a/b/c/d/deep.js",
+                "/app.js",
+                "/lib/myapp.js",
+                "/lib/vendor.js"
+            ],
+            paths(&results)
+        );
+        let results = search(Path::new(&dir), "VENDOR", 5).unwrap();
+        assert_eq!(vec!["/lib/vendor.js"], paths(&results));
+    }
+
+    #[test]
+    fn depth_test() {
+        let dir = tree();
+        let results = search(Path::new(&dir), "deep.js", 4).unwrap();
+        assert!(results.is_empty());
+        let results = search(Path::new(&dir), "deep.js", 5).unwrap();
+        assert_eq!(vec!["/a/b/c/d/deep.js"], paths(&results));
+        let results = search(Path::new(&dir), "*.js", 1).unwrap();
+        assert_eq!(vec!["/app.js"], paths(&results));
+    }
+
+    #[test]
+    fn ordering_test() {
+        let dir = tree();
+        let results = search(Path::new(&dir), "app.js", 5).unwrap();
+        assert_eq!(
+            vec!["/app.js", "/lib/app.js.map", "/lib/myapp.js"],
+            paths(&results)
+        );
+    }
+}
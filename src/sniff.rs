@@ -0,0 +1,148 @@
+use crate::glob;
+
+/// How many leading bytes are inspected, as in the WHATWG sniffing spec.
+pub const SNIFF_LEN: usize = 512;
+
+struct Signature {
+    offset: usize,
+    magic: &'static [u8],
+    mime: &'static str,
+    /// Globs of `Content-Type`s that also fit the content, such as the
+    /// office formats that are ZIP files underneath.
+    compatible: &'static [&'static str],
+}
+
+const SIGNATURES: [Signature; 12] = [
+    Signature {
+        offset: 0,
+        magic: b"\x89PNG\r\n\x1a\n",
+        mime: "image/png",
+        compatible: &["image/apng"],
+    },
+    Signature {
+        offset: 0,
+        magic: b"\xff\xd8\xff",
+        mime: "image/jpeg",
+        compatible: &[],
+    },
+    Signature {
+        offset: 0,
+        magic: b"GIF8",
+        mime: "image/gif",
+        compatible: &[],
+    },
+    Signature {
+        offset: 8,
+        magic: b"WEBP",
+        mime: "image/webp",
+        compatible: &[],
+    },
+    Signature {
+        offset: 0,
+        magic: b"%PDF-",
+        mime: "application/pdf",
+        compatible: &[],
+    },
+    Signature {
+        offset: 0,
+        magic: b"PK\x03\x04",
+        mime: "application/zip",
+        compatible: &[
+            "application/*+zip",
+            "application/vnd.*",
+            "application/java-archive",
+        ],
+    },
+    Signature {
+        offset: 0,
+        magic: b"\x1f\x8b",
+        mime: "application/gzip",
+        compatible: &["application/x-gzip"],
+    },
+    Signature {
+        offset: 0,
+        magic: b"\0asm",
+        mime: "application/wasm",
+        compatible: &[],
+    },
+    Signature {
+        offset: 0,
+        magic: b"wOFF",
+        mime: "font/woff",
+        compatible: &["application/font-woff"],
+    },
+    Signature {
+        offset: 0,
+        magic: b"wOF2",
+        mime: "font/woff2",
+        compatible: &[],
+    },
+    Signature {
+        offset: 0,
+        magic: b"OggS",
+        mime: "audio/ogg",
+        compatible: &["video/ogg", "application/ogg"],
+    },
+    Signature {
+        offset: 0,
+        magic: b"\x7fELF",
+        mime: "application/x-executable",
+        compatible: &["application/octet-stream"],
+    },
+];
+
+/// The type a file's leading bytes identify, if any signature matches.
+pub fn detect(data: &[u8]) -> Option<&'static str> {
+    let data = &data[..data.len().min(SNIFF_LEN)];
+    SIGNATURES
+        .iter()
+        .find(|signature| {
+            data.get(signature.offset..signature.offset + signature.magic.len())
+                == Some(signature.magic)
+        })
+        .map(|signature| signature.mime)
+}
+
+/// The detected type when it contradicts `content_type`. Content without a
+/// known signature, such as text, never counts as a mismatch.
+pub fn mismatch(content_type: &str, data: &[u8]) -> Option<&'static str> {
+    let detected = detect(data)?;
+    let signature = SIGNATURES
+        .iter()
+        .find(|signature| signature.mime == detected)?;
+    let fits = content_type == detected
+        || signature
+            .compatible
+            .iter()
+            .any(|pattern| glob::matches(pattern, content_type));
+    (!fits).then_some(detected)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect_test() {
+        assert_eq!(Some("image/png"), detect(b"\x89PNG\r\n\x1a\n...."));
+        assert_eq!(Some("image/webp"), detect(b"RIFF\0\0\0\0WEBPVP8 "));
+        assert_eq!(Some("application/pdf"), detect(b"%PDF-1.7"));
+        assert_eq!(None, detect(b"<!doctype html>"));
+        assert_eq!(None, detect(b""));
+    }
+
+    #[test]
+    fn mismatch_test() {
+        assert_eq!(Some("application/pdf"), mismatch("image/png", b"%PDF-1.7"));
+        assert_eq!(None, mismatch("application/pdf", b"%PDF-1.7"));
+        assert_eq!(
+            None,
+            mismatch(
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+                b"PK\x03\x04"
+            )
+        );
+        assert_eq!(None, mismatch("application/epub+zip", b"PK\x03\x04"));
+        assert_eq!(None, mismatch("text/plain", b"just text"));
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use hyper::Method;
+
+const METHODS: [Method; 7] = [
+    Method::GET,
+    Method::HEAD,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::OPTIONS,
+    Method::PATCH,
+];
+
+/// Lock-free request counters behind the `--stats-path` endpoint.
+pub struct Stats {
+    started: Instant,
+    requests: AtomicU64,
+    bytes: AtomicU64,
+    response_micros: AtomicU64,
+    active_connections: AtomicU64,
+    /// Indexed by status code minus 100.
+    statuses: [AtomicU64; 500],
+    /// Indexed like `METHODS`, with every other method counted last.
+    methods: [AtomicU64; METHODS.len() + 1],
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: AtomicU64::default(),
+            bytes: AtomicU64::default(),
+            response_micros: AtomicU64::default(),
+            active_connections: AtomicU64::default(),
+            statuses: std::array::from_fn(|_| AtomicU64::default()),
+            methods: std::array::from_fn(|_| AtomicU64::default()),
+        }
+    }
+}
+
+impl Stats {
+    pub fn record(&self, method: &Method, status: u16, bytes: u64, elapsed: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.response_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        if let Some(count) = status
+            .checked_sub(100)
+            .and_then(|index| self.statuses.get(index as usize))
+        {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+        let index = METHODS
+            .iter()
+            .position(|known| known == method)
+            .unwrap_or(METHODS.len());
+        self.methods[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a connection as active until the returned guard is dropped.
+    pub fn open_connection(self: &Arc<Self>) -> OpenConnection {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        OpenConnection(self.clone())
+    }
+
+    /// Zeroes the request counters; uptime and open connections are facts
+    /// about the process rather than totals, so they are kept.
+    pub fn reset(&self) {
+        let counters = [&self.requests, &self.bytes, &self.response_micros];
+        for counter in counters
+            .into_iter()
+            .chain(&self.statuses)
+            .chain(&self.methods)
+        {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let average_ms = match requests {
+            0 => 0.0,
+            requests => {
+                self.response_micros.load(Ordering::Relaxed) as f64 / requests as f64 / 1000.0
+            }
+        };
+        let statuses: Vec<String> = self
+            .statuses
+            .iter()
+            .enumerate()
+            .map(|(index, count)| (index + 100, count.load(Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .map(|(status, count)| format!("\"{}\":{}", status, count))
+            .collect();
+        let methods: Vec<String> = self
+            .methods
+            .iter()
+            .enumerate()
+            .map(|(index, count)| {
+                let name = METHODS.get(index).map_or("OTHER", Method::as_str);
+                (name, count.load(Ordering::Relaxed))
+            })
+            .filter(|(_, count)| *count > 0)
+            .map(|(method, count)| format!("\"{}\":{}", method, count))
+            .collect();
+        format!(
+            "{{\"uptime_seconds\":{},\"total_requests\":{},\"total_bytes\":{},\"average_response_ms\":{:.3},\"active_connections\":{},\"status_codes\":{{{}}},\"methods\":{{{}}}}}",
+            self.started.elapsed().as_secs(),
+            requests,
+            self.bytes.load(Ordering::Relaxed),
+            average_ms,
+            self.active_connections.load(Ordering::Relaxed),
+            statuses.join(","),
+            methods.join(",")
+        )
+    }
+}
+
+pub struct OpenConnection(Arc<Stats>);
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_json_test() {
+        let stats = Arc::new(Stats::default());
+        let connection = stats.open_connection();
+        stats.record(&Method::GET, 200, 10, Duration::from_millis(2));
+        stats.record(&Method::GET, 404, 5, Duration::from_millis(4));
+        stats.record(
+            &Method::from_bytes(b"PROPFIND").unwrap(),
+            405,
+            0,
+            Duration::ZERO,
+        );
+        assert_eq!(
+            r#"{"uptime_seconds":0,"total_requests":3,"total_bytes":15,"average_response_ms":2.000,"active_connections":1,"status_codes":{"200":1,"404":1,"405":1},"methods":{"GET":2,"OTHER":1}}"#,
+            stats.to_json()
+        );
+
+        stats.reset();
+        assert_eq!(
+            r#"{"uptime_seconds":0,"total_requests":0,"total_bytes":0,"average_response_ms":0.000,"active_connections":1,"status_codes":{},"methods":{}}"#,
+            stats.to_json()
+        );
+        drop(connection);
+        assert!(stats.to_json().contains(r#""active_connections":0,"#));
+    }
+}
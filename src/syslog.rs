@@ -0,0 +1,148 @@
+use log::Level;
+
+/// Syslog facility codes by name, as accepted by `--log-syslog-facility`.
+pub fn parse_facility(name: &str) -> Option<u8> {
+    let facility = match name.to_ascii_lowercase().as_str() {
+        "kern" => 0,
+        "user" => 1,
+        "mail" => 2,
+        "daemon" => 3,
+        "auth" => 4,
+        "syslog" => 5,
+        "lpr" => 6,
+        "news" => 7,
+        "uucp" => 8,
+        "cron" => 9,
+        "authpriv" => 10,
+        "ftp" => 11,
+        name => {
+            let local: u8 = name.strip_prefix("local")?.parse().ok()?;
+            if local > 7 {
+                return None;
+            }
+            16 + local
+        }
+    };
+    Some(facility)
+}
+
+/// Syslog severity for a log level: errors are `LOG_ERR`, access lines
+/// are `LOG_INFO`.
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Formats an RFC 3164 style message; the local daemon adds the timestamp
+/// and hostname.
+fn format_message(facility: u8, level: Level, message: &str) -> String {
+    format!(
+        "<{}>serve-dir[{}]: {}",
+        facility * 8 + severity(level),
+        std::process::id(),
+        message
+    )
+}
+
+/// Sends log records to the local syslog daemon over its datagram socket.
+#[cfg(unix)]
+pub struct Syslog {
+    socket: std::os::unix::net::UnixDatagram,
+    facility: u8,
+}
+
+#[cfg(unix)]
+impl Syslog {
+    /// Sockets tried by [`Syslog::connect_default`]: Linux, then macOS.
+    const PATHS: [&'static str; 2] = ["/dev/log", "/var/run/syslog"];
+
+    pub fn connect(path: &std::path::Path, facility: u8) -> std::io::Result<Self> {
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Self { socket, facility })
+    }
+
+    pub fn connect_default(facility: u8) -> std::io::Result<Self> {
+        let mut last_err = None;
+        for path in Self::PATHS {
+            match Self::connect(path.as_ref(), facility) {
+                Ok(syslog) => return Ok(syslog),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap())
+    }
+
+    pub fn send(&self, level: Level, message: &str) {
+        let message = format_message(self.facility, level, message);
+        let _ = self.socket.send(message.as_bytes());
+    }
+}
+
+#[cfg(not(unix))]
+pub struct Syslog;
+
+#[cfg(not(unix))]
+impl Syslog {
+    pub fn connect_default(_facility: u8) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "syslog is only supported on unix",
+        ))
+    }
+
+    pub fn send(&self, _level: Level, _message: &str) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_facility_test() {
+        assert_eq!(Some(16), parse_facility("local0"));
+        assert_eq!(Some(23), parse_facility("LOCAL7"));
+        assert_eq!(Some(3), parse_facility("daemon"));
+        assert_eq!(None, parse_facility("local8"));
+        assert_eq!(None, parse_facility("nope"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mock_socket_test() {
+        use crate::logger::Logger;
+        use log::{LevelFilter, Log, Record};
+
+        let path = format!("{}syslog.sock", crate::test::temp_dir("syslog"));
+        let server = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+        let syslog = Syslog::connect(path.as_ref(), parse_facility("local0").unwrap()).unwrap();
+        let logger = Logger::new(LevelFilter::Info, Box::new(std::io::sink())).with_syslog(syslog);
+        for (level, status) in [(Level::Info, 200), (Level::Error, 500)] {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .args(format_args!("[{}] [GET] /", status))
+                    .build(),
+            );
+        }
+
+        let mut buf = [0; 256];
+        let received = |buf: &mut [u8]| {
+            let len = server.recv(buf).unwrap();
+            String::from_utf8_lossy(&buf[..len]).into_owned()
+        };
+        let pid = std::process::id();
+        assert_eq!(
+            format!("<134>serve-dir[{}]: [200] [GET] /", pid),
+            received(&mut buf)
+        );
+        assert_eq!(
+            format!("<131>serve-dir[{}]: [500] [GET] /", pid),
+            received(&mut buf)
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! systemd socket activation (`LISTEN_FDS`) and readiness notification
+//! (`NOTIFY_SOCKET`). Both are no-ops outside systemd and on non-Unix targets.
+
+/// First file descriptor passed by systemd, after stdin, stdout and stderr.
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// The descriptor systemd passed to this process, if `LISTEN_PID` names us
+/// and `LISTEN_FDS` is at least one.
+#[cfg(unix)]
+fn activation_fd(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<i32> {
+    let listen_pid: u32 = listen_pid?.parse().ok()?;
+    let listen_fds: u32 = listen_fds?.parse().ok()?;
+    (listen_pid == pid && listen_fds >= 1).then_some(LISTEN_FDS_START)
+}
+
+/// Takes over the socket systemd bound for us, if any.
+#[cfg(unix)]
+pub fn activated_listener() -> Option<std::io::Result<std::net::TcpListener>> {
+    let fd = activation_fd(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )?;
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+    Some(listener_from_fd(fd))
+}
+
+#[cfg(not(unix))]
+pub fn activated_listener() -> Option<std::io::Result<std::net::TcpListener>> {
+    None
+}
+
+#[cfg(unix)]
+fn listener_from_fd(fd: i32) -> std::io::Result<std::net::TcpListener> {
+    use std::os::unix::io::FromRawFd;
+    // SAFETY: systemd hands over ownership of the descriptor, and nothing
+    // else in this process refers to it.
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Sends `READY=1` to the service manager when started with `Type=notify`.
+#[cfg(unix)]
+pub fn notify_ready() -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let path = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let socket = UnixDatagram::unbound()?;
+    let path = path.to_string_lossy();
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(b"READY=1", &addr)?;
+        }
+        _ => {
+            socket.send_to(b"READY=1", path.as_ref())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn notify_ready() -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        os::unix::io::IntoRawFd,
+    };
+
+    #[test]
+    fn activation_fd_test() {
+        assert_eq!(Some(3), activation_fd(Some("42"), Some("1"), 42));
+        assert_eq!(None, activation_fd(Some("41"), Some("1"), 42));
+        assert_eq!(None, activation_fd(Some("42"), Some("0"), 42));
+        assert_eq!(None, activation_fd(None, Some("1"), 42));
+    }
+
+    #[tokio::test]
+    async fn pre_bound_socket_test() {
+        let bound = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = bound.local_addr().unwrap();
+        let listener = listener_from_fd(bound.into_raw_fd()).unwrap();
+
+        let make_service = hyper::service::make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(hyper::service::service_fn(|_| async {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::from("ok")))
+            }))
+        });
+        let server = hyper::Server::from_tcp(listener)
+            .unwrap()
+            .serve(make_service);
+        tokio::spawn(server);
+
+        let response = tokio::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        })
+        .await
+        .unwrap();
+        assert!(response.starts_with("HTTP/1.0 200 OK"));
+        assert!(response.ends_with("ok"));
+    }
+}
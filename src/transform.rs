@@ -0,0 +1,112 @@
+/// `--transform=text/html:FROM:TO`: every `FROM` in files of that content
+/// type is replaced with `TO` before they are sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransformRule {
+    /// A content type such as `text/html`, or `text/*` for a whole type.
+    pub content_type: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl TransformRule {
+    /// Parses `type/subtype:from:to`. `from` ends at the first colon after
+    /// the content type, so `to` may be a URL.
+    pub fn parse(rule: &str) -> Option<Self> {
+        let (content_type, replacement) = rule.split_once(':')?;
+        let (from, to) = replacement.split_once(':')?;
+        if !content_type.contains('/') || from.is_empty() {
+            return None;
+        }
+        Some(Self {
+            content_type: content_type.to_ascii_lowercase(),
+            from: String::from(from),
+            to: String::from(to),
+        })
+    }
+
+    /// Whether the rule applies to a file served as `essence`, the content
+    /// type without parameters.
+    pub fn matches(&self, essence: &str) -> bool {
+        match self.content_type.strip_suffix("/*") {
+            Some(type_) => essence
+                .split_once('/')
+                .is_some_and(|(essence_type, _)| essence_type.eq_ignore_ascii_case(type_)),
+            None => essence.eq_ignore_ascii_case(&self.content_type),
+        }
+    }
+}
+
+/// `body` with every rule matching `essence` applied in order, or `None`
+/// when nothing was replaced.
+pub fn apply(rules: &[TransformRule], essence: &str, body: &[u8]) -> Option<Vec<u8>> {
+    let mut replaced = None;
+    for rule in rules.iter().filter(|rule| rule.matches(essence)) {
+        let current: &[u8] = replaced.as_deref().unwrap_or(body);
+        if let Some(next) = replace_all(current, rule.from.as_bytes(), rule.to.as_bytes()) {
+            replaced = Some(next);
+        }
+    }
+    replaced
+}
+
+fn replace_all(haystack: &[u8], from: &[u8], to: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut rest = haystack;
+    while let Some(start) = rest.windows(from.len()).position(|window| window == from) {
+        out.extend_from_slice(&rest[..start]);
+        out.extend_from_slice(to);
+        rest = &rest[start + from.len()..];
+    }
+    if rest.len() == haystack.len() {
+        return None;
+    }
+    out.extend_from_slice(rest);
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(
+            Some(TransformRule {
+                content_type: String::from("text/html"),
+                from: String::from("{{BASE_URL}}"),
+                to: String::from("https://example.com:8443"),
+            }),
+            TransformRule::parse("Text/HTML:{{BASE_URL}}:https://example.com:8443")
+        );
+        // Replacing with nothing removes the placeholder.
+        assert_eq!(
+            Some(String::new()),
+            TransformRule::parse("text/*:@@:").map(|rule| rule.to)
+        );
+        assert_eq!(None, TransformRule::parse("html:a:b"));
+        assert_eq!(None, TransformRule::parse("text/html::b"));
+        assert_eq!(None, TransformRule::parse("text/html:a"));
+    }
+
+    #[test]
+    fn apply_test() {
+        let rules = [
+            TransformRule::parse("text/html:{{BASE_URL}}:https://example.com").unwrap(),
+            TransformRule::parse("text/*:{{ENV}}:staging").unwrap(),
+        ];
+        let html = b"<a href=\"{{BASE_URL}}/a\">{{ENV}}</a><img src=\"{{BASE_URL}}/b.png\">";
+        assert_eq!(
+            Some(
+                b"<a href=\"https://example.com/a\">staging</a><img src=\"https://example.com/b.png\">"
+                    .to_vec()
+            ),
+            apply(&rules, "text/html", html)
+        );
+        assert_eq!(
+            Some(b"{{BASE_URL}} staging".to_vec()),
+            apply(&rules, "text/css", b"{{BASE_URL}} {{ENV}}")
+        );
+        assert_eq!(None, apply(&rules, "image/png", html));
+        assert_eq!(None, apply(&rules, "text/html", b"no placeholders"));
+    }
+}
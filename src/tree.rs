@@ -0,0 +1,152 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{glob, json};
+
+pub struct FileEntry {
+    /// URI path of the file, starting with `/`.
+    pub path: String,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+impl FileEntry {
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
+    }
+}
+
+/// Recursively collects the files under `root`, looking at most `max_depth`
+/// directory levels deep (files in `root` are level 1). Hidden entries are
+/// skipped, as they are never served, and symlinked directories are not
+/// followed.
+pub fn walk(root: &Path, max_depth: Option<usize>) -> std::io::Result<Vec<FileEntry>> {
+    let mut entries = Vec::new();
+    let mut pending: Vec<(PathBuf, String, usize)> = vec![(root.to_path_buf(), String::new(), 1)];
+    while let Some((dir, prefix, depth)) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = format!("{}/{}", prefix, name);
+            if entry.file_type()?.is_dir() {
+                if max_depth.is_none_or(|max_depth| depth < max_depth) {
+                    pending.push((entry.path(), path, depth + 1));
+                }
+                continue;
+            }
+            let metadata = match entry.metadata() {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            entries.push(FileEntry {
+                path,
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Every file under `root` sorted by path, keeping those matching the
+/// `filter` glob. A glob containing `/` is matched against the path,
+/// otherwise against the file name.
+pub async fn list_directory_tree(
+    root: &Path,
+    filter: Option<&str>,
+) -> std::io::Result<Vec<FileEntry>> {
+    let root = root.to_path_buf();
+    let mut entries = tokio::task::spawn_blocking(move || walk(&root, None))
+        .await
+        .unwrap_or_else(|err| Err(std::io::Error::other(err)))?;
+    if let Some(filter) = filter {
+        entries.retain(|entry| {
+            if filter.contains('/') {
+                glob::matches(filter.trim_start_matches('/'), &entry.path[1..])
+            } else {
+                glob::matches(filter, entry.name())
+            }
+        });
+    }
+    Ok(entries)
+}
+
+/// One path per line, relative to the served directory.
+pub fn render_lines(entries: &[FileEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| format!("{}\n", &entry.path[1..]))
+        .collect()
+}
+
+pub fn render_json(entries: &[FileEntry]) -> String {
+    let items: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"path\":{},\"size\":{},\"mtime\":{}}}",
+                json::string(&entry.path),
+                entry.size,
+                json::string(&json::rfc3339(entry.modified))
+            )
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tree() -> String {
+        let dir = crate::test::temp_dir("tree");
+        std::fs::create_dir_all(format!("{}js/lib", dir)).unwrap();
+        std::fs::create_dir_all(format!("{}.git", dir)).unwrap();
+        for (path, body) in [
+            ("index.html", "<html>"),
+            ("js/app.js", "app"),
+            ("js/lib/util.js", "util"),
+            (".git/HEAD", "ref"),
+        ] {
+            std::fs::write(format!("{}{}", dir, path), body).unwrap();
+        }
+        dir
+    }
+
+    #[tokio::test]
+    async fn output_format_test() {
+        let dir = tree();
+        let entries = list_directory_tree(Path::new(&dir), None).await.unwrap();
+        assert_eq!(
+            "index.html\njs/app.js\njs/lib/util.js\n",
+            render_lines(&entries)
+        );
+        let json = render_json(&entries[1..2]);
+        assert!(json.starts_with(r#"[{"path":"/js/app.js","size":3,"mtime":""#));
+        assert!(json.ends_with(r#"Z"}]"#));
+    }
+
+    #[tokio::test]
+    async fn filter_test() {
+        let dir = tree();
+        let entries = list_directory_tree(Path::new(&dir), Some("*.js"))
+            .await
+            .unwrap();
+        assert_eq!("js/app.js\njs/lib/util.js\n", render_lines(&entries));
+        let entries = list_directory_tree(Path::new(&dir), Some("js/*.js"))
+            .await
+            .unwrap();
+        // `*` also matches `/`, as in the other globs the server accepts.
+        assert_eq!("js/app.js\njs/lib/util.js\n", render_lines(&entries));
+        let entries = list_directory_tree(Path::new(&dir), Some("*.css"))
+            .await
+            .unwrap();
+        assert!(entries.is_empty());
+    }
+}
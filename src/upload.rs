@@ -0,0 +1,193 @@
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+use hyper::{
+    body::{Bytes, HttpBody},
+    header::CONTENT_LENGTH,
+    Body, Request,
+};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::watch,
+};
+
+/// Header a client sets on a PUT to make its progress observable at
+/// `/_upload-progress/<id>`.
+pub const UPLOAD_ID_HEADER: &str = "x-upload-id";
+pub const UPLOAD_PROGRESS_PREFIX: &str = "/_upload-progress/";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UploadProgress {
+    pub uploaded: u64,
+    pub total: Option<u64>,
+    /// Average since the first byte arrived.
+    pub bytes_per_second: u64,
+}
+
+impl UploadProgress {
+    pub fn to_json(self) -> String {
+        match self.total {
+            Some(total) => {
+                let percent = (self.uploaded * 100).checked_div(total).unwrap_or(100);
+                format!(
+                    "{{\"uploaded\":{},\"total\":{},\"percent\":{},\"bytes_per_second\":{}}}",
+                    self.uploaded, total, percent, self.bytes_per_second
+                )
+            }
+            None => format!(
+                "{{\"uploaded\":{},\"total\":null,\"percent\":null,\"bytes_per_second\":{}}}",
+                self.uploaded, self.bytes_per_second
+            ),
+        }
+    }
+}
+
+/// Why an upload body could not be read.
+#[derive(Debug)]
+pub enum BodyError {
+    /// The body is longer than `--max-body-size`.
+    TooLarge,
+    Read(hyper::Error),
+    Write(std::io::Error),
+}
+
+impl From<BodyError> for std::io::Error {
+    fn from(err: BodyError) -> Self {
+        match err {
+            BodyError::TooLarge => std::io::Error::other("body too large"),
+            BodyError::Read(err) => std::io::Error::other(err),
+            BodyError::Write(err) => err,
+        }
+    }
+}
+
+/// In-flight uploads by id. Entries are removed once the upload finishes;
+/// subscribers already holding a receiver still see the final value.
+#[derive(Default)]
+pub struct UploadTracker(Mutex<HashMap<String, watch::Receiver<UploadProgress>>>);
+
+impl UploadTracker {
+    pub fn subscribe(&self, id: &str) -> Option<watch::Receiver<UploadProgress>> {
+        self.0.lock().unwrap().get(id).cloned()
+    }
+
+    /// Reads the whole request body into memory, see [`Self::copy_body`].
+    pub async fn read_body(
+        &self,
+        request: Request<Body>,
+        limit: Option<u64>,
+    ) -> Result<Vec<u8>, BodyError> {
+        let mut data = Vec::new();
+        self.copy_body(request, limit, &mut data).await?;
+        Ok(data)
+    }
+
+    /// Writes the request body to `writer` chunk by chunk as it arrives,
+    /// publishing progress when the request carries an upload id. Stops
+    /// early once more than `limit` bytes arrive. Returns the body length.
+    pub async fn copy_body<W: AsyncWrite + Unpin>(
+        &self,
+        request: Request<Body>,
+        limit: Option<u64>,
+        writer: &mut W,
+    ) -> Result<u64, BodyError> {
+        let id = request
+            .headers()
+            .get(UPLOAD_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let total = request
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+
+        if total.zip(limit).is_some_and(|(total, limit)| total > limit) {
+            return Err(BodyError::TooLarge);
+        }
+
+        let mut progress = UploadProgress {
+            total,
+            ..Default::default()
+        };
+        let sender = id.as_ref().map(|id| {
+            let (sender, receiver) = watch::channel(progress);
+            self.0.lock().unwrap().insert(id.clone(), receiver);
+            sender
+        });
+
+        let mut body = request.into_body();
+        let mut started: Option<Instant> = None;
+        let result = loop {
+            match body.data().await {
+                Some(Ok(chunk)) => {
+                    let started = *started.get_or_insert_with(Instant::now);
+                    progress.uploaded += chunk.len() as u64;
+                    if limit.is_some_and(|limit| progress.uploaded > limit) {
+                        break Err(BodyError::TooLarge);
+                    }
+                    if let Err(err) = writer.write_all(&chunk).await {
+                        break Err(BodyError::Write(err));
+                    }
+                    let elapsed = started.elapsed().as_secs_f64();
+                    if elapsed > 0.0 {
+                        progress.bytes_per_second = (progress.uploaded as f64 / elapsed) as u64;
+                    }
+                    if let Some(sender) = &sender {
+                        sender.send_replace(progress);
+                    }
+                }
+                Some(Err(err)) => break Err(BodyError::Read(err)),
+                None => break writer.flush().await.map_err(BodyError::Write),
+            }
+        };
+        if let Some(id) = id {
+            self.0.lock().unwrap().remove(&id);
+        }
+        result.map(|()| progress.uploaded)
+    }
+}
+
+/// Streams progress as Server-Sent Events until the upload finishes.
+pub fn progress_events(mut receiver: watch::Receiver<UploadProgress>) -> Body {
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        loop {
+            let progress = *receiver.borrow_and_update();
+            let event = format!("data: {}\n\n", progress.to_json());
+            if sender.send_data(Bytes::from(event)).await.is_err() {
+                return;
+            }
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    });
+    body
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn progress_json_test() {
+        let progress = UploadProgress {
+            uploaded: 5,
+            total: Some(20),
+            bytes_per_second: 1000,
+        };
+        assert_eq!(
+            r#"{"uploaded":5,"total":20,"percent":25,"bytes_per_second":1000}"#,
+            progress.to_json()
+        );
+        let progress = UploadProgress {
+            uploaded: 5,
+            total: None,
+            bytes_per_second: 0,
+        };
+        assert_eq!(
+            r#"{"uploaded":5,"total":null,"percent":null,"bytes_per_second":0}"#,
+            progress.to_json()
+        );
+    }
+}
@@ -0,0 +1,593 @@
+use std::{
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{
+    header::{HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
+    http::response::Builder,
+    Body, Method, Request, Response, StatusCode, Uri,
+};
+use log::{error, info, warn};
+
+use crate::{
+    dir_config, error_response, etag,
+    fs::{FollowSymlinks, Metadata},
+    is_authorized_for, is_forbidden,
+    listing::{html_escape, percent_encode},
+    not_found_uri, percent_decode, resolve_collection, resolve_path, ServeConfig,
+};
+
+pub static DAV: HeaderName = HeaderName::from_static("dav");
+static DEPTH: HeaderName = HeaderName::from_static("depth");
+static DESTINATION: HeaderName = HeaderName::from_static("destination");
+static OVERWRITE: HeaderName = HeaderName::from_static("overwrite");
+static LOCK_TOKEN: HeaderName = HeaderName::from_static("lock-token");
+
+/// Compliance classes for the `DAV` header. Class 2 is claimed because some
+/// clients, such as macOS Finder, mount read-only without LOCK support.
+pub const COMPLIANCE: HeaderValue = HeaderValue::from_static("1, 2");
+
+/// The extension methods served with `--webdav`.
+pub const METHODS: [&str; 6] = ["PROPFIND", "MKCOL", "COPY", "MOVE", "LOCK", "UNLOCK"];
+
+pub fn is_webdav_method(method: &Method) -> bool {
+    METHODS.contains(&method.as_str())
+}
+
+/// A file or directory described in a PROPFIND response.
+#[derive(Debug)]
+pub struct Resource {
+    /// Percent-encoded path, ending in `/` for directories.
+    pub href: String,
+    pub name: String,
+    pub metadata: Metadata,
+    pub etag: Option<String>,
+}
+
+/// Handles the `--webdav` extension methods; DELETE goes through
+/// [`crate::write::handle_delete`].
+pub async fn handle_webdav(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    match request.method().as_str() {
+        "PROPFIND" => propfind(request, shared_data, response_builder, time_of_request).await,
+        "MKCOL" => mkcol(request, shared_data, response_builder, time_of_request).await,
+        "COPY" | "MOVE" => {
+            copy_or_move(request, shared_data, response_builder, time_of_request).await
+        }
+        "LOCK" => lock(request, response_builder, time_of_request),
+        _ => {
+            info!(
+                "{}: [204] [UNLOCK] {} unlocked",
+                time_of_request,
+                request.uri()
+            );
+            response_builder
+                .status(StatusCode::NO_CONTENT)
+                .body(Body::empty())
+                .unwrap()
+        }
+    }
+}
+
+async fn propfind(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let uri = request.uri();
+    // Depth defaults to infinity, which RFC 4918 lets a server refuse
+    // rather than walk the whole tree.
+    let depth = request
+        .headers()
+        .get(&DEPTH)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("infinity");
+    let children = match depth {
+        "0" => false,
+        "1" => true,
+        _ => {
+            warn!(
+                "{}: [403] [PROPFIND] {} refused depth {}",
+                time_of_request, uri, depth
+            );
+            return response_builder
+                .status(StatusCode::FORBIDDEN)
+                .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+                .body(Body::from(
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                     <D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>\n",
+                ))
+                .unwrap();
+        }
+    };
+
+    let path = resolve_collection(&shared_data.directory_path, uri.path());
+    let metadata = match &path {
+        Some(path) => shared_data.fs.metadata(path).await.ok(),
+        None => None,
+    };
+    let (Some(path), Some(metadata)) = (path, metadata) else {
+        warn!(
+            "{}: [404] [PROPFIND] {} requested address not found",
+            time_of_request,
+            not_found_uri(&shared_data, uri)
+        );
+        return error_response(&shared_data, response_builder, 404, "404 Not Found").await;
+    };
+    let root = Path::new(&shared_data.directory_path);
+    if is_forbidden(&shared_data, uri.path(), &path)
+        || !shared_data.follow_symlinks.allows(root, &path)
+    {
+        warn!(
+            "{}: [403] [PROPFIND] {} requested forbidden path",
+            time_of_request, uri
+        );
+        return error_response(&shared_data, response_builder, 403, "Forbidden").await;
+    }
+
+    let mut href = String::from(uri.path());
+    if metadata.is_dir && !href.ends_with('/') {
+        href.push('/');
+    }
+    let name = percent_decode(href.trim_end_matches('/'))
+        .rsplit('/')
+        .next()
+        .map(String::from)
+        .unwrap_or_default();
+    let mut resources = vec![resource(&shared_data, &path, href.clone(), name, metadata).await];
+    if children && metadata.is_dir {
+        let mut entries = match shared_data.fs.read_dir(&path).await {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("{}: [500] [PROPFIND] {} {} ", time_of_request, uri, err);
+                return error_response(
+                    &shared_data,
+                    response_builder,
+                    500,
+                    "Something Went Wrong :(",
+                )
+                .await;
+            }
+        };
+        // Hidden entries are never served, so they are not listed either,
+        // and neither are broken symlinks.
+        entries.retain(|entry| {
+            !entry.name.starts_with('.') && !entry.symlink.as_ref().is_some_and(|link| link.broken)
+        });
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        for entry in entries {
+            let child_uri = format!("{}{}", href, percent_encode(&entry.name));
+            let child_path = path.join(&entry.name);
+            // What a GET would refuse is not listed either.
+            if is_forbidden(&shared_data, &child_uri, &child_path)
+                || !shared_data.follow_symlinks.allows(root, &child_path)
+                || (entry.metadata.is_dir
+                    && !is_authorized_below(&request, &shared_data, &child_uri).await)
+            {
+                continue;
+            }
+            let mut child_href = child_uri;
+            if entry.metadata.is_dir {
+                child_href.push('/');
+            }
+            resources.push(
+                resource(
+                    &shared_data,
+                    &child_path,
+                    child_href,
+                    entry.name,
+                    entry.metadata,
+                )
+                .await,
+            );
+        }
+    }
+
+    info!(
+        "{}: [207] [PROPFIND] {} listed {} resources",
+        time_of_request,
+        uri,
+        resources.len()
+    );
+    webdav_propfind_response(response_builder, &resources)
+}
+
+/// Whether the `.servedir` files down to `uri_path` let the request in. A
+/// file that can't be read keeps it out.
+async fn is_authorized_below(
+    request: &Request<Body>,
+    shared_data: &ServeConfig,
+    uri_path: &str,
+) -> bool {
+    !shared_data.dir_config
+        || dir_config::load(&shared_data.directory_path, uri_path)
+            .await
+            .is_ok_and(|config| is_authorized_for(request, shared_data, Some(&config)))
+}
+
+async fn resource(
+    shared_data: &ServeConfig,
+    path: &Path,
+    href: String,
+    name: String,
+    metadata: Metadata,
+) -> Resource {
+    let etag = if metadata.is_dir {
+        None
+    } else {
+        // Same tag a GET would send; only the hash mode needs the body.
+        let body = if shared_data.etag_mode == etag::EtagMode::Hash {
+            shared_data.fs.read_file(path).await.unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let tag = etag::etag(
+            shared_data.etag_mode,
+            path,
+            &metadata,
+            &body,
+            &shared_data.etag_cache,
+        );
+        Some(if shared_data.weak_etag {
+            etag::weak(&tag)
+        } else {
+            tag
+        })
+    };
+    Resource {
+        href,
+        name,
+        metadata,
+        etag,
+    }
+}
+
+/// The `207 Multi-Status` answer to a PROPFIND, with the live properties of
+/// each resource. Requested property names are not looked at; every
+/// response is an `allprop`.
+pub fn webdav_propfind_response(
+    response_builder: Builder,
+    resources: &[Resource],
+) -> Response<Body> {
+    response_builder
+        .status(StatusCode::MULTI_STATUS)
+        .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(multistatus(resources)))
+        .unwrap()
+}
+
+fn multistatus(resources: &[Resource]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for resource in resources {
+        xml.push_str("<D:response><D:href>");
+        xml.push_str(&html_escape(&resource.href));
+        xml.push_str("</D:href><D:propstat><D:prop>");
+        xml.push_str(&format!(
+            "<D:displayname>{}</D:displayname>",
+            html_escape(&resource.name)
+        ));
+        if resource.metadata.is_dir {
+            xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            xml.push_str("<D:resourcetype/>");
+            xml.push_str(&format!(
+                "<D:getcontentlength>{}</D:getcontentlength>",
+                resource.metadata.len
+            ));
+        }
+        if let Some(modified) = resource.metadata.modified {
+            xml.push_str(&format!(
+                "<D:getlastmodified>{}</D:getlastmodified>",
+                httpdate::fmt_http_date(modified)
+            ));
+        }
+        if let Some(etag) = &resource.etag {
+            xml.push_str(&format!("<D:getetag>{}</D:getetag>", html_escape(etag)));
+        }
+        xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+    }
+    xml.push_str("</D:multistatus>\n");
+    xml
+}
+
+async fn mkcol(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let uri = request.uri();
+    let Some(path) = resolve_path(&shared_data.directory_path, uri.path()) else {
+        warn!(
+            "{}: [403] [MKCOL] {} requested invalid path",
+            time_of_request, uri
+        );
+        return error_response(&shared_data, response_builder, 403, "Invalid Path").await;
+    };
+    let has_body = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .is_some_and(|length| length != "0");
+    let (status, message) = if has_body {
+        (415, "MKCOL bodies are not supported")
+    } else if path.exists() {
+        (405, "Path already exists")
+    } else {
+        match tokio::fs::create_dir(&path).await {
+            Ok(()) => {
+                info!("{}: [201] [MKCOL] {} created", time_of_request, uri);
+                return response_builder
+                    .status(StatusCode::CREATED)
+                    .body(Body::empty())
+                    .unwrap();
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                (409, "Parent directory does not exist")
+            }
+            Err(err) => {
+                error!("{}: [500] [MKCOL] {} {} ", time_of_request, uri, err);
+                (500, "Something Went Wrong :(")
+            }
+        }
+    };
+    if status != 500 {
+        warn!(
+            "{}: [{}] [MKCOL] {} {}",
+            time_of_request, status, uri, message
+        );
+    }
+    error_response(&shared_data, response_builder, status, message).await
+}
+
+async fn copy_or_move(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let method = request.method().clone();
+    let uri = request.uri();
+    let source = resolve_path(&shared_data.directory_path, uri.path());
+    // The destination may be an absolute URL or just a path.
+    let destination_uri = request
+        .headers()
+        .get(&DESTINATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Uri>().ok());
+    let destination = destination_uri
+        .as_ref()
+        .and_then(|destination| resolve_path(&shared_data.directory_path, destination.path()));
+    let (Some(source), Some(destination_uri), Some(destination)) =
+        (source, destination_uri, destination)
+    else {
+        warn!(
+            "{}: [403] [{}] {} invalid source or destination",
+            time_of_request, method, uri
+        );
+        return error_response(&shared_data, response_builder, 403, "Invalid Path").await;
+    };
+    // Both ends get the checks a GET of them would, and the destination
+    // the credentials its own `.servedir` asks for.
+    let root = Path::new(&shared_data.directory_path);
+    let follow_symlinks = shared_data.follow_symlinks;
+    let allowed = !is_forbidden(&shared_data, uri.path(), &source)
+        && !is_forbidden(&shared_data, destination_uri.path(), &destination)
+        && follow_symlinks.allows(root, &source)
+        && destination
+            .parent()
+            .is_some_and(|parent| follow_symlinks.allows(root, parent))
+        && follow_symlinks.allows(root, &destination)
+        && !dir_config::is_config_path(destination_uri.path())
+        && is_authorized_below(&request, &shared_data, destination_uri.path()).await;
+    if !allowed {
+        warn!(
+            "{}: [403] [{}] {} forbidden source or destination",
+            time_of_request, method, uri
+        );
+        return error_response(&shared_data, response_builder, 403, "Forbidden").await;
+    }
+    let overwrite = request
+        .headers()
+        .get(&OVERWRITE)
+        .is_none_or(|value| !value.as_bytes().eq_ignore_ascii_case(b"f"));
+
+    let existed = destination.exists();
+    let refused = if !source.exists() {
+        Some((404, "404 Not Found"))
+    } else if destination.starts_with(&source) {
+        Some((403, "Destination is inside the source"))
+    } else if existed && !overwrite {
+        Some((412, "Destination exists"))
+    } else if !destination.parent().is_some_and(Path::is_dir) {
+        Some((409, "Parent directory does not exist"))
+    } else {
+        None
+    };
+    if let Some((status, message)) = refused {
+        warn!(
+            "{}: [{}] [{}] {} {}",
+            time_of_request, status, method, uri, message
+        );
+        return error_response(&shared_data, response_builder, status, message).await;
+    }
+
+    let is_move = method.as_str() == "MOVE";
+    if is_move {
+        shared_data.etag_cache.forget(&source);
+    }
+    let result = tokio::task::spawn_blocking({
+        let destination = destination.clone();
+        let root = root.to_path_buf();
+        move || {
+            if existed {
+                remove(&destination)?;
+            }
+            if is_move {
+                std::fs::rename(&source, &destination)
+            } else {
+                copy_recursive(&root, follow_symlinks, &source, &destination)
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|err| Err(io::Error::other(err)));
+    if let Err(err) = result {
+        error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+        return error_response(
+            &shared_data,
+            response_builder,
+            500,
+            "Something Went Wrong :(",
+        )
+        .await;
+    }
+
+    let status = if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    };
+    info!(
+        "{}: [{}] [{}] {} to {}",
+        time_of_request,
+        status.as_u16(),
+        method,
+        uri,
+        destination.display()
+    );
+    response_builder.status(status).body(Body::empty()).unwrap()
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+}
+
+/// Copies `from` to `to`, leaving out the links below `from` that
+/// `follow_symlinks` would not serve, so a copy can't pull in files from
+/// outside `root`.
+fn copy_recursive(
+    root: &Path,
+    follow_symlinks: FollowSymlinks,
+    from: &Path,
+    to: &Path,
+) -> io::Result<()> {
+    if from.symlink_metadata()?.file_type().is_symlink() && !follow_symlinks.allows(root, from) {
+        return Ok(());
+    }
+    if !from.is_dir() {
+        return std::fs::copy(from, to).map(|_| ());
+    }
+    std::fs::create_dir(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(
+            root,
+            follow_symlinks,
+            &entry.path(),
+            &to.join(entry.file_name()),
+        )?;
+    }
+    Ok(())
+}
+
+/// Grants every LOCK with a fresh token. Locks are not enforced; they are
+/// only issued so that clients which insist on locking can write.
+fn lock(
+    request: Request<Body>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    static LOCKS: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let token = format!(
+        "opaquelocktoken:{:x}-{:x}",
+        nanos,
+        LOCKS.fetch_add(1, Ordering::Relaxed)
+    );
+    info!(
+        "{}: [200] [LOCK] {} granted {}",
+        time_of_request,
+        request.uri(),
+        token
+    );
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock>\
+         <D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>\
+         <D:depth>0</D:depth><D:timeout>Second-3600</D:timeout>\
+         <D:locktoken><D:href>{}</D:href></D:locktoken>\
+         <D:lockroot><D:href>{}</D:href></D:lockroot>\
+         </D:activelock></D:lockdiscovery></D:prop>\n",
+        token,
+        html_escape(request.uri().path())
+    );
+    response_builder
+        .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+        .header(&LOCK_TOKEN, format!("<{}>", token))
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn multistatus_test() {
+        let resources = [
+            Resource {
+                href: String::from("/docs/"),
+                name: String::from("docs"),
+                metadata: Metadata {
+                    len: 0,
+                    modified: None,
+                    is_dir: true,
+                },
+                etag: None,
+            },
+            Resource {
+                href: String::from("/docs/a%20%26%20b.txt"),
+                name: String::from("a & b.txt"),
+                metadata: Metadata {
+                    len: 12,
+                    modified: Some(UNIX_EPOCH + Duration::from_secs(1_000_000_000)),
+                    is_dir: false,
+                },
+                etag: Some(String::from("\"abc\"")),
+            },
+        ];
+        let xml = multistatus(&resources);
+        assert!(xml.starts_with(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n"
+        ));
+        assert!(xml.contains("<D:response><D:href>/docs/</D:href><D:propstat><D:prop><D:displayname>docs</D:displayname><D:resourcetype><D:collection/></D:resourcetype></D:prop>"));
+        assert!(xml.contains("<D:href>/docs/a%20%26%20b.txt</D:href>"));
+        assert!(xml.contains("<D:displayname>a &amp; b.txt</D:displayname><D:resourcetype/><D:getcontentlength>12</D:getcontentlength><D:getlastmodified>Sun, 09 Sep 2001 01:46:40 GMT</D:getlastmodified><D:getetag>&quot;abc&quot;</D:getetag>"));
+        assert_eq!(
+            2,
+            xml.matches("<D:status>HTTP/1.1 200 OK</D:status>").count()
+        );
+        assert!(xml.ends_with("</D:multistatus>\n"));
+    }
+}
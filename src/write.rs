@@ -0,0 +1,633 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use log::{error, info, log, warn};
+
+use hyper::{
+    header::{CONTENT_ENCODING, CONTENT_TYPE},
+    http::response::Builder,
+    Body, Request, Response, StatusCode,
+};
+
+use crate::{
+    audit::AuditEntry, digest, dir_config::DirConfig, error_response, is_forbidden, json,
+    logger::access_level, multipart, not_found_uri, resolve_collection, resolve_path,
+    upload::BodyError, ServeConfig,
+};
+
+/// What is known about a write operation before it runs, for the audit log.
+struct Audit {
+    operation: &'static str,
+    path: String,
+    client: Option<String>,
+    user: Option<String>,
+    time_of_request: u128,
+    size_before: Option<u64>,
+}
+
+impl Audit {
+    fn start(
+        operation: &'static str,
+        request: &Request<Body>,
+        shared_data: &ServeConfig,
+        time_of_request: u128,
+    ) -> Self {
+        let path = request.uri().path();
+        Self {
+            operation,
+            path: String::from(path),
+            client: request
+                .extensions()
+                .get::<SocketAddr>()
+                .map(|addr| addr.ip().to_string()),
+            user: authenticated_user(request, shared_data),
+            time_of_request,
+            size_before: file_size(shared_data, path),
+        }
+    }
+
+    fn finish(self, shared_data: &ServeConfig, response: &Response<Body>) {
+        let audit_logger = match &shared_data.audit_logger {
+            Some(audit_logger) => audit_logger,
+            None => return,
+        };
+        let entry = AuditEntry {
+            timestamp: self.time_of_request,
+            client: self.client,
+            user: self.user.as_deref(),
+            operation: self.operation,
+            path: &self.path,
+            size_before: self.size_before,
+            size_after: file_size(shared_data, &self.path),
+            success: response.status().is_success(),
+        };
+        if let Err(err) = audit_logger.lock().unwrap().record(&entry) {
+            error!("failed to write audit log entry {}", err);
+        }
+    }
+}
+
+/// Whether a write may touch `path`: not a file a GET of `uri_path` would
+/// refuse, and not reached through a link `--follow-symlinks` would not
+/// follow. The nearest part of `path` that exists is what is checked, so
+/// directories a PUT creates can't lead out of the served directory.
+fn is_writable(shared_data: &ServeConfig, uri_path: &str, path: &Path) -> bool {
+    let root = Path::new(&shared_data.directory_path);
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.symlink_metadata().is_ok())
+        .unwrap_or(path);
+    !is_forbidden(shared_data, uri_path, path) && shared_data.follow_symlinks.allows(root, existing)
+}
+
+/// The user the request was let in as, by the `.servedir` credentials for
+/// its path or else `--auth`; `None` when neither asked for any.
+fn authenticated_user(request: &Request<Body>, shared_data: &ServeConfig) -> Option<String> {
+    let user = match request
+        .extensions()
+        .get::<DirConfig>()
+        .filter(|config| config.auth.is_some())
+    {
+        Some(config) => config.auth_user.as_ref(),
+        None => shared_data
+            .auth
+            .as_ref()
+            .and(shared_data.auth_user.as_ref()),
+    };
+    user.cloned()
+}
+
+fn file_size(shared_data: &ServeConfig, uri_path: &str) -> Option<u64> {
+    let path = resolve_path(&shared_data.directory_path, uri_path)?;
+    std::fs::metadata(path)
+        .ok()
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+}
+
+/// Writes the request body to the requested path inside the served directory.
+///
+/// The body is streamed to a hidden temporary file next to the target and
+/// renamed over it once complete, so a failed upload leaves any existing file
+/// untouched. Responds 201 when the file is created and 204 when it is
+/// overwritten. A `Content-MD5` or `Digest` header that does not match the
+/// body is a 400 and nothing is written; checking one reads the upload back
+/// into memory.
+pub async fn handle_put(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let audit = Audit::start("PUT", &request, &shared_data, time_of_request);
+    let response = put_file(
+        request,
+        shared_data.clone(),
+        response_builder,
+        time_of_request,
+    )
+    .await;
+    audit.finish(&shared_data, &response);
+    response
+}
+
+async fn put_file(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let uri = request.uri().clone();
+    let file_path = match resolve_path(&shared_data.directory_path, uri.path()) {
+        Some(path) => path,
+        None => {
+            warn!(
+                "{}: [403] [PUT] {} requested invalid path",
+                time_of_request, uri
+            );
+            return error_response(&shared_data, response_builder, 403, "Invalid Path").await;
+        }
+    };
+    if !is_writable(&shared_data, uri.path(), &file_path) {
+        warn!(
+            "{}: [403] [PUT] {} refused forbidden path",
+            time_of_request, uri
+        );
+        return error_response(&shared_data, response_builder, 403, "Forbidden").await;
+    }
+
+    let is_encoded = request
+        .headers()
+        .get(CONTENT_ENCODING)
+        .is_some_and(|encoding| encoding != "identity");
+    if is_encoded {
+        warn!(
+            "{}: [415] [PUT] {} unsupported content encoding",
+            time_of_request, uri
+        );
+        return error_response(
+            &shared_data,
+            response_builder,
+            415,
+            "Unsupported Content-Encoding",
+        )
+        .await;
+    }
+
+    if file_path.is_dir() {
+        warn!(
+            "{}: [409] [PUT] {} path is a directory",
+            time_of_request, uri
+        );
+        return error_response(&shared_data, response_builder, 409, "Path is a directory").await;
+    }
+    let existed = file_path.is_file();
+
+    let expected_digests = digest::expected_digests(request.headers());
+    if let Some(parent) = file_path.parent() {
+        if let Err(err) = tokio::fs::create_dir_all(parent).await {
+            return internal_error(
+                &shared_data,
+                response_builder,
+                time_of_request,
+                "PUT",
+                &uri,
+                err,
+            )
+            .await;
+        }
+    }
+    let temp_path = temp_path(&file_path);
+    let mut temp_file = match tokio::fs::File::create(&temp_path).await {
+        Ok(file) => file,
+        Err(err) => {
+            return internal_error(
+                &shared_data,
+                response_builder,
+                time_of_request,
+                "PUT",
+                &uri,
+                err,
+            )
+            .await;
+        }
+    };
+    let copied = shared_data
+        .uploads
+        .copy_body(request, shared_data.max_body_size, &mut temp_file)
+        .await;
+    drop(temp_file);
+    let written = match copied {
+        Ok(_) => Ok(()),
+        Err(BodyError::TooLarge) => {
+            discard(&temp_path).await;
+            return too_large(&shared_data, response_builder, time_of_request, "PUT", &uri).await;
+        }
+        Err(err) => Err(err.into()),
+    };
+    let written = match written {
+        Ok(()) if !expected_digests.is_empty() => tokio::fs::read(&temp_path)
+            .await
+            .map(|body| digest::verify(&body, &expected_digests)),
+        written => written.map(Ok),
+    };
+    let result = match written {
+        Ok(Ok(())) => tokio::fs::rename(&temp_path, &file_path).await,
+        Ok(Err(message)) => {
+            discard(&temp_path).await;
+            warn!("{}: [400] [PUT] {} {}", time_of_request, uri, message);
+            return error_response(&shared_data, response_builder, 400, message).await;
+        }
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        discard(&temp_path).await;
+        return internal_error(
+            &shared_data,
+            response_builder,
+            time_of_request,
+            "PUT",
+            &uri,
+            err,
+        )
+        .await;
+    }
+
+    let status = if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    };
+    log!(
+        access_level(status.as_u16()),
+        "{}: [{}] [PUT] {} wrote file",
+        time_of_request,
+        status.as_u16(),
+        uri
+    );
+    response_builder.status(status).body(Body::empty()).unwrap()
+}
+
+async fn too_large(
+    shared_data: &ServeConfig,
+    response_builder: Builder,
+    time_of_request: u128,
+    method: &str,
+    uri: &hyper::Uri,
+) -> Response<Body> {
+    warn!(
+        "{}: [413] [{}] {} body exceeds --max-body-size",
+        time_of_request, method, uri
+    );
+    error_response(shared_data, response_builder, 413, "Payload Too Large").await
+}
+
+/// Saves the files of a `multipart/form-data` POST into the requested
+/// directory. Only parts named `file` that carry a file name are written,
+/// under their sanitized name.
+///
+/// Responds 201 with the names and sizes written. If any of the files
+/// exists and `--allow-overwrite` is not set, this is a 409 and nothing is
+/// written.
+pub async fn handle_upload(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let uri = request.uri().clone();
+    let client = request
+        .extensions()
+        .get::<SocketAddr>()
+        .map(|addr| addr.ip().to_string());
+    let user = authenticated_user(&request, &shared_data);
+    let dir_path = match resolve_collection(&shared_data.directory_path, uri.path()) {
+        Some(path) => path,
+        None => {
+            warn!(
+                "{}: [403] [POST] {} requested invalid path",
+                time_of_request, uri
+            );
+            return error_response(&shared_data, response_builder, 403, "Invalid Path").await;
+        }
+    };
+    if !dir_path.is_dir() {
+        warn!(
+            "{}: [404] [POST] {} upload target is not a directory",
+            time_of_request,
+            not_found_uri(&shared_data, &uri)
+        );
+        return error_response(&shared_data, response_builder, 404, "404 Not Found").await;
+    }
+
+    let boundary = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(multipart::boundary);
+    let boundary = match boundary {
+        Some(boundary) => boundary,
+        None => {
+            warn!(
+                "{}: [415] [POST] {} expected multipart/form-data",
+                time_of_request, uri
+            );
+            return error_response(
+                &shared_data,
+                response_builder,
+                415,
+                "Expected multipart/form-data",
+            )
+            .await;
+        }
+    };
+
+    let body = match shared_data
+        .uploads
+        .read_body(request, shared_data.max_body_size)
+        .await
+    {
+        Ok(body) => body,
+        Err(BodyError::TooLarge) => {
+            return too_large(
+                &shared_data,
+                response_builder,
+                time_of_request,
+                "POST",
+                &uri,
+            )
+            .await;
+        }
+        Err(err) => {
+            return internal_error(
+                &shared_data,
+                response_builder,
+                time_of_request,
+                "POST",
+                &uri,
+                err.into(),
+            )
+            .await;
+        }
+    };
+    let parts = match multipart::parse(&body, &boundary) {
+        Ok(parts) => parts,
+        Err(message) => {
+            warn!("{}: [400] [POST] {} {}", time_of_request, uri, message);
+            return error_response(&shared_data, response_builder, 400, message).await;
+        }
+    };
+
+    let mut files = Vec::new();
+    for part in parts {
+        if part.name.as_deref() != Some("file") {
+            continue;
+        }
+        let Some(filename) = part.filename.as_deref() else {
+            continue;
+        };
+        let Some(name) = multipart::sanitize_filename(filename) else {
+            warn!(
+                "{}: [400] [POST] {} invalid file name {:?}",
+                time_of_request, uri, filename
+            );
+            return error_response(&shared_data, response_builder, 400, "Invalid file name").await;
+        };
+        files.push((String::from(name), part.data));
+    }
+    if files.is_empty() {
+        warn!(
+            "{}: [400] [POST] {} no files in upload",
+            time_of_request, uri
+        );
+        return error_response(&shared_data, response_builder, 400, "No files in upload").await;
+    }
+    let refused = files.iter().find(|(name, _)| {
+        let path = format!("{}/{}", uri.path().trim_end_matches('/'), name);
+        !is_writable(&shared_data, &path, &dir_path.join(name))
+    });
+    if let Some((name, _)) = refused {
+        warn!(
+            "{}: [403] [POST] {} refused forbidden path {}",
+            time_of_request, uri, name
+        );
+        return error_response(&shared_data, response_builder, 403, "Forbidden").await;
+    }
+    if !shared_data.allow_overwrite {
+        if let Some((name, _)) = files.iter().find(|(name, _)| dir_path.join(name).exists()) {
+            warn!(
+                "{}: [409] [POST] {} {} already exists",
+                time_of_request, uri, name
+            );
+            return error_response(&shared_data, response_builder, 409, "File already exists")
+                .await;
+        }
+    }
+
+    let mut audits = Vec::new();
+    let mut result = Ok(());
+    for (name, data) in &files {
+        let path = format!("{}/{}", uri.path().trim_end_matches('/'), name);
+        let audit = Audit {
+            operation: "POST",
+            size_before: file_size(&shared_data, &path),
+            path,
+            client: client.clone(),
+            user: user.clone(),
+            time_of_request,
+        };
+        audits.push(audit);
+        result = tokio::fs::write(dir_path.join(name), data).await;
+        if result.is_err() {
+            break;
+        }
+    }
+    let response = match result {
+        Ok(()) => {
+            let listed: Vec<String> = files
+                .iter()
+                .map(|(name, data)| {
+                    format!(
+                        "{{\"name\":{},\"size\":{}}}",
+                        json::string(name),
+                        data.len()
+                    )
+                })
+                .collect();
+            info!(
+                "{}: [201] [POST] {} uploaded {} files",
+                time_of_request,
+                uri,
+                files.len()
+            );
+            response_builder
+                .status(StatusCode::CREATED)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(format!("{{\"files\":[{}]}}", listed.join(","))))
+                .unwrap()
+        }
+        Err(err) => {
+            internal_error(
+                &shared_data,
+                response_builder,
+                time_of_request,
+                "POST",
+                &uri,
+                err,
+            )
+            .await
+        }
+    };
+    for audit in audits {
+        audit.finish(&shared_data, &response);
+    }
+    response
+}
+
+/// A hidden, unique name next to `file_path` for an upload in progress.
+fn temp_path(file_path: &Path) -> PathBuf {
+    static UPLOADS: AtomicU64 = AtomicU64::new(0);
+    let name = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    file_path.with_file_name(format!(
+        ".{}.{}-{}.upload",
+        name,
+        std::process::id(),
+        UPLOADS.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Removes an unfinished upload.
+async fn discard(temp_path: &Path) {
+    if let Err(err) = tokio::fs::remove_file(temp_path).await {
+        error!("failed to remove {} {}", temp_path.display(), err);
+    }
+}
+
+async fn internal_error(
+    shared_data: &ServeConfig,
+    response_builder: Builder,
+    time_of_request: u128,
+    method: &str,
+    uri: &hyper::Uri,
+    err: std::io::Error,
+) -> Response<Body> {
+    error!("{}: [500] [{}] {} {} ", time_of_request, method, uri, err);
+    error_response(
+        shared_data,
+        response_builder,
+        500,
+        "Something Went Wrong :(",
+    )
+    .await
+}
+
+/// Deletes the requested file, or directory when `--allow-delete-dirs` is set.
+pub async fn handle_delete(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let audit = Audit::start("DELETE", &request, &shared_data, time_of_request);
+    let response = delete_file(
+        request,
+        shared_data.clone(),
+        response_builder,
+        time_of_request,
+    )
+    .await;
+    audit.finish(&shared_data, &response);
+    response
+}
+
+async fn delete_file(
+    request: Request<Body>,
+    shared_data: Arc<ServeConfig>,
+    response_builder: Builder,
+    time_of_request: u128,
+) -> Response<Body> {
+    let uri = request.uri().clone();
+    let client = request
+        .extensions()
+        .get::<SocketAddr>()
+        .map_or_else(|| String::from("-"), |addr| addr.ip().to_string());
+    let file_path = match resolve_path(&shared_data.directory_path, uri.path()) {
+        Some(path) => path,
+        None => {
+            warn!(
+                "{}: [403] [DELETE] {} {} requested invalid path",
+                time_of_request, uri, client
+            );
+            return error_response(&shared_data, response_builder, 403, "Invalid Path").await;
+        }
+    };
+    if !is_writable(&shared_data, uri.path(), &file_path) {
+        warn!(
+            "{}: [403] [DELETE] {} {} refused forbidden path",
+            time_of_request, uri, client
+        );
+        return error_response(&shared_data, response_builder, 403, "Forbidden").await;
+    }
+
+    let result = if file_path.is_file() {
+        tokio::fs::remove_file(&file_path).await
+    } else if file_path.is_dir() {
+        if !shared_data.allow_delete_dirs && !shared_data.webdav {
+            warn!(
+                "{}: [403] [DELETE] {} {} refused to delete directory",
+                time_of_request, uri, client
+            );
+            return error_response(
+                &shared_data,
+                response_builder,
+                403,
+                "Deleting directories is not allowed",
+            )
+            .await;
+        }
+        tokio::fs::remove_dir_all(&file_path).await
+    } else {
+        warn!(
+            "{}: [404] [DELETE] {} {} requested address not found",
+            time_of_request,
+            not_found_uri(&shared_data, &uri),
+            client
+        );
+        return error_response(&shared_data, response_builder, 404, "404 Not Found").await;
+    };
+    shared_data.etag_cache.forget(&file_path);
+    if let Err(err) = result {
+        error!(
+            "{}: [500] [DELETE] {} {} {} ",
+            time_of_request, uri, client, err
+        );
+        return error_response(
+            &shared_data,
+            response_builder,
+            500,
+            "Something Went Wrong :(",
+        )
+        .await;
+    }
+
+    info!(
+        "{}: [204] [DELETE] {} {} deleted {}",
+        time_of_request,
+        uri,
+        client,
+        file_path.display()
+    );
+    response_builder
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .unwrap()
+}
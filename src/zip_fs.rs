@@ -0,0 +1,392 @@
+//! `--zip=./dist.zip`: the entries of a ZIP archive served as if they were
+//! files in the served directory.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    ops::Bound,
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    archive::Crc32,
+    fs::{DirEntry, FileSystem, FsFuture, Metadata},
+    inflate,
+};
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+struct Entry {
+    method: u16,
+    crc: u32,
+    /// Where the entry's data is in the archive.
+    start: usize,
+    compressed_len: usize,
+    len: u64,
+    modified: Option<SystemTime>,
+    encrypted: bool,
+}
+
+impl Entry {
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            len: self.len,
+            modified: self.modified,
+            is_dir: false,
+        }
+    }
+}
+
+/// An archive read into memory once at startup, its entries keyed by the
+/// path they are served at. Directories are implied by the entries below
+/// them, and archives inside the archive are just files.
+pub struct ZipFileSystem {
+    archive: Vec<u8>,
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn u16_at(data: &[u8], at: usize) -> io::Result<u16> {
+    data.get(at..at + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| invalid("truncated ZIP archive"))
+}
+
+fn u32_at(data: &[u8], at: usize) -> io::Result<u32> {
+    data.get(at..at + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| invalid("truncated ZIP archive"))
+}
+
+/// The MS-DOS time and date of an entry, read as UTC like
+/// [`crate::archive::ZipWriter`] writes them.
+fn dos_modified(time: u16, date: u16) -> Option<SystemTime> {
+    let (year, month, day) = (
+        1980 + i64::from(date >> 9),
+        i64::from((date >> 5) & 0xf),
+        i64::from(date & 0x1f),
+    );
+    if !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    // Days-from-civil, see http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let secs = days * 86400
+        + i64::from(time >> 11) * 3600
+        + i64::from((time >> 5) & 0x3f) * 60
+        + i64::from(time & 0x1f) * 2;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+/// Whether `name` stays below the directory it is unpacked into.
+fn is_safe_name(name: &str) -> bool {
+    !name.contains('\\')
+        && Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+impl ZipFileSystem {
+    pub fn open(path: &Path, root: &Path) -> io::Result<Self> {
+        Self::new(std::fs::read(path)?, root)
+    }
+
+    /// Reads the central directory of `archive`, serving its entries under
+    /// `root`. Entries whose names would leave `root` are skipped.
+    pub fn new(archive: Vec<u8>, root: &Path) -> io::Result<Self> {
+        // The end record is last, after a comment of at most 64 KiB.
+        let lowest = archive.len().saturating_sub(22 + u16::MAX as usize);
+        let end = (lowest..=archive.len().saturating_sub(22))
+            .rev()
+            .find(|&at| archive[at..].starts_with(&0x0605_4b50u32.to_le_bytes()))
+            .ok_or_else(|| invalid("not a ZIP archive"))?;
+        let count = u16_at(&archive, end + 10)?;
+        let mut at = u32_at(&archive, end + 16)? as usize;
+        if at == u32::MAX as usize {
+            return Err(invalid("ZIP64 archives are not supported"));
+        }
+
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            if u32_at(&archive, at)? != 0x0201_4b50 {
+                return Err(invalid("corrupt ZIP central directory"));
+            }
+            let flags = u16_at(&archive, at + 8)?;
+            let method = u16_at(&archive, at + 10)?;
+            let (time, date) = (u16_at(&archive, at + 12)?, u16_at(&archive, at + 14)?);
+            let crc = u32_at(&archive, at + 16)?;
+            let compressed_len = u32_at(&archive, at + 20)? as usize;
+            let len = u32_at(&archive, at + 24)?;
+            let name_len = u16_at(&archive, at + 28)? as usize;
+            let extra_len = u16_at(&archive, at + 30)? as usize;
+            let comment_len = u16_at(&archive, at + 32)? as usize;
+            let local = u32_at(&archive, at + 42)? as usize;
+            let name = archive
+                .get(at + 46..at + 46 + name_len)
+                .ok_or_else(|| invalid("truncated ZIP archive"))?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            at += 46 + name_len + extra_len + comment_len;
+
+            if name.ends_with('/') || !is_safe_name(&name) {
+                continue;
+            }
+            if u32_at(&archive, local)? != 0x0403_4b50 {
+                return Err(invalid("corrupt ZIP local header"));
+            }
+            let start = local
+                + 30
+                + u16_at(&archive, local + 26)? as usize
+                + u16_at(&archive, local + 28)? as usize;
+            if archive.len() < start + compressed_len {
+                return Err(invalid("truncated ZIP archive"));
+            }
+            entries.insert(
+                root.join(&name),
+                Entry {
+                    method,
+                    crc,
+                    start,
+                    compressed_len,
+                    len: u64::from(len),
+                    modified: dos_modified(time, date),
+                    encrypted: flags & 1 == 1,
+                },
+            );
+        }
+        Ok(Self { archive, entries })
+    }
+
+    /// Entries below `path`, in path order.
+    fn below<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = (&'a PathBuf, &'a Entry)> + 'a {
+        self.entries
+            .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+            .take_while(move |(file, _)| file.starts_with(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.below(path).next().is_some()
+    }
+
+    fn read(&self, entry: &Entry) -> io::Result<Vec<u8>> {
+        let unsupported = |message: String| io::Error::new(io::ErrorKind::Unsupported, message);
+        if entry.encrypted {
+            return Err(unsupported(String::from(
+                "encrypted ZIP entries are not supported",
+            )));
+        }
+        let data = &self.archive[entry.start..entry.start + entry.compressed_len];
+        let data = match entry.method {
+            STORED => data.to_vec(),
+            DEFLATED => inflate::inflate(data, entry.len as usize)
+                .ok_or_else(|| invalid("corrupt deflated ZIP entry"))?,
+            method => {
+                return Err(unsupported(format!(
+                    "ZIP compression method {} is not supported",
+                    method
+                )))
+            }
+        };
+        let mut crc = Crc32::default();
+        crc.update(&data);
+        if data.len() as u64 != entry.len || crc.value() != entry.crc {
+            return Err(invalid("ZIP entry does not match its checksum"));
+        }
+        Ok(data)
+    }
+}
+
+fn not_found() -> io::Error {
+    io::Error::from(io::ErrorKind::NotFound)
+}
+
+impl FileSystem for ZipFileSystem {
+    fn read_file<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Vec<u8>> {
+        Box::pin(async move { self.read(self.entries.get(path).ok_or_else(not_found)?) })
+    }
+
+    fn metadata<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Metadata> {
+        let metadata = match self.entries.get(path) {
+            Some(entry) => Ok(entry.metadata()),
+            None if self.is_dir(path) => Ok(Metadata {
+                len: 0,
+                modified: None,
+                is_dir: true,
+            }),
+            None => Err(not_found()),
+        };
+        Box::pin(async move { metadata })
+    }
+
+    fn read_dir<'a>(&'a self, path: &'a Path) -> FsFuture<'a, Vec<DirEntry>> {
+        Box::pin(async move {
+            if !self.is_dir(path) {
+                return Err(not_found());
+            }
+            let mut entries: Vec<DirEntry> = Vec::new();
+            for (file, entry) in self.below(path) {
+                let Ok(relative) = file.strip_prefix(path) else {
+                    continue;
+                };
+                let mut components = relative.components();
+                let Some(name) = components.next() else {
+                    continue;
+                };
+                let name = name.as_os_str().to_string_lossy().into_owned();
+                // Entries are in path order, so a directory's files follow
+                // one another.
+                if entries.last().is_some_and(|last| last.name == name) {
+                    continue;
+                }
+                let metadata = if components.next().is_some() {
+                    Metadata {
+                        len: 0,
+                        modified: None,
+                        is_dir: true,
+                    }
+                } else {
+                    entry.metadata()
+                };
+                entries.push(DirEntry {
+                    name,
+                    metadata,
+                    symlink: None,
+                });
+            }
+            Ok(entries)
+        })
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.entries.contains_key(path)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::archive::ZipWriter;
+
+    /// An archive of stored entries, as `--archive` writes them.
+    pub fn stored_zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let modified = UNIX_EPOCH + Duration::from_secs(1709214306);
+        let mut writer = ZipWriter::default();
+        let mut zip = Vec::new();
+        for (name, data) in files {
+            zip.extend(writer.entry(name, modified, data.as_bytes()).unwrap());
+        }
+        zip.extend(writer.finish());
+        zip
+    }
+
+    /// A one-entry archive holding `compressed`, `data` deflated.
+    fn deflated_zip(name: &str, compressed: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut crc = Crc32::default();
+        crc.update(data);
+        let header = |out: &mut Vec<u8>| {
+            out.extend_from_slice(&20u16.to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+            out.extend_from_slice(&DEFLATED.to_le_bytes());
+            out.extend_from_slice(&[0; 4]);
+            out.extend_from_slice(&crc.value().to_le_bytes());
+            out.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&0u16.to_le_bytes());
+        };
+        let mut zip = 0x0403_4b50u32.to_le_bytes().to_vec();
+        header(&mut zip);
+        zip.extend_from_slice(name.as_bytes());
+        zip.extend_from_slice(compressed);
+        let central_at = zip.len() as u32;
+        zip.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&20u16.to_le_bytes());
+        header(&mut zip);
+        zip.extend_from_slice(&[0; 14]);
+        zip.extend_from_slice(name.as_bytes());
+        let central_len = zip.len() as u32 - central_at;
+        zip.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0, 0, 0, 0, 1, 0, 1, 0]);
+        zip.extend_from_slice(&central_len.to_le_bytes());
+        zip.extend_from_slice(&central_at.to_le_bytes());
+        zip.extend_from_slice(&0u16.to_le_bytes());
+        zip
+    }
+
+    #[tokio::test]
+    async fn stored_entries_test() {
+        let zip = stored_zip(&[
+            ("index.html", "<h1>hi</h1>"),
+            ("assets/app.js", "alert(1)"),
+            ("assets/img/logo.svg", "<svg/>"),
+            ("../escape.txt", "no"),
+            ("inner.zip", "PK"),
+        ]);
+        let fs = ZipFileSystem::new(zip, Path::new("/srv/")).unwrap();
+        assert!(fs.is_file(Path::new("/srv/index.html")));
+        assert!(fs.is_file(Path::new("/srv/inner.zip")));
+        assert!(!fs.is_file(Path::new("/srv/assets")));
+        assert!(!fs.is_file(Path::new("/escape.txt")));
+        assert_eq!(
+            b"alert(1)".to_vec(),
+            fs.read_file(Path::new("/srv/assets/app.js")).await.unwrap()
+        );
+        let metadata = fs.metadata(Path::new("/srv/index.html")).await.unwrap();
+        assert_eq!(11, metadata.len);
+        // DOS times have two-second resolution.
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(1709214306)),
+            metadata.modified
+        );
+        assert!(fs.metadata(Path::new("/srv/assets")).await.unwrap().is_dir);
+        assert!(fs.metadata(Path::new("/srv/missing")).await.is_err());
+
+        let names: Vec<_> = fs
+            .read_dir(Path::new("/srv/assets"))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.metadata.is_dir))
+            .collect();
+        assert_eq!(
+            vec![(String::from("app.js"), false), (String::from("img"), true)],
+            names
+        );
+        assert!(fs.read_dir(Path::new("/srv/index.html")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn deflated_entry_test() {
+        let compressed = [
+            0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0xc0, 0x20, 0xcb, 0xf3, 0x8b, 0x72, 0x52,
+            0xb8, 0x00,
+        ];
+        let data = b"hello hello hello hello world\n";
+        let fs = ZipFileSystem::new(
+            deflated_zip("hello.txt", &compressed, data),
+            Path::new("/srv"),
+        )
+        .unwrap();
+        let path = Path::new("/srv/hello.txt");
+        assert_eq!(data.to_vec(), fs.read_file(path).await.unwrap());
+        assert_eq!(30, fs.metadata(path).await.unwrap().len);
+
+        // A checksum that doesn't match is an error rather than bad data.
+        let fs = ZipFileSystem::new(
+            deflated_zip("hello.txt", &compressed, b"hello hello hello hello World\n"),
+            Path::new("/srv"),
+        )
+        .unwrap();
+        assert!(fs.read_file(path).await.is_err());
+        assert!(ZipFileSystem::new(b"not a zip".to_vec(), Path::new("/srv")).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::{Command, Stdio},
+};
+
+#[test]
+fn no_access_log_test() {
+    let dir = std::env::temp_dir().join(format!("serve-dir-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("index.html"), "hello").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_serve-dir"))
+        .arg(&dir)
+        .arg("--port=0")
+        .arg("--no-access-log")
+        .arg("--no-banner")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut startup = String::new();
+    stdout.read_line(&mut startup).unwrap();
+    assert!(startup.starts_with("Serving "), "{}", startup);
+    let addr = startup.trim_end().rsplit(' ').next().unwrap().to_string();
+    let mut port_line = String::new();
+    stdout.read_line(&mut port_line).unwrap();
+    assert!(addr.ends_with(&format!(
+        ":{}",
+        port_line
+            .trim_end()
+            .strip_prefix("SERVE_DIR_PORT=")
+            .unwrap()
+    )));
+
+    for path in ["/", "/missing"] {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        write!(stream, "GET {} HTTP/1.0\r\n\r\n", path).unwrap();
+        stream.read_to_string(&mut String::new()).unwrap();
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert_eq!("", rest);
+}
+
+/// Starts a server on `--port=0` and returns it with the port it reported.
+fn start_random_port(dir: &std::path::Path) -> (std::process::Child, u16) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_serve-dir"))
+        .arg(dir)
+        .arg("--port=0")
+        .arg("--silent")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let port = line
+        .trim_end()
+        .strip_prefix("SERVE_DIR_PORT=")
+        .unwrap_or_else(|| panic!("no port line in {:?}", line))
+        .parse()
+        .unwrap();
+    (child, port)
+}
+
+#[test]
+fn random_port_test() {
+    let dir = std::env::temp_dir().join(format!("serve-dir-port-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("index.html"), "hello").unwrap();
+
+    let (mut first, first_port) = start_random_port(&dir);
+    let (mut second, second_port) = start_random_port(&dir);
+    assert_ne!(0, first_port);
+    assert_ne!(first_port, second_port);
+    for port in [first_port, second_port] {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "GET / HTTP/1.0\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+    }
+    for child in [&mut first, &mut second] {
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
+
+#[test]
+fn http10_keep_alive_test() {
+    let dir = std::env::temp_dir().join(format!("serve-dir-keep-alive-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("index.html"), "hello").unwrap();
+
+    let (mut child, port) = start_random_port(&dir);
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    for _ in 0..2 {
+        write!(stream, "GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n").unwrap();
+        let mut head = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            head.push(line.trim_end().to_ascii_lowercase());
+        }
+        assert_eq!("http/1.0 200 ok", head[0]);
+        assert!(
+            head.contains(&String::from("connection: keep-alive")),
+            "{:?}",
+            head
+        );
+        let mut body = [0; 5];
+        reader.read_exact(&mut body).unwrap();
+        assert_eq!(b"hello", &body);
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn banner_test() {
+    let dir = std::env::temp_dir().join(format!("serve-dir-banner-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_serve-dir"))
+        .arg(&dir)
+        .arg("--port=0")
+        .arg("--auth=user:secret")
+        .arg("--404=./404.html")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut banner = Vec::new();
+    for line in stdout.lines() {
+        let line = line.unwrap();
+        let done = line.starts_with('└');
+        banner.push(line);
+        if done {
+            break;
+        }
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let row = |key: &str| {
+        banner
+            .iter()
+            .find(|line| line.starts_with(&format!("│ {} ", key)))
+            .map(|line| line.split('│').nth(2).unwrap().trim().to_string())
+            .unwrap_or_else(|| panic!("no {} row in {:?}", key, banner))
+    };
+    assert!(banner[0].starts_with('┌'));
+    assert_eq!(format!("{}/", dir.display()), row("directory"));
+    assert!(row("address").starts_with("127.0.0.1:"));
+    assert_eq!("basic, user user", row("auth"));
+    assert_eq!("./404.html", row("404 page"));
+    assert_eq!("--port --auth --404", row("options"));
+    assert!(!banner.concat().contains("secret"));
+}
+
+#[cfg(unix)]
+#[test]
+fn daemon_test() {
+    let dir = std::env::temp_dir().join(format!("serve-dir-daemon-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let pid_file = dir.join("serve-dir.pid");
+    let pid_arg = format!("--pid-file={}", pid_file.display());
+    let log_arg = format!("--log-file={}", dir.join("access.log").display());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_serve-dir"))
+        .arg(&dir)
+        .arg("--port=0")
+        .arg("--daemon")
+        .arg(&pid_arg)
+        .arg(&log_arg)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let pid: i32 = std::fs::read_to_string(&pid_file)
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    // SAFETY: signal 0 only checks the process exists.
+    assert_eq!(0, unsafe { libc::kill(pid, 0) });
+    let log = std::fs::read_to_string(dir.join("access.log")).unwrap();
+    assert!(log.contains("│ address"), "{}", log);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_serve-dir"))
+        .arg("--stop")
+        .arg(&pid_arg)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        format!("Stopped serve-dir {}\n", pid),
+        String::from_utf8(output.stdout).unwrap()
+    );
+    assert!(!pid_file.exists());
+
+    // The daemon is no longer our child, so wait for it to be gone (or a
+    // zombie, when nothing reaps orphans in this environment).
+    let stopped = (0..100).any(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid));
+        // SAFETY: signal 0 only checks the process exists.
+        let exists = unsafe { libc::kill(pid, 0) } == 0;
+        !exists || stat.is_ok_and(|stat| stat.contains(") Z "))
+    });
+    assert!(stopped, "serve-dir {} still running", pid);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_serve-dir"))
+        .arg("--stop")
+        .arg(&pid_arg)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}